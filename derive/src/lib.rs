@@ -0,0 +1,136 @@
+//! Dérive `Serializable` et `Deserializable` pour une structure dont chaque
+//! champ implémente `Column` : les champs sont écrits dans un enregistrement,
+//! une colonne par champ, dans leur ordre de déclaration.
+//!
+//! Dérive aussi `FromRow`, qui lit une structure dans une ligne de résultat.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, Index};
+
+/// Chemin du module `data` de la base, valable aussi dans la crate elle-même
+/// grâce à son `extern crate self`
+fn data_path() -> TokenStream2 {
+    quote!(::rusty_sqlite_tutorial::data)
+}
+
+/// Champs de la structure, ou l'erreur à signaler à la compilation
+fn fields<'a>(input: &'a DeriveInput, derive: &str) -> Result<&'a Fields, syn::Error> {
+    match &input.data {
+        Data::Struct(data) => Ok(&data.fields),
+        _ => Err(syn::Error::new_spanned(
+            &input.ident,
+            format!("only structs can derive {derive}"),
+        )),
+    }
+}
+
+#[proc_macro_derive(Serializable)]
+pub fn derive_serializable(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let fields = match fields(&input, "Serializable") {
+        Ok(fields) => fields,
+        Err(error) => return error.to_compile_error().into(),
+    };
+    let data = data_path();
+    // accès aux champs nommés ou positionnels
+    let columns = fields.iter().enumerate().map(|(index, field)| match &field.ident {
+        Some(name) => quote!(&self.#name),
+        None => {
+            let index = Index::from(index);
+            quote!(&self.#index)
+        }
+    });
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    quote! {
+        impl #impl_generics #data::Serializable for #name #type_generics #where_clause {
+            fn serialize(
+                &self,
+                cursor: &mut ::std::io::Cursor<&mut [u8]>,
+            ) -> ::std::result::Result<(), ::rusty_sqlite_tutorial::errors::SerializationError> {
+                #data::write_record(cursor, &[#(#columns),*])
+            }
+        }
+    }
+    .into()
+}
+
+#[proc_macro_derive(Deserializable)]
+pub fn derive_deserializable(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let fields = match fields(&input, "Deserializable") {
+        Ok(fields) => fields,
+        Err(error) => return error.to_compile_error().into(),
+    };
+    let data = data_path();
+    // les colonnes sont lues dans l'ordre de déclaration des champs
+    let column = quote!(record.column(cursor)?);
+    let value = match fields {
+        Fields::Named(fields) => {
+            let names = fields.named.iter().map(|field| &field.ident);
+            quote!(Self { #(#names: #column),* })
+        }
+        Fields::Unnamed(fields) => {
+            let columns = fields.unnamed.iter().map(|_| &column);
+            quote!(Self(#(#columns),*))
+        }
+        Fields::Unit => quote!(Self),
+    };
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    quote! {
+        impl #impl_generics #data::Deserializable for #name #type_generics #where_clause {
+            fn deserialize(
+                cursor: &mut #data::Reader<'_>,
+            ) -> ::std::result::Result<Self, ::rusty_sqlite_tutorial::errors::DeserializationError> {
+                let mut record = #data::RecordReader::new(cursor)?;
+                let value = #value;
+                record.finish(cursor)?;
+                ::std::result::Result::Ok(value)
+            }
+        }
+    }
+    .into()
+}
+
+#[proc_macro_derive(FromRow)]
+pub fn derive_from_row(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    let fields = match fields(&input, "FromRow") {
+        Ok(fields) => fields,
+        Err(error) => return error.to_compile_error().into(),
+    };
+    let row = quote!(::rusty_sqlite_tutorial::row);
+    // un champ nommé est lu dans la colonne de même nom, un champ
+    // positionnel dans la colonne de même position
+    let value = match fields {
+        Fields::Named(fields) => {
+            let columns = fields.named.iter().map(|field| {
+                let name = field.ident.as_ref().expect("named field");
+                let column = name.to_string();
+                let column = column.strip_prefix("r#").unwrap_or(&column);
+                quote!(#name: row.get(#column)?)
+            });
+            quote!(Self { #(#columns),* })
+        }
+        Fields::Unnamed(fields) => {
+            let columns = (0..fields.unnamed.len()).map(|index| quote!(row.get(#index)?));
+            quote!(Self(#(#columns),*))
+        }
+        Fields::Unit => quote!(Self),
+    };
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    quote! {
+        impl #impl_generics #row::FromRow for #name #type_generics #where_clause {
+            fn from_row(
+                row: &#row::Row,
+            ) -> ::std::result::Result<Self, ::rusty_sqlite_tutorial::errors::RowError> {
+                ::std::result::Result::Ok(#value)
+            }
+        }
+    }
+    .into()
+}
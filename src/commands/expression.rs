@@ -0,0 +1,960 @@
+use crate::commands::lexer::{tokenize, Placeholder, Token};
+use crate::data::{Affinity, Date, Time, Timestamp, Value};
+use crate::errors::{CommandError, EvaluationError};
+use crate::functions::{Function, Functions};
+use std::cmp::Ordering;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+/// Niveaux d'imbrication acceptés dans une expression, comme dans SQLite
+const MAX_DEPTH: usize = 1000;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Comparison {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Comparison {
+    fn from_symbol(symbol: &str) -> Option<Self> {
+        match symbol {
+            "=" | "==" => Some(Comparison::Equal),
+            "<>" | "!=" => Some(Comparison::NotEqual),
+            "<" => Some(Comparison::Less),
+            "<=" => Some(Comparison::LessOrEqual),
+            ">" => Some(Comparison::Greater),
+            ">=" => Some(Comparison::GreaterOrEqual),
+            _ => None,
+        }
+    }
+
+    fn symbol(self) -> &'static str {
+        match self {
+            Comparison::Equal => "=",
+            Comparison::NotEqual => "<>",
+            Comparison::Less => "<",
+            Comparison::LessOrEqual => "<=",
+            Comparison::Greater => ">",
+            Comparison::GreaterOrEqual => ">=",
+        }
+    }
+
+    fn matches(self, ordering: Ordering) -> bool {
+        match self {
+            Comparison::Equal => ordering.is_eq(),
+            Comparison::NotEqual => ordering.is_ne(),
+            Comparison::Less => ordering.is_lt(),
+            Comparison::LessOrEqual => ordering.is_le(),
+            Comparison::Greater => ordering.is_gt(),
+            Comparison::GreaterOrEqual => ordering.is_ge(),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Expression {
+    Literal(Value),
+    Comparison {
+        left: Box<Expression>,
+        operator: Comparison,
+        right: Box<Expression>,
+    },
+    /// `CAST(expression AS type)`
+    Cast {
+        expression: Box<Expression>,
+        affinity: Affinity,
+    },
+    /// Paramètre d'une requête préparée, NULL tant qu'il n'est pas lié
+    Parameter(Placeholder),
+    /// Colonne de la ligne lue
+    Column(String),
+    /// Appel de fonction, `count(*)` n'a pas d'argument
+    Function {
+        name: String,
+        arguments: Vec<Expression>,
+    },
+    /// Résultat d'un appel de fonction d'agrégat, calculé sur toutes les
+    /// lignes avant l'évaluation de l'expression
+    Aggregate(usize),
+    /// `*` d'une liste de select, remplacé par les colonnes de la source
+    Wildcard,
+}
+
+/// Ce que voit une expression pendant son évaluation
+pub struct Context<'a> {
+    functions: &'a Functions,
+    /// nom et type déclaré de chaque colonne de la ligne
+    columns: &'a [(&'a str, &'a str)],
+    row: &'a [Value],
+    /// résultats des fonctions d'agrégat, et les appels qui les ont produits
+    aggregates: &'a [Value],
+    calls: &'a [AggregateCall],
+}
+
+impl<'a> Context<'a> {
+    /// Contexte sans ligne, pour une expression constante
+    pub fn new(functions: &'a Functions) -> Self {
+        Self {
+            functions,
+            columns: &[],
+            row: &[],
+            aggregates: &[],
+            calls: &[],
+        }
+    }
+
+    pub fn with_row(self, columns: &'a [(&'a str, &'a str)], row: &'a [Value]) -> Self {
+        Self { columns, row, ..self }
+    }
+
+    pub fn with_aggregates(self, aggregates: &'a [Value], calls: &'a [AggregateCall]) -> Self {
+        Self {
+            aggregates,
+            calls,
+            ..self
+        }
+    }
+
+    fn column(&self, name: &str) -> Result<usize, EvaluationError> {
+        self.columns
+            .iter()
+            .position(|(column, _)| column.eq_ignore_ascii_case(name))
+            .ok_or_else(|| EvaluationError::UnknownColumn(name.to_string()))
+    }
+}
+
+/// Appel d'une fonction d'agrégat, sorti de l'expression pour être calculé
+/// ligne par ligne
+pub struct AggregateCall {
+    pub name: String,
+    pub arguments: Vec<Expression>,
+}
+
+/// Plus grand numéro de paramètre, comme dans SQLite
+pub const MAX_PARAMETERS: usize = 32766;
+
+/// Paramètres d'une commande, numérotés dans leur ordre d'apparition
+#[derive(Debug, PartialEq, Default, Clone)]
+pub struct Parameters {
+    /// nom de chaque paramètre, `None` pour un paramètre sans nom
+    names: Vec<Option<String>>,
+}
+
+impl Parameters {
+    /// Numéro du paramètre, à partir de 0. Un paramètre nommé garde le
+    /// numéro de sa première apparition. Au-delà de `MAX_PARAMETERS`, le
+    /// paramètre est refusé.
+    pub fn index(&mut self, placeholder: &Placeholder) -> Result<usize, CommandError> {
+        let index = match placeholder {
+            Placeholder::Next => self.names.len(),
+            Placeholder::Index(index) => index.saturating_sub(1),
+            Placeholder::Name(name) => match self.index_of(name) {
+                Some(index) => return Ok(index),
+                None => self.names.len(),
+            },
+        };
+        if index >= MAX_PARAMETERS {
+            return Err(CommandError::TooManyParameters);
+        }
+        if index >= self.names.len() {
+            self.names.resize(index + 1, None);
+        }
+        if let Placeholder::Name(name) = placeholder {
+            self.names[index] = Some(name.clone());
+        }
+        Ok(index)
+    }
+
+    /// Numéro d'un paramètre nommé, préfixe compris : `:nom`
+    pub fn index_of(&self, name: &str) -> Option<usize> {
+        self.names.iter().position(|found| found.as_deref() == Some(name))
+    }
+
+    /// Nombre de valeurs à lier
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+}
+
+impl Expression {
+    /// Remplace les paramètres par les valeurs liées, NULL pour ceux qui
+    /// n'en ont pas
+    pub fn bind(&self, parameters: &mut Parameters, values: &[Value]) -> Result<Expression, CommandError> {
+        Ok(match self {
+            Expression::Parameter(placeholder) => {
+                let index = parameters.index(placeholder)?;
+                Expression::Literal(values.get(index).cloned().unwrap_or(Value::Null))
+            }
+            Expression::Literal(_) => self.clone(),
+            Expression::Comparison {
+                left,
+                operator,
+                right,
+            } => Expression::Comparison {
+                left: Box::new(left.bind(parameters, values)?),
+                operator: *operator,
+                right: Box::new(right.bind(parameters, values)?),
+            },
+            Expression::Cast {
+                expression,
+                affinity,
+            } => Expression::Cast {
+                expression: Box::new(expression.bind(parameters, values)?),
+                affinity: *affinity,
+            },
+            Expression::Function { name, arguments } => Expression::Function {
+                name: name.clone(),
+                arguments: arguments
+                    .iter()
+                    .map(|argument| argument.bind(parameters, values))
+                    .collect::<Result<_, _>>()?,
+            },
+            Expression::Column(_) | Expression::Aggregate(_) | Expression::Wildcard => self.clone(),
+        })
+    }
+
+    pub fn evaluate(&self, context: &Context) -> Result<Value, EvaluationError> {
+        match self {
+            Expression::Literal(value) => Ok(value.clone()),
+            Expression::Parameter(_) => Ok(Value::Null),
+            Expression::Column(name) => Ok(context.row[context.column(name)?].clone()),
+            Expression::Aggregate(index) => Ok(context.aggregates[*index].clone()),
+            Expression::Wildcard => Err(EvaluationError::UnknownColumn("*".to_string())),
+            Expression::Comparison {
+                left,
+                operator,
+                right,
+            } => {
+                let (left_affinity, right_affinity) =
+                    comparison_affinities(left.affinity(context), right.affinity(context));
+                let mut left = left.evaluate(context)?;
+                let mut right = right.evaluate(context)?;
+                if let Some(affinity) = left_affinity {
+                    left = left.apply_affinity(affinity);
+                }
+                if let Some(affinity) = right_affinity {
+                    right = right.apply_affinity(affinity);
+                }
+                match left.compare(&right) {
+                    Some(ordering) => Ok(Value::Boolean(operator.matches(ordering))),
+                    // comparer avec NULL donne NULL
+                    None => Ok(Value::Null),
+                }
+            }
+            Expression::Cast {
+                expression,
+                affinity,
+            } => Ok(expression.evaluate(context)?.cast(*affinity)),
+            Expression::Function { name, arguments } => {
+                let values = arguments
+                    .iter()
+                    .map(|argument| argument.evaluate(context))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let json = arguments
+                    .iter()
+                    .map(|argument| argument.is_json(context))
+                    .collect::<Vec<_>>();
+                context.functions.call_json(name, &values, &json)
+            }
+        }
+    }
+
+    /// Le texte de l'expression est du JSON produit par une fonction JSON
+    pub fn is_json(&self, context: &Context) -> bool {
+        match self {
+            Expression::Function { name, arguments } => context.functions.is_json(name, arguments.len()),
+            Expression::Aggregate(index) => context
+                .calls
+                .get(*index)
+                .is_some_and(|call| context.functions.is_json(&call.name, call.arguments.len())),
+            _ => false,
+        }
+    }
+
+    /// Affinité du résultat : celle du type déclaré d'une colonne, aucune
+    /// pour un littéral
+    fn affinity(&self, context: &Context) -> Option<Affinity> {
+        match self {
+            Expression::Cast { affinity, .. } => Some(*affinity),
+            Expression::Column(name) => {
+                let (_, declared_type) = context.columns[context.column(name).ok()?];
+                Some(Affinity::from_type_name(declared_type))
+            }
+            _ => None,
+        }
+    }
+
+    /// Calcule une fois pour toutes ce qui ne dépend d'aucune ligne : les
+    /// comparaisons et conversions de constantes, et les appels de fonctions
+    /// déterministes sur des constantes
+    pub fn fold(self, functions: &Functions) -> Result<Expression, EvaluationError> {
+        let is_literal = |expression: &Expression| matches!(expression, Expression::Literal(_));
+        let folded = match self {
+            Expression::Comparison {
+                left,
+                operator,
+                right,
+            } => Expression::Comparison {
+                left: Box::new(left.fold(functions)?),
+                operator,
+                right: Box::new(right.fold(functions)?),
+            },
+            Expression::Cast {
+                expression,
+                affinity,
+            } => Expression::Cast {
+                expression: Box::new(expression.fold(functions)?),
+                affinity,
+            },
+            Expression::Function { name, arguments } => {
+                let arguments = arguments
+                    .into_iter()
+                    .map(|argument| argument.fold(functions))
+                    .collect::<Result<Vec<_>, _>>()?;
+                let deterministic = match functions.find(&name, arguments.len())? {
+                    Function::Scalar { deterministic, .. } => *deterministic,
+                    Function::Json(_) => true,
+                    Function::Aggregate(_) => false,
+                };
+                // un littéral perdrait la marque du JSON renvoyé par l'appel
+                let keep = !deterministic || functions.is_json(&name, arguments.len());
+                let function = Expression::Function { name, arguments };
+                if keep {
+                    return Ok(function);
+                }
+                function
+            }
+            expression => return Ok(expression),
+        };
+        let constant = match &folded {
+            Expression::Comparison { left, right, .. } => is_literal(left) && is_literal(right),
+            Expression::Cast { expression, .. } => is_literal(expression),
+            Expression::Function { arguments, .. } => arguments.iter().all(is_literal),
+            _ => false,
+        };
+        match constant {
+            true => Ok(Expression::Literal(folded.evaluate(&Context::new(functions))?)),
+            false => Ok(folded),
+        }
+    }
+
+    /// Remplace chaque appel de fonction d'agrégat par son résultat à venir,
+    /// et ajoute l'appel à `calls`
+    pub fn extract_aggregates(
+        self,
+        functions: &Functions,
+        calls: &mut Vec<AggregateCall>,
+    ) -> Result<Expression, EvaluationError> {
+        Ok(match self {
+            Expression::Comparison {
+                left,
+                operator,
+                right,
+            } => Expression::Comparison {
+                left: Box::new(left.extract_aggregates(functions, calls)?),
+                operator,
+                right: Box::new(right.extract_aggregates(functions, calls)?),
+            },
+            Expression::Cast {
+                expression,
+                affinity,
+            } => Expression::Cast {
+                expression: Box::new(expression.extract_aggregates(functions, calls)?),
+                affinity,
+            },
+            Expression::Function { name, arguments } => {
+                if functions.is_aggregate(&name, arguments.len())? {
+                    // un agrégat ne peut pas en contenir un autre
+                    if arguments.iter().any(|argument| argument.has_aggregate(functions)) {
+                        return Err(EvaluationError::MisusedAggregate(name));
+                    }
+                    calls.push(AggregateCall { name, arguments });
+                    Expression::Aggregate(calls.len() - 1)
+                } else {
+                    Expression::Function {
+                        arguments: arguments
+                            .into_iter()
+                            .map(|argument| argument.extract_aggregates(functions, calls))
+                            .collect::<Result<_, _>>()?,
+                        name,
+                    }
+                }
+            }
+            expression => expression,
+        })
+    }
+
+    /// L'expression appelle une fonction d'agrégat
+    pub fn has_aggregate(&self, functions: &Functions) -> bool {
+        match self {
+            Expression::Comparison { left, right, .. } => {
+                left.has_aggregate(functions) || right.has_aggregate(functions)
+            }
+            Expression::Cast { expression, .. } => expression.has_aggregate(functions),
+            Expression::Function { name, arguments } => {
+                functions.is_aggregate(name, arguments.len()).unwrap_or(false)
+                    || arguments.iter().any(|argument| argument.has_aggregate(functions))
+            }
+            _ => false,
+        }
+    }
+
+    /// Vérifie que chaque colonne citée existe, avant de lire les lignes
+    pub fn check_columns(&self, columns: &[(&str, &str)]) -> Result<(), EvaluationError> {
+        match self {
+            Expression::Column(name) => match columns.iter().any(|(column, _)| column.eq_ignore_ascii_case(name)) {
+                true => Ok(()),
+                false => Err(EvaluationError::UnknownColumn(name.clone())),
+            },
+            Expression::Comparison { left, right, .. } => {
+                left.check_columns(columns)?;
+                right.check_columns(columns)
+            }
+            Expression::Cast { expression, .. } => expression.check_columns(columns),
+            Expression::Function { arguments, .. } => arguments
+                .iter()
+                .try_for_each(|argument| argument.check_columns(columns)),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Écriture SQL de l'expression, qui sert de nom à sa colonne de résultat
+impl Display for Expression {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Expression::Literal(value) => match value {
+                Value::Text(text) => write!(f, "'{}'", text.replace('\'', "''")),
+                Value::Date(date) => write!(f, "DATE '{date}'"),
+                Value::Time(time) => write!(f, "TIME '{time}'"),
+                Value::Timestamp(timestamp) => write!(f, "TIMESTAMP '{timestamp}'"),
+                Value::Boolean(value) => write!(f, "{}", value.to_string().to_uppercase()),
+                value => write!(f, "{value}"),
+            },
+            Expression::Comparison {
+                left,
+                operator,
+                right,
+            } => write!(f, "{left} {} {right}", operator.symbol()),
+            Expression::Cast {
+                expression,
+                affinity,
+            } => write!(f, "CAST({expression} AS {})", format!("{affinity:?}").to_uppercase()),
+            Expression::Parameter(Placeholder::Next) => write!(f, "?"),
+            Expression::Parameter(Placeholder::Index(index)) => write!(f, "?{index}"),
+            Expression::Parameter(Placeholder::Name(name)) => write!(f, "{name}"),
+            Expression::Column(name) => write!(f, "{name}"),
+            Expression::Function { name, arguments } if arguments.is_empty() && name.eq_ignore_ascii_case("count") => {
+                write!(f, "{name}(*)")
+            }
+            Expression::Function { name, arguments } if arguments.len() == 2 && matches!(name.as_str(), "->" | "->>") => {
+                write!(f, "{} {name} {}", arguments[0], arguments[1])
+            }
+            Expression::Function { name, arguments } if arguments.len() == 2 && name == "match" => {
+                write!(f, "{} MATCH {}", arguments[0], arguments[1])
+            }
+            Expression::Function { name, arguments } => {
+                let arguments = arguments.iter().map(Expression::to_string).collect::<Vec<_>>();
+                write!(f, "{name}({})", arguments.join(", "))
+            }
+            Expression::Aggregate(index) => write!(f, "<aggregate {index}>"),
+            Expression::Wildcard => write!(f, "*"),
+        }
+    }
+}
+
+/// Affinités à appliquer aux opérandes d'une comparaison, comme SQLite :
+/// face à un opérande numérique, un texte ou une valeur sans affinité est
+/// converti en nombre ; face à un texte, une valeur sans affinité en texte
+fn comparison_affinities(
+    left: Option<Affinity>,
+    right: Option<Affinity>,
+) -> (Option<Affinity>, Option<Affinity>) {
+    let numeric = |affinity: Option<Affinity>| affinity.is_some_and(Affinity::is_numeric);
+    let text_or_none = |affinity: Option<Affinity>| {
+        matches!(affinity, None | Some(Affinity::Text | Affinity::Blob))
+    };
+    let none = |affinity: Option<Affinity>| matches!(affinity, None | Some(Affinity::Blob));
+    if numeric(left) && text_or_none(right) {
+        (None, Some(Affinity::Numeric))
+    } else if numeric(right) && text_or_none(left) {
+        (Some(Affinity::Numeric), None)
+    } else if left == Some(Affinity::Text) && none(right) {
+        (None, Some(Affinity::Text))
+    } else if right == Some(Affinity::Text) && none(left) {
+        (Some(Affinity::Text), None)
+    } else {
+        (None, None)
+    }
+}
+
+/// Analyse descendante d'une commande découpée en lexèmes
+pub struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+    /// expressions en cours d'analyse, imbriquées les unes dans les autres
+    depth: usize,
+}
+
+impl Parser {
+    pub fn new(input: &str) -> Result<Self, CommandError> {
+        Ok(Self {
+            tokens: tokenize(input)?,
+            position: 0,
+            depth: 0,
+        })
+    }
+
+    pub fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    /// Lexème situé `offset` lexèmes après le prochain
+    pub fn peek_at(&self, offset: usize) -> Option<&Token> {
+        self.tokens.get(self.position + offset)
+    }
+
+    pub fn next(&mut self) -> Result<Token, CommandError> {
+        let token = self
+            .tokens
+            .get(self.position)
+            .cloned()
+            .ok_or(CommandError::NotEnoughArguments)?;
+        self.position += 1;
+        Ok(token)
+    }
+
+    /// Consomme le mot-clé s'il est le prochain lexème
+    pub fn keyword(&mut self, keyword: &str) -> bool {
+        let found = matches!(self.peek(), Some(Token::Identifier(name)) if name.eq_ignore_ascii_case(keyword));
+        self.position += found as usize;
+        found
+    }
+
+    /// Consomme le symbole s'il est le prochain lexème
+    pub fn symbol(&mut self, symbol: &str) -> bool {
+        let found = matches!(self.peek(), Some(Token::Symbol(found)) if *found == symbol);
+        self.position += found as usize;
+        found
+    }
+
+    pub fn expect_symbol(&mut self, symbol: &str) -> Result<(), CommandError> {
+        match self.next()? {
+            Token::Symbol(found) if found == symbol => Ok(()),
+            token => Err(CommandError::UnexpectedToken(format!("{token:?}"))),
+        }
+    }
+
+    /// Vérifie que toute la commande a été lue
+    pub fn end(&mut self) -> Result<(), CommandError> {
+        self.symbol(";");
+        match self.peek() {
+            None => Ok(()),
+            Some(_) => Err(CommandError::TooManyArguments),
+        }
+    }
+
+    /// Liste d'expressions séparées par des virgules
+    pub fn expressions(&mut self) -> Result<Vec<Expression>, CommandError> {
+        let mut expressions = vec![self.expression()?];
+        while self.symbol(",") {
+            expressions.push(self.expression()?);
+        }
+        Ok(expressions)
+    }
+
+    pub fn expression(&mut self) -> Result<Expression, CommandError> {
+        // chaque niveau d'imbrication prend de la place sur la pile
+        if self.depth == MAX_DEPTH {
+            return Err(CommandError::ExpressionTooDeep);
+        }
+        self.depth += 1;
+        let expression = self.comparison();
+        self.depth -= 1;
+        expression
+    }
+
+    fn comparison(&mut self) -> Result<Expression, CommandError> {
+        let left = self.extraction()?;
+        // `<colonne> MATCH <requête>`, recherche dans une table fts
+        if self.keyword("match") {
+            return Ok(Expression::Function {
+                name: "match".to_string(),
+                arguments: vec![left, self.extraction()?],
+            });
+        }
+        let operator = match self.peek() {
+            Some(Token::Symbol(symbol)) => Comparison::from_symbol(symbol),
+            _ => None,
+        };
+        let Some(operator) = operator else {
+            return Ok(left);
+        };
+        self.position += 1;
+        Ok(Expression::Comparison {
+            left: Box::new(left),
+            operator,
+            right: Box::new(self.extraction()?),
+        })
+    }
+
+    /// `json -> chemin` et `json ->> chemin`, évalués de gauche à droite
+    fn extraction(&mut self) -> Result<Expression, CommandError> {
+        let mut expression = self.primary()?;
+        loop {
+            let name = match self.peek() {
+                Some(Token::Symbol(symbol @ ("->" | "->>"))) => symbol.to_string(),
+                _ => return Ok(expression),
+            };
+            self.position += 1;
+            expression = Expression::Function {
+                name,
+                arguments: vec![expression, self.primary()?],
+            };
+        }
+    }
+
+    fn primary(&mut self) -> Result<Expression, CommandError> {
+        if self.symbol("(") {
+            let expression = self.expression()?;
+            self.expect_symbol(")")?;
+            return Ok(expression);
+        }
+        if matches!(self.peek(), Some(Token::Identifier(name)) if name.eq_ignore_ascii_case("cast"))
+            && self.peek_at(1) == Some(&Token::Symbol("("))
+        {
+            self.position += 2;
+            return self.cast();
+        }
+        if let Some(Token::Identifier(name)) = self.peek() {
+            let name = name.clone();
+            if self.peek_at(1) == Some(&Token::Symbol("(")) {
+                self.position += 2;
+                return self.call(name);
+            }
+            if !is_literal_keyword(&name) {
+                self.position += 1;
+                return Ok(Expression::Column(name));
+            }
+        }
+        if let Some(Token::Parameter(placeholder)) = self.peek() {
+            let expression = Expression::Parameter(placeholder.clone());
+            self.position += 1;
+            return Ok(expression);
+        }
+        if self.symbol("-") {
+            return self.negative();
+        }
+        Ok(Expression::Literal(self.literal()?))
+    }
+
+    /// `cast(<expression> as <type>)`, après la parenthèse ouvrante
+    fn cast(&mut self) -> Result<Expression, CommandError> {
+        let expression = self.expression()?;
+        if !self.keyword("as") {
+            return Err(CommandError::UnexpectedToken(format!("{:?}", self.next()?)));
+        }
+        let affinity = Affinity::from_type_name(&self.type_name()?);
+        self.expect_symbol(")")?;
+        Ok(Expression::Cast {
+            expression: Box::new(expression),
+            affinity,
+        })
+    }
+
+    /// Nombre négatif, après le signe moins
+    fn negative(&mut self) -> Result<Expression, CommandError> {
+        match self.next()? {
+            Token::Integer(value) => Ok(Expression::Literal(Value::Integer(-value))),
+            Token::Magnitude(value) => Ok(Expression::Literal(match 0_i64.checked_sub_unsigned(value) {
+                Some(value) => Value::Integer(value),
+                None => Value::Real(-(value as f64)),
+            })),
+            Token::Real(value) => Ok(Expression::Literal(Value::Real(-value))),
+            token => Err(CommandError::UnexpectedToken(format!("{token:?}"))),
+        }
+    }
+
+    /// Arguments d'un appel de fonction, après la parenthèse ouvrante
+    fn call(&mut self, name: String) -> Result<Expression, CommandError> {
+        // count(*) compte les lignes : aucun argument
+        if name.eq_ignore_ascii_case("count") && self.symbol("*") {
+            self.expect_symbol(")")?;
+            return Ok(Expression::Function {
+                name,
+                arguments: vec![],
+            });
+        }
+        let arguments = match self.symbol(")") {
+            true => vec![],
+            false => {
+                let arguments = self.expressions()?;
+                self.expect_symbol(")")?;
+                arguments
+            }
+        };
+        Ok(Expression::Function { name, arguments })
+    }
+
+    /// Nom de type : un ou plusieurs mots, suivis d'une taille facultative
+    /// comme dans `VARCHAR(255)` ou `DECIMAL(10, 2)`
+    pub fn type_name(&mut self) -> Result<String, CommandError> {
+        let mut words = vec![];
+        while let Some(Token::Identifier(word)) = self.peek() {
+            words.push(word.clone());
+            self.position += 1;
+        }
+        if words.is_empty() {
+            return Err(CommandError::UnexpectedToken(format!("{:?}", self.next()?)));
+        }
+        if self.symbol("(") {
+            while !self.symbol(")") {
+                match self.next()? {
+                    Token::Integer(_) | Token::Symbol(",") | Token::Symbol("+") | Token::Symbol("-") => {}
+                    token => return Err(CommandError::UnexpectedToken(format!("{token:?}"))),
+                }
+            }
+        }
+        Ok(words.join(" "))
+    }
+
+    /// Valeur écrite telle quelle dans la commande
+    fn literal(&mut self) -> Result<Value, CommandError> {
+        match self.next()? {
+            Token::Integer(value) => Ok(Value::Integer(value)),
+            Token::Magnitude(value) => Ok(Value::Real(value as f64)),
+            Token::Real(value) => Ok(Value::Real(value)),
+            Token::Text(value) => Ok(Value::Text(value)),
+            Token::Blob(value) => Ok(Value::Blob(value)),
+            Token::Identifier(name) => match name.to_ascii_lowercase().as_str() {
+                "null" => Ok(Value::Null),
+                "true" => Ok(Value::Boolean(true)),
+                "false" => Ok(Value::Boolean(false)),
+                // DATE '2024-01-31', TIME '12:00:00', TIMESTAMP '2024-01-31 12:00:00'
+                keyword @ ("date" | "time" | "timestamp") => {
+                    let Token::Text(text) = self.next()? else {
+                        return Err(CommandError::InvalidLiteral(name));
+                    };
+                    match keyword {
+                        "date" => Date::from_str(&text).map(Value::Date),
+                        "time" => Time::from_str(&text).map(Value::Time),
+                        _ => Timestamp::from_str(&text).map(Value::Timestamp),
+                    }
+                }
+                _ => Err(CommandError::UnexpectedToken(name)),
+            },
+            token => Err(CommandError::UnexpectedToken(format!("{token:?}"))),
+        }
+    }
+}
+
+/// Mots-clés qui commencent un littéral plutôt qu'un nom
+pub fn is_literal_keyword(name: &str) -> bool {
+    ["null", "true", "false", "date", "time", "timestamp"]
+        .iter()
+        .any(|keyword| name.eq_ignore_ascii_case(keyword))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    fn evaluate(input: &str) -> Result<Vec<Value>, CommandError> {
+        let mut parser = Parser::new(input)?;
+        let expressions = parser.expressions()?;
+        parser.end()?;
+        let functions = Functions::new();
+        let context = Context::new(&functions);
+        Ok(expressions
+            .iter()
+            .map(|expression| expression.evaluate(&context).expect("evaluation failed"))
+            .collect())
+    }
+
+    #[test]
+    fn test_literals() {
+        assert_eq!(
+            evaluate("1, -2.5, 'a', x'00', null, TRUE, date '2024-01-31', time '08:00', timestamp '1970-01-01T00:01'"),
+            Ok(vec![
+                Value::Integer(1),
+                Value::Real(-2.5),
+                Value::Text("a".to_string()),
+                Value::Blob(vec![0]),
+                Value::Null,
+                Value::Boolean(true),
+                Value::Date(Date(19_753)),
+                Value::Time(Time(28_800)),
+                Value::Timestamp(Timestamp(60)),
+            ])
+        );
+        assert_eq!(
+            evaluate("date '2024-02-30'"),
+            Err(CommandError::InvalidLiteral("2024-02-30".to_string()))
+        );
+        // le plus petit entier s'écrit sans passer par un flottant
+        assert_eq!(
+            evaluate("-9223372036854775808, 9223372036854775808, -18446744073709551615"),
+            Ok(vec![
+                Value::Integer(i64::MIN),
+                Value::Real(9_223_372_036_854_775_808.0),
+                Value::Real(-18_446_744_073_709_551_615.0),
+            ])
+        );
+        assert_eq!(evaluate("1 2"), Err(CommandError::TooManyArguments));
+        assert_eq!(evaluate("1,"), Err(CommandError::NotEnoughArguments));
+    }
+
+    #[test]
+    fn test_cast() {
+        assert_eq!(
+            evaluate("cast('12abc' as integer), CAST(1.9 AS INT), cast(5 as varchar(10)), cast(null as real), cast('3.0' as decimal(10, 2))"),
+            Ok(vec![
+                Value::Integer(12),
+                Value::Integer(1),
+                Value::Text("5".to_string()),
+                Value::Null,
+                Value::Integer(3),
+            ])
+        );
+        assert!(matches!(evaluate("cast(1 integer)"), Err(CommandError::UnexpectedToken(_))));
+        assert_eq!(evaluate("cast(1 as integer"), Err(CommandError::NotEnoughArguments));
+    }
+
+    #[test]
+    fn test_comparison_affinity() {
+        // sans affinité, un nombre et un texte ne sont jamais égaux
+        assert_eq!(evaluate("12 = '12'"), Ok(vec![Value::Boolean(false)]));
+        // face à une valeur d'affinité numérique, le texte est converti
+        assert_eq!(
+            evaluate("cast(12 as integer) = '12', '12.0' = cast('12' as real), cast(12 as text) = 12"),
+            Ok(vec![Value::Boolean(true), Value::Boolean(true), Value::Boolean(true)])
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        let mut parser = Parser::new("1 <= 2.5, 'it''s' != NULL, cast(x'0A' as text) = true").unwrap();
+        let expressions = parser.expressions().unwrap();
+        assert_eq!(
+            expressions.iter().map(Expression::to_string).collect::<Vec<_>>(),
+            ["1 <= 2.5", "'it''s' <> NULL", "CAST(X'0A' AS TEXT) = TRUE"]
+        );
+    }
+
+    #[test]
+    fn test_bind() {
+        let mut parser = Parser::new("?, ?3 = :a, :a, ?, cast(@b as text)").unwrap();
+        let expressions = parser.expressions().unwrap();
+        let values = (1..=6).map(Value::Integer).collect::<Vec<_>>();
+        let mut parameters = Parameters::default();
+        let functions = Functions::new();
+        let bound = expressions
+            .iter()
+            .map(|expression| expression.bind(&mut parameters, &values[..5]).unwrap().evaluate(&Context::new(&functions)))
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        // ? vaut 1, ?3 vaut 3, :a prend le numéro 4, le ? suivant le 5 et @b le 6
+        assert_eq!(
+            bound,
+            [
+                Value::Integer(1),
+                Value::Boolean(false),
+                Value::Integer(4),
+                Value::Integer(5),
+                Value::Null,
+            ]
+        );
+        assert_eq!(parameters.len(), 6);
+        assert_eq!(parameters.index_of(":a"), Some(3));
+        assert_eq!(parameters.index_of("@b"), Some(5));
+        assert_eq!(parameters.index_of("a"), None);
+    }
+
+    #[test]
+    fn test_calls() {
+        let mut parser = Parser::new("count(*), Twice(id, 'a'), now(), name = 'x'").unwrap();
+        let expressions = parser.expressions().unwrap();
+        assert_eq!(
+            expressions[1],
+            Expression::Function {
+                name: "Twice".to_string(),
+                arguments: vec![
+                    Expression::Column("id".to_string()),
+                    Expression::Literal(Value::Text("a".to_string()))
+                ]
+            }
+        );
+        let written = expressions.iter().map(Expression::to_string).collect::<Vec<_>>();
+        assert_eq!(written, ["count(*)", "Twice(id, 'a')", "now()", "name = 'x'"]);
+        // seul count accepte *
+        assert!(Parser::new("sum(*)").unwrap().expression().is_err());
+
+        let mut functions = Functions::new();
+        functions.register_scalar("twice", 2, true, |arguments| {
+            Ok(Value::Text(format!("{}{}", arguments[0], arguments[1])))
+        });
+        let columns = [("id", "integer"), ("name", "text")];
+        let row = [Value::Integer(7), Value::Text("x".to_string())];
+        let context = Context::new(&functions).with_row(&columns, &row);
+        assert_eq!(expressions[1].evaluate(&context), Ok(Value::Text("7a".to_string())));
+        assert_eq!(expressions[3].evaluate(&context), Ok(Value::Boolean(true)));
+        assert_eq!(
+            expressions[2].evaluate(&context),
+            Err(EvaluationError::UnknownFunction("now".to_string()))
+        );
+        // la colonne entière impose son affinité au texte comparé
+        let id = Parser::new("id = '7'").unwrap().expression().unwrap();
+        assert_eq!(id.evaluate(&context), Ok(Value::Boolean(true)));
+
+        // les appels déterministes sur des constantes sont calculés d'avance
+        let folded = Parser::new("twice(1, twice(2, 3)) = id").unwrap().expression().unwrap();
+        assert_eq!(
+            folded.fold(&functions).unwrap().to_string(),
+            "'123' = id"
+        );
+        let mut calls = vec![];
+        let extracted = expressions[0].clone().extract_aggregates(&functions, &mut calls);
+        assert_eq!(extracted, Ok(Expression::Aggregate(0)));
+        assert_eq!(calls.len(), 1);
+    }
+
+    #[test]
+    fn test_comparisons() {
+        assert_eq!(
+            evaluate("1 < 1.5, 2 = 2.0, 'a' <> 'b', 3 >= null, date '2024-01-01' < date '2024-01-02', (true = 1)"),
+            Ok(vec![
+                Value::Boolean(true),
+                Value::Boolean(true),
+                Value::Boolean(true),
+                Value::Null,
+                Value::Boolean(true),
+                Value::Boolean(true),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_depth() {
+        // sans optimisation, la limite tient dans la pile de 8 Mio d'un
+        // thread principal mais pas dans celle d'un thread de test
+        let parse = thread::Builder::new().stack_size(8 << 20).spawn(|| {
+            let nested = |depth: usize| format!("{}1{}", "(".repeat(depth - 1), ")".repeat(depth - 1));
+            assert_eq!(evaluate(&nested(MAX_DEPTH)), Ok(vec![Value::Integer(1)]));
+            // la pile ne déborde pas, l'analyse s'arrête à la limite
+            assert_eq!(evaluate(&nested(MAX_DEPTH + 1)), Err(CommandError::ExpressionTooDeep));
+            assert_eq!(evaluate(&nested(3000)), Err(CommandError::ExpressionTooDeep));
+            let calls = format!("{}1{}", "abs(".repeat(MAX_DEPTH), ")".repeat(MAX_DEPTH));
+            assert_eq!(evaluate(&calls), Err(CommandError::ExpressionTooDeep));
+        });
+        parse.expect("spawn failed").join().expect("parse failed");
+    }
+}
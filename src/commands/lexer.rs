@@ -0,0 +1,220 @@
+use crate::commands::expression::MAX_PARAMETERS;
+use crate::errors::CommandError;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Token {
+    Integer(i64),
+    /// entier trop grand pour un `i64`, lu comme un flottant sauf s'il est
+    /// l'opposé du plus petit entier
+    Magnitude(u64),
+    Real(f64),
+    /// chaîne entre apostrophes
+    Text(String),
+    /// blob écrit `X'0A1B'`
+    Blob(Vec<u8>),
+    /// mot-clé ou nom, éventuellement entre guillemets
+    Identifier(String),
+    Symbol(&'static str),
+    /// paramètre dont la valeur est liée à l'exécution
+    Parameter(Placeholder),
+}
+
+/// Écritures d'un paramètre, comme dans SQLite
+#[derive(Debug, PartialEq, Clone)]
+pub enum Placeholder {
+    /// `?` : le paramètre qui suit le plus grand numéro déjà vu
+    Next,
+    /// `?NNN` : le paramètre de ce numéro, à partir de 1
+    Index(usize),
+    /// `:nom`, `@nom` ou `$nom`, le préfixe fait partie du nom
+    Name(String),
+}
+
+/// Symboles reconnus, les plus longs d'abord
+const SYMBOLS: [&str; 20] = [
+    "->>", "->", "<=", ">=", "<>", "!=", "==", "||", "(", ")", ",", "=", "<", ">", "+", "-", "*", "/", "%", ";",
+];
+
+/// Découpe une commande en lexèmes
+pub fn tokenize(input: &str) -> Result<Vec<Token>, CommandError> {
+    let mut tokens = vec![];
+    let mut rest = input;
+    loop {
+        rest = rest.trim_start();
+        let Some(first) = rest.chars().next() else {
+            return Ok(tokens);
+        };
+        let (token, size) = match first {
+            '0'..='9' | '.' => number(rest)?,
+            '\'' => {
+                let (text, size) = quoted(rest, '\'')?;
+                (Token::Text(text), size)
+            }
+            '"' => {
+                let (name, size) = quoted(rest, '"')?;
+                (Token::Identifier(name), size)
+            }
+            'x' | 'X' if rest[1..].starts_with('\'') => {
+                let (hex, size) = quoted(&rest[1..], '\'')?;
+                (Token::Blob(blob(&hex)?), size + 1)
+            }
+            c if c.is_alphabetic() || c == '_' => {
+                let size = name_size(rest);
+                (Token::Identifier(rest[..size].to_string()), size)
+            }
+            '?' => {
+                let size = 1 + rest[1..]
+                    .find(|c: char| !c.is_ascii_digit())
+                    .unwrap_or(rest.len() - 1);
+                let placeholder = match &rest[1..size] {
+                    "" => Placeholder::Next,
+                    digits => match digits.parse() {
+                        Ok(index) if (1..=MAX_PARAMETERS).contains(&index) => Placeholder::Index(index),
+                        _ => return Err(CommandError::InvalidLiteral(rest[..size].to_string())),
+                    },
+                };
+                (Token::Parameter(placeholder), size)
+            }
+            ':' | '@' | '$' if rest[1..].starts_with(|c: char| c.is_alphanumeric() || c == '_') => {
+                let size = 1 + name_size(&rest[1..]);
+                (Token::Parameter(Placeholder::Name(rest[..size].to_string())), size)
+            }
+            _ => match SYMBOLS.iter().find(|symbol| rest.starts_with(**symbol)) {
+                Some(symbol) => (Token::Symbol(symbol), symbol.len()),
+                None => return Err(CommandError::UnexpectedToken(first.to_string())),
+            },
+        };
+        tokens.push(token);
+        rest = &rest[size..];
+    }
+}
+
+/// Taille d'un nom fait de lettres, de chiffres et de `_`
+fn name_size(input: &str) -> usize {
+    input
+        .find(|c: char| !c.is_alphanumeric() && c != '_')
+        .unwrap_or(input.len())
+}
+
+/// Entier ou flottant, un entier trop grand devient un flottant
+fn number(input: &str) -> Result<(Token, usize), CommandError> {
+    let digits = |from: usize| {
+        input[from..]
+            .find(|c: char| !c.is_ascii_digit())
+            .map_or(input.len(), |size| from + size)
+    };
+    let mut size = digits(0);
+    let mut real = false;
+    if input[size..].starts_with('.') {
+        real = true;
+        size = digits(size + 1);
+    }
+    if input[size..].starts_with(['e', 'E']) {
+        real = true;
+        let sign = input[size + 1..].starts_with(['+', '-']) as usize;
+        size = digits(size + 1 + sign);
+    }
+    let text = &input[..size];
+    let invalid = || CommandError::InvalidLiteral(text.to_string());
+    let token = match (text.parse::<i64>(), text.parse::<u64>()) {
+        (Ok(value), _) if !real => Token::Integer(value),
+        (_, Ok(value)) if !real => Token::Magnitude(value),
+        _ => Token::Real(text.parse().map_err(|_| invalid())?),
+    };
+    Ok((token, size))
+}
+
+/// Contenu entre deux délimiteurs, un délimiteur doublé en fait partie
+fn quoted(input: &str, delimiter: char) -> Result<(String, usize), CommandError> {
+    let mut content = String::new();
+    let mut chars = input.char_indices().skip(1).peekable();
+    while let Some((index, c)) = chars.next() {
+        if c == delimiter && chars.next_if(|(_, next)| *next == delimiter).is_none() {
+            return Ok((content, index + 1));
+        }
+        content.push(c);
+    }
+    Err(CommandError::InvalidLiteral(input.to_string()))
+}
+
+fn blob(hex: &str) -> Result<Vec<u8>, CommandError> {
+    let invalid = || CommandError::InvalidLiteral(format!("X'{hex}'"));
+    if !hex.len().is_multiple_of(2) || !hex.is_ascii() {
+        return Err(invalid());
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(&hex[index..index + 2], 16).map_err(|_| invalid()))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokenize() {
+        assert_eq!(
+            tokenize("select 1, 2.5, 'it''s', X'0aFF', \"my name\" <= 1e3"),
+            Ok(vec![
+                Token::Identifier("select".to_string()),
+                Token::Integer(1),
+                Token::Symbol(","),
+                Token::Real(2.5),
+                Token::Symbol(","),
+                Token::Text("it's".to_string()),
+                Token::Symbol(","),
+                Token::Blob(vec![0x0A, 0xFF]),
+                Token::Symbol(","),
+                Token::Identifier("my name".to_string()),
+                Token::Symbol("<="),
+                Token::Real(1000.0),
+            ])
+        );
+        // le nom x seul reste un identifiant
+        assert_eq!(tokenize("x"), Ok(vec![Token::Identifier("x".to_string())]));
+        assert_eq!(tokenize("99999999999999999999"), Ok(vec![Token::Real(1e20)]));
+        assert_eq!(tokenize("9223372036854775808"), Ok(vec![Token::Magnitude(1 << 63)]));
+        assert_eq!(tokenize("'abc"), Err(CommandError::InvalidLiteral("'abc".to_string())));
+        assert_eq!(tokenize("X'ABC'"), Err(CommandError::InvalidLiteral("X'ABC'".to_string())));
+        assert_eq!(tokenize("1 # 2"), Err(CommandError::UnexpectedToken("#".to_string())));
+        // le symbole le plus long l'emporte
+        assert_eq!(
+            tokenize("a->>b->-1"),
+            Ok(vec![
+                Token::Identifier("a".to_string()),
+                Token::Symbol("->>"),
+                Token::Identifier("b".to_string()),
+                Token::Symbol("->"),
+                Token::Symbol("-"),
+                Token::Integer(1),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_tokenize_parameters() {
+        assert_eq!(
+            tokenize("?, ?12,:name, @x_1 $y"),
+            Ok(vec![
+                Token::Parameter(Placeholder::Next),
+                Token::Symbol(","),
+                Token::Parameter(Placeholder::Index(12)),
+                Token::Symbol(","),
+                Token::Parameter(Placeholder::Name(":name".to_string())),
+                Token::Symbol(","),
+                Token::Parameter(Placeholder::Name("@x_1".to_string())),
+                Token::Parameter(Placeholder::Name("$y".to_string())),
+            ])
+        );
+        assert_eq!(tokenize("?0"), Err(CommandError::InvalidLiteral("?0".to_string())));
+        // comme dans SQLite, pas plus de 32766 paramètres
+        assert_eq!(tokenize("?32766"), Ok(vec![Token::Parameter(Placeholder::Index(32766))]));
+        assert_eq!(tokenize("?32767"), Err(CommandError::InvalidLiteral("?32767".to_string())));
+        assert_eq!(
+            tokenize("?18446744073709551615"),
+            Err(CommandError::InvalidLiteral("?18446744073709551615".to_string()))
+        );
+        assert_eq!(tokenize(": name"), Err(CommandError::UnexpectedToken(":".to_string())));
+    }
+}
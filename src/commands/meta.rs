@@ -3,7 +3,9 @@ use crate::database::Database;
 
 #[derive(Debug, PartialEq)]
 pub enum MetaCommand {
-    Exit
+    Exit,
+    /// liste les tables et les vues
+    Schema,
 }
 
 impl TryFromStr for MetaCommand {
@@ -12,17 +14,24 @@ impl TryFromStr for MetaCommand {
     fn try_from_str(command: &str) -> Result<Option<Self>, Self::Error> {
         match command {
             ".exit" => Ok(Some(MetaCommand::Exit)),
+            ".schema" => Ok(Some(MetaCommand::Schema)),
             _ => Ok(None)
         }
     }
 }
 
 impl Execute for MetaCommand {
-    fn execute(self, _: &mut Database) -> Result<(), ExecutionError> {
+    fn execute(self, database: &mut Database) -> Result<(), ExecutionError> {
         match self {
             MetaCommand::Exit => {
                 std::process::exit(0);
             }
+            MetaCommand::Schema => {
+                for command in database.schema() {
+                    println!("{command}");
+                }
+                Ok(())
+            }
         }
     }
 }
@@ -33,5 +42,9 @@ fn test_parse_meta_command() {
         MetaCommand::try_from_str(".exit"),
         Ok(Some(MetaCommand::Exit))
     );
+    assert_eq!(
+        MetaCommand::try_from_str(".schema"),
+        Ok(Some(MetaCommand::Schema))
+    );
     assert_eq!(MetaCommand::try_from_str("unknown command"), Ok(None));
 }
@@ -1,10 +1,16 @@
 use crate::commands::meta::MetaCommand;
-use crate::commands::sql::SqlCommand;
+use crate::data::Value;
 use crate::database::Database;
 use crate::errors::{CommandError, ExecutionError};
 
+pub use crate::commands::expression::{Expression, Parameters};
+pub use crate::commands::sql::SqlCommand;
+
 mod sql;
 mod meta;
+mod pragma;
+mod lexer;
+mod expression;
 
 #[derive(Debug, PartialEq)]
 pub enum Command<'a> {
@@ -38,7 +44,20 @@ pub trait Execute {
     fn execute(self, database: &mut Database) -> Result<(), ExecutionError>;
 }
 
-pub fn parse(input: &str) -> Result<Command, CommandError> {
+/// Lignes renvoyées par une commande, aucune pour une écriture
+#[derive(Debug, PartialEq, Default)]
+pub struct ResultSet {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Value>>,
+}
+
+/// Analyse une commande SQL, les méta-commandes du REPL n'en sont pas
+pub fn parse_sql(input: &str) -> Result<SqlCommand, CommandError> {
+    SqlCommand::try_from_str(input)?
+        .ok_or_else(|| CommandError::UnknownCommand(input.trim().to_string()))
+}
+
+pub fn parse(input: &str) -> Result<Command<'_>, CommandError> {
     let input = input.trim_start();
     // on utilise le . comme discriminant de meta-commande
     let command = if input.starts_with(".") {
@@ -67,19 +86,22 @@ mod tests {
                     id: 1,
                     username: "name".to_string(),
                     email: "email@domain.tld".to_string()
-                })
+                }),
+                converted: false
             }))
         );
         assert_eq!(
             parse("select car"),
             Ok(Command::Sql(SqlCommand::Select {
-                table: TableName::Car
+                table: TableName::Car,
+                limit: None
             }))
         );
         assert_eq!(
             parse("create car"),
             Ok(Command::Sql(SqlCommand::Create {
-                table: TableName::Car
+                table: TableName::Car,
+                strict: false
             }))
         );
         assert_eq!(
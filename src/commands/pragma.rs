@@ -0,0 +1,90 @@
+use crate::commands::{CommandError, ExecutionError, ResultSet};
+use crate::data::Value;
+use crate::database::Database;
+use crate::pager::JournalMode;
+use std::str::FromStr;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Pragma {
+    /// Affiche ou change le mode de journalisation
+    JournalMode(Option<JournalMode>),
+    /// Recopie le journal WAL dans le fichier principal
+    WalCheckpoint,
+}
+
+impl FromStr for Pragma {
+    type Err = CommandError;
+
+    /// Analyse `nom` ou `nom = valeur`
+    fn from_str(payload: &str) -> Result<Self, Self::Err> {
+        let (name, value) = match payload.split_once('=') {
+            Some((name, value)) => (name.trim(), Some(value.trim())),
+            None => (payload.trim(), None),
+        };
+        if name.is_empty() {
+            return Err(CommandError::NotEnoughArguments);
+        }
+        match (name.to_ascii_lowercase().as_str(), value) {
+            ("journal_mode", None) => Ok(Pragma::JournalMode(None)),
+            ("journal_mode", Some(mode)) => Ok(Pragma::JournalMode(Some(mode.parse()?))),
+            ("wal_checkpoint", None) => Ok(Pragma::WalCheckpoint),
+            ("wal_checkpoint", Some(_)) => Err(CommandError::TooManyArguments),
+            _ => Err(CommandError::UnknownPragma(name.to_string())),
+        }
+    }
+}
+
+impl Pragma {
+    /// Applique le pragma, et renvoie la valeur qu'il lit s'il en lit une
+    pub fn run(&self, database: &mut Database) -> Result<ResultSet, ExecutionError> {
+        match self {
+            Pragma::JournalMode(mode) => {
+                if let Some(mode) = mode {
+                    database
+                        .set_journal_mode(*mode)
+                        .map_err(ExecutionError::Storage)?;
+                }
+                let mode = match database.journal_mode() {
+                    Some(mode) => mode.as_str(),
+                    None => "memory",
+                };
+                Ok(ResultSet {
+                    columns: vec!["journal_mode".to_string()],
+                    rows: vec![vec![Value::Text(mode.to_string())]],
+                })
+            }
+            Pragma::WalCheckpoint => {
+                database.checkpoint().map_err(ExecutionError::Storage)?;
+                Ok(ResultSet::default())
+            }
+        }
+    }
+}
+
+#[test]
+fn test_parse_pragma() {
+    assert_eq!(Pragma::from_str("journal_mode"), Ok(Pragma::JournalMode(None)));
+    assert_eq!(
+        Pragma::from_str("journal_mode=WAL"),
+        Ok(Pragma::JournalMode(Some(JournalMode::Wal)))
+    );
+    assert_eq!(
+        Pragma::from_str(" journal_mode =  delete "),
+        Ok(Pragma::JournalMode(Some(JournalMode::Delete)))
+    );
+    assert_eq!(Pragma::from_str("wal_checkpoint"), Ok(Pragma::WalCheckpoint));
+    // valeur inconnue
+    assert_eq!(
+        Pragma::from_str("journal_mode = memory"),
+        Err(CommandError::InvalidPragmaValue("memory".to_string()))
+    );
+    // pragma inconnu
+    assert_eq!(
+        Pragma::from_str("page_size"),
+        Err(CommandError::UnknownPragma("page_size".to_string()))
+    );
+    assert_eq!(
+        Pragma::from_str("wal_checkpoint = 1"),
+        Err(CommandError::TooManyArguments)
+    );
+}
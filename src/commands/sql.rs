@@ -1,13 +1,56 @@
-use crate::commands::{CommandError, Execute, ExecutionError, TryFromStr};
+use crate::commands::expression::{is_literal_keyword, AggregateCall, Context, Expression, Parameters, Parser};
+use crate::commands::lexer::Token;
+use crate::commands::pragma::Pragma;
+use crate::commands::{CommandError, Execute, ExecutionError, ResultSet, TryFromStr};
 use crate::database::Database;
-use std::str::{FromStr, SplitWhitespace};
-use crate::data::{Car, Record, TableName, User};
+use std::cmp::Ordering;
+use std::str::FromStr;
+use crate::data::{Affinity, Car, Record, TableName, User, Value};
+use crate::errors::EvaluationError;
+use crate::fts::{misused_match, Search, HIDDEN_COLUMNS};
+use crate::vtab::{self, Schema};
+use crate::functions::{Function, Functions};
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum SqlCommand {
-    Insert { data: Record },
-    Select { table: TableName },
-    Create { table: TableName },
+    /// `converted` indique qu'une valeur a dû être convertie vers le type de
+    /// sa colonne, ce qu'une table STRICT refuse
+    Insert { data: Record, converted: bool },
+    /// insert dont les valeurs dépendent de paramètres, l'enregistrement
+    /// n'est construit qu'une fois les paramètres liés
+    InsertValues { table: TableName, values: Vec<Expression> },
+    Select { table: TableName, limit: Option<usize> },
+    /// select sans table, qui évalue une liste d'expressions
+    SelectExpressions { expressions: Vec<Expression> },
+    /// select <expressions> from <source> [where <condition>] [limit n]
+    Query {
+        expressions: Vec<Expression>,
+        source: Source,
+        filter: Option<Expression>,
+        limit: Option<usize>,
+    },
+    Create { table: TableName, strict: bool },
+    /// create virtual table <nom> using <module>(<arguments>)
+    CreateVirtual { name: String, module: String, arguments: Vec<String> },
+    /// create view <nom> as <select>, le select gardé tel qu'écrit
+    CreateView { name: String, select: String },
+    /// drop view [if exists] <nom>
+    DropView { name: String, if_exists: bool },
+    Begin,
+    Commit,
+    Rollback,
+    Savepoint { name: String },
+    Release { name: String },
+    RollbackTo { name: String },
+    Pragma(Pragma),
+}
+
+/// Ce qui fournit les lignes d'un select
+#[derive(Debug, PartialEq, Clone)]
+pub enum Source {
+    Table(TableName),
+    /// fonction table, comme `json_each('[1, 2]')`
+    Function { name: String, arguments: Vec<Expression> },
 }
 
 impl TryFromStr for SqlCommand {
@@ -24,24 +67,66 @@ impl TryFromStr for SqlCommand {
                 let command = &input[0..first_space_index];
                 let payload = &input[first_space_index + 1..];
                 match command {
+                    "insert" if payload.split_whitespace().next().is_some_and(|word| word.eq_ignore_ascii_case("into")) => {
+                        insert_into(&mut Parser::new(payload)?).map(Some)
+                    }
                     "insert" => {
                         // création d'un itérateur sur les espaces blancs
-                        let parameters = payload.split_whitespace();
-                        let data = Record::from_parameters(parameters)?;
-
-                        Ok(Some(SqlCommand::Insert { data }))
+                        let mut parameters = payload.split_whitespace();
+                        let table = parameters
+                            .next()
+                            .ok_or(CommandError::NotEnoughArguments)?
+                            .to_string();
+                        let table = TableName::from_str(&table)?;
+                        let values = parameters.map(parameter).collect::<Vec<_>>();
+                        check_arguments(&table, values.len())?;
+                        let command = SqlCommand::InsertValues { table, values };
+                        // sans paramètre, l'enregistrement est construit tout de suite
+                        match command.parameters()?.is_empty() {
+                            true => command.bind(&[]).map(Some),
+                            false => Ok(Some(command)),
+                        }
                     }
                     "select" => {
+                        // select <table> [limit n] : toutes les colonnes de la table
+                        let mut parser = Parser::new(payload)?;
+                        let table_name = matches!(parser.peek(), Some(Token::Identifier(name)) if !is_literal_keyword(name));
+                        let limit_or_end = match parser.peek_at(1) {
+                            Some(Token::Identifier(keyword)) => keyword.eq_ignore_ascii_case("limit"),
+                            token => token.is_none(),
+                        };
+                        if !table_name || !limit_or_end {
+                            return query(&mut parser).map(Some);
+                        }
                         let mut parameters = payload.split_whitespace();
                         let table = parameters
                             .next()
                             .ok_or(CommandError::NotEnoughArguments)?
                             .to_string();
-                        let table = TableName::from_str(&table)?;
+                        // une vue ou une table virtuelle n'est connue qu'à l'exécution
+                        let table = TableName::from_name(&table);
+                        // clause limit optionnelle
+                        let limit = match parameters.next() {
+                            Some(keyword) if keyword.eq_ignore_ascii_case("limit") => Some(
+                                parameters
+                                    .next()
+                                    .ok_or(CommandError::NotEnoughArguments)?
+                                    .parse()
+                                    .map_err(|_| CommandError::ExpectingInteger)?,
+                            ),
+                            Some(_) => return Err(CommandError::TooManyArguments)?,
+                            None => None,
+                        };
                         if parameters.next().is_some() {
                             return Err(CommandError::TooManyArguments)?;
                         }
-                        Ok(Some(SqlCommand::Select { table }))
+                        Ok(Some(SqlCommand::Select { table, limit }))
+                    }
+                    "create" if payload.split_whitespace().next().is_some_and(|word| word.eq_ignore_ascii_case("virtual")) => {
+                        create_virtual(&mut Parser::new(payload)?).map(Some)
+                    }
+                    "create" if first_word(payload).0.eq_ignore_ascii_case("view") => {
+                        create_view(first_word(payload).1).map(Some)
                     }
                     "create" => {
                         let mut parameters = payload.split_whitespace();
@@ -50,10 +135,86 @@ impl TryFromStr for SqlCommand {
                             .ok_or(CommandError::NotEnoughArguments)?
                             .to_string();
                         let table = TableName::from_str(&table)?;
+                        // create <table> [strict]
+                        let strict = match parameters.next() {
+                            Some(option) if option.eq_ignore_ascii_case("strict") => true,
+                            Some(_) => return Err(CommandError::TooManyArguments)?,
+                            None => false,
+                        };
                         if parameters.next().is_some() {
                             return Err(CommandError::TooManyArguments)?;
                         }
-                        Ok(Some(SqlCommand::Create { table }))
+                        Ok(Some(SqlCommand::Create { table, strict }))
+                    }
+                    "rollback" => {
+                        // rollback [transaction] [to [savepoint] <nom>]
+                        let mut parameters = payload.split_whitespace().peekable();
+                        parameters.next_if(|keyword| keyword.eq_ignore_ascii_case("transaction"));
+                        match parameters.next() {
+                            None => Ok(Some(SqlCommand::Rollback)),
+                            Some(keyword) if keyword.eq_ignore_ascii_case("to") => {
+                                parameters
+                                    .next_if(|keyword| keyword.eq_ignore_ascii_case("savepoint"));
+                                let name = parameters
+                                    .next()
+                                    .ok_or(CommandError::NotEnoughArguments)?
+                                    .to_string();
+                                if parameters.next().is_some() {
+                                    return Err(CommandError::TooManyArguments)?;
+                                }
+                                Ok(Some(SqlCommand::RollbackTo { name }))
+                            }
+                            Some(_) => Err(CommandError::TooManyArguments)?,
+                        }
+                    }
+                    "savepoint" | "release" => {
+                        // release [savepoint] <nom>
+                        let mut parameters = payload.split_whitespace().peekable();
+                        if command == "release" {
+                            parameters
+                                .next_if(|keyword| keyword.eq_ignore_ascii_case("savepoint"));
+                        }
+                        let name = parameters
+                            .next()
+                            .ok_or(CommandError::NotEnoughArguments)?
+                            .to_string();
+                        if parameters.next().is_some() {
+                            return Err(CommandError::TooManyArguments)?;
+                        }
+                        match command {
+                            "savepoint" => Ok(Some(SqlCommand::Savepoint { name })),
+                            _ => Ok(Some(SqlCommand::Release { name })),
+                        }
+                    }
+                    "begin" | "commit" => {
+                        // le mot-clé transaction est facultatif
+                        let mut parameters = payload.split_whitespace();
+                        if !parameters
+                            .next()
+                            .is_some_and(|keyword| keyword.eq_ignore_ascii_case("transaction"))
+                            || parameters.next().is_some()
+                        {
+                            return Err(CommandError::TooManyArguments)?;
+                        }
+                        SqlCommand::try_from_str(command)
+                    }
+                    "pragma" => Ok(Some(SqlCommand::Pragma(Pragma::from_str(payload)?))),
+                    // seules les vues peuvent être supprimées
+                    "drop" if first_word(payload).0.eq_ignore_ascii_case("view") => {
+                        let mut parameters = payload.split_whitespace().skip(1).peekable();
+                        let if_exists = parameters.next_if(|keyword| keyword.eq_ignore_ascii_case("if")).is_some();
+                        if if_exists && parameters.next_if(|keyword| keyword.eq_ignore_ascii_case("exists")).is_none() {
+                            return Err(CommandError::NotEnoughArguments);
+                        }
+                        let name = parameters
+                            .next()
+                            .ok_or(CommandError::NotEnoughArguments)?
+                            .trim_end_matches(';')
+                            .to_string();
+                        if parameters.next().is_some() {
+                            return Err(CommandError::TooManyArguments)?;
+                        }
+                        Ok(Some(SqlCommand::DropView { name, if_exists }))
                     }
                     _ => Ok(None),
                 }
@@ -62,70 +223,858 @@ impl TryFromStr for SqlCommand {
                 "insert" => Err(CommandError::NotEnoughArguments)?,
                 "select" => Err(CommandError::NotEnoughArguments)?,
                 "create" => Err(CommandError::NotEnoughArguments)?,
+                "begin" => Ok(Some(SqlCommand::Begin)),
+                "commit" => Ok(Some(SqlCommand::Commit)),
+                "rollback" => Ok(Some(SqlCommand::Rollback)),
+                "savepoint" => Err(CommandError::NotEnoughArguments)?,
+                "release" => Err(CommandError::NotEnoughArguments)?,
+                "pragma" => Err(CommandError::NotEnoughArguments)?,
                 _ => Ok(None),
             },
         }
     }
 }
 
-impl Execute for SqlCommand {
-    fn execute(self, database: &mut Database) -> Result<(), ExecutionError> {
+impl SqlCommand {
+    /// Paramètres de la commande, dans leur ordre d'apparition
+    pub fn parameters(&self) -> Result<Parameters, CommandError> {
+        let mut parameters = Parameters::default();
+        self.bind_with(&mut parameters, &[])?;
+        Ok(parameters)
+    }
+
+    /// Commande où chaque paramètre est remplacé par la valeur de même
+    /// numéro, NULL s'il en manque
+    pub fn bind(&self, values: &[Value]) -> Result<SqlCommand, CommandError> {
+        match self.bind_with(&mut Parameters::default(), values)? {
+            // les lignes d'une table virtuelle sont construites par son module
+            command @ SqlCommand::InsertValues { table: TableName::Virtual(_), .. } => Ok(command),
+            // l'enregistrement n'est construit que si toutes les valeurs sont
+            // connues, sinon elles sont calculées à l'exécution
+            SqlCommand::InsertValues { table, values } => {
+                let literals = values
+                    .iter()
+                    .map(|value| match value {
+                        Expression::Literal(value) => Some(value.clone()),
+                        _ => None,
+                    })
+                    .collect::<Option<Vec<_>>>();
+                match literals {
+                    Some(literals) => {
+                        let (data, converted) = Record::from_values(table, literals)?;
+                        Ok(SqlCommand::Insert { data, converted })
+                    }
+                    None => Ok(SqlCommand::InsertValues { table, values }),
+                }
+            }
+            command => Ok(command),
+        }
+    }
+
+    fn bind_with(&self, parameters: &mut Parameters, values: &[Value]) -> Result<SqlCommand, CommandError> {
+        let bind = |expressions: &Vec<Expression>, parameters: &mut Parameters| {
+            expressions
+                .iter()
+                .map(|expression| expression.bind(parameters, values))
+                .collect::<Result<_, _>>()
+        };
+        Ok(match self {
+            SqlCommand::InsertValues { table, values } => SqlCommand::InsertValues {
+                table: table.clone(),
+                values: bind(values, parameters)?,
+            },
+            SqlCommand::SelectExpressions { expressions } => SqlCommand::SelectExpressions {
+                expressions: bind(expressions, parameters)?,
+            },
+            SqlCommand::Query {
+                expressions,
+                source,
+                filter,
+                limit,
+            } => SqlCommand::Query {
+                expressions: bind(expressions, parameters)?,
+                source: match source {
+                    Source::Table(table) => Source::Table(table.clone()),
+                    Source::Function { name, arguments } => Source::Function {
+                        name: name.clone(),
+                        arguments: bind(arguments, parameters)?,
+                    },
+                },
+                filter: filter
+                    .as_ref()
+                    .map(|filter| filter.bind(parameters, values))
+                    .transpose()?,
+                limit: *limit,
+            },
+            command => command.clone(),
+        })
+    }
+
+    /// Nom des colonnes du résultat : celui de la table, ou l'écriture de
+    /// l'expression telle que dans la commande, paramètres compris
+    pub fn columns(&self) -> Vec<String> {
+        match self {
+            // celles d'une vue ou d'une table virtuelle, connues à l'exécution
+            SqlCommand::Select { table: TableName::Virtual(_), .. } => vec!["*".to_string()],
+            SqlCommand::Select { table, .. } => {
+                table.columns().iter().map(|(name, _)| name.to_string()).collect()
+            }
+            SqlCommand::SelectExpressions { expressions } | SqlCommand::Query { expressions, .. } => {
+                expressions.iter().map(Expression::to_string).collect()
+            }
+            _ => vec![],
+        }
+    }
+
+    /// La commande ne fait que lire la base
+    pub fn is_query(&self) -> bool {
+        matches!(
+            self,
+            SqlCommand::Select { .. } | SqlCommand::SelectExpressions { .. } | SqlCommand::Query { .. }
+        )
+    }
+
+    /// Exécute une commande qui ne fait que lire, `None` pour une commande
+    /// qui modifie la base
+    pub fn query(&self, database: &Database) -> Option<Result<ResultSet, ExecutionError>> {
+        match self {
+            // les lignes d'une table de l'application telles qu'elles sont rangées
+            SqlCommand::Select { table, limit } if !matches!(table, TableName::Virtual(_)) => {
+                Some(select(database, table, *limit).map(|rows| ResultSet {
+                    columns: self.columns(),
+                    rows,
+                }))
+            }
+            command => command.plan(database).map(|plan| {
+                let plan = plan?;
+                let rows = plan.rows(database)?.collect::<Result<_, _>>()?;
+                Ok(ResultSet {
+                    columns: plan.names,
+                    rows,
+                })
+            }),
+        }
+    }
+
+    /// Plan d'une commande qui ne fait que lire, `None` pour une commande
+    /// qui modifie la base. `select <nom>` lit toutes les colonnes d'une vue
+    /// ou d'une table virtuelle.
+    fn plan(&self, database: &Database) -> Option<Result<Plan, ExecutionError>> {
         match self {
-            SqlCommand::Insert { data } => {
-                database.insert(data).map_err(ExecutionError::Insertion)?;
-                println!("Record inserted successfully");
+            SqlCommand::Select { table, limit } => Some(Plan::new(
+                database,
+                &[Expression::Wildcard],
+                Some(&Source::Table(table.clone())),
+                None,
+                *limit,
+            )),
+            SqlCommand::SelectExpressions { expressions } => Some(Plan::new(database, expressions, None, None, None)),
+            SqlCommand::Query {
+                expressions,
+                source,
+                filter,
+                limit,
+            } => Some(Plan::new(database, expressions, Some(source), filter.as_ref(), *limit)),
+            _ => None,
+        }
+    }
+
+    /// Exécute la commande et renvoie les lignes qu'elle produit
+    pub fn run(&self, database: &mut Database) -> Result<ResultSet, ExecutionError> {
+        if let Some(result) = self.query(database) {
+            return result;
+        }
+        match self {
+            SqlCommand::Insert { data, converted } => {
+                database
+                    .insert_converted(data.clone(), *converted)
+                    .map_err(ExecutionError::Insertion)?;
             }
-            SqlCommand::Select { table } => {
-                for user in database.select(table).map_err(ExecutionError::Select)? {
-                    println!("{:?}", user);
+            SqlCommand::Create { table, strict } => {
+                match strict {
+                    true => database.create_strict_table(table.clone()),
+                    false => database.create_table(table.clone()),
                 }
+                .map_err(ExecutionError::Create)?;
+            }
+            SqlCommand::Begin => {
+                database.begin().map_err(ExecutionError::Transaction)?;
+            }
+            SqlCommand::Commit => {
+                database.commit().map_err(ExecutionError::Transaction)?;
+            }
+            SqlCommand::Rollback => {
+                database.rollback().map_err(ExecutionError::Transaction)?;
             }
-            SqlCommand::Create { table } => {
-                database.create_table(table).map_err(ExecutionError::Create)?;
-                println!("Table created successfully");
+            SqlCommand::Savepoint { name } => {
+                database.savepoint(name.clone()).map_err(ExecutionError::Transaction)?;
             }
+            SqlCommand::Release { name } => {
+                database.release(name).map_err(ExecutionError::Transaction)?;
+            }
+            SqlCommand::RollbackTo { name } => {
+                database.rollback_to(name).map_err(ExecutionError::Transaction)?;
+            }
+            SqlCommand::CreateVirtual { name, module, arguments } => {
+                database
+                    .create_virtual_table(name, module, arguments)
+                    .map_err(ExecutionError::Create)?;
+            }
+            SqlCommand::CreateView { name, select } => {
+                // une vue qui ne peut pas être lue n'est pas créée, ce qui
+                // interdit aussi qu'elle passe par elle-même ; seule sa
+                // première ligne est lue, la source pouvant être sans fin
+                view(database, select)?.rows(database)?.next().transpose()?;
+                database.create_view(name, select).map_err(ExecutionError::Create)?;
+            }
+            SqlCommand::DropView { name, if_exists } => {
+                if !*if_exists || database.view(name).is_some() {
+                    database.drop_view(name).map_err(ExecutionError::Create)?;
+                }
+            }
+            SqlCommand::Pragma(pragma) => return pragma.run(database),
+            // les paramètres non liés valent NULL
+            SqlCommand::InsertValues { table, values } => {
+                let context = Context::new(database.functions());
+                let values = values
+                    .iter()
+                    .map(|value| value.evaluate(&context))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(ExecutionError::Evaluation)?;
+                if let TableName::Virtual(name) = table {
+                    let count = |columns: usize| match values.len().cmp(&columns) {
+                        Ordering::Less => Err(ExecutionError::Command(CommandError::NotEnoughArguments)),
+                        Ordering::Greater => Err(ExecutionError::Command(CommandError::TooManyArguments)),
+                        Ordering::Equal => Ok(()),
+                    };
+                    if let Some((fts, _)) = database.fts_table(name) {
+                        count(fts.columns().len())?;
+                        database.insert_virtual(name, &values).map_err(ExecutionError::Insertion)?;
+                    } else {
+                        let table = database
+                            .virtual_tables()
+                            .get(name)
+                            .ok_or_else(|| ExecutionError::Command(CommandError::UnknownTable(name.clone())))?;
+                        count(table.schema().columns.len())?;
+                        table.update(&values).map_err(ExecutionError::Evaluation)?;
+                    }
+                    return Ok(ResultSet::default());
+                }
+                let (data, converted) =
+                    Record::from_values(table.clone(), values).map_err(ExecutionError::Command)?;
+                return SqlCommand::Insert { data, converted }.run(database);
+            }
+            SqlCommand::Select { .. } | SqlCommand::SelectExpressions { .. } | SqlCommand::Query { .. } => {}
         }
-        Ok(())
+        Ok(ResultSet::default())
+    }
+
+    /// Nombre de lignes modifiées par la commande
+    pub fn changes(&self) -> usize {
+        matches!(self, SqlCommand::Insert { .. } | SqlCommand::InsertValues { .. }) as usize
     }
 }
 
+fn select(database: &Database, table: &TableName, limit: Option<usize>) -> Result<Vec<Vec<Value>>, ExecutionError> {
+    let cursor = database.cursor(table.clone()).map_err(ExecutionError::Select)?;
+    // le curseur est paresseux : le limit arrête la lecture des lignes
+    cursor
+        .take(limit.unwrap_or(usize::MAX))
+        .map(|record| record.map(|record| record.values()))
+        .collect::<Result<_, _>>()
+        .map_err(ExecutionError::Select)
+}
 
-impl Record {
-    pub fn from_parameters(mut parameters: SplitWhitespace) -> Result<Record, CommandError> {
-        let record_type_string = parameters
-            .next()
-            .ok_or(CommandError::NotEnoughArguments)?
-            .to_string();
-        let record_type = TableName::from_str(&record_type_string)?;
-        match record_type {
-            TableName::User => {
-                let id = parameters
-                    .next()
-                    .ok_or(CommandError::NotEnoughArguments)?
-                    .parse()
-                    .map_err(|_| CommandError::ExpectingInteger)?;
-                let username = parameters
-                    .next()
-                    .ok_or(CommandError::NotEnoughArguments)?
-                    .to_string();
-                let email = parameters
-                    .next()
-                    .ok_or(CommandError::NotEnoughArguments)?
-                    .to_string();
-                Ok(Record::User(User::new(id, username, email)))
-            }
-            TableName::Car => {
-                let id = parameters
-                    .next()
-                    .ok_or(CommandError::NotEnoughArguments)?
-                    .to_string();
-                let brand = parameters
-                    .next()
-                    .ok_or(CommandError::NotEnoughArguments)?
-                    .to_string();
-                Ok(Record::Car(Car::new(id, brand)))
+/// Analyse un select d'expressions, avec ou sans table
+fn query(parser: &mut Parser) -> Result<SqlCommand, CommandError> {
+    // `*` désigne toutes les colonnes de la table, `None` en attendant de la
+    // connaître
+    let mut items = vec![];
+    loop {
+        match parser.symbol("*") {
+            true => items.push(None),
+            false => items.push(Some(parser.expression()?)),
+        }
+        if !parser.symbol(",") {
+            break;
+        }
+    }
+    if !parser.keyword("from") {
+        parser.end()?;
+        let expressions = items
+            .into_iter()
+            .collect::<Option<_>>()
+            .ok_or_else(|| CommandError::UnexpectedToken("*".to_string()))?;
+        return Ok(SqlCommand::SelectExpressions { expressions });
+    }
+    let source = match parser.next()? {
+        Token::Identifier(name) if parser.symbol("(") => {
+            let arguments = match parser.symbol(")") {
+                true => vec![],
+                false => {
+                    let arguments = parser.expressions()?;
+                    parser.expect_symbol(")")?;
+                    arguments
+                }
+            };
+            Source::Function { name, arguments }
+        }
+        // une table inconnue peut être une table virtuelle, vérifiée à l'exécution
+        Token::Identifier(name) => Source::Table(TableName::from_name(&name)),
+        token => return Err(CommandError::UnexpectedToken(format!("{token:?}"))),
+    };
+    let filter = match parser.keyword("where") {
+        true => Some(parser.expression()?),
+        false => None,
+    };
+    let limit = match parser.keyword("limit") {
+        true => match parser.next()? {
+            Token::Integer(limit) => Some(usize::try_from(limit).map_err(|_| CommandError::ExpectingInteger)?),
+            _ => return Err(CommandError::ExpectingInteger),
+        },
+        false => None,
+    };
+    parser.end()?;
+    // les colonnes d'une fonction table ou d'une table virtuelle ne sont
+    // connues qu'à l'exécution
+    let expressions = items
+        .into_iter()
+        .flat_map(|item| match (item, &source) {
+            (Some(expression), _) => vec![expression],
+            (None, Source::Table(TableName::Virtual(_))) => vec![Expression::Wildcard],
+            (None, Source::Table(table)) => table
+                .columns()
+                .iter()
+                .map(|(name, _)| Expression::Column(name.to_string()))
+                .collect(),
+            (None, Source::Function { .. }) => vec![Expression::Wildcard],
+        })
+        .collect();
+    Ok(SqlCommand::Query {
+        expressions,
+        source,
+        filter,
+        limit,
+    })
+}
+
+/// Analyse `insert into <table> values (<expressions>)`, qui accepte des
+/// textes avec des espaces et les tables virtuelles
+fn insert_into(parser: &mut Parser) -> Result<SqlCommand, CommandError> {
+    parser.keyword("into");
+    let Token::Identifier(name) = parser.next()? else {
+        return Err(CommandError::NotEnoughArguments);
+    };
+    if !parser.keyword("values") {
+        return Err(CommandError::UnexpectedToken(format!("{:?}", parser.next()?)));
+    }
+    parser.expect_symbol("(")?;
+    let values = parser.expressions()?;
+    parser.expect_symbol(")")?;
+    parser.end()?;
+    let table = TableName::from_name(&name);
+    if !matches!(table, TableName::Virtual(_)) {
+        check_arguments(&table, values.len())?;
+    }
+    let command = SqlCommand::InsertValues { table, values };
+    match command.parameters()?.is_empty() {
+        true => command.bind(&[]),
+        false => Ok(command),
+    }
+}
+
+/// Analyse `virtual table <nom> using <module>(<arguments>)`, les arguments
+/// étant des noms séparés par des virgules
+fn create_virtual(parser: &mut Parser) -> Result<SqlCommand, CommandError> {
+    parser.keyword("virtual");
+    if !parser.keyword("table") {
+        return Err(CommandError::UnexpectedToken(format!("{:?}", parser.next()?)));
+    }
+    let name = identifier(parser)?;
+    if !parser.keyword("using") {
+        return Err(CommandError::UnexpectedToken(format!("{:?}", parser.next()?)));
+    }
+    let module = identifier(parser)?;
+    let mut arguments = vec![];
+    if parser.symbol("(") && !parser.symbol(")") {
+        loop {
+            arguments.push(identifier(parser)?);
+            if !parser.symbol(",") {
+                break;
             }
         }
+        parser.expect_symbol(")")?;
+    }
+    parser.end()?;
+    Ok(SqlCommand::CreateVirtual { name, module, arguments })
+}
+
+fn identifier(parser: &mut Parser) -> Result<String, CommandError> {
+    match parser.next()? {
+        Token::Identifier(name) => Ok(name),
+        token => Err(CommandError::UnexpectedToken(format!("{token:?}"))),
+    }
+}
+
+/// Select prêt à être exécuté : sa source, et ses expressions vérifiées sur
+/// les colonnes de la source. Si elles appellent des fonctions d'agrégat,
+/// une seule ligne est produite, calculée sur toutes les lignes retenues par
+/// le filtre. Une vue y est remplacée par le plan de sa requête, dont les
+/// lignes sont lues à la demande comme celles d'une table.
+struct Plan {
+    input: Input,
+    /// nom et type déclaré de chaque colonne de la source
+    columns: Vec<(String, String)>,
+    /// nom des colonnes du résultat, `*` remplacé par celles de la source
+    names: Vec<String>,
+    expressions: Vec<Expression>,
+    calls: Vec<AggregateCall>,
+    filter: Option<Expression>,
+    limit: usize,
+}
+
+/// Ce qui fournit les lignes d'un plan
+enum Input {
+    /// sans source, les expressions sont calculées une fois
+    Nothing,
+    Table(TableName),
+    /// table de l'application, et les valeurs de ses paramètres
+    Virtual { name: String, arguments: Vec<Value> },
+    /// table fts, et la requête de son `MATCH` avec la colonne cherchée
+    Fts { name: String, search: Option<(String, Option<usize>)> },
+    /// fonction table, comme `json_each('[1, 2]')`
+    Function { name: String, arguments: Vec<Value> },
+    View(Box<Plan>),
+}
+
+impl Plan {
+    fn new(
+        database: &Database,
+        expressions: &[Expression],
+        source: Option<&Source>,
+        filter: Option<&Expression>,
+        limit: Option<usize>,
+    ) -> Result<Self, ExecutionError> {
+        let functions = database.functions();
+        let unknown = |name: &String| ExecutionError::Command(CommandError::UnknownTable(name.clone()));
+        let owned = |columns: &[(&str, &str)]| {
+            columns
+                .iter()
+                .map(|(name, kind)| (name.to_string(), kind.to_string()))
+                .collect::<Vec<_>>()
+        };
+        let mut filter = filter;
+        let mut visible = usize::MAX;
+        let (input, columns) = match source {
+            // une vue est remplacée par le plan de sa requête
+            Some(Source::Table(TableName::Virtual(name))) if database.view(name).is_some() => {
+                let plan = view(database, database.view(name).unwrap_or_default())?;
+                let columns = plan.names.iter().map(|name| (name.clone(), String::new())).collect();
+                (Input::View(Box::new(plan)), columns)
+            }
+            // une table de l'application, lue sans argument
+            Some(Source::Table(TableName::Virtual(name))) if database.fts_table(name).is_none() => {
+                let schema = database.virtual_tables().get(name).ok_or_else(|| unknown(name))?.schema();
+                visible = schema.columns.len();
+                let input = Input::Virtual {
+                    name: name.clone(),
+                    arguments: vec![],
+                };
+                (input, owned(&schema_columns(&schema)))
+            }
+            Some(Source::Table(TableName::Virtual(name))) => {
+                let (fts, _) = database.fts_table(name).ok_or_else(|| unknown(name))?;
+                let columns = fts.columns().iter().map(String::as_str).chain(HIDDEN_COLUMNS).map(|name| (name, ""));
+                // `<table> MATCH <requête>` cherche dans toutes les colonnes,
+                // `<colonne> MATCH <requête>` dans une seule
+                let search = match filter {
+                    Some(Expression::Function { name: function, arguments })
+                        if function.eq_ignore_ascii_case("match") && arguments.len() == 2 =>
+                    {
+                        let column = match &arguments[0] {
+                            Expression::Column(column) if column.eq_ignore_ascii_case(name) => None,
+                            Expression::Column(column) => Some(
+                                fts.columns()
+                                    .iter()
+                                    .position(|name| name.eq_ignore_ascii_case(column))
+                                    .ok_or_else(|| ExecutionError::Evaluation(EvaluationError::UnknownColumn(column.clone())))?,
+                            ),
+                            _ => return Err(ExecutionError::Evaluation(misused_match())),
+                        };
+                        let query = arguments[1].evaluate(&Context::new(functions)).map_err(ExecutionError::Evaluation)?;
+                        filter = None;
+                        Some((query.to_string(), column))
+                    }
+                    _ => None,
+                };
+                // `*` ne donne que les colonnes déclarées
+                visible = fts.columns().len();
+                let input = Input::Fts {
+                    name: name.clone(),
+                    search,
+                };
+                (input, owned(&columns.collect::<Vec<_>>()))
+            }
+            Some(Source::Table(table)) => (Input::Table(table.clone()), owned(table.columns())),
+            Some(Source::Function { name, arguments }) => {
+                let context = Context::new(functions);
+                let arguments = arguments
+                    .iter()
+                    .map(|argument| argument.evaluate(&context))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(ExecutionError::Evaluation)?;
+                // les arguments d'une table de l'application vont à ses paramètres
+                match database.virtual_tables().get(name) {
+                    Some(table) => {
+                        let schema = table.schema();
+                        visible = schema.columns.len();
+                        let input = Input::Virtual {
+                            name: name.clone(),
+                            arguments,
+                        };
+                        (input, owned(&schema_columns(&schema)))
+                    }
+                    None => {
+                        let table = functions.table(name).map_err(ExecutionError::Evaluation)?;
+                        // les colonnes d'une fonction table n'ont pas de type déclaré
+                        let columns = table.columns.iter().map(|name| (name.clone(), String::new())).collect();
+                        let input = Input::Function {
+                            name: name.clone(),
+                            arguments,
+                        };
+                        (input, columns)
+                    }
+                }
+            }
+            None => (Input::Nothing, vec![]),
+        };
+        let borrowed = borrowed(&columns);
+        let expressions = expressions
+            .iter()
+            .flat_map(|expression| match expression {
+                Expression::Wildcard => columns
+                    .iter()
+                    .take(visible)
+                    .map(|(name, _)| Expression::Column(name.clone()))
+                    .collect(),
+                expression => vec![expression.clone()],
+            })
+            .collect::<Vec<_>>();
+        let names = expressions.iter().map(Expression::to_string).collect();
+        let plan = |expression: &Expression| {
+            let expression = expression.clone().fold(functions)?;
+            expression.check_columns(&borrowed)?;
+            Ok(expression)
+        };
+        let mut calls = vec![];
+        let expressions = expressions
+            .iter()
+            .map(|expression| plan(expression)?.extract_aggregates(functions, &mut calls))
+            .collect::<Result<Vec<_>, EvaluationError>>()
+            .map_err(ExecutionError::Evaluation)?;
+        let filter = filter
+            .map(|filter| match filter.has_aggregate(functions) {
+                true => Err(EvaluationError::MisusedAggregate(filter.to_string())),
+                false => plan(filter),
+            })
+            .transpose()
+            .map_err(ExecutionError::Evaluation)?;
+        Ok(Self {
+            input,
+            columns,
+            names,
+            expressions,
+            calls,
+            filter,
+            limit: limit.unwrap_or(usize::MAX),
+        })
+    }
+
+    /// Lignes du résultat. Sans agrégat, elles sont calculées à la demande
+    /// et la lecture de la source s'arrête une fois le limit atteint.
+    fn rows<'a>(&'a self, database: &'a Database) -> Result<Rows<'a>, ExecutionError> {
+        let functions = database.functions();
+        let unknown = |name: &String| ExecutionError::Command(CommandError::UnknownTable(name.clone()));
+        let rows = match &self.input {
+            Input::Nothing => materialized(vec![vec![]]),
+            // le curseur est paresseux : les lignes sont lues au fur et à mesure
+            Input::Table(table) => {
+                let cursor = database.cursor(table.clone()).map_err(ExecutionError::Select)?;
+                Box::new(cursor.map(|record| record.map(|record| record.values()).map_err(ExecutionError::Select)))
+            }
+            Input::Virtual { name, arguments } => {
+                let table = database.virtual_tables().get(name).ok_or_else(|| unknown(name))?;
+                streamed(vtab::scan(name, table, arguments).map_err(ExecutionError::Evaluation)?)
+            }
+            Input::Fts { name, search } => {
+                let (fts, table) = database.fts_table(name).ok_or_else(|| unknown(name))?;
+                let search = search.as_ref().map(|(query, column)| Search { query, column: *column });
+                materialized(fts.rows(table, search)?)
+            }
+            Input::Function { name, arguments } => {
+                let table = functions.table(name).map_err(ExecutionError::Evaluation)?;
+                materialized((table.function)(arguments).map_err(ExecutionError::Evaluation)?)
+            }
+            Input::View(plan) => plan.rows(database)?,
+        };
+        let columns = borrowed(&self.columns);
+        if self.calls.is_empty() {
+            let rows = rows
+                .filter_map(move |row| {
+                    row.and_then(|row| Ok(self.keep(functions, &columns, &row)?.then_some(row)))
+                        .transpose()
+                        .map(|row| row.and_then(|row| self.evaluate(functions, &columns, &row, &[])))
+                })
+                .take(self.limit);
+            return Ok(Box::new(rows));
+        }
+        // les lignes retenues par le filtre, lues une à une
+        let selected = rows.filter_map(|row| {
+            row.and_then(|row| Ok(self.keep(functions, &columns, &row)?.then_some(row)))
+                .transpose()
+        });
+        let (aggregates, last) = aggregate(database, &self.calls, &columns, selected)?;
+        // les colonnes hors agrégat sont lues dans la dernière ligne, NULL sans ligne
+        let row = last.unwrap_or_else(|| vec![Value::Null; columns.len()]);
+        let rows = vec![self.evaluate(functions, &columns, &row, &aggregates)?];
+        Ok(materialized(rows.into_iter().take(self.limit).collect()))
+    }
+
+    /// La ligne est retenue par le filtre
+    fn keep(&self, functions: &Functions, columns: &[(&str, &str)], row: &[Value]) -> Result<bool, ExecutionError> {
+        match &self.filter {
+            Some(filter) => filter
+                .evaluate(&Context::new(functions).with_row(columns, row))
+                .map(|value| value.is_true())
+                .map_err(ExecutionError::Evaluation),
+            None => Ok(true),
+        }
+    }
+
+    /// Valeur des expressions sur une ligne de la source
+    fn evaluate(
+        &self,
+        functions: &Functions,
+        columns: &[(&str, &str)],
+        row: &[Value],
+        aggregates: &[Value],
+    ) -> Result<Vec<Value>, ExecutionError> {
+        let context = Context::new(functions)
+            .with_row(columns, row)
+            .with_aggregates(aggregates, &self.calls);
+        self.expressions
+            .iter()
+            .map(|expression| expression.evaluate(&context))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(ExecutionError::Evaluation)
+    }
+}
+
+/// Colonnes d'un plan, sous la forme que lisent les expressions
+fn borrowed(columns: &[(String, String)]) -> Vec<(&str, &str)> {
+    columns.iter().map(|(name, kind)| (name.as_str(), kind.as_str())).collect()
+}
+
+/// Lignes d'une source, lues à la demande
+type Rows<'a> = Box<dyn Iterator<Item = Result<Vec<Value>, ExecutionError>> + 'a>;
+
+/// Lignes d'une source déjà toutes calculées
+fn materialized<'a>(rows: Vec<Vec<Value>>) -> Rows<'a> {
+    Box::new(rows.into_iter().map(Ok))
+}
+
+/// Lignes d'une table de l'application, lues au fur et à mesure
+fn streamed(rows: vtab::Scan<'_>) -> Rows<'_> {
+    Box::new(rows.map(|row| row.map_err(ExecutionError::Evaluation)))
+}
+
+/// Analyse `<nom> as <select>`, le select devant pouvoir être exécuté tel
+/// quel à chaque lecture de la vue
+fn create_view(payload: &str) -> Result<SqlCommand, CommandError> {
+    let (name, rest) = first_word(payload);
+    let (keyword, select) = first_word(rest);
+    if name.is_empty() || select.is_empty() {
+        return Err(CommandError::NotEnoughArguments);
+    }
+    if !keyword.eq_ignore_ascii_case("as") {
+        return Err(CommandError::UnexpectedToken(keyword.to_string()));
+    }
+    let select = select.trim_end().trim_end_matches(';').trim_end().to_string();
+    match SqlCommand::try_from_str(&select)? {
+        Some(command @ (SqlCommand::Select { .. } | SqlCommand::SelectExpressions { .. } | SqlCommand::Query { .. }))
+            if command.parameters().is_ok_and(|parameters| parameters.is_empty()) =>
+        {
+            Ok(SqlCommand::CreateView {
+                name: name.to_string(),
+                select,
+            })
+        }
+        _ => Err(CommandError::InvalidView(select)),
+    }
+}
+
+/// Premier mot du texte et ce qui le suit
+fn first_word(text: &str) -> (&str, &str) {
+    let text = text.trim_start();
+    let end = text.find(char::is_whitespace).unwrap_or(text.len());
+    (&text[..end], text[end..].trim_start())
+}
+
+/// Plan de la requête d'une vue
+fn view(database: &Database, select: &str) -> Result<Plan, ExecutionError> {
+    SqlCommand::try_from_str(select)
+        .map_err(ExecutionError::Command)?
+        .and_then(|command| command.plan(database))
+        .unwrap_or_else(|| Err(ExecutionError::Command(CommandError::InvalidView(select.to_string()))))
+}
+
+/// Colonnes d'une table virtuelle, sans type déclaré, paramètres compris
+fn schema_columns(schema: &Schema) -> Vec<(&str, &str)> {
+    schema
+        .columns
+        .iter()
+        .chain(&schema.parameters)
+        .map(|name| (name.as_str(), ""))
+        .collect()
+}
+
+/// Calcule les fonctions d'agrégat sur les lignes retenues, lues une à une.
+/// Renvoie aussi la dernière ligne.
+fn aggregate(
+    database: &Database,
+    calls: &[AggregateCall],
+    columns: &[(&str, &str)],
+    rows: impl Iterator<Item = Result<Vec<Value>, ExecutionError>>,
+) -> Result<(Vec<Value>, Option<Vec<Value>>), ExecutionError> {
+    let functions = database.functions();
+    let mut accumulators = calls
+        .iter()
+        .map(|call| {
+            let Function::Aggregate(factory) = functions.find(&call.name, call.arguments.len())? else {
+                return Err(EvaluationError::MisusedAggregate(call.name.clone()));
+            };
+            Ok(factory())
+        })
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(ExecutionError::Evaluation)?;
+    let mut last = None;
+    for row in rows {
+        let row = row?;
+        let context = Context::new(functions).with_row(columns, &row);
+        for (call, accumulator) in calls.iter().zip(&mut accumulators) {
+            let arguments = call
+                .arguments
+                .iter()
+                .map(|argument| argument.evaluate(&context))
+                .collect::<Result<Vec<_>, _>>()
+                .map_err(ExecutionError::Evaluation)?;
+            let json = call
+                .arguments
+                .iter()
+                .map(|argument| argument.is_json(&context))
+                .collect::<Vec<_>>();
+            accumulator
+                .step_json(&arguments, &json)
+                .map_err(ExecutionError::Evaluation)?;
+        }
+        last = Some(row);
+    }
+    let aggregates = accumulators
+        .into_iter()
+        .map(|accumulator| accumulator.finalize())
+        .collect::<Result<_, _>>()
+        .map_err(ExecutionError::Evaluation)?;
+    Ok((aggregates, last))
+}
+
+impl Execute for SqlCommand {
+    fn execute(self, database: &mut Database) -> Result<(), ExecutionError> {
+        let result = self.run(database)?;
+        match self {
+            SqlCommand::Insert { .. } | SqlCommand::InsertValues { .. } => {
+                println!("Record inserted successfully")
+            }
+            SqlCommand::Create { .. } | SqlCommand::CreateVirtual { .. } => println!("Table created successfully"),
+            SqlCommand::CreateView { .. } => println!("View created successfully"),
+            SqlCommand::DropView { .. } => println!("View dropped successfully"),
+            SqlCommand::Pragma(Pragma::WalCheckpoint) => println!("Checkpoint completed"),
+            _ => {}
+        }
+        for row in result.rows {
+            let values = row.iter().map(Value::to_string).collect::<Vec<_>>();
+            println!("{}", values.join(" | "));
+        }
+        Ok(())
+    }
+}
+
+impl Record {
+    /// Construit l'enregistrement à partir des valeurs d'un insert, chacune
+    /// convertie selon l'affinité de sa colonne. Renvoie aussi si une valeur
+    /// a dû changer de type.
+    pub fn from_values(table: TableName, values: Vec<Value>) -> Result<(Record, bool), CommandError> {
+        check_arguments(&table, values.len())?;
+        let columns = table.columns();
+        let mut converted = false;
+        let mut values = values
+            .into_iter()
+            .zip(columns)
+            .map(|(value, (_, declared_type))| {
+                let coerced = value.clone().apply_affinity(Affinity::from_type_name(declared_type));
+                converted |= std::mem::discriminant(&coerced) != std::mem::discriminant(&value);
+                coerced
+            })
+            .collect::<Vec<_>>()
+            .into_iter();
+        let record = match table {
+            TableName::User => Record::User(User::new(
+                integer(values.next())?,
+                text(values.next())?,
+                text(values.next())?,
+            )),
+            TableName::Car => Record::Car(Car::new(text(values.next())?, text(values.next())?)),
+            TableName::Virtual(name) => return Err(CommandError::UnknownTable(name)),
+        };
+        Ok((record, converted))
+    }
+}
+
+/// Valeur d'un paramètre d'insert : un littéral (`12`, `1.5`, `'texte'`,
+/// `null`...), un paramètre lié (`?`, `:nom`) ou à défaut le mot lui-même,
+/// comme texte
+fn parameter(word: &str) -> Expression {
+    let text = || Expression::Literal(Value::Text(word.to_string()));
+    let mut parser = match Parser::new(word) {
+        Ok(parser) => parser,
+        Err(_) => return text(),
+    };
+    let is_name = matches!(parser.peek(), Some(Token::Identifier(name)) if !is_literal_keyword(name));
+    match parser.expression() {
+        Ok(expression) if !is_name && parser.end().is_ok() => expression,
+        _ => text(),
+    }
+}
+
+/// Vérifie que l'insert a une valeur par colonne
+fn check_arguments(table: &TableName, count: usize) -> Result<(), CommandError> {
+    match count.cmp(&table.columns().len()) {
+        Ordering::Less => Err(CommandError::NotEnoughArguments),
+        Ordering::Greater => Err(CommandError::TooManyArguments),
+        Ordering::Equal => Ok(()),
+    }
+}
+
+fn integer(value: Option<Value>) -> Result<i64, CommandError> {
+    match value {
+        Some(Value::Integer(value)) => Ok(value),
+        _ => Err(CommandError::ExpectingInteger),
+    }
+}
+
+fn text(value: Option<Value>) -> Result<String, CommandError> {
+    match value {
+        Some(Value::Text(value)) => Ok(value),
+        _ => Err(CommandError::ExpectingText),
     }
 }
 
@@ -139,7 +1088,8 @@ fn test_parse_command_insert() {
                 id: 1,
                 username: "name".to_string(),
                 email: "email@domain.tld".to_string()
-            })
+            }),
+            converted: false
         }))
     );
     // robustesse sur le nombre d'espaces
@@ -150,7 +1100,8 @@ fn test_parse_command_insert() {
                 id: 1,
                 username: "name".to_string(),
                 email: "email@domain.tld".to_string()
-            })
+            }),
+            converted: false
         }))
     );
     // pas assez d'arguments
@@ -171,29 +1122,167 @@ fn test_parse_command_insert() {
         SqlCommand::try_from_str("insert user one name email@domain.tld"),
         Err(CommandError::ExpectingInteger)
     );
+    assert_eq!(
+        SqlCommand::try_from_str("insert user 1.5 name email@domain.tld"),
+        Err(CommandError::ExpectingInteger)
+    );
     // commande inconnue
     assert_eq!(SqlCommand::try_from_str("unknown command"), Ok(None));
 }
 
+#[test]
+fn test_parse_command_insert_parameters() {
+    use crate::commands::lexer::Placeholder;
+
+    // l'enregistrement attend que les paramètres soient liés
+    let command = SqlCommand::try_from_str("insert user ? :name 'email'")
+        .expect("parse failed")
+        .expect("not a command");
+    assert_eq!(
+        command,
+        SqlCommand::InsertValues {
+            table: TableName::User,
+            values: vec![
+                Expression::Parameter(Placeholder::Next),
+                Expression::Parameter(Placeholder::Name(":name".to_string())),
+                Expression::Literal(Value::Text("email".to_string())),
+            ]
+        }
+    );
+    assert_eq!(command.parameters().map(|parameters| parameters.index_of(":name")), Ok(Some(1)));
+    assert_eq!(
+        command.bind(&[Value::Text("7".to_string()), Value::Text("name".to_string())]),
+        Ok(SqlCommand::Insert {
+            data: Record::User(User::new(7, "name".to_string(), "email".to_string())),
+            converted: true
+        })
+    );
+    // le nombre de valeurs est vérifié dès l'analyse
+    assert_eq!(
+        SqlCommand::try_from_str("insert user ? ?"),
+        Err(CommandError::NotEnoughArguments)
+    );
+}
+
+#[test]
+fn test_parse_command_insert_affinity() {
+    let user = |id: i64, username: &str| {
+        Record::User(User {
+            id,
+            username: username.to_string(),
+            email: "email".to_string(),
+        })
+    };
+    // les valeurs sont converties vers le type déclaré de la colonne
+    assert_eq!(
+        SqlCommand::try_from_str("insert user '12' 'name' email"),
+        Ok(Some(SqlCommand::Insert {
+            data: user(12, "name"),
+            converted: true
+        }))
+    );
+    assert_eq!(
+        SqlCommand::try_from_str("insert user 2.0 42 email"),
+        Ok(Some(SqlCommand::Insert {
+            data: user(2, "42"),
+            converted: true
+        }))
+    );
+    assert_eq!(
+        SqlCommand::try_from_str("insert user -3 'name' email"),
+        Ok(Some(SqlCommand::Insert {
+            data: user(-3, "name"),
+            converted: false
+        }))
+    );
+}
+
+#[test]
+fn test_execute_strict_insert() {
+    use crate::errors::{ExecutionError, InsertionError};
+
+    let mut database = Database::new();
+    SqlCommand::Create {
+        table: TableName::User,
+        strict: true,
+    }
+    .execute(&mut database)
+    .expect("create failed");
+    let insert = |command: &str| {
+        SqlCommand::try_from_str(command)
+            .expect("parse failed")
+            .expect("not a command")
+    };
+    assert_eq!(
+        insert("insert user '1' name email").execute(&mut database),
+        Err(ExecutionError::Insertion(InsertionError::TypeMismatch(TableName::User)))
+    );
+    assert_eq!(insert("insert user 1 name email").execute(&mut database), Ok(()));
+}
+
 #[test]
 fn test_parse_command_select() {
     // commande select correcte
     assert_eq!(
         SqlCommand::try_from_str("select Car"),
         Ok(Some(SqlCommand::Select {
-            table: TableName::Car
+            table: TableName::Car,
+            limit: None
         }))
     );
     assert_eq!(
         SqlCommand::try_from_str("    select  User   "),
         Ok(Some(SqlCommand::Select {
-            table: TableName::User
+            table: TableName::User,
+            limit: None
         }))
     );
-    // table inconnue
+    // clause limit
     assert_eq!(
-        SqlCommand::try_from_str("select unknown"),
-        Err(CommandError::UnknownTable("unknown".to_string()))
+        SqlCommand::try_from_str("select user limit 10"),
+        Ok(Some(SqlCommand::Select {
+            table: TableName::User,
+            limit: Some(10)
+        }))
+    );
+    assert_eq!(
+        SqlCommand::try_from_str("select user limit ten"),
+        Err(CommandError::ExpectingInteger)
+    );
+    assert_eq!(
+        SqlCommand::try_from_str("select user limit"),
+        Err(CommandError::NotEnoughArguments)
+    );
+    assert_eq!(
+        SqlCommand::try_from_str("select user limit 10 value"),
+        Err(CommandError::TooManyArguments)
+    );
+    // une table inconnue peut être une vue : l'erreur vient à l'exécution
+    let command = SqlCommand::try_from_str("select unknown").unwrap().unwrap();
+    assert_eq!(
+        command.run(&mut Database::new()),
+        Err(ExecutionError::Command(CommandError::UnknownTable("unknown".to_string())))
+    );
+    // select d'expressions sans table
+    use crate::commands::expression::Comparison;
+    use crate::data::{Date, Value};
+    assert_eq!(
+        SqlCommand::try_from_str("select 1.5, null, date '2024-01-31' > '2024'"),
+        Ok(Some(SqlCommand::SelectExpressions {
+            expressions: vec![
+                Expression::Literal(Value::Real(1.5)),
+                Expression::Literal(Value::Null),
+                Expression::Comparison {
+                    left: Box::new(Expression::Literal(Value::Date(Date(19_753)))),
+                    operator: Comparison::Greater,
+                    right: Box::new(Expression::Literal(Value::Text("2024".to_string()))),
+                },
+            ]
+        }))
+    );
+    assert_eq!(
+        SqlCommand::try_from_str("select 'abc"),
+        Err(CommandError::InvalidLiteral("'abc".to_string()))
     );
     // trop d'arguments
     assert_eq!(
@@ -209,19 +1298,71 @@ fn test_parse_command_select() {
     assert_eq!(SqlCommand::try_from_str("unknown command"), Ok(None));
 }
 
+#[test]
+fn test_parse_command_query() {
+    use crate::commands::expression::Comparison;
+
+    let column = |name: &str| Expression::Column(name.to_string());
+    // * désigne les colonnes de la table
+    assert_eq!(
+        SqlCommand::try_from_str("select *, count(*) from car where brand = 'x' limit 2"),
+        Ok(Some(SqlCommand::Query {
+            expressions: vec![
+                column("id"),
+                column("brand"),
+                Expression::Function {
+                    name: "count".to_string(),
+                    arguments: vec![]
+                }
+            ],
+            source: Source::Table(TableName::Car),
+            filter: Some(Expression::Comparison {
+                left: Box::new(column("brand")),
+                operator: Comparison::Equal,
+                right: Box::new(Expression::Literal(Value::Text("x".to_string())))
+            }),
+            limit: Some(2)
+        }))
+    );
+    // une table inconnue peut être virtuelle : l'erreur vient à l'exécution
+    let mut database = Database::new();
+    let command = SqlCommand::try_from_str("select * from unknown").unwrap().unwrap();
+    assert_eq!(
+        command.run(&mut database),
+        Err(ExecutionError::Command(CommandError::UnknownTable("unknown".to_string())))
+    );
+    assert_eq!(
+        SqlCommand::try_from_str("select *"),
+        Err(CommandError::UnexpectedToken("*".to_string()))
+    );
+    assert_eq!(
+        SqlCommand::try_from_str("select id from user limit -1"),
+        Err(CommandError::ExpectingInteger)
+    );
+}
+
 #[test]
 fn test_parse_command_create() {
     // commande select correcte
     assert_eq!(
         SqlCommand::try_from_str("create Car"),
         Ok(Some(SqlCommand::Create {
-            table: TableName::Car
+            table: TableName::Car,
+            strict: false
         }))
     );
     assert_eq!(
         SqlCommand::try_from_str("    create  User   "),
         Ok(Some(SqlCommand::Create {
-            table: TableName::User
+            table: TableName::User,
+            strict: false
+        }))
+    );
+    assert_eq!(
+        SqlCommand::try_from_str("create user STRICT"),
+        Ok(Some(SqlCommand::Create {
+            table: TableName::User,
+            strict: true
         }))
     );
     // table inconnue
@@ -239,7 +1380,142 @@ fn test_parse_command_create() {
         SqlCommand::try_from_str("create"),
         Err(CommandError::NotEnoughArguments)
     );
+    // table virtuelle
+    assert_eq!(
+        SqlCommand::try_from_str("create virtual table docs using fts(title, body);"),
+        Ok(Some(SqlCommand::CreateVirtual {
+            name: "docs".to_string(),
+            module: "fts".to_string(),
+            arguments: vec!["title".to_string(), "body".to_string()]
+        }))
+    );
+    assert_eq!(
+        SqlCommand::try_from_str("create virtual table docs fts(title)"),
+        Err(CommandError::UnexpectedToken("Identifier(\"fts\")".to_string()))
+    );
     // commande inconnue
     assert_eq!(SqlCommand::try_from_str("unknown command"), Ok(None));
 }
 
+
+#[test]
+fn test_parse_command_view() {
+    assert_eq!(
+        SqlCommand::try_from_str("create view adults as  select id, username from user where id > 1 ;"),
+        Ok(Some(SqlCommand::CreateView {
+            name: "adults".to_string(),
+            select: "select id, username from user where id > 1".to_string()
+        }))
+    );
+    assert_eq!(
+        SqlCommand::try_from_str("create view adults select id from user"),
+        Err(CommandError::UnexpectedToken("select".to_string()))
+    );
+    // seul un select sans paramètre définit une vue
+    assert_eq!(
+        SqlCommand::try_from_str("create view adults as select id from user where id > ?"),
+        Err(CommandError::InvalidView("select id from user where id > ?".to_string()))
+    );
+    assert_eq!(
+        SqlCommand::try_from_str("create view adults as begin"),
+        Err(CommandError::InvalidView("begin".to_string()))
+    );
+    assert_eq!(
+        SqlCommand::try_from_str("drop view adults;"),
+        Ok(Some(SqlCommand::DropView {
+            name: "adults".to_string(),
+            if_exists: false
+        }))
+    );
+    assert_eq!(
+        SqlCommand::try_from_str("drop view if exists adults"),
+        Ok(Some(SqlCommand::DropView {
+            name: "adults".to_string(),
+            if_exists: true
+        }))
+    );
+    assert_eq!(
+        SqlCommand::try_from_str("drop view if exists"),
+        Err(CommandError::NotEnoughArguments)
+    );
+    assert_eq!(SqlCommand::try_from_str("drop table user"), Ok(None));
+}
+
+#[test]
+fn test_parse_command_transaction() {
+    assert_eq!(SqlCommand::try_from_str("begin"), Ok(Some(SqlCommand::Begin)));
+    assert_eq!(
+        SqlCommand::try_from_str("  begin   transaction "),
+        Ok(Some(SqlCommand::Begin))
+    );
+    assert_eq!(SqlCommand::try_from_str("commit"), Ok(Some(SqlCommand::Commit)));
+    assert_eq!(
+        SqlCommand::try_from_str("rollback transaction"),
+        Ok(Some(SqlCommand::Rollback))
+    );
+    // trop d'arguments
+    assert_eq!(
+        SqlCommand::try_from_str("commit user"),
+        Err(CommandError::TooManyArguments)
+    );
+    assert_eq!(
+        SqlCommand::try_from_str("begin transaction now"),
+        Err(CommandError::TooManyArguments)
+    );
+}
+
+#[test]
+fn test_parse_command_savepoint() {
+    assert_eq!(
+        SqlCommand::try_from_str("savepoint sp1"),
+        Ok(Some(SqlCommand::Savepoint {
+            name: "sp1".to_string()
+        }))
+    );
+    assert_eq!(
+        SqlCommand::try_from_str("release sp1"),
+        Ok(Some(SqlCommand::Release {
+            name: "sp1".to_string()
+        }))
+    );
+    assert_eq!(
+        SqlCommand::try_from_str("release savepoint sp1"),
+        Ok(Some(SqlCommand::Release {
+            name: "sp1".to_string()
+        }))
+    );
+    assert_eq!(
+        SqlCommand::try_from_str("rollback to sp1"),
+        Ok(Some(SqlCommand::RollbackTo {
+            name: "sp1".to_string()
+        }))
+    );
+    assert_eq!(
+        SqlCommand::try_from_str("rollback transaction to savepoint sp1"),
+        Ok(Some(SqlCommand::RollbackTo {
+            name: "sp1".to_string()
+        }))
+    );
+    // pas assez d'arguments
+    assert_eq!(
+        SqlCommand::try_from_str("savepoint"),
+        Err(CommandError::NotEnoughArguments)
+    );
+    assert_eq!(
+        SqlCommand::try_from_str("release savepoint"),
+        Err(CommandError::NotEnoughArguments)
+    );
+    assert_eq!(
+        SqlCommand::try_from_str("rollback to"),
+        Err(CommandError::NotEnoughArguments)
+    );
+    // trop d'arguments
+    assert_eq!(
+        SqlCommand::try_from_str("savepoint sp1 sp2"),
+        Err(CommandError::TooManyArguments)
+    );
+    assert_eq!(
+        SqlCommand::try_from_str("rollback sp1"),
+        Err(CommandError::TooManyArguments)
+    );
+}
@@ -0,0 +1,1032 @@
+use crate::commands::{parse_sql, Parameters, ResultSet, SqlCommand};
+use crate::data::{Record, TableName, Value};
+use crate::database::Database;
+use crate::errors::{
+    CommandError, CreationError, EvaluationError, ExecutionError, InsertionError, SelectError,
+    StorageError, TransactionError,
+};
+use crate::mvcc::{IsolatedTransaction, ReadView};
+use crate::row::{FromRow, Rows};
+use crate::vtab::VirtualTable;
+use std::collections::VecDeque;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+/// Nombre de requêtes préparées gardées par une connexion
+const STATEMENT_CACHE_CAPACITY: usize = 16;
+/// Propriétaire d'une base sans transaction SQL en cours
+const NO_OWNER: u64 = 0;
+
+/// Identifiant de la prochaine connexion ouverte
+static NEXT_ID: AtomicU64 = AtomicU64::new(NO_OWNER + 1);
+
+/// Connexion à une base de données, partageable entre threads.
+///
+/// Cloner une connexion en ouvre une nouvelle sur la même base. Les
+/// écritures prennent un verrou exclusif le temps de s'appliquer. Les
+/// lectures ne prennent un verrou partagé que le temps de figer une vue de
+/// la base, qu'elles parcourent ensuite sans bloquer personne.
+///
+/// Les transactions sont isolées par instantané : chacune voit la base
+/// telle qu'elle était à son démarrage et n'écrit qu'à son commit, les
+/// autres connexions ne voient donc jamais une transaction à moitié faite.
+/// Une transaction SQL (`begin`) appartient à la connexion qui l'a ouverte :
+/// jusqu'à sa fin, les autres lisent la base à son dernier commit et leurs
+/// écritures échouent avec `StorageError::Busy`.
+pub struct Connection {
+    database: Arc<RwLock<Database>>,
+    /// connexion dont la transaction SQL est en cours, ne change que sous le
+    /// verrou exclusif de la base
+    owner: Arc<AtomicU64>,
+    id: u64,
+    /// dernières requêtes préparées, la plus récemment utilisée en dernier
+    cache: Mutex<VecDeque<(String, Arc<Prepared>)>>,
+}
+
+/// La nouvelle connexion part avec un cache de requêtes vide
+impl Clone for Connection {
+    fn clone(&self) -> Self {
+        Self {
+            database: self.database.clone(),
+            owner: self.owner.clone(),
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            cache: Mutex::default(),
+        }
+    }
+}
+
+/// Une transaction SQL laissée ouverte est annulée avec sa connexion
+impl Drop for Connection {
+    fn drop(&mut self) {
+        if self.owner.load(Ordering::Relaxed) == self.id {
+            let mut database = self.write_lock();
+            let _ = database.rollback();
+            self.owner.store(NO_OWNER, Ordering::Relaxed);
+        }
+    }
+}
+
+impl Connection {
+    pub fn new(database: Database) -> Self {
+        Self {
+            database: Arc::new(RwLock::new(database)),
+            owner: Arc::new(AtomicU64::new(NO_OWNER)),
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            cache: Mutex::default(),
+        }
+    }
+
+    /// Ouvre une base de données stockée dans un fichier
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, StorageError> {
+        Ok(Self::new(Database::open(path)?))
+    }
+
+    /// Base de données en mémoire
+    pub fn memory() -> Self {
+        Self::new(Database::new())
+    }
+
+    // un thread qui panique au milieu d'une transaction la défait en
+    // relâchant son verrou, la base reste donc utilisable
+    fn read_lock(&self) -> RwLockReadGuard<'_, Database> {
+        self.database.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write_lock(&self) -> RwLockWriteGuard<'_, Database> {
+        self.database.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Exécute une lecture, en parallèle des autres lecteurs
+    pub fn read<T>(&self, read: impl FnOnce(&Database) -> T) -> T {
+        read(&self.read_lock())
+    }
+
+    /// Exécute une écriture, seul sur la base
+    pub fn write<T>(&self, write: impl FnOnce(&mut Database) -> T) -> T {
+        write(&mut self.write_lock())
+    }
+
+    /// Une autre connexion a une transaction SQL en cours
+    fn foreign_transaction(&self) -> bool {
+        let owner = self.owner.load(Ordering::Relaxed);
+        owner != NO_OWNER && owner != self.id
+    }
+
+    /// Exécute une écriture, refusée pendant la transaction SQL d'une autre
+    /// connexion dont le rollback la déferait. Une transaction démarrée par
+    /// l'écriture appartient à cette connexion jusqu'à sa fin.
+    fn write_owned<T, E>(
+        &self,
+        write: impl FnOnce(&mut Database) -> Result<T, E>,
+        busy: impl FnOnce(StorageError) -> E,
+    ) -> Result<T, E> {
+        let mut database = self.write_lock();
+        if self.foreign_transaction() {
+            return Err(busy(StorageError::Busy));
+        }
+        let result = write(&mut database);
+        let owner = if database.in_transaction() { self.id } else { NO_OWNER };
+        self.owner.store(owner, Ordering::Relaxed);
+        result
+    }
+
+    pub fn create_table(&self, table_name: TableName) -> Result<(), CreationError> {
+        self.write_owned(|database| database.create_table(table_name), CreationError::Storage)
+    }
+
+    pub fn insert(&self, data: Record) -> Result<(), InsertionError> {
+        self.write_owned(|database| database.insert(data), InsertionError::Storage)
+    }
+
+    /// Vue figée de la base au dernier commit
+    pub fn snapshot(&self) -> ReadView {
+        self.read(Database::read_view)
+    }
+
+    pub fn select(&self, table_name: TableName) -> Result<Vec<Record>, SelectError> {
+        self.snapshot().select(table_name)
+    }
+
+    /// Exécute une commande SQL et renvoie le nombre de lignes modifiées,
+    /// les paramètres de la commande prennent les valeurs `params`
+    pub fn execute(&self, sql: &str, params: &[Value]) -> Result<usize, ExecutionError> {
+        self.prepare_cached(sql)
+            .map_err(ExecutionError::Command)?
+            .execute(params)
+    }
+
+    /// Exécute une requête et lit chaque ligne du résultat dans un `T`
+    pub fn query_as<T: FromRow>(&self, sql: &str, params: &[Value]) -> Result<Vec<T>, ExecutionError> {
+        self.prepare_cached(sql)
+            .map_err(ExecutionError::Command)?
+            .query_as(params)
+    }
+
+    /// Rend une fonction appelable depuis les expressions SQL de toutes les
+    /// connexions à la base. Une arité négative accepte n'importe quel
+    /// nombre d'arguments. Une fonction déterministe appelée sur des
+    /// constantes n'est calculée qu'une fois par requête.
+    pub fn create_scalar_function(
+        &self,
+        name: &str,
+        arity: i32,
+        deterministic: bool,
+        function: impl Fn(&[Value]) -> Result<Value, EvaluationError> + Send + Sync + 'static,
+    ) {
+        self.write(|database| {
+            database
+                .functions_mut()
+                .register_scalar(name, arity, deterministic, function)
+        })
+    }
+
+    /// Rend une fonction d'agrégat appelable depuis les expressions SQL :
+    /// `init` donne l'état de départ, `step` le met à jour pour chaque ligne
+    /// et `finalize` en tire le résultat
+    pub fn create_aggregate_function<S: Send + 'static>(
+        &self,
+        name: &str,
+        arity: i32,
+        init: impl Fn() -> S + Send + Sync + 'static,
+        step: impl Fn(&mut S, &[Value]) -> Result<(), EvaluationError> + Send + Sync + 'static,
+        finalize: impl Fn(S) -> Result<Value, EvaluationError> + Send + Sync + 'static,
+    ) {
+        self.write(|database| {
+            database
+                .functions_mut()
+                .register_aggregate(name, arity, init, step, finalize)
+        })
+    }
+
+    /// Rend une table virtuelle lisible en SQL sous ce nom depuis toutes
+    /// les connexions à la base, à la place de celle qui le portait
+    pub fn register_virtual_table(&self, name: &str, table: impl VirtualTable + 'static) {
+        self.write(|database| database.virtual_tables_mut().register(name, table))
+    }
+
+    /// Analyse une commande SQL, pour l'exécuter ensuite autant de fois
+    /// que voulu
+    pub fn prepare(&self, sql: &str) -> Result<Statement<'_>, CommandError> {
+        let command = parse_sql(sql)?;
+        Ok(Statement {
+            connection: self,
+            prepared: Arc::new(Prepared {
+                parameters: command.parameters()?,
+                command,
+            }),
+        })
+    }
+
+    /// Comme `prepare`, mais une commande déjà analysée par cette connexion
+    /// n'est pas analysée à nouveau
+    pub fn prepare_cached(&self, sql: &str) -> Result<Statement<'_>, CommandError> {
+        let mut cache = self.cache.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(position) = cache.iter().position(|(cached, _)| cached == sql) {
+            let entry = cache.remove(position).expect("position in cache");
+            let prepared = entry.1.clone();
+            cache.push_back(entry);
+            return Ok(Statement {
+                connection: self,
+                prepared,
+            });
+        }
+        let statement = self.prepare(sql)?;
+        if cache.len() == STATEMENT_CACHE_CAPACITY {
+            cache.pop_front();
+        }
+        cache.push_back((sql.to_string(), statement.prepared.clone()));
+        Ok(statement)
+    }
+
+    /// Les lectures partagent la base le temps de la requête, les autres
+    /// commandes la prennent pour elles seules. Pendant la transaction SQL
+    /// d'une autre connexion, une lecture parcourt une copie de la base à son
+    /// dernier commit, sans garder de verrou.
+    fn run(&self, command: &SqlCommand) -> Result<ResultSet, ExecutionError> {
+        let read = {
+            let database = self.read_lock();
+            match self.foreign_transaction() && command.is_query() {
+                true => {
+                    let committed = database.committed();
+                    drop(database);
+                    command.query(&committed)
+                }
+                false => command.query(&database),
+            }
+        };
+        match read {
+            Some(result) => result,
+            None => self.write_owned(|database| command.run(database), ExecutionError::Storage),
+        }
+    }
+
+    /// Démarre une transaction qui voit la base telle qu'elle est maintenant
+    pub fn begin(&self) -> TransactionGuard<'_> {
+        TransactionGuard {
+            connection: self,
+            transaction: IsolatedTransaction::new(self.snapshot()),
+        }
+    }
+}
+
+/// Commande analysée, partagée entre les requêtes préparées en cache
+struct Prepared {
+    command: SqlCommand,
+    parameters: Parameters,
+}
+
+/// Commande SQL analysée, prête à être exécutée sur sa connexion.
+///
+/// Les paramètres `?`, `?NNN`, `:nom`, `@nom` et `$nom` sont numérotés à
+/// partir de 0 dans leur ordre d'apparition, comme dans SQLite : `?NNN`
+/// désigne le paramètre NNN - 1 et un nom déjà vu garde son numéro.
+pub struct Statement<'a> {
+    connection: &'a Connection,
+    prepared: Arc<Prepared>,
+}
+
+impl Statement<'_> {
+    /// Nombre de valeurs à lier à chaque exécution
+    pub fn parameter_count(&self) -> usize {
+        self.prepared.parameters.len()
+    }
+
+    /// Numéro d'un paramètre nommé, préfixe compris : `:nom`
+    pub fn parameter_index(&self, name: &str) -> Option<usize> {
+        self.prepared.parameters.index_of(name)
+    }
+
+    /// Lie une valeur à chaque paramètre
+    fn bind(&self, params: &[Value]) -> Result<SqlCommand, CommandError> {
+        if params.len() != self.parameter_count() {
+            return Err(CommandError::ParameterCount {
+                expected: self.parameter_count(),
+                given: params.len(),
+            });
+        }
+        self.prepared.command.bind(params)
+    }
+
+    /// Valeurs des paramètres nommés, rangées selon leur numéro
+    fn named(&self, params: &[(&str, Value)]) -> Result<Vec<Value>, CommandError> {
+        let mut values = vec![None; self.parameter_count()];
+        for (name, value) in params {
+            let index = self
+                .parameter_index(name)
+                .ok_or_else(|| CommandError::UnknownParameter(name.to_string()))?;
+            values[index] = Some(value.clone());
+        }
+        values
+            .into_iter()
+            .collect::<Option<_>>()
+            .ok_or(CommandError::ParameterCount {
+                expected: self.parameter_count(),
+                given: params.len(),
+            })
+    }
+
+    /// Exécute la commande et renvoie le nombre de lignes modifiées
+    pub fn execute(&mut self, params: &[Value]) -> Result<usize, ExecutionError> {
+        let command = self.bind(params).map_err(ExecutionError::Command)?;
+        self.connection.run(&command)?;
+        Ok(command.changes())
+    }
+
+    /// Exécute la commande et renvoie les lignes qu'elle produit
+    pub fn query(&mut self, params: &[Value]) -> Result<Rows, ExecutionError> {
+        let command = self.bind(params).map_err(ExecutionError::Command)?;
+        let result = self.connection.run(&command)?;
+        // les colonnes sont nommées d'après la commande avant liaison, sauf
+        // `*` dont les colonnes ne sont connues qu'à l'exécution
+        let columns = self.prepared.command.columns();
+        let columns = match columns.iter().any(|column| column == "*") {
+            true => result.columns,
+            false => columns,
+        };
+        Ok(Rows::new(columns, result.rows))
+    }
+
+    /// Exécute la commande et lit chaque ligne du résultat dans un `T`
+    pub fn query_as<T: FromRow>(&mut self, params: &[Value]) -> Result<Vec<T>, ExecutionError> {
+        self.query(params)?
+            .map(|row| T::from_row(&row).map_err(ExecutionError::Row))
+            .collect()
+    }
+
+    /// `execute` avec des valeurs liées par le nom de leur paramètre
+    pub fn execute_named(&mut self, params: &[(&str, Value)]) -> Result<usize, ExecutionError> {
+        let params = self.named(params).map_err(ExecutionError::Command)?;
+        self.execute(&params)
+    }
+
+    /// `query` avec des valeurs liées par le nom de leur paramètre
+    pub fn query_named(&mut self, params: &[(&str, Value)]) -> Result<Rows, ExecutionError> {
+        let params = self.named(params).map_err(ExecutionError::Command)?;
+        self.query(&params)
+    }
+}
+
+/// Transaction en cours sur une connexion.
+///
+/// Ses écritures restent en attente jusqu'au `commit`, et sont oubliées si
+/// elle est abandonnée.
+pub struct TransactionGuard<'a> {
+    connection: &'a Connection,
+    transaction: IsolatedTransaction,
+}
+
+impl TransactionGuard<'_> {
+    pub fn create_table(&mut self, table_name: TableName) -> Result<(), CreationError> {
+        self.transaction.create_table(table_name)
+    }
+
+    pub fn insert(&mut self, data: Record) -> Result<(), InsertionError> {
+        self.transaction.insert(data)
+    }
+
+    pub fn select(&self, table_name: TableName) -> Result<Vec<Record>, SelectError> {
+        self.transaction.select(table_name)
+    }
+
+    /// Valide la transaction, une erreur `is_retryable` indique qu'une autre
+    /// transaction a créé les mêmes tables ou a la base pour elle, et qu'il
+    /// faut recommencer
+    pub fn commit(self) -> Result<(), TransactionError> {
+        let transaction = self.transaction;
+        self.connection
+            .write_owned(|database| transaction.commit(database), TransactionError::Storage)
+    }
+
+    /// Abandonne la transaction, ses écritures sont oubliées
+    pub fn rollback(self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::User;
+    use std::thread;
+
+    fn user(i: i64) -> Record {
+        Record::User(User::new(i, format!("test_{i}"), format!("email_{i}@example.com")))
+    }
+
+    #[test]
+    fn test_concurrent_readers_and_writers() {
+        const WRITERS: i64 = 4;
+        const TRANSACTIONS: i64 = 25;
+        const ROWS: i64 = 4;
+        let connection = Connection::memory();
+        connection.create_table(TableName::User).expect("Creation failed");
+
+        thread::scope(|scope| {
+            for writer in 0..WRITERS {
+                let connection = connection.clone();
+                scope.spawn(move || {
+                    for transaction in 0..TRANSACTIONS {
+                        loop {
+                            let mut guard = connection.begin();
+                            for row in 0..ROWS {
+                                let id = (writer * TRANSACTIONS + transaction) * ROWS + row;
+                                guard.insert(user(id)).expect("insert failed");
+                            }
+                            // une transaction sur deux est annulée
+                            if transaction % 2 == 1 {
+                                guard.rollback();
+                                break;
+                            }
+                            match guard.commit() {
+                                Ok(()) => break,
+                                // un autre écrivain est passé avant, on recommence
+                                Err(err) if err.is_retryable() => continue,
+                                Err(err) => panic!("commit failed: {err}"),
+                            }
+                        }
+                    }
+                });
+            }
+            for _reader in 0..4 {
+                let connection = connection.clone();
+                scope.spawn(move || {
+                    let mut previous = 0;
+                    for _ in 0..100 {
+                        let rows = connection.select(TableName::User).expect("select failed");
+                        // les transactions sont vues en entier ou pas du tout
+                        assert_eq!(rows.len() as i64 % ROWS, 0);
+                        assert!(rows.len() >= previous);
+                        previous = rows.len();
+                    }
+                });
+            }
+        });
+
+        let rows = connection.select(TableName::User).expect("select failed");
+        assert_eq!(rows.len() as i64, WRITERS * (TRANSACTIONS + 1) / 2 * ROWS);
+        assert!(!connection.read(Database::in_transaction));
+    }
+
+    #[test]
+    fn test_snapshot_isolation() {
+        let connection = Connection::memory();
+        connection.create_table(TableName::User).expect("Creation failed");
+        connection.insert(user(1)).expect("insert failed");
+
+        let mut first = connection.begin();
+        let second_connection = connection.clone();
+        let mut second = second_connection.begin();
+        // chacun voit ses propres écritures mais pas celles de l'autre
+        first.insert(user(2)).expect("insert failed");
+        second.insert(user(3)).expect("insert failed");
+        assert_eq!(first.select(TableName::User), Ok(vec![user(1), user(2)]));
+        assert_eq!(second.select(TableName::User), Ok(vec![user(1), user(3)]));
+        assert_eq!(connection.select(TableName::User), Ok(vec![user(1)]));
+
+        // une lecture commencée avant le commit garde sa vue
+        let snapshot = connection.snapshot();
+        first.commit().expect("commit failed");
+        assert_eq!(snapshot.select(TableName::User), Ok(vec![user(1)]));
+        assert_eq!(connection.select(TableName::User), Ok(vec![user(1), user(2)]));
+        assert_eq!(second.select(TableName::User), Ok(vec![user(1), user(3)]));
+
+        // des ajouts dans une même table ne sont pas en conflit
+        second.commit().expect("commit failed");
+        assert_eq!(connection.select(TableName::User), Ok(vec![user(1), user(2), user(3)]));
+
+        // le second crée une table créée depuis son démarrage
+        let mut first = connection.begin();
+        let mut second = second_connection.begin();
+        first.create_table(TableName::Car).expect("Creation failed");
+        second.create_table(TableName::Car).expect("Creation failed");
+        second.insert(user(4)).expect("insert failed");
+        first.commit().expect("commit failed");
+        let err = second.commit().expect_err("commit should conflict");
+        assert_eq!(err, TransactionError::Conflict(TableName::Car));
+        assert!(err.is_retryable());
+        assert_eq!(connection.select(TableName::User), Ok(vec![user(1), user(2), user(3)]));
+    }
+
+    #[test]
+    fn test_sql_transaction_per_connection() {
+        let first = Connection::memory();
+        first.create_table(TableName::User).expect("Creation failed");
+        let second = first.clone();
+        first.execute("begin", &[]).expect("begin failed");
+        first.insert(user(1)).expect("insert failed");
+        // l'autre connexion ne voit pas la transaction et ne peut pas écrire
+        assert_eq!(first.query_as::<(i64,)>("select id from user", &[]), Ok(vec![(1,)]));
+        assert_eq!(second.query_as::<(i64,)>("select id from user", &[]), Ok(vec![]));
+        assert_eq!(second.insert(user(2)), Err(InsertionError::Storage(StorageError::Busy)));
+        assert_eq!(
+            second.execute("begin", &[]),
+            Err(ExecutionError::Storage(StorageError::Busy))
+        );
+        first.execute("rollback", &[]).expect("rollback failed");
+        second.insert(user(2)).expect("insert failed");
+        assert_eq!(first.select(TableName::User), Ok(vec![user(2)]));
+
+        // la transaction d'une connexion fermée est annulée
+        second.execute("begin", &[]).expect("begin failed");
+        second.insert(user(3)).expect("insert failed");
+        drop(second);
+        assert!(!first.read(Database::in_transaction));
+        first.insert(user(4)).expect("insert failed");
+        assert_eq!(first.select(TableName::User), Ok(vec![user(2), user(4)]));
+    }
+
+    #[test]
+    fn test_commit_in_sql_transaction() {
+        let connection = Connection::memory();
+        connection.create_table(TableName::User).expect("Creation failed");
+        let other = connection.clone();
+        other.execute("begin", &[]).expect("begin failed");
+        let mut guard = connection.begin();
+        guard.insert(user(1)).expect("insert failed");
+        // le rollback de l'autre connexion déferait le commit
+        let err = guard.commit().expect_err("commit should be busy");
+        assert_eq!(err, TransactionError::Storage(StorageError::Busy));
+        assert!(err.is_retryable());
+        other.execute("rollback", &[]).expect("rollback failed");
+
+        // même dans sa propre transaction SQL, le commit reste séparé
+        connection.execute("begin", &[]).expect("begin failed");
+        let mut guard = connection.begin();
+        guard.insert(user(2)).expect("insert failed");
+        assert_eq!(guard.commit(), Err(TransactionError::Storage(StorageError::Busy)));
+        connection.execute("rollback", &[]).expect("rollback failed");
+
+        let mut guard = connection.begin();
+        guard.insert(user(3)).expect("insert failed");
+        guard.commit().expect("commit failed");
+        assert!(!connection.read(Database::in_transaction));
+        assert_eq!(connection.select(TableName::User), Ok(vec![user(3)]));
+    }
+
+    #[test]
+    fn test_strict_table() {
+        let connection = Connection::memory();
+        connection.execute("create user strict", &[]).expect("create failed");
+        // un enregistrement a déjà le type de ses colonnes
+        connection.insert(user(1)).expect("insert failed");
+        assert_eq!(
+            connection.execute("insert user '2' name email", &[]),
+            Err(ExecutionError::Insertion(InsertionError::TypeMismatch(TableName::User)))
+        );
+        assert_eq!(
+            connection.execute("insert user ? name email", &[Value::Text("3".to_string())]),
+            Err(ExecutionError::Insertion(InsertionError::TypeMismatch(TableName::User)))
+        );
+        // la vérification est faite par la base, quel que soit le chemin
+        assert_eq!(
+            connection.write(|database| database.insert_converted(user(4), true)),
+            Err(InsertionError::TypeMismatch(TableName::User))
+        );
+        assert_eq!(connection.select(TableName::User), Ok(vec![user(1)]));
+    }
+
+    #[test]
+    fn test_abandoned_transaction() {
+        let connection = Connection::memory();
+        connection.create_table(TableName::User).expect("Creation failed");
+        let other = connection.clone();
+        thread::spawn(move || {
+            let mut guard = other.begin();
+            guard.insert(user(1)).expect("insert failed");
+            panic!("writer crashed");
+        })
+        .join()
+        .expect_err("writer should panic");
+        // le rollback a eu lieu et la base reste utilisable
+        assert_eq!(connection.select(TableName::User), Ok(vec![]));
+        connection.insert(user(2)).expect("insert failed");
+        assert_eq!(connection.select(TableName::User), Ok(vec![user(2)]));
+    }
+
+    #[test]
+    fn test_statement() {
+        let connection = Connection::memory();
+        assert_eq!(connection.execute("create user", &[]), Ok(0));
+        assert_eq!(connection.execute("insert user 1 one one@example.com", &[]), Ok(1));
+        assert_eq!(connection.execute("insert user 2 two two@example.com", &[]), Ok(1));
+        let mut statement = connection.prepare("select user limit 5").expect("prepare failed");
+        let rows = statement.query(&[]).expect("query failed");
+        assert_eq!(rows.columns(), ["id", "username", "email"]);
+        let names = rows
+            .map(|row| Ok((row.get::<i64>(0)?, row.get::<String>("username")?)))
+            .collect::<Result<Vec<_>, crate::errors::RowError>>();
+        assert_eq!(names, Ok(vec![(1, "one".to_string()), (2, "two".to_string())]));
+        // une requête préparée se rejoue et voit les nouvelles lignes
+        connection
+            .execute("insert user 3 three three@example.com", &[])
+            .expect("insert failed");
+        assert_eq!(statement.query(&[]).map(Iterator::count), Ok(3));
+
+        let mut statement = connection
+            .prepare("select 1 < 2, cast('7' as integer)")
+            .expect("prepare failed");
+        let row = statement
+            .query(&[])
+            .expect("query failed")
+            .next()
+            .expect("no row");
+        assert_eq!(row.columns(), ["1 < 2", "CAST('7' AS INTEGER)"]);
+        assert_eq!(row.get::<bool>(0), Ok(true));
+        assert_eq!(row.get::<i64>(1), Ok(7));
+
+        assert_eq!(
+            connection.prepare("drop user").err(),
+            Some(CommandError::UnknownCommand("drop user".to_string()))
+        );
+        assert_eq!(
+            connection.execute("select user", &[Value::Integer(1)]),
+            Err(ExecutionError::Command(CommandError::ParameterCount { expected: 0, given: 1 }))
+        );
+    }
+
+    #[test]
+    fn test_bound_parameters() {
+        let connection = Connection::memory();
+        connection.execute("create user", &[]).expect("create failed");
+        let mut insert = connection.prepare("insert user ? ? ?").expect("prepare failed");
+        assert_eq!(insert.parameter_count(), 3);
+        for i in 1..=3 {
+            let name = Value::Text(format!("test_{i}"));
+            // une valeur liée n'est jamais relue comme du SQL
+            let email = Value::Text(format!("'); drop {i}"));
+            assert_eq!(insert.execute(&[Value::Integer(i), name, email]), Ok(1));
+        }
+        assert_eq!(
+            insert.execute(&[Value::Integer(4)]),
+            Err(ExecutionError::Command(CommandError::ParameterCount { expected: 3, given: 1 }))
+        );
+        assert_eq!(
+            insert.execute(&[Value::Text("four".to_string()), Value::Null, Value::Null]),
+            Err(ExecutionError::Command(CommandError::ExpectingInteger))
+        );
+        assert_eq!(
+            connection.select(TableName::User).map(|users| users.len()),
+            Ok(3)
+        );
+
+        let mut named = connection
+            .prepare("insert user ?1 :name :name")
+            .expect("prepare failed");
+        assert_eq!(named.parameter_index(":name"), Some(1));
+        assert_eq!(
+            named.execute_named(&[(":name", Value::Text("same".to_string()))]),
+            Err(ExecutionError::Command(CommandError::ParameterCount { expected: 2, given: 1 }))
+        );
+        assert_eq!(
+            named.execute_named(&[(":id", Value::Integer(5))]),
+            Err(ExecutionError::Command(CommandError::UnknownParameter(":id".to_string())))
+        );
+        named
+            .execute(&[Value::Integer(5), Value::Text("same".to_string())])
+            .expect("insert failed");
+        assert_eq!(
+            connection.select(TableName::User).map(|users| users.last().cloned()),
+            Ok(Some(Record::User(User::new(5, "same".to_string(), "same".to_string()))))
+        );
+
+        let mut select = connection.prepare("select :a = ?, :a").expect("prepare failed");
+        let row = select
+            .query_named(&[(":a", Value::Integer(1))])
+            .err();
+        assert_eq!(
+            row,
+            Some(ExecutionError::Command(CommandError::ParameterCount { expected: 2, given: 1 }))
+        );
+        let row = select
+            .query(&[Value::Integer(1), Value::Real(1.0)])
+            .expect("query failed")
+            .next()
+            .expect("no row");
+        assert_eq!(row.columns(), [":a = ?", ":a"]);
+        assert_eq!(row.values(), [Value::Boolean(true), Value::Integer(1)]);
+
+        // le nombre de paramètres est borné
+        let last = connection.prepare("select ?32766").expect("prepare failed");
+        assert_eq!(last.parameter_count(), 32766);
+        let many = format!("select {}", vec!["?"; 32767].join(", "));
+        assert_eq!(connection.prepare(&many).err(), Some(CommandError::TooManyParameters));
+    }
+
+    #[test]
+    fn test_statement_cache() {
+        let connection = Connection::memory();
+        let first = connection.prepare_cached("select ?").expect("prepare failed");
+        let second = connection.prepare_cached("select ?").expect("prepare failed");
+        assert!(Arc::ptr_eq(&first.prepared, &second.prepared));
+        // au-delà de sa capacité, le cache oublie la requête la plus ancienne
+        for i in 0..STATEMENT_CACHE_CAPACITY {
+            connection.prepare_cached(&format!("select {i}")).expect("prepare failed");
+        }
+        let third = connection.prepare_cached("select ?").expect("prepare failed");
+        assert!(!Arc::ptr_eq(&first.prepared, &third.prepared));
+        // une commande invalide n'est pas gardée
+        assert!(connection.prepare_cached("select (").is_err());
+        assert_eq!(connection.cache.lock().unwrap().len(), STATEMENT_CACHE_CAPACITY);
+        // une nouvelle connexion a son propre cache
+        assert!(connection.clone().cache.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_query_as() {
+        use crate::errors::RowError;
+        use crate::row::FromRow;
+
+        #[derive(Debug, PartialEq, FromRow)]
+        struct Contact {
+            email: String,
+            id: i64,
+        }
+
+        let connection = Connection::memory();
+        connection.execute("create user", &[]).expect("create failed");
+        connection
+            .execute("insert user 1 one one@example.com", &[])
+            .expect("insert failed");
+        assert_eq!(
+            connection.query_as::<Contact>("select user", &[]),
+            Ok(vec![Contact {
+                email: "one@example.com".to_string(),
+                id: 1
+            }])
+        );
+        assert_eq!(
+            connection.query_as::<(i64, String)>("select user", &[]),
+            Ok(vec![(1, "one".to_string())])
+        );
+        assert_eq!(
+            connection.query_as::<(String,)>("select user", &[]),
+            Err(ExecutionError::Row(RowError::InvalidType {
+                column: "id".to_string(),
+                expected: "String",
+                value: Value::Integer(1)
+            }))
+        );
+        assert_eq!(
+            connection.query_as::<Contact>("select ?", &[Value::Integer(1)]),
+            Err(ExecutionError::Row(RowError::UnknownColumn("email".to_string())))
+        );
+    }
+
+    #[test]
+    fn test_functions() {
+        use crate::errors::EvaluationError;
+        use std::sync::atomic::{AtomicUsize, Ordering};
+
+        let connection = Connection::memory();
+        connection.execute("create user", &[]).expect("create failed");
+        for (id, name) in [(1, "Ada"), (2, "Alan Turing"), (3, "Grace")] {
+            connection
+                .execute("insert user ? ? email", &[Value::Integer(id), Value::Text(name.to_string())])
+                .expect("insert failed");
+        }
+        connection.create_scalar_function("slugify", 1, true, |arguments| match &arguments[0] {
+            Value::Text(text) => Ok(Value::Text(text.to_lowercase().replace(' ', "-"))),
+            value => Ok(value.clone()),
+        });
+        assert_eq!(
+            connection.query_as::<(String,)>("select slugify(username) from user where id = 2", &[]),
+            Ok(vec![("alan-turing".to_string(),)])
+        );
+        connection.create_aggregate_function(
+            "longest",
+            1,
+            String::new,
+            |longest, arguments| {
+                let text = arguments[0].to_string();
+                if text.len() > longest.len() {
+                    *longest = text;
+                }
+                Ok(())
+            },
+            |longest| Ok(Value::Text(longest)),
+        );
+        assert_eq!(
+            connection.query_as::<(String, i64, i64)>("select longest(username), count(*), max(id) from user", &[]),
+            Ok(vec![("Alan Turing".to_string(), 3, 3)])
+        );
+        assert_eq!(
+            connection.query_as::<(i64,)>("select count(*) from user where id > 5", &[]),
+            Ok(vec![(0,)])
+        );
+
+        // appelée sur une constante, une fonction déterministe n'est
+        // calculée qu'une fois, les autres le sont pour chaque ligne
+        let calls = Arc::new(AtomicUsize::new(0));
+        for (name, deterministic) in [("once", true), ("each", false)] {
+            let calls = calls.clone();
+            connection.create_scalar_function(name, 1, deterministic, move |arguments| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                Ok(arguments[0].clone())
+            });
+        }
+        connection.execute("select once(1), id from user", &[]).expect("select failed");
+        assert_eq!(calls.swap(0, Ordering::SeqCst), 1);
+        connection.execute("select each(1), id from user", &[]).expect("select failed");
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+
+        assert_eq!(
+            connection.execute("select unknown(1)", &[]),
+            Err(ExecutionError::Evaluation(EvaluationError::UnknownFunction("unknown".to_string())))
+        );
+        assert_eq!(
+            connection.execute("select slugify()", &[]),
+            Err(ExecutionError::Evaluation(EvaluationError::WrongArgumentCount {
+                function: "slugify".to_string(),
+                given: 0
+            }))
+        );
+        assert_eq!(
+            connection.execute("select id from user where count(*) > 1", &[]),
+            Err(ExecutionError::Evaluation(EvaluationError::MisusedAggregate("count(*) > 1".to_string())))
+        );
+        assert_eq!(
+            connection.execute("select max(count(*)) from user", &[]),
+            Err(ExecutionError::Evaluation(EvaluationError::MisusedAggregate("max".to_string())))
+        );
+        assert_eq!(
+            connection.execute("select age from user", &[]),
+            Err(ExecutionError::Evaluation(EvaluationError::UnknownColumn("age".to_string())))
+        );
+    }
+
+    #[test]
+    fn test_json() {
+        let connection = Connection::memory();
+        connection.execute("create user", &[]).expect("create failed");
+        for (id, name) in [(1, "Ada"), (2, "Grace")] {
+            connection
+                .execute("insert user ? ? email", &[Value::Integer(id), Value::Text(name.to_string())])
+                .expect("insert failed");
+        }
+        // le JSON renvoyé par une fonction JSON n'est pas cité une seconde fois
+        assert_eq!(
+            connection.query_as::<(String,)>("select json_object('a', json_array(1, 2), 'b', '[3]')", &[]),
+            Ok(vec![(r#"{"a":[1,2],"b":"[3]"}"#.to_string(),)])
+        );
+        assert_eq!(
+            connection.query_as::<(String, i64)>(
+                "select '{\"a\": {\"b\": [\"x\", 2]}}' -> 'a' ->> '$.b[0]', ? ->> -1",
+                &[Value::Text("[1, 2, 3]".to_string())]
+            ),
+            Ok(vec![("x".to_string(), 3)])
+        );
+        assert_eq!(
+            connection.query_as::<(String,)>("select json_group_array(json_object('id', id)) from user", &[]),
+            Ok(vec![(r#"[{"id":1},{"id":2}]"#.to_string(),)])
+        );
+        let mut statement = connection.prepare("select * from json_each(?) where value > 1").expect("prepare failed");
+        let rows = statement.query(&[Value::Text("[1, 2, 3]".to_string())]).expect("query failed");
+        assert_eq!(rows.columns(), ["key", "value", "type", "atom", "id", "parent", "fullkey", "path"]);
+        let keys = rows.map(|row| row.get::<i64>(0)).collect::<Result<Vec<_>, _>>();
+        assert_eq!(keys, Ok(vec![1, 2]));
+        assert_eq!(
+            connection.query_as::<(String, String)>("select fullkey, type from json_tree('{\"a\": [null]}')", &[]),
+            Ok(vec![
+                ("$".to_string(), "object".to_string()),
+                ("$.a".to_string(), "array".to_string()),
+                ("$.a[0]".to_string(), "null".to_string()),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_fts() {
+        let connection = Connection::memory();
+        connection
+            .execute("create virtual table docs using fts(title, body)", &[])
+            .expect("create failed");
+        for (title, body) in [
+            ("Rust", "Rust is a systems programming language, rust everywhere"),
+            ("Databases", "A database stores data"),
+            ("Rust and data", "A small database written in Rust"),
+        ] {
+            connection
+                .execute("insert into docs values (?, ?)", &[Value::Text(title.to_string()), Value::Text(body.to_string())])
+                .expect("insert failed");
+        }
+        let titles = |query: &str| {
+            connection
+                .query_as::<(String,)>(query, &[])
+                .map(|rows| rows.into_iter().map(|(title,)| title).collect::<Vec<_>>())
+        };
+        // les documents qui citent le plus le terme sont classés d'abord
+        assert_eq!(
+            titles("select title from docs where docs match 'rust'"),
+            Ok(vec!["Rust".to_string(), "Rust and data".to_string()])
+        );
+        assert_eq!(
+            titles("select title from docs where docs match '\"database written\" data*'"),
+            Ok(vec!["Rust and data".to_string()])
+        );
+        assert_eq!(
+            titles("select title from docs where title match 'data*'"),
+            Ok(vec!["Databases".to_string(), "Rust and data".to_string()])
+        );
+        let mut statement = connection.prepare("select *, rowid from docs where body match ?").expect("prepare failed");
+        let rows = statement.query(&[Value::Text("stores".to_string())]).expect("query failed");
+        assert_eq!(rows.columns(), ["title", "body", "rowid"]);
+        let rowids = rows.map(|row| row.get::<i64>(2)).collect::<Result<Vec<_>, _>>();
+        assert_eq!(rowids, Ok(vec![2]));
+        // MATCH n'a de sens que comme filtre d'une table fts
+        assert!(titles("select title from docs where title = 'x' match 'x'").is_err());
+        assert!(titles("select title from docs where docs match '\"open'").is_err());
+        assert!(connection.execute("insert into docs values ('only title')", &[]).is_err());
+    }
+
+    #[test]
+    fn test_virtual_tables() {
+        use crate::vtab::tests::Collection;
+
+        let connection = Connection::memory();
+        let rows: [&[&str]; 3] = [&["Paris", "France"], &["Lyon", "France"], &["Turin", "Italy"]];
+        connection.register_virtual_table("cities", Collection::new(&["city", "country"], &rows));
+        connection
+            .execute("insert into cities values ('Rome', 'Italy')", &[])
+            .expect("insert failed");
+        assert_eq!(
+            connection.query_as::<(String,)>("select city from cities where country = 'Italy'", &[]),
+            Ok(vec![("Turin".to_string(),), ("Rome".to_string(),)])
+        );
+        assert!(connection.execute("insert into cities values ('Nice')", &[]).is_err());
+        // le nom est pris par la table de l'application
+        assert!(connection.execute("create virtual table cities using fts(city)", &[]).is_err());
+
+        let mut statement = connection.prepare("select * from generate_series(?, 10, 4)").expect("prepare failed");
+        let rows = statement.query(&[Value::Integer(1)]).expect("query failed");
+        assert_eq!(rows.columns(), ["value"]);
+        let values = rows.map(|row| row.get::<i64>(0)).collect::<Result<Vec<_>, _>>();
+        assert_eq!(values, Ok(vec![1, 5, 9]));
+        assert_eq!(
+            connection.query_as::<(i64, i64)>("select sum(value), max(step) from generate_series(1, 100)", &[]),
+            Ok(vec![(5050, 1)])
+        );
+        // les lignes sont lues à la demande, jusqu'au limit
+        assert_eq!(
+            connection.query_as::<(i64,)>("select value from generate_series(1, 10000000000) limit 2", &[]),
+            Ok(vec![(1,), (2,)])
+        );
+        assert_eq!(
+            connection.query_as::<(i64,)>("select value from generate_series(5) where value > 6 limit 2", &[]),
+            Ok(vec![(7,), (8,)])
+        );
+        assert!(connection.query_as::<(i64,)>("select value from generate_series", &[]).is_err());
+        assert!(connection.query_as::<(i64,)>("select value from generate_series(1, 2, 3, 4)", &[]).is_err());
+        assert!(connection.execute("insert into generate_series values (1)", &[]).is_err());
+    }
+
+    #[test]
+    fn test_views() {
+        let connection = Connection::memory();
+        connection.execute("create user", &[]).expect("create failed");
+        for (id, name) in [(1, "Ada"), (2, "Grace"), (3, "Alan")] {
+            connection
+                .execute("insert user ? ? email", &[Value::Integer(id), Value::Text(name.to_string())])
+                .expect("insert failed");
+        }
+        connection
+            .execute("create view recent as select id, username from user where id > 1;", &[])
+            .expect("create view failed");
+        connection
+            .execute("create view names as select upper(username) from recent", &[])
+            .expect("create view failed");
+        // la vue suit les lignes ajoutées après sa création
+        connection
+            .execute("insert user 4 Barbara email", &[])
+            .expect("insert failed");
+        assert_eq!(
+            connection.query_as::<(String,)>("select username from recent where id < 4", &[]),
+            Ok(vec![("Grace".to_string(),), ("Alan".to_string(),)])
+        );
+        let mut statement = connection.prepare("select * from recent limit 1").expect("prepare failed");
+        assert_eq!(statement.query(&[]).expect("query failed").columns(), ["id", "username"]);
+        assert_eq!(
+            connection.query_as::<(i64,)>("select count(*) from names", &[]),
+            Ok(vec![(3,)])
+        );
+        // `select <vue>` lit toutes ses colonnes
+        assert_eq!(
+            connection.query_as::<(i64, String)>("select RECENT limit 1", &[]),
+            Ok(vec![(2, "Grace".to_string())])
+        );
+        // la requête de la vue est lue à la demande, jusqu'au limit
+        connection
+            .execute("create view numbers as select value from generate_series(1, 10000000000)", &[])
+            .expect("create view failed");
+        assert_eq!(
+            connection.query_as::<(i64,)>("select * from numbers limit 2", &[]),
+            Ok(vec![(1,), (2,)])
+        );
+        assert_eq!(connection.query_as::<(i64,)>("select numbers limit 1", &[]), Ok(vec![(1,)]));
+        assert!(connection.execute("create view broken as select id from nowhere", &[]).is_err());
+        assert!(connection.execute("create view recent as select id from user", &[]).is_err());
+        assert!(connection.execute("insert into recent values (5, 'Edsger')", &[]).is_err());
+        connection.execute("drop view names", &[]).expect("drop failed");
+        assert!(connection.query_as::<(String,)>("select * from names", &[]).is_err());
+        assert!(connection.execute("drop view names", &[]).is_err());
+        connection.execute("drop view if exists names", &[]).expect("drop failed");
+    }
+}
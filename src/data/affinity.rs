@@ -0,0 +1,162 @@
+use crate::data::datetime::{Date, Time, Timestamp};
+use crate::data::value::Value;
+
+/// Type préféré d'une colonne, qui guide la conversion des valeurs qu'elle
+/// reçoit, comme dans SQLite
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Affinity {
+    Integer,
+    Real,
+    Text,
+    /// aucune conversion
+    Blob,
+    Numeric,
+}
+
+impl Affinity {
+    /// Affinité d'un type déclaré, d'après les règles de SQLite appliquées
+    /// dans l'ordre : `INT`, puis `CHAR`, `CLOB` ou `TEXT`, puis `BLOB` ou
+    /// aucun type, puis `REAL`, `FLOA` ou `DOUB`, sinon numérique
+    pub fn from_type_name(name: &str) -> Self {
+        let name = name.to_ascii_uppercase();
+        let contains = |patterns: &[&str]| patterns.iter().any(|pattern| name.contains(pattern));
+        if contains(&["INT"]) {
+            Affinity::Integer
+        } else if contains(&["CHAR", "CLOB", "TEXT"]) {
+            Affinity::Text
+        } else if name.trim().is_empty() || contains(&["BLOB"]) {
+            Affinity::Blob
+        } else if contains(&["REAL", "FLOA", "DOUB"]) {
+            Affinity::Real
+        } else {
+            Affinity::Numeric
+        }
+    }
+
+    pub fn is_numeric(self) -> bool {
+        matches!(self, Affinity::Integer | Affinity::Real | Affinity::Numeric)
+    }
+}
+
+/// Nombre écrit dans un texte, `None` si le texte n'est pas entièrement un
+/// nombre, espaces autour exceptés
+fn parse_number(text: &str) -> Option<Value> {
+    let text = text.trim();
+    if let Ok(value) = text.parse::<i64>() {
+        return Some(Value::Integer(value));
+    }
+    // refuse les écritures acceptées par Rust mais pas par SQL : inf, NaN...
+    if !text.bytes().any(|byte| byte.is_ascii_digit())
+        || !text.bytes().all(|byte| byte.is_ascii_digit() || b"+-.eE".contains(&byte))
+    {
+        return None;
+    }
+    text.parse::<f64>().ok().map(Value::Real)
+}
+
+/// Plus long préfixe numérique d'un texte, 0 s'il n'y en a pas, comme le
+/// fait `CAST` dans SQLite
+fn number_prefix(text: &str) -> Value {
+    let text = text.trim_start();
+    (1..=text.len())
+        .rev()
+        .filter(|size| text.is_char_boundary(*size))
+        .find_map(|size| parse_number(&text[..size]))
+        .unwrap_or(Value::Integer(0))
+}
+
+/// Un flottant sans partie décimale qui tient dans un entier devient un entier
+fn integral(value: f64) -> Option<i64> {
+    (value.fract() == 0.0 && value >= i64::MIN as f64 && value < i64::MAX as f64)
+        .then_some(value as i64)
+}
+
+impl Value {
+    /// Convertit la valeur pour une colonne de l'affinité donnée, seulement
+    /// si la conversion ne perd rien : sinon la valeur est gardée telle quelle
+    pub fn apply_affinity(self, affinity: Affinity) -> Value {
+        match (affinity, self) {
+            (Affinity::Blob, value) | (_, value @ (Value::Null | Value::Blob(_))) => value,
+            (Affinity::Text, Value::Text(text)) => Value::Text(text),
+            (Affinity::Text, value) => Value::Text(value.to_string()),
+            (affinity, Value::Text(text)) => match parse_number(&text) {
+                Some(number) => number.apply_affinity(affinity),
+                None => Value::Text(text),
+            },
+            (affinity, Value::Boolean(value)) => Value::Integer(value as i64).apply_affinity(affinity),
+            (affinity, Value::Date(Date(value)) | Value::Time(Time(value)) | Value::Timestamp(Timestamp(value))) => {
+                Value::Integer(value).apply_affinity(affinity)
+            }
+            (Affinity::Real, Value::Integer(value)) => Value::Real(value as f64),
+            (Affinity::Integer | Affinity::Numeric, Value::Real(value)) => match integral(value) {
+                Some(value) => Value::Integer(value),
+                None => Value::Real(value),
+            },
+            (_, value) => value,
+        }
+    }
+
+    /// `CAST(valeur AS type)` : contrairement à l'affinité, la conversion
+    /// a toujours lieu, quitte à perdre de l'information
+    pub fn cast(self, affinity: Affinity) -> Value {
+        match (affinity, self) {
+            (_, Value::Null) => Value::Null,
+            (Affinity::Blob, Value::Blob(data)) => Value::Blob(data),
+            (Affinity::Blob, value) => Value::Blob(value.to_string().into_bytes()),
+            (affinity, Value::Blob(data)) => {
+                Value::Text(String::from_utf8_lossy(&data).into_owned()).cast(affinity)
+            }
+            (Affinity::Text, value) => value.apply_affinity(Affinity::Text),
+            (affinity, Value::Text(text)) => number_prefix(&text).cast(affinity),
+            (Affinity::Integer, Value::Real(value)) => Value::Integer(value as i64),
+            (affinity, value) => value.apply_affinity(affinity),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_type_name() {
+        assert_eq!(Affinity::from_type_name("BIGINT"), Affinity::Integer);
+        assert_eq!(Affinity::from_type_name("varchar(255)"), Affinity::Text);
+        assert_eq!(Affinity::from_type_name(""), Affinity::Blob);
+        assert_eq!(Affinity::from_type_name("DOUBLE PRECISION"), Affinity::Real);
+        assert_eq!(Affinity::from_type_name("DECIMAL(10,5)"), Affinity::Numeric);
+        // les règles s'appliquent dans l'ordre : "POINT" contient "INT"
+        assert_eq!(Affinity::from_type_name("FLOATING POINT"), Affinity::Integer);
+    }
+
+    #[test]
+    fn test_apply_affinity() {
+        let text = |text: &str| Value::Text(text.to_string());
+        assert_eq!(text(" 12 ").apply_affinity(Affinity::Integer), Value::Integer(12));
+        assert_eq!(text("1e2").apply_affinity(Affinity::Integer), Value::Integer(100));
+        assert_eq!(text("1.5").apply_affinity(Affinity::Numeric), Value::Real(1.5));
+        assert_eq!(text("12abc").apply_affinity(Affinity::Integer), text("12abc"));
+        assert_eq!(text("inf").apply_affinity(Affinity::Real), text("inf"));
+        assert_eq!(Value::Integer(3).apply_affinity(Affinity::Real), Value::Real(3.0));
+        assert_eq!(Value::Real(3.0).apply_affinity(Affinity::Integer), Value::Integer(3));
+        assert_eq!(Value::Real(3.5).apply_affinity(Affinity::Integer), Value::Real(3.5));
+        assert_eq!(Value::Real(3.0).apply_affinity(Affinity::Text), text("3.0"));
+        assert_eq!(Value::Boolean(true).apply_affinity(Affinity::Integer), Value::Integer(1));
+        assert_eq!(Value::Integer(3).apply_affinity(Affinity::Blob), Value::Integer(3));
+        assert_eq!(Value::Null.apply_affinity(Affinity::Text), Value::Null);
+    }
+
+    #[test]
+    fn test_cast() {
+        let text = |text: &str| Value::Text(text.to_string());
+        assert_eq!(text("12abc").cast(Affinity::Integer), Value::Integer(12));
+        assert_eq!(text("abc").cast(Affinity::Integer), Value::Integer(0));
+        assert_eq!(text(" -1.5x").cast(Affinity::Real), Value::Real(-1.5));
+        assert_eq!(text("3.0").cast(Affinity::Numeric), Value::Integer(3));
+        assert_eq!(Value::Real(-3.9).cast(Affinity::Integer), Value::Integer(-3));
+        assert_eq!(Value::Integer(7).cast(Affinity::Text), text("7"));
+        assert_eq!(Value::Integer(7).cast(Affinity::Blob), Value::Blob(b"7".to_vec()));
+        assert_eq!(Value::Blob(b"42".to_vec()).cast(Affinity::Integer), Value::Integer(42));
+        assert_eq!(Value::Null.cast(Affinity::Integer), Value::Null);
+    }
+}
@@ -1,11 +1,9 @@
-use std::io::Cursor;
-use crate::data::serde::{Deserializable, Serializable};
-use crate::errors::{DeserializationError, SerializationError};
+use crate::data::{Deserializable, Serializable};
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Serializable, Deserializable)]
 pub struct Car {
-    id: String,
-    brand: String,
+    pub(crate) id: String,
+    pub(crate) brand: String,
 }
 
 impl Car {
@@ -13,20 +11,3 @@ impl Car {
         Self { id, brand }
     }
 }
-
-impl Serializable for Car {
-    fn serialize(&self, cursor: &mut Cursor<&mut [u8]>) -> Result<(), SerializationError> {
-        self.id.serialize(cursor)?;
-        self.brand.serialize(cursor)?;
-        Ok(())
-    }
-}
-
-impl Deserializable for Car {
-    fn deserialize(cursor: &mut Cursor<&[u8]>) -> Result<Self, DeserializationError> {
-        Ok(Car {
-            id: String::deserialize(cursor)?,
-            brand: String::deserialize(cursor)?,
-        })
-    }
-}
@@ -0,0 +1,201 @@
+//! Types temporels, stockés comme des entiers et écrits au format ISO 8601.
+
+use crate::errors::CommandError;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+const SECONDS_PER_DAY: i64 = 24 * 60 * 60;
+
+/// Date, en nombre de jours depuis le 1er janvier 1970
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+pub struct Date(pub i64);
+
+/// Heure de la journée, en secondes depuis minuit
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+pub struct Time(pub i64);
+
+/// Instant, en secondes depuis le 1er janvier 1970 à minuit UTC
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Copy, Hash)]
+pub struct Timestamp(pub i64);
+
+impl Date {
+    pub fn from_ymd(year: i64, month: u32, day: u32) -> Option<Self> {
+        if !(1..=12).contains(&month) || day < 1 || day > days_in_month(year, month) {
+            return None;
+        }
+        // algorithme "days from civil" de Howard Hinnant, sur des ères de 400 ans
+        let year = if month <= 2 { year - 1 } else { year };
+        let era = year.div_euclid(400);
+        let year_of_era = year.rem_euclid(400);
+        let month = month as i64;
+        let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day as i64 - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        Some(Date(era * 146_097 + day_of_era - 719_468))
+    }
+
+    /// Année, mois et jour
+    pub fn ymd(&self) -> (i64, u32, u32) {
+        let days = self.0 + 719_468;
+        let era = days.div_euclid(146_097);
+        let day_of_era = days.rem_euclid(146_097);
+        let year_of_era =
+            (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted_month = (5 * day_of_year + 2) / 153;
+        let day = (day_of_year - (153 * shifted_month + 2) / 5 + 1) as u32;
+        let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 } as u32;
+        let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+        (year, month, day)
+    }
+}
+
+impl Time {
+    pub fn from_hms(hour: u32, minute: u32, second: u32) -> Option<Self> {
+        if hour > 23 || minute > 59 || second > 59 {
+            return None;
+        }
+        Some(Time((hour * 3600 + minute * 60 + second) as i64))
+    }
+
+    /// Heures, minutes et secondes
+    pub fn hms(&self) -> (u32, u32, u32) {
+        let seconds = self.0.rem_euclid(SECONDS_PER_DAY) as u32;
+        (seconds / 3600, seconds / 60 % 60, seconds % 60)
+    }
+}
+
+impl Timestamp {
+    pub fn new(date: Date, time: Time) -> Self {
+        Timestamp(date.0 * SECONDS_PER_DAY + time.0)
+    }
+
+    pub fn date(&self) -> Date {
+        Date(self.0.div_euclid(SECONDS_PER_DAY))
+    }
+
+    pub fn time(&self) -> Time {
+        Time(self.0.rem_euclid(SECONDS_PER_DAY))
+    }
+}
+
+fn is_leap_year(year: i64) -> bool {
+    year % 4 == 0 && (year % 100 != 0 || year % 400 == 0)
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 if is_leap_year(year) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Lit un nombre d'exactement `digits` chiffres
+fn number(input: &str, digits: usize) -> Option<u32> {
+    if input.len() != digits || !input.bytes().all(|byte| byte.is_ascii_digit()) {
+        return None;
+    }
+    input.parse().ok()
+}
+
+impl FromStr for Date {
+    type Err = CommandError;
+
+    /// `AAAA-MM-JJ`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(3, '-');
+        let mut next = |digits| parts.next().and_then(|part| number(part, digits));
+        match (next(4), next(2), next(2)) {
+            (Some(year), Some(month), Some(day)) => Date::from_ymd(year as i64, month, day),
+            _ => None,
+        }
+        .ok_or_else(|| CommandError::InvalidLiteral(s.to_string()))
+    }
+}
+
+impl FromStr for Time {
+    type Err = CommandError;
+
+    /// `HH:MM[:SS]`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(3, ':');
+        let hour = parts.next().and_then(|part| number(part, 2));
+        let minute = parts.next().and_then(|part| number(part, 2));
+        let second = match parts.next() {
+            Some(part) => number(part, 2),
+            None => Some(0),
+        };
+        match (hour, minute, second) {
+            (Some(hour), Some(minute), Some(second)) => Time::from_hms(hour, minute, second),
+            _ => None,
+        }
+        .ok_or_else(|| CommandError::InvalidLiteral(s.to_string()))
+    }
+}
+
+impl FromStr for Timestamp {
+    type Err = CommandError;
+
+    /// `AAAA-MM-JJ[ HH:MM[:SS]]`, la date et l'heure pouvant aussi être
+    /// séparées par un `T`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = |_| CommandError::InvalidLiteral(s.to_string());
+        match s.split_once([' ', 'T']) {
+            Some((date, time)) => Ok(Timestamp::new(
+                date.parse().map_err(invalid)?,
+                time.parse().map_err(invalid)?,
+            )),
+            None => Ok(Timestamp::new(s.parse()?, Time(0))),
+        }
+    }
+}
+
+impl Display for Date {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let (year, month, day) = self.ymd();
+        write!(f, "{year:04}-{month:02}-{day:02}")
+    }
+}
+
+impl Display for Time {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let (hour, minute, second) = self.hms();
+        write!(f, "{hour:02}:{minute:02}:{second:02}")
+    }
+}
+
+impl Display for Timestamp {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.date(), self.time())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_date() {
+        assert_eq!(Date::from_str("1970-01-01"), Ok(Date(0)));
+        assert_eq!(Date::from_str("2000-03-01"), Ok(Date(11_017)));
+        assert_eq!(Date::from_str("1969-12-31"), Ok(Date(-1)));
+        for date in ["2024-02-29", "1900-02-28", "0001-01-01", "9999-12-31"] {
+            assert_eq!(Date::from_str(date).map(|date| date.to_string()), Ok(date.to_string()));
+        }
+        for date in ["2023-02-29", "2024-13-01", "2024-1-01", "2024-01-01x", ""] {
+            assert_eq!(Date::from_str(date), Err(CommandError::InvalidLiteral(date.to_string())));
+        }
+    }
+
+    #[test]
+    fn test_time_and_timestamp() {
+        assert_eq!(Time::from_str("12:30"), Ok(Time(45_000)));
+        assert_eq!(Time::from_str("23:59:59").map(|time| time.to_string()), Ok("23:59:59".to_string()));
+        assert!(Time::from_str("24:00").is_err());
+        assert_eq!(Timestamp::from_str("1970-01-02T00:00:01"), Ok(Timestamp(86_401)));
+        assert_eq!(Timestamp::from_str("1970-01-02"), Ok(Timestamp(86_400)));
+        assert_eq!(Timestamp(-1).to_string(), "1969-12-31 23:59:59");
+        assert!(Timestamp::from_str("1970-01-02 25:00").is_err());
+    }
+}
@@ -0,0 +1,430 @@
+//! Format des enregistrements, inspiré de celui de SQLite.
+//!
+//! Un enregistrement commence par un en-tête : sa taille en varint puis le
+//! code de type de chaque colonne, en varint lui aussi. Les valeurs suivent
+//! dans l'ordre des colonnes, sur la taille indiquée par leur code :
+//!
+//! | code          | valeur                                   |
+//! |---------------|------------------------------------------|
+//! | 0             | NULL, aucun octet                        |
+//! | 1 à 6         | entier sur 1, 2, 3, 4, 6 ou 8 octets     |
+//! | 7             | flottant sur 8 octets                    |
+//! | 8, 9          | entiers 0 et 1, aucun octet              |
+//! | N ≥ 12 pair   | blob de (N - 12) / 2 octets              |
+//! | N ≥ 13 impair | texte de (N - 13) / 2 octets             |
+//!
+//! Les booléens et les types temporels sont stockés comme des entiers.
+//!
+//! Les colonnes absentes de l'en-tête, ajoutées au schéma après l'écriture
+//! de la ligne, sont lues comme NULL.
+
+use crate::data::datetime::{Date, Time, Timestamp};
+use crate::data::primitives::{payload_size, read_payload, read_varint, write_payload, write_varint};
+use crate::data::serde::Reader;
+use crate::errors::{BufferError, DeserializationError, SerializationError};
+use std::io::{Read, Write};
+
+pub const NULL: u64 = 0;
+const INTEGER: u64 = 6;
+const REAL: u64 = 7;
+const ZERO: u64 = 8;
+const ONE: u64 = 9;
+const BLOB: u64 = 12;
+const TEXT: u64 = 13;
+/// taille des entiers selon leur code
+const INTEGER_SIZES: [usize; 7] = [0, 1, 2, 3, 4, 6, 8];
+
+/// Valeur qui peut être stockée dans une colonne d'un enregistrement
+pub trait Column {
+    /// Code de type de la valeur
+    fn serial_type(&self) -> u64;
+    /// Écrit la valeur, sur la taille annoncée par son code
+    fn write(&self, cursor: &mut std::io::Cursor<&mut [u8]>) -> Result<(), SerializationError>;
+    /// Relit une valeur écrite avec le code donné
+    fn read(serial_type: u64, cursor: &mut Reader<'_>) -> Result<Self, DeserializationError>
+    where
+        Self: Sized;
+}
+
+/// Écrit un enregistrement : l'en-tête des codes de type puis les valeurs
+pub fn write_record(
+    cursor: &mut std::io::Cursor<&mut [u8]>,
+    columns: &[&dyn Column],
+) -> Result<(), SerializationError> {
+    // un varint tient sur 10 octets au plus
+    let mut header = vec![0_u8; columns.len() * 10];
+    let mut writer = std::io::Cursor::new(&mut header[..]);
+    for column in columns {
+        write_varint(&mut writer, column.serial_type())?;
+    }
+    let size = writer.position() as usize;
+    write_varint(cursor, size as u64)?;
+    cursor
+        .write_all(&header[..size])
+        .map_err(|e| SerializationError::Buffer(BufferError::BufferFull(e.to_string())))?;
+    for column in columns {
+        column.write(cursor)?;
+    }
+    Ok(())
+}
+
+/// Lecture d'un enregistrement colonne par colonne
+pub struct RecordReader {
+    serial_types: Vec<u64>,
+    /// prochaine colonne à lire
+    next: usize,
+}
+
+impl RecordReader {
+    /// Lit l'en-tête de l'enregistrement, le curseur est ensuite placé sur
+    /// la première valeur
+    pub fn new(cursor: &mut Reader<'_>) -> Result<Self, DeserializationError> {
+        let size = read_varint(cursor)?;
+        let end = cursor.position().saturating_add(size);
+        if end > cursor.len() as u64 {
+            return Err(DeserializationError::Buffer(BufferError::ReadTooMuch(format!(
+                "record header of {size} bytes"
+            ))));
+        }
+        let mut serial_types = vec![];
+        while cursor.position() < end {
+            serial_types.push(read_varint(cursor)?);
+        }
+        if cursor.position() != end {
+            return Err(DeserializationError::UnableToDeserializeVarint);
+        }
+        Ok(Self {
+            serial_types,
+            next: 0,
+        })
+    }
+
+    /// Nombre de colonnes écrites dans l'enregistrement
+    pub fn len(&self) -> usize {
+        self.serial_types.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.serial_types.is_empty()
+    }
+
+    /// Lit la colonne suivante, NULL si l'enregistrement n'en a plus
+    pub fn column<T: Column>(&mut self, cursor: &mut Reader<'_>) -> Result<T, DeserializationError> {
+        let serial_type = self.serial_types.get(self.next).copied().unwrap_or(NULL);
+        self.next += 1;
+        T::read(serial_type, cursor)
+    }
+
+    /// Passe les colonnes qui n'ont pas été lues pour placer le curseur sur
+    /// l'enregistrement suivant
+    pub fn finish(self, cursor: &mut Reader<'_>) -> Result<(), DeserializationError> {
+        for serial_type in self.serial_types.iter().skip(self.next) {
+            let size = value_size(*serial_type)?;
+            cursor.set_position(cursor.position() + size as u64);
+        }
+        Ok(())
+    }
+}
+
+/// Place occupée dans la ligne par une valeur du type donné
+fn value_size(serial_type: u64) -> Result<usize, DeserializationError> {
+    match serial_type {
+        NULL..=INTEGER => Ok(INTEGER_SIZES[serial_type as usize]),
+        REAL => Ok(8),
+        ZERO | ONE => Ok(0),
+        n if n >= BLOB => Ok(payload_size(((n - BLOB) / 2) as usize)),
+        n => Err(DeserializationError::UnexpectedType(n)),
+    }
+}
+
+/// Plus petit code d'entier capable de représenter la valeur
+fn integer_type(value: i64) -> u64 {
+    match value {
+        0 => ZERO,
+        1 => ONE,
+        _ => (1..6)
+            .find(|code| {
+                let bits = INTEGER_SIZES[*code] * 8 - 1;
+                (-(1_i64 << bits)..1_i64 << bits).contains(&value)
+            })
+            .unwrap_or(INTEGER as usize) as u64,
+    }
+}
+
+impl Column for i64 {
+    fn serial_type(&self) -> u64 {
+        integer_type(*self)
+    }
+
+    fn write(&self, cursor: &mut std::io::Cursor<&mut [u8]>) -> Result<(), SerializationError> {
+        let size = value_size(integer_type(*self)).unwrap_or(8);
+        cursor
+            .write_all(&self.to_le_bytes()[..size])
+            .map_err(|e| SerializationError::Buffer(BufferError::BufferFull(e.to_string())))
+    }
+
+    fn read(serial_type: u64, cursor: &mut Reader<'_>) -> Result<Self, DeserializationError> {
+        match serial_type {
+            NULL => Err(DeserializationError::UnexpectedNull),
+            ZERO => Ok(0),
+            ONE => Ok(1),
+            1..=INTEGER => {
+                let size = INTEGER_SIZES[serial_type as usize];
+                let mut data = [0_u8; 8];
+                cursor
+                    .read_exact(&mut data[..size])
+                    .map_err(|e| DeserializationError::Buffer(BufferError::ReadTooMuch(e.to_string())))?;
+                // extension du signe
+                if data[size - 1] & 0x80 != 0 {
+                    data[size..].fill(0xFF);
+                }
+                Ok(i64::from_le_bytes(data))
+            }
+            n => Err(DeserializationError::UnexpectedType(n)),
+        }
+    }
+}
+
+impl Column for String {
+    fn serial_type(&self) -> u64 {
+        TEXT + 2 * self.len() as u64
+    }
+
+    fn write(&self, cursor: &mut std::io::Cursor<&mut [u8]>) -> Result<(), SerializationError> {
+        write_payload(cursor, self.as_bytes())
+    }
+
+    fn read(serial_type: u64, cursor: &mut Reader<'_>) -> Result<Self, DeserializationError> {
+        match serial_type {
+            NULL => Err(DeserializationError::UnexpectedNull),
+            n if n >= TEXT && n % 2 == 1 => String::from_utf8(read_payload(cursor, (n - TEXT) / 2)?)
+                .map_err(DeserializationError::UnableToDeserializeString),
+            n => Err(DeserializationError::UnexpectedType(n)),
+        }
+    }
+}
+
+impl Column for f64 {
+    fn serial_type(&self) -> u64 {
+        REAL
+    }
+
+    fn write(&self, cursor: &mut std::io::Cursor<&mut [u8]>) -> Result<(), SerializationError> {
+        cursor
+            .write_all(&self.to_le_bytes())
+            .map_err(|e| SerializationError::Buffer(BufferError::BufferFull(e.to_string())))
+    }
+
+    fn read(serial_type: u64, cursor: &mut Reader<'_>) -> Result<Self, DeserializationError> {
+        match serial_type {
+            REAL => {
+                let mut data = [0_u8; 8];
+                cursor
+                    .read_exact(&mut data)
+                    .map_err(|e| DeserializationError::Buffer(BufferError::ReadTooMuch(e.to_string())))?;
+                Ok(f64::from_le_bytes(data))
+            }
+            // un entier est lu comme un flottant
+            _ => i64::read(serial_type, cursor).map(|value| value as f64),
+        }
+    }
+}
+
+impl Column for bool {
+    fn serial_type(&self) -> u64 {
+        if *self {
+            ONE
+        } else {
+            ZERO
+        }
+    }
+
+    fn write(&self, _cursor: &mut std::io::Cursor<&mut [u8]>) -> Result<(), SerializationError> {
+        Ok(())
+    }
+
+    fn read(serial_type: u64, cursor: &mut Reader<'_>) -> Result<Self, DeserializationError> {
+        i64::read(serial_type, cursor).map(|value| value != 0)
+    }
+}
+
+impl Column for Vec<u8> {
+    fn serial_type(&self) -> u64 {
+        BLOB + 2 * self.len() as u64
+    }
+
+    fn write(&self, cursor: &mut std::io::Cursor<&mut [u8]>) -> Result<(), SerializationError> {
+        write_payload(cursor, self)
+    }
+
+    fn read(serial_type: u64, cursor: &mut Reader<'_>) -> Result<Self, DeserializationError> {
+        match serial_type {
+            NULL => Err(DeserializationError::UnexpectedNull),
+            n if n >= BLOB && n % 2 == 0 => read_payload(cursor, (n - BLOB) / 2),
+            n => Err(DeserializationError::UnexpectedType(n)),
+        }
+    }
+}
+
+/// Les types temporels sont stockés sous forme d'entiers
+macro_rules! temporal_column {
+    ($($name:ident),*) => {
+        $(
+            impl Column for $name {
+                fn serial_type(&self) -> u64 {
+                    self.0.serial_type()
+                }
+
+                fn write(&self, cursor: &mut std::io::Cursor<&mut [u8]>) -> Result<(), SerializationError> {
+                    self.0.write(cursor)
+                }
+
+                fn read(serial_type: u64, cursor: &mut Reader<'_>) -> Result<Self, DeserializationError> {
+                    i64::read(serial_type, cursor).map($name)
+                }
+            }
+        )*
+    };
+}
+
+temporal_column!(Date, Time, Timestamp);
+
+/// Colonne qui accepte NULL
+impl<T: Column> Column for Option<T> {
+    fn serial_type(&self) -> u64 {
+        self.as_ref().map_or(NULL, Column::serial_type)
+    }
+
+    fn write(&self, cursor: &mut std::io::Cursor<&mut [u8]>) -> Result<(), SerializationError> {
+        match self {
+            Some(value) => value.write(cursor),
+            None => Ok(()),
+        }
+    }
+
+    fn read(serial_type: u64, cursor: &mut Reader<'_>) -> Result<Self, DeserializationError> {
+        match serial_type {
+            NULL => Ok(None),
+            _ => T::read(serial_type, cursor).map(Some),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_integer_types() {
+        for (value, serial_type, size) in [
+            (0, ZERO, 0),
+            (1, ONE, 0),
+            (2, 1, 1),
+            (-128, 1, 1),
+            (128, 2, 2),
+            (-32_769, 3, 3),
+            (1 << 30, 4, 4),
+            (1 << 40, 5, 6),
+            (i64::MIN, 6, 8),
+            (i64::MAX, 6, 8),
+        ] {
+            assert_eq!(value.serial_type(), serial_type, "{value}");
+            let mut buf = [0_u8; 16];
+            let mut writer = Cursor::new(&mut buf[..]);
+            value.write(&mut writer).expect("serialization error");
+            assert_eq!(writer.position(), size, "{value}");
+            let mut reader = Reader::new(&buf);
+            assert_eq!(i64::read(serial_type, &mut reader), Ok(value));
+        }
+    }
+
+    #[test]
+    fn test_record() {
+        let mut buf = [0_u8; 64];
+        let mut writer = Cursor::new(&mut buf[..]);
+        write_record(&mut writer, &[&7_i64, &None::<String>, &Some("abc".to_string())])
+            .expect("serialization error");
+        // en-tête de 3 codes puis 1 octet pour l'entier, rien pour NULL, 3 pour le texte
+        assert_eq!(writer.position(), 1 + 3 + 1 + 3);
+        assert_eq!(&buf[..4], &[3, 1, NULL as u8, 19]);
+        let mut reader = Reader::new(&buf);
+        let mut record = RecordReader::new(&mut reader).expect("deserialization error");
+        assert_eq!(record.column::<i64>(&mut reader), Ok(7));
+        assert_eq!(record.column::<Option<String>>(&mut reader), Ok(None));
+        assert_eq!(record.column::<String>(&mut reader), Ok("abc".to_string()));
+        // une colonne absente de l'enregistrement est NULL
+        assert_eq!(record.column::<Option<i64>>(&mut reader), Ok(None));
+        assert_eq!(record.column::<i64>(&mut reader), Err(DeserializationError::UnexpectedNull));
+    }
+
+    #[test]
+    fn test_column_types() {
+        let mut buf = [0_u8; 64];
+        let mut writer = Cursor::new(&mut buf[..]);
+        write_record(
+            &mut writer,
+            &[&1.5_f64, &true, &vec![0xCA_u8, 0xFE], &Date(19_000), &Time(3_600), &Timestamp(-5)],
+        )
+        .expect("serialization error");
+        let mut reader = Reader::new(&buf);
+        let mut record = RecordReader::new(&mut reader).expect("deserialization error");
+        assert_eq!(record.column::<f64>(&mut reader), Ok(1.5));
+        assert_eq!(record.column::<bool>(&mut reader), Ok(true));
+        assert_eq!(record.column::<Vec<u8>>(&mut reader), Ok(vec![0xCA, 0xFE]));
+        assert_eq!(record.column::<Date>(&mut reader), Ok(Date(19_000)));
+        assert_eq!(record.column::<Time>(&mut reader), Ok(Time(3_600)));
+        assert_eq!(record.column::<Timestamp>(&mut reader), Ok(Timestamp(-5)));
+        record.finish(&mut reader).expect("deserialization error");
+    }
+
+    #[test]
+    fn test_skip_columns() {
+        let mut buf = [0_u8; 64];
+        let mut writer = Cursor::new(&mut buf[..]);
+        write_record(&mut writer, &[&1_000_i64, &"abc".to_string()]).expect("serialization error");
+        write_record(&mut writer, &[&2_i64]).expect("serialization error");
+        let mut reader = Reader::new(&buf);
+        // les colonnes non lues sont passées
+        let mut record = RecordReader::new(&mut reader).expect("deserialization error");
+        assert_eq!(record.column::<i64>(&mut reader), Ok(1_000));
+        record.finish(&mut reader).expect("deserialization error");
+        let mut record = RecordReader::new(&mut reader).expect("deserialization error");
+        assert_eq!(record.column::<i64>(&mut reader), Ok(2));
+        // un texte n'est pas lu comme un entier
+        let mut reader = Reader::new(&buf);
+        let mut record = RecordReader::new(&mut reader).expect("deserialization error");
+        record.column::<i64>(&mut reader).expect("deserialization error");
+        assert_eq!(record.column::<i64>(&mut reader), Err(DeserializationError::UnexpectedType(19)));
+    }
+
+    #[test]
+    fn test_derive() {
+        use crate::data::{Deserializable, Serializable};
+
+        #[derive(Debug, PartialEq, Serializable, Deserializable)]
+        struct Document {
+            title: String,
+            size: Option<i64>,
+            content: Vec<u8>,
+        }
+
+        #[derive(Debug, PartialEq, Serializable, Deserializable)]
+        struct Point(f64, f64);
+
+        let document = Document {
+            title: "notes".to_string(),
+            size: None,
+            content: vec![1, 2, 3],
+        };
+        let mut buf = [0_u8; 64];
+        let mut writer = Cursor::new(&mut buf[..]);
+        document.serialize(&mut writer).expect("serialization error");
+        Point(1.5, -2.0).serialize(&mut writer).expect("serialization error");
+        // une colonne par champ, dans l'ordre de déclaration
+        assert_eq!(&buf[..4], &[3, 23, NULL as u8, 18]);
+        let mut reader = Reader::new(&buf);
+        assert_eq!(Document::deserialize(&mut reader), Ok(document));
+        assert_eq!(Point::deserialize(&mut reader), Ok(Point(1.5, -2.0)));
+    }
+}
@@ -0,0 +1,111 @@
+//! Anciens formats d'enregistrement, relus pour migrer les fichiers
+//! écrits par une version précédente.
+
+use crate::data::primitives::{read_payload, read_varint, FixedI64};
+use crate::data::serde::{Deserializable, Reader};
+use crate::data::{Car, User};
+use crate::errors::{BufferError, DeserializationError};
+use std::io::Read;
+
+/// Chaîne au format 1 : sa longueur tient sur un seul octet
+fn string_v1(cursor: &mut Reader<'_>) -> Result<String, DeserializationError> {
+    let mut data = [0_u8; 1];
+    cursor
+        .read_exact(&mut data)
+        .map_err(|e| DeserializationError::Buffer(BufferError::ReadTooMuch(e.to_string())))?;
+    let mut data = vec![0_u8; data[0] as usize];
+    cursor
+        .read_exact(&mut data)
+        .map_err(|e| DeserializationError::Buffer(BufferError::ReadTooMuch(e.to_string())))?;
+    String::from_utf8(data).map_err(DeserializationError::UnableToDeserializeString)
+}
+
+/// Chaîne au format 2 : longueur en varint, toujours suivie des octets dans
+/// la ligne
+fn string_v2(cursor: &mut Reader<'_>) -> Result<String, DeserializationError> {
+    let size = read_varint(cursor)?;
+    let remaining = cursor.len() as u64 - cursor.position().min(cursor.len() as u64);
+    if size > remaining {
+        return Err(DeserializationError::Buffer(BufferError::ReadTooMuch(format!(
+            "expected {size} bytes, {remaining} remaining"
+        ))));
+    }
+    let mut data = vec![0_u8; size as usize];
+    cursor
+        .read_exact(&mut data)
+        .map_err(|e| DeserializationError::Buffer(BufferError::ReadTooMuch(e.to_string())))?;
+    String::from_utf8(data).map_err(DeserializationError::UnableToDeserializeString)
+}
+
+/// Chaîne au format 3 : longueur en varint, octets dans la ligne ou dans
+/// des pages de débordement
+fn string_v3(cursor: &mut Reader<'_>) -> Result<String, DeserializationError> {
+    let size = read_varint(cursor)?;
+    String::from_utf8(read_payload(cursor, size)?).map_err(DeserializationError::UnableToDeserializeString)
+}
+
+/// `User` au format 1
+pub struct UserV1(pub User);
+
+impl Deserializable for UserV1 {
+    fn deserialize(cursor: &mut Reader<'_>) -> Result<Self, DeserializationError> {
+        Ok(UserV1(User::new(
+            FixedI64::deserialize(cursor)?.0,
+            string_v1(cursor)?,
+            string_v1(cursor)?,
+        )))
+    }
+}
+
+/// `Car` au format 1
+pub struct CarV1(pub Car);
+
+impl Deserializable for CarV1 {
+    fn deserialize(cursor: &mut Reader<'_>) -> Result<Self, DeserializationError> {
+        Ok(CarV1(Car::new(string_v1(cursor)?, string_v1(cursor)?)))
+    }
+}
+
+/// `User` au format 2
+pub struct UserV2(pub User);
+
+impl Deserializable for UserV2 {
+    fn deserialize(cursor: &mut Reader<'_>) -> Result<Self, DeserializationError> {
+        Ok(UserV2(User::new(
+            FixedI64::deserialize(cursor)?.0,
+            string_v2(cursor)?,
+            string_v2(cursor)?,
+        )))
+    }
+}
+
+/// `Car` au format 2
+pub struct CarV2(pub Car);
+
+impl Deserializable for CarV2 {
+    fn deserialize(cursor: &mut Reader<'_>) -> Result<Self, DeserializationError> {
+        Ok(CarV2(Car::new(string_v2(cursor)?, string_v2(cursor)?)))
+    }
+}
+
+/// `User` au format 3, champs écrits à la suite sans en-tête
+pub struct UserV3(pub User);
+
+impl Deserializable for UserV3 {
+    fn deserialize(cursor: &mut Reader<'_>) -> Result<Self, DeserializationError> {
+        Ok(UserV3(User::new(
+            FixedI64::deserialize(cursor)?.0,
+            string_v3(cursor)?,
+            string_v3(cursor)?,
+        )))
+    }
+}
+
+/// `Car` au format 3
+pub struct CarV3(pub Car);
+
+impl Deserializable for CarV3 {
+    fn deserialize(cursor: &mut Reader<'_>) -> Result<Self, DeserializationError> {
+        Ok(CarV3(Car::new(string_v3(cursor)?, string_v3(cursor)?)))
+    }
+}
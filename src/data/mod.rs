@@ -3,8 +3,19 @@ mod serde;
 mod primitives;
 mod car;
 mod records;
+mod format;
+mod value;
+mod datetime;
+mod affinity;
+pub mod legacy;
 
 pub use records::{Record, TableName};
 pub use user::User;
 pub use car::Car;
-pub use serde::*;
\ No newline at end of file
+pub use serde::*;
+pub use format::{write_record, Column, RecordReader};
+pub use rusty_sqlite_derive::{Deserializable, Serializable};
+pub use primitives::FixedI64;
+pub use value::Value;
+pub use affinity::Affinity;
+pub use datetime::{Date, Time, Timestamp};
\ No newline at end of file
@@ -1,53 +1,204 @@
 
 use crate::errors::{BufferError, DeserializationError, SerializationError};
+use crate::pager::PAGE_SIZE;
 use std::io::{Read, Write};
-use crate::data::serde::{Deserializable, Serializable};
+use crate::data::serde::{Deserializable, Reader, Serializable};
 
-impl Serializable for String {
-    fn serialize(&self, cursor: &mut std::io::Cursor<&mut [u8]>) -> Result<(), SerializationError> {
-        cursor
-            .write(&[self.len() as u8])
-            .map_err(|e| SerializationError::Buffer(BufferError::BufferFull(e.to_string())))?;
-        // encode la string
-        cursor
-            .write(self.as_bytes())
-            .map_err(|e| SerializationError::Buffer(BufferError::BufferFull(e.to_string())))?;
-        Ok(())
+/// Taille au-delà de laquelle une valeur est placée dans des pages de
+/// débordement plutôt que dans la ligne
+pub const OVERFLOW_THRESHOLD: usize = PAGE_SIZE / 4;
+/// distance jusqu'à la page suivante (0 pour la dernière), octets utilisés
+const OVERFLOW_HEADER_SIZE: usize = 4 + 4;
+
+/// Écrit un entier non signé au format LEB128 : 7 bits par octet, en
+/// commençant par les poids faibles, le bit de poids fort de chaque octet
+/// indiquant qu'un autre octet suit
+pub(crate) fn write_varint(cursor: &mut std::io::Cursor<&mut [u8]>, mut value: u64) -> Result<(), SerializationError> {
+    let mut data = [0_u8; 10];
+    let mut size = 0;
+    loop {
+        data[size] = (value & 0x7F) as u8;
+        value >>= 7;
+        size += 1;
+        if value == 0 {
+            break;
+        }
+        data[size - 1] |= 0x80;
     }
+    cursor
+        .write_all(&data[..size])
+        .map_err(|e| SerializationError::Buffer(BufferError::BufferFull(e.to_string())))
 }
 
-impl Deserializable for String {
-    fn deserialize(cursor: &mut std::io::Cursor<&[u8]>) -> Result<Self, DeserializationError> {
+pub(crate) fn read_varint(cursor: &mut Reader<'_>) -> Result<u64, DeserializationError> {
+    let mut value = 0_u64;
+    // un u64 tient sur 10 octets au plus
+    for shift in (0..64).step_by(7) {
         let mut data = [0_u8; 1];
         cursor
             .read_exact(&mut data)
             .map_err(|e| DeserializationError::Buffer(BufferError::ReadTooMuch(e.to_string())))?;
-        let size = data[0] as usize;
-        let mut data = vec![0_u8; size];
+        value |= ((data[0] & 0x7F) as u64) << shift;
+        if data[0] & 0x80 == 0 {
+            return Ok(value);
+        }
+    }
+    Err(DeserializationError::UnableToDeserializeVarint)
+}
+
+/// Écrit une suite d'octets dont la longueur est connue du lecteur.
+///
+/// Au-delà de `OVERFLOW_THRESHOLD`, les octets sont répartis dans une chaîne
+/// de pages de débordement prises à la fin du buffer, qui est raccourci
+/// d'autant, et la ligne ne garde que la distance jusqu'à la première page.
+/// Les distances étant relatives, le lecteur retrouve les pages quelle que
+/// soit la position du buffer d'écriture dans la table.
+pub(crate) fn write_payload(cursor: &mut std::io::Cursor<&mut [u8]>, data: &[u8]) -> Result<(), SerializationError> {
+    if data.len() <= OVERFLOW_THRESHOLD {
+        return cursor
+            .write_all(data)
+            .map_err(|e| SerializationError::Buffer(BufferError::BufferFull(e.to_string())));
+    }
+    let capacity = PAGE_SIZE - OVERFLOW_HEADER_SIZE;
+    let pages = data.len().div_ceil(capacity);
+    let position = cursor.position() as usize;
+    let len = cursor.get_ref().len();
+    // la ligne doit encore pouvoir recevoir la distance jusqu'aux pages
+    if position + size_of::<u32>() + pages * PAGE_SIZE > len {
+        return Err(SerializationError::Buffer(BufferError::BufferFull(format!(
+            "{pages} overflow pages do not fit in {} bytes",
+            len.saturating_sub(position)
+        ))));
+    }
+    // les pages sont détachées de la fin du buffer d'écriture, qui s'arrête
+    // désormais avant elles
+    let overflow = cursor
+        .get_mut()
+        .split_off_mut(len - pages * PAGE_SIZE..)
+        .expect("overflow pages in buffer");
+    for (index, (page, chunk)) in overflow
+        .chunks_mut(PAGE_SIZE)
+        .zip(data.chunks(capacity))
+        .enumerate()
+    {
+        let next = if index + 1 < pages { PAGE_SIZE as u32 } else { 0 };
+        page[..4].copy_from_slice(&next.to_le_bytes());
+        page[4..8].copy_from_slice(&(chunk.len() as u32).to_le_bytes());
+        page[OVERFLOW_HEADER_SIZE..OVERFLOW_HEADER_SIZE + chunk.len()].copy_from_slice(chunk);
+        // le buffer d'écriture peut contenir des octets d'une autre ligne
+        page[OVERFLOW_HEADER_SIZE + chunk.len()..].fill(0);
+    }
+    let distance = (len - pages * PAGE_SIZE - position) as u32;
+    cursor.write_all(&distance.to_le_bytes()).map_err(|e| {
+        // les pages ne sont pas gardées, elles redeviennent de l'espace libre
+        overflow.fill(0);
+        SerializationError::Buffer(BufferError::BufferFull(e.to_string()))
+    })
+}
+
+/// Place occupée dans la ligne par une suite d'octets de cette taille
+pub(crate) fn payload_size(size: usize) -> usize {
+    if size <= OVERFLOW_THRESHOLD {
+        size
+    } else {
+        size_of::<u32>()
+    }
+}
+
+/// Relit `size` octets écrits par `write_payload`, en suivant si besoin la
+/// chaîne de pages de débordement
+pub(crate) fn read_payload(cursor: &mut Reader<'_>, size: u64) -> Result<Vec<u8>, DeserializationError> {
+    if size <= OVERFLOW_THRESHOLD as u64 {
+        let mut data = vec![0_u8; size as usize];
         cursor
             .read_exact(&mut data)
             .map_err(|e| DeserializationError::Buffer(BufferError::ReadTooMuch(e.to_string())))?;
+        return Ok(data);
+    }
+    let mut distance = [0_u8; 4];
+    let mut page = cursor.position() as usize;
+    cursor
+        .read_exact(&mut distance)
+        .map_err(|e| DeserializationError::Buffer(BufferError::ReadTooMuch(e.to_string())))?;
+    let mut data = Vec::with_capacity((size as usize).min(cursor.len()));
+    let mut distance = u32::from_le_bytes(distance) as usize;
+    while distance != 0 {
+        page += distance;
+        let mut header = [0_u8; OVERFLOW_HEADER_SIZE];
+        if !cursor.read_at(page, &mut header) {
+            return Err(DeserializationError::UnableToReadOverflow);
+        }
+        distance = u32::from_le_bytes(header[..4].try_into().unwrap()) as usize;
+        let used = u32::from_le_bytes(header[4..].try_into().unwrap()) as usize;
+        if used > PAGE_SIZE - OVERFLOW_HEADER_SIZE || data.len() + used > size as usize {
+            return Err(DeserializationError::UnableToReadOverflow);
+        }
+        let start = data.len();
+        data.resize(start + used, 0);
+        if !cursor.read_at(page + OVERFLOW_HEADER_SIZE, &mut data[start..]) {
+            return Err(DeserializationError::UnableToReadOverflow);
+        }
+    }
+    if data.len() != size as usize {
+        return Err(DeserializationError::UnableToReadOverflow);
+    }
+    Ok(data)
+}
 
-        String::from_utf8(data).map_err(DeserializationError::UnableToDeserializeString)
+impl Serializable for String {
+    fn serialize(&self, cursor: &mut std::io::Cursor<&mut [u8]>) -> Result<(), SerializationError> {
+        // la longueur est préfixée en varint, sans limite de taille
+        write_varint(cursor, self.len() as u64)?;
+        write_payload(cursor, self.as_bytes())
+    }
+}
+
+impl Deserializable for String {
+    fn deserialize(cursor: &mut Reader<'_>) -> Result<Self, DeserializationError> {
+        let size = read_varint(cursor)?;
+        String::from_utf8(read_payload(cursor, size)?).map_err(DeserializationError::UnableToDeserializeString)
     }
 }
 
+/// Les entiers isolés sont écrits en varint après un codage zigzag, qui
+/// entrelace positifs et négatifs pour que les petites valeurs des deux
+/// signes tiennent sur peu d'octets : 0, -1, 1, -2... deviennent 0, 1, 2, 3...
+/// Dans une ligne, la taille d'un entier est donnée par son code de type
+/// (voir `format`), qui le réduit de même à 1 octet de -128 à 127.
 impl Serializable for i64 {
+    fn serialize(&self, cursor: &mut std::io::Cursor<&mut [u8]>) -> Result<(), SerializationError> {
+        write_varint(cursor, ((*self << 1) ^ (*self >> 63)) as u64)
+    }
+}
+
+impl Deserializable for i64 {
+    fn deserialize(cursor: &mut Reader<'_>) -> Result<Self, DeserializationError> {
+        let value = read_varint(cursor)?;
+        Ok((value >> 1) as i64 ^ -((value & 1) as i64))
+    }
+}
+
+/// Entier toujours écrit sur 8 octets, pour les structures dont la
+/// disposition ne doit pas dépendre des valeurs, comme la page d'en-tête
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct FixedI64(pub i64);
+
+impl Serializable for FixedI64 {
     fn serialize(&self, cursor: &mut std::io::Cursor<&mut [u8]>) -> Result<(), SerializationError> {
         cursor
-            .write(self.to_le_bytes().as_ref())
+            .write_all(self.0.to_le_bytes().as_ref())
             .map_err(|e| SerializationError::Buffer(BufferError::BufferFull(e.to_string())))?;
         Ok(())
     }
 }
 
-impl Deserializable for i64 {
-    fn deserialize(cursor: &mut std::io::Cursor<&[u8]>) -> Result<Self, DeserializationError> {
+impl Deserializable for FixedI64 {
+    fn deserialize(cursor: &mut Reader<'_>) -> Result<Self, DeserializationError> {
         let mut data = [0_u8; size_of::<i64>()];
         cursor
             .read_exact(&mut data)
             .map_err(|e| DeserializationError::Buffer(BufferError::ReadTooMuch(e.to_string())))?;
-        Ok(i64::from_le_bytes(data))
+        Ok(FixedI64(i64::from_le_bytes(data)))
     }
 }
 
@@ -60,13 +211,40 @@ mod tests {
         let mut buf = [0_u8; 1024];
         let mut writer = Cursor::new(&mut buf[..]);
         42_i64.serialize(&mut writer).expect("serialization error");
-        let mut reader = Cursor::new(&buf[..]);
+        let mut reader = Reader::new(&buf);
         assert_eq!(
             i64::deserialize(&mut reader).expect("deserialization error"),
             42_i64
         );
     }
 
+    #[test]
+    fn test_serialization_compact_i64() {
+        for (value, size) in [
+            (0_i64, 1),
+            (-1, 1),
+            (42, 1),
+            (-64, 1),
+            (64, 2),
+            (1 << 20, 4),
+            (i64::MIN, 10),
+            (i64::MAX, 10),
+        ] {
+            let mut buf = [0_u8; 16];
+            let mut writer = Cursor::new(&mut buf[..]);
+            value.serialize(&mut writer).expect("serialization error");
+            assert_eq!(writer.position(), size, "{value}");
+            let mut reader = Reader::new(&buf);
+            assert_eq!(i64::deserialize(&mut reader), Ok(value));
+        }
+        // la version fixe occupe toujours 8 octets
+        let mut buf = [0_u8; 16];
+        let mut writer = Cursor::new(&mut buf[..]);
+        FixedI64(1).serialize(&mut writer).expect("serialization error");
+        assert_eq!(writer.position(), 8);
+        assert_eq!(FixedI64::deserialize(&mut Reader::new(&buf)), Ok(FixedI64(1)));
+    }
+
     #[test]
     fn test_serialization_string() {
         let mut buf = [0_u8; 1024];
@@ -75,12 +253,91 @@ mod tests {
             .to_string()
             .serialize(&mut writer)
             .expect("serialization error");
-        let mut reader = Cursor::new(&buf[..]);
+        let mut reader = Reader::new(&buf);
         assert_eq!(
             String::deserialize(&mut reader).expect("deserialization error"),
             "toto".to_string()
         );
     }
+    #[test]
+    fn test_varint() {
+        for value in [0, 1, 127, 128, 255, 300, 16_383, 16_384, u32::MAX as u64, u64::MAX] {
+            let mut buf = [0_u8; 16];
+            let mut writer = Cursor::new(&mut buf[..]);
+            write_varint(&mut writer, value).expect("serialization error");
+            let size = writer.position();
+            let mut reader = Reader::new(&buf);
+            assert_eq!(read_varint(&mut reader), Ok(value));
+            assert_eq!(reader.position(), size);
+        }
+        // les petites valeurs tiennent sur un octet
+        let mut buf = [0_u8; 16];
+        let mut writer = Cursor::new(&mut buf[..]);
+        write_varint(&mut writer, 127).expect("serialization error");
+        assert_eq!(writer.position(), 1);
+        write_varint(&mut writer, 128).expect("serialization error");
+        assert_eq!(&buf[..3], &[0x7F, 0x80, 0x01]);
+        // un varint sans fin est refusé
+        let mut reader = Reader::new(&[0xFF_u8; 16]);
+        assert_eq!(
+            read_varint(&mut reader),
+            Err(DeserializationError::UnableToDeserializeVarint)
+        );
+    }
+
+    #[test]
+    fn test_serialization_long_string() {
+        let mut buf = vec![0_u8; 200_000];
+        for size in [255, 256, 70_000] {
+            let data = "é".repeat(size);
+            let mut writer = Cursor::new(&mut buf[..]);
+            data.serialize(&mut writer).expect("serialization error");
+            42_i64.serialize(&mut writer).expect("serialization error");
+            let mut reader = Reader::new(&buf);
+            assert_eq!(String::deserialize(&mut reader), Ok(data));
+            assert_eq!(i64::deserialize(&mut reader), Ok(42));
+        }
+    }
+
+    #[test]
+    fn test_serialization_overflow() {
+        // une chaîne trop grande pour le buffer est refusée, pas tronquée
+        let mut buf = [0_u8; 16];
+        let mut writer = Cursor::new(&mut buf[..]);
+        assert!("x".repeat(32).serialize(&mut writer).is_err());
+        // une longueur qui dépasse les données disponibles aussi
+        let mut reader = Reader::new(&[0xE8_u8, 0x07, b'a']);
+        assert!(matches!(
+            String::deserialize(&mut reader),
+            Err(DeserializationError::Buffer(BufferError::ReadTooMuch(_)))
+        ));
+    }
+
+    #[test]
+    fn test_overflow_pages() {
+        let mut buf = vec![0_u8; 10 * PAGE_SIZE];
+        let data = "x".repeat(2 * PAGE_SIZE);
+        let mut writer = Cursor::new(&mut buf[..]);
+        data.serialize(&mut writer).expect("serialization error");
+        // la ligne ne garde que la longueur et la distance jusqu'aux pages,
+        // prises à la fin du buffer
+        assert_eq!(writer.position(), 2 + 4);
+        assert_eq!(writer.get_ref().len(), 7 * PAGE_SIZE);
+        let mut reader = Reader::new(&buf);
+        assert_eq!(String::deserialize(&mut reader), Ok(data));
+        assert_eq!(reader.position(), 2 + 4);
+        // une chaîne de pages abîmée est détectée
+        buf[9 * PAGE_SIZE + 4] = 0xFF;
+        assert_eq!(
+            String::deserialize(&mut Reader::new(&buf)),
+            Err(DeserializationError::UnableToReadOverflow)
+        );
+        // les pages doivent tenir dans le buffer
+        let mut buf = vec![0_u8; PAGE_SIZE];
+        let mut writer = Cursor::new(&mut buf[..]);
+        assert!("x".repeat(PAGE_SIZE).serialize(&mut writer).is_err());
+    }
+
     #[test]
     fn test_serialization_multiple() {
         let mut buf = [0_u8; 1024];
@@ -90,7 +347,7 @@ mod tests {
             .to_string()
             .serialize(&mut writer)
             .expect("serialization error");
-        let mut reader = Cursor::new(&buf[..]);
+        let mut reader = Reader::new(&buf);
         assert_eq!(
             i64::deserialize(&mut reader).expect("deserialization error"),
             42_i64
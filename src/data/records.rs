@@ -1,5 +1,6 @@
 use crate::data::car::Car;
 use crate::data::user::User;
+use crate::data::value::Value;
 use crate::errors::CommandError;
 use std::str::FromStr;
 
@@ -7,6 +8,34 @@ use std::str::FromStr;
 pub enum TableName {
     User,
     Car,
+    /// table virtuelle, nommée à sa création et gérée par son module
+    Virtual(String),
+}
+
+impl TableName {
+    pub fn as_str(&self) -> &str {
+        match self {
+            TableName::User => "user",
+            TableName::Car => "car",
+            TableName::Virtual(name) => name,
+        }
+    }
+
+    /// Nom et type déclaré de chaque colonne, dans l'ordre de l'insertion.
+    /// Celles d'une table virtuelle sont connues de son module.
+    pub fn columns(&self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            TableName::User => &[("id", "INTEGER"), ("username", "TEXT"), ("email", "TEXT")],
+            TableName::Car => &[("id", "TEXT"), ("brand", "TEXT")],
+            TableName::Virtual(_) => &[],
+        }
+    }
+
+    /// Table désignée par un nom dans une commande : une table virtuelle ou
+    /// une vue si ce n'est pas celui d'une table ordinaire
+    pub fn from_name(name: &str) -> TableName {
+        TableName::from_str(name).unwrap_or_else(|_| TableName::Virtual(name.to_string()))
+    }
 }
 
 impl FromStr for TableName {
@@ -21,8 +50,30 @@ impl FromStr for TableName {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone)]
 pub enum Record {
     User(User),
     Car(Car),
+}
+
+impl Record {
+    /// Table dans laquelle l'enregistrement est stocké
+    pub fn table_name(&self) -> TableName {
+        match self {
+            Record::User(_) => TableName::User,
+            Record::Car(_) => TableName::Car,
+        }
+    }
+
+    /// Valeurs des colonnes, dans l'ordre de `TableName::columns`
+    pub fn values(&self) -> Vec<Value> {
+        match self {
+            Record::User(user) => vec![
+                Value::Integer(user.id),
+                Value::Text(user.username.clone()),
+                Value::Text(user.email.clone()),
+            ],
+            Record::Car(car) => vec![Value::Text(car.id.clone()), Value::Text(car.brand.clone())],
+        }
+    }
 }
\ No newline at end of file
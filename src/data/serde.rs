@@ -1,9 +1,118 @@
 use crate::errors::{DeserializationError, SerializationError};
+use crate::pager::PAGE_SIZE;
+use std::io::Read;
 
 pub trait Serializable {
     fn serialize(&self, cursor: &mut std::io::Cursor<&mut [u8]>) -> Result<(), SerializationError>;
 }
 
 pub trait Deserializable: Sized {
-    fn deserialize(cursor: &mut std::io::Cursor<&[u8]>) -> Result<Self, DeserializationError>;
+    fn deserialize(cursor: &mut Reader<'_>) -> Result<Self, DeserializationError>;
+}
+
+/// Lecteur d'un buffer découpé en morceaux qui ne se suivent pas forcément
+/// en mémoire : une table partage ainsi ses pages avec ses versions figées
+/// sans avoir à les recopier bout à bout.
+pub struct Reader<'a> {
+    /// morceaux du buffer, tous de `chunk_size` octets sauf le dernier
+    chunks: Vec<&'a [u8]>,
+    chunk_size: usize,
+    len: usize,
+    position: u64,
+}
+
+impl<'a> Reader<'a> {
+    /// Lecteur d'un buffer d'un seul tenant
+    pub fn new(data: &'a [u8]) -> Self {
+        Self {
+            chunks: vec![data],
+            chunk_size: data.len().max(1),
+            len: data.len(),
+            position: 0,
+        }
+    }
+
+    /// Lecteur de pages de `PAGE_SIZE` octets mises bout à bout
+    pub fn from_pages(pages: impl IntoIterator<Item = &'a [u8]>) -> Self {
+        let chunks = pages.into_iter().collect::<Vec<_>>();
+        Self {
+            len: chunks.iter().map(|chunk| chunk.len()).sum(),
+            chunks,
+            chunk_size: PAGE_SIZE,
+            position: 0,
+        }
+    }
+
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    pub fn set_position(&mut self, position: u64) {
+        self.position = position;
+    }
+
+    /// Taille totale du buffer
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Copie les octets qui commencent à `position` sans déplacer le
+    /// lecteur, faux si le buffer s'arrête avant
+    pub fn read_at(&self, mut position: usize, data: &mut [u8]) -> bool {
+        if position.checked_add(data.len()).is_none_or(|end| end > self.len) {
+            return false;
+        }
+        let mut copied = 0;
+        while copied < data.len() {
+            let chunk = &self.chunks[position / self.chunk_size][position % self.chunk_size..];
+            let size = chunk.len().min(data.len() - copied);
+            data[copied..copied + size].copy_from_slice(&chunk[..size]);
+            copied += size;
+            position += size;
+        }
+        true
+    }
+}
+
+impl Read for Reader<'_> {
+    fn read(&mut self, data: &mut [u8]) -> std::io::Result<usize> {
+        if self.position >= self.len as u64 {
+            return Ok(0);
+        }
+        let position = self.position as usize;
+        let chunk = &self.chunks[position / self.chunk_size][position % self.chunk_size..];
+        let size = chunk.len().min(data.len());
+        data[..size].copy_from_slice(&chunk[..size]);
+        self.position += size as u64;
+        Ok(size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reader_pages() {
+        let first = [1_u8; PAGE_SIZE];
+        let second = [2_u8; PAGE_SIZE];
+        let mut reader = Reader::from_pages([&first[..], &second[..]]);
+        assert_eq!(reader.len(), 2 * PAGE_SIZE);
+        // une lecture à cheval sur deux pages les recolle
+        reader.set_position(PAGE_SIZE as u64 - 2);
+        let mut data = [0_u8; 4];
+        reader.read_exact(&mut data).expect("read failed");
+        assert_eq!(data, [1, 1, 2, 2]);
+        assert_eq!(reader.position(), PAGE_SIZE as u64 + 2);
+        let mut data = [0_u8; 3];
+        assert!(reader.read_at(PAGE_SIZE - 1, &mut data));
+        assert_eq!(data, [1, 2, 2]);
+        assert!(!reader.read_at(2 * PAGE_SIZE - 2, &mut data));
+        reader.set_position(2 * PAGE_SIZE as u64);
+        assert!(reader.read_exact(&mut data).is_err());
+    }
 }
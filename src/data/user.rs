@@ -1,7 +1,6 @@
-use crate::data::serde::{Deserializable, Serializable};
-use crate::errors::{DeserializationError, SerializationError};
+use crate::data::{Deserializable, Serializable};
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Serializable, Deserializable)]
 pub struct User {
     pub(crate) id: i64,
     pub(crate) username: String,
@@ -18,30 +17,10 @@ impl User {
     }
 }
 
-impl Serializable for User {
-    fn serialize(&self, cursor: &mut std::io::Cursor<&mut [u8]>) -> Result<(), SerializationError> {
-        self.id.serialize(cursor)?;
-        self.username.serialize(cursor)?;
-        self.email.serialize(cursor)?;
-
-        Ok(())
-    }
-}
-
-impl Deserializable for User {
-    fn deserialize(cursor: &mut std::io::Cursor<&[u8]>) -> Result<Self, DeserializationError> {
-        // recreate User
-        Ok(User {
-            id: i64::deserialize(cursor)?,
-            username: String::deserialize(cursor)?,
-            email: String::deserialize(cursor)?,
-        })
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::data::Reader;
     use std::io::Cursor;
 
     #[test]
@@ -55,7 +34,7 @@ mod tests {
         let mut writer = Cursor::new(&mut buffer[..]);
         user.serialize(&mut writer)
             .expect("Unable to serialize user");
-        let mut reader = Cursor::new(&buffer[..]);
+        let mut reader = Reader::new(&buffer);
         let result = User::deserialize(&mut reader).expect("Unable to deserialize user");
         assert_eq!(user, result);
     }
@@ -79,7 +58,7 @@ mod tests {
         devil
             .serialize(&mut writer)
             .expect("Unable to serialize user");
-        let mut reader = Cursor::new(&buffer[..]);
+        let mut reader = Reader::new(&buffer);
         let result = User::deserialize(&mut reader).expect("Unable to deserialize user");
         assert_eq!(user, result);
         let result = User::deserialize(&mut reader).expect("Unable to deserialize user");
@@ -97,7 +76,7 @@ mod tests {
                 .expect("Unable to serialize user");
         }
         // scan
-        let mut reader = Cursor::new(&buffer[..]);
+        let mut reader = Reader::new(&buffer);
         for i in 0..50 {
             let user = User::new(i, format!("test_{i}"), format!("email_{i}@example.com"));
             let result = User::deserialize(&mut reader).expect("Unable to deserialize user");
@@ -125,7 +104,7 @@ mod tests {
         }
         // scan des User
         // on créé un reader unique pour le scan
-        let mut reader = Cursor::new(&buffer[..]);
+        let mut reader = Reader::new(&buffer);
         for i in 0..nb_inserts {
             let user = User::new(i, format!("test_{i}"), format!("email_{i}@example.com"));
             let result = User::deserialize(&mut reader).expect("Unable to deserialize user");
@@ -0,0 +1,263 @@
+use crate::data::datetime::{Date, Time, Timestamp};
+use crate::data::Affinity;
+use crate::data::format::Column;
+use crate::data::serde::Reader;
+use crate::errors::{DeserializationError, SerializationError};
+use std::cmp::Ordering;
+use std::fmt::{Display, Formatter};
+
+/// Valeur d'une colonne ou d'une expression, quel que soit son type
+#[derive(Debug, PartialEq, Clone)]
+pub enum Value {
+    Null,
+    Integer(i64),
+    Real(f64),
+    Boolean(bool),
+    Text(String),
+    Blob(Vec<u8>),
+    Date(Date),
+    Time(Time),
+    Timestamp(Timestamp),
+}
+
+/// Classes de valeurs dans l'ordre de tri : NULL, nombres, textes puis blobs
+#[derive(PartialEq, Eq, PartialOrd, Ord)]
+enum Class {
+    Null,
+    Numeric,
+    Text,
+    Blob,
+}
+
+impl Value {
+    fn class(&self) -> Class {
+        match self {
+            Value::Null => Class::Null,
+            Value::Integer(_)
+            | Value::Real(_)
+            | Value::Boolean(_)
+            | Value::Date(_)
+            | Value::Time(_)
+            | Value::Timestamp(_) => Class::Numeric,
+            Value::Text(_) => Class::Text,
+            Value::Blob(_) => Class::Blob,
+        }
+    }
+
+    /// Valeur numérique d'un nombre, d'un booléen ou d'un type temporel
+    fn as_number(&self) -> Option<Number> {
+        match self {
+            Value::Integer(value) => Some(Number::Integer(*value)),
+            Value::Real(value) => Some(Number::Real(*value)),
+            Value::Boolean(value) => Some(Number::Integer(*value as i64)),
+            Value::Date(Date(value)) | Value::Time(Time(value)) | Value::Timestamp(Timestamp(value)) => {
+                Some(Number::Integer(*value))
+            }
+            _ => None,
+        }
+    }
+
+    fn is_temporal(&self) -> bool {
+        matches!(self, Value::Date(_) | Value::Time(_) | Value::Timestamp(_))
+    }
+
+    /// Compare deux valeurs comme SQLite : la comparaison avec NULL est
+    /// inconnue, les nombres se comparent entre eux quel que soit leur type,
+    /// puis viennent les textes et enfin les blobs. Une date ou une heure
+    /// comparée à un texte l'est via son écriture ISO.
+    pub fn compare(&self, other: &Value) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Null, _) | (_, Value::Null) => None,
+            (Value::Text(text), temporal) if temporal.is_temporal() => {
+                Some(text.as_str().cmp(temporal.to_string().as_str()))
+            }
+            (temporal, Value::Text(text)) if temporal.is_temporal() => {
+                Some(temporal.to_string().as_str().cmp(text))
+            }
+            (Value::Text(left), Value::Text(right)) => Some(left.cmp(right)),
+            (Value::Blob(left), Value::Blob(right)) => Some(left.cmp(right)),
+            _ => match (self.as_number(), other.as_number()) {
+                (Some(left), Some(right)) => left.compare(right),
+                _ => Some(self.class().cmp(&other.class())),
+            },
+        }
+    }
+
+    /// Valeur d'une condition : NULL est faux, le reste est vrai si sa
+    /// valeur numérique n'est pas nulle
+    pub fn is_true(&self) -> bool {
+        match self.clone().cast(Affinity::Numeric).as_number() {
+            Some(Number::Integer(value)) => value != 0,
+            Some(Number::Real(value)) => value != 0.0,
+            None => false,
+        }
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Number {
+    Integer(i64),
+    Real(f64),
+}
+
+impl Number {
+    /// Comparaison exacte : un entier n'est pas arrondi en flottant, et un
+    /// flottant NaN n'est comparable à rien
+    fn compare(self, other: Number) -> Option<Ordering> {
+        match (self, other) {
+            (Number::Integer(left), Number::Integer(right)) => Some(left.cmp(&right)),
+            (Number::Integer(left), Number::Real(right)) => compare_integer_real(left, right),
+            (Number::Real(left), Number::Integer(right)) => compare_integer_real(right, left).map(Ordering::reverse),
+            (Number::Real(left), Number::Real(right)) => left.partial_cmp(&right),
+        }
+    }
+}
+
+/// Compare un entier à un flottant sans perdre de précision au-delà de 2^53
+fn compare_integer_real(integer: i64, real: f64) -> Option<Ordering> {
+    // 2^63, premier flottant hors des entiers
+    const LIMIT: f64 = 9_223_372_036_854_775_808.0;
+    if real.is_nan() {
+        return None;
+    }
+    if real >= LIMIT {
+        return Some(Ordering::Less);
+    }
+    if real < -LIMIT {
+        return Some(Ordering::Greater);
+    }
+    // la partie entière du flottant tient alors exactement dans un entier,
+    // sa partie décimale départage les égalités
+    let truncated = real.trunc();
+    match integer.cmp(&(truncated as i64)) {
+        Ordering::Equal => 0.0.partial_cmp(&(real - truncated)),
+        ordering => Some(ordering),
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Null => write!(f, "NULL"),
+            Value::Integer(value) => write!(f, "{value}"),
+            // un flottant garde toujours sa partie décimale
+            Value::Real(value) => write!(f, "{value:?}"),
+            Value::Boolean(value) => write!(f, "{value}"),
+            Value::Text(value) => write!(f, "{value}"),
+            Value::Blob(value) => {
+                write!(f, "X'")?;
+                value.iter().try_for_each(|byte| write!(f, "{byte:02X}"))?;
+                write!(f, "'")
+            }
+            Value::Date(value) => write!(f, "{value}"),
+            Value::Time(value) => write!(f, "{value}"),
+            Value::Timestamp(value) => write!(f, "{value}"),
+        }
+    }
+}
+
+/// Sur disque, une valeur ne garde que sa classe de stockage : les booléens
+/// et les types temporels sont relus comme des entiers
+impl Column for Value {
+    fn serial_type(&self) -> u64 {
+        match self {
+            Value::Null => None::<i64>.serial_type(),
+            Value::Integer(value) => value.serial_type(),
+            Value::Real(value) => value.serial_type(),
+            Value::Boolean(value) => value.serial_type(),
+            Value::Text(value) => value.serial_type(),
+            Value::Blob(value) => value.serial_type(),
+            Value::Date(value) => value.serial_type(),
+            Value::Time(value) => value.serial_type(),
+            Value::Timestamp(value) => value.serial_type(),
+        }
+    }
+
+    fn write(&self, cursor: &mut std::io::Cursor<&mut [u8]>) -> Result<(), SerializationError> {
+        match self {
+            Value::Null => Ok(()),
+            Value::Integer(value) => value.write(cursor),
+            Value::Real(value) => value.write(cursor),
+            Value::Boolean(value) => value.write(cursor),
+            Value::Text(value) => value.write(cursor),
+            Value::Blob(value) => value.write(cursor),
+            Value::Date(value) => value.write(cursor),
+            Value::Time(value) => value.write(cursor),
+            Value::Timestamp(value) => value.write(cursor),
+        }
+    }
+
+    fn read(serial_type: u64, cursor: &mut Reader<'_>) -> Result<Self, DeserializationError> {
+        match serial_type {
+            0 => Ok(Value::Null),
+            7 => f64::read(serial_type, cursor).map(Value::Real),
+            1..=9 => i64::read(serial_type, cursor).map(Value::Integer),
+            n if n >= 12 && n % 2 == 0 => Vec::read(serial_type, cursor).map(Value::Blob),
+            n if n >= 13 => String::read(serial_type, cursor).map(Value::Text),
+            n => Err(DeserializationError::UnexpectedType(n)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::format::{write_record, RecordReader};
+    use std::io::Cursor;
+
+    #[test]
+    fn test_compare() {
+        let less = Some(Ordering::Less);
+        assert_eq!(Value::Integer(1).compare(&Value::Real(1.5)), less);
+        assert_eq!(Value::Real(2.0).compare(&Value::Integer(2)), Some(Ordering::Equal));
+        assert_eq!(Value::Boolean(true).compare(&Value::Integer(1)), Some(Ordering::Equal));
+        assert_eq!(Value::Integer(1_000).compare(&Value::Text("1".to_string())), less);
+        assert_eq!(Value::Text("b".to_string()).compare(&Value::Blob(vec![0])), less);
+        assert_eq!(Value::Null.compare(&Value::Null), None);
+        // comparaison IEEE entre flottants
+        assert_eq!(Value::Real(0.0).compare(&Value::Real(-0.0)), Some(Ordering::Equal));
+        assert_eq!(Value::Real(f64::NAN).compare(&Value::Real(f64::NAN)), None);
+        // sans arrondir les grands entiers
+        let large = Value::Integer((1 << 53) + 1);
+        assert_eq!(large.compare(&Value::Real((1_i64 << 53) as f64)), Some(Ordering::Greater));
+        assert_eq!(Value::Real((1_i64 << 53) as f64).compare(&large), less);
+        assert_eq!(Value::Integer(i64::MAX).compare(&Value::Real(i64::MAX as f64)), less);
+        assert_eq!(Value::Integer(i64::MIN).compare(&Value::Real(i64::MIN as f64)), Some(Ordering::Equal));
+        assert_eq!(Value::Integer(-2).compare(&Value::Real(-1.5)), less);
+        assert_eq!(Value::Integer(-1).compare(&Value::Real(-1.5)), Some(Ordering::Greater));
+        assert_eq!(Value::Integer(1).compare(&Value::Null), None);
+        // les dates se comparent entre elles et à leur écriture ISO
+        assert_eq!(Value::Date(Date(1)).compare(&Value::Date(Date(2))), less);
+        assert_eq!(
+            Value::Date(Date(0)).compare(&Value::Text("1970-01-01".to_string())),
+            Some(Ordering::Equal)
+        );
+    }
+
+    #[test]
+    fn test_column() {
+        let values = [
+            Value::Null,
+            Value::Integer(-300),
+            Value::Real(0.25),
+            Value::Text("abc".to_string()),
+            Value::Blob(vec![1, 2, 3]),
+        ];
+        let mut buf = [0_u8; 64];
+        let mut writer = Cursor::new(&mut buf[..]);
+        let columns = values.iter().map(|value| value as &dyn Column).collect::<Vec<_>>();
+        write_record(&mut writer, &columns).expect("serialization error");
+        let mut reader = Reader::new(&buf);
+        let mut record = RecordReader::new(&mut reader).expect("deserialization error");
+        for value in values {
+            assert_eq!(record.column::<Value>(&mut reader), Ok(value));
+        }
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(Value::Real(2.0).to_string(), "2.0");
+        assert_eq!(Value::Blob(vec![0x0A, 0xFF]).to_string(), "X'0AFF'");
+        assert_eq!(Value::Timestamp(Timestamp(0)).to_string(), "1970-01-01 00:00:00");
+    }
+}
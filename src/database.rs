@@ -1,36 +1,462 @@
-use crate::data::{Car, Record, TableName, User};
-use crate::errors::{CreationError, InsertionError, SelectError};
-use crate::table::Table;
-use std::collections::HashMap;
+use crate::data::legacy::{CarV1, CarV2, CarV3, UserV1, UserV2, UserV3};
+use crate::data::{Car, Deserializable, FixedI64, Reader, Record, Serializable, TableName, User};
+use crate::mvcc::ReadView;
+use crate::fts::Fts;
+use crate::functions::Functions;
+use crate::vtab::VirtualTables;
+use crate::errors::{
+    CreationError, InsertionError, SelectError, SerializationError, StorageError,
+    TransactionError,
+};
+use crate::data::Value;
+use crate::pager::{JournalMode, Pager, PAGE_SIZE};
+use crate::table::{Cursor, Table, TableState, TABLE_PAGES};
+use crate::transaction::{Snapshot, Transaction};
+use crate::vfs::{OsVfs, Vfs};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
 
-const DATABASE_SIZE : usize = 1024*1024;
+/// Signature écrite en tête du fichier de base de données
+const MAGIC: &str = "rusty-sqlite";
+/// Version du format des enregistrements :
+/// 1. longueur des chaînes sur un octet
+/// 2. longueur des chaînes en varint
+/// 3. grandes valeurs dans des pages de débordement
+/// 4. en-tête des types de colonne dans chaque enregistrement
+/// 5. tables fts rangées en listes par terme
+const FORMAT_VERSION: i64 = 5;
 
 pub struct Database {
     tables: HashMap<TableName, Table>,
+    /// transaction en cours, en dehors d'une transaction
+    /// chaque commande est validée immédiatement
+    transaction: Option<Transaction>,
+    /// fichier de stockage, absent pour une base en mémoire
+    pager: Option<Pager>,
+    /// fonctions appelables depuis les expressions SQL
+    functions: Functions,
+    /// définition des tables virtuelles, dont les lignes sont rangées dans
+    /// `tables` : seules comptent celles qui y ont encore leur table
+    fts_tables: HashMap<String, Fts>,
+    /// tables virtuelles enregistrées par l'application
+    virtual_tables: VirtualTables,
+    /// select de chaque vue, par nom
+    views: BTreeMap<String, String>,
 }
+
+impl Default for Database {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Database {
     pub fn new() -> Self {
         Self {
             tables: Default::default(),
+            transaction: None,
+            pager: None,
+            functions: Functions::new(),
+            fts_tables: HashMap::new(),
+            virtual_tables: VirtualTables::new(),
+            views: BTreeMap::new(),
         }
     }
 
+    /// Ouvre une base de données stockée dans un fichier, en la créant si besoin
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, StorageError> {
+        Self::open_with(Arc::new(OsVfs), path)
+    }
+
+    pub(crate) fn open_with(vfs: Arc<dyn Vfs>, path: impl AsRef<Path>) -> Result<Self, StorageError> {
+        let mut pager = Pager::open(vfs, path)?;
+        let mut database = Self::new();
+        let mut mode = JournalMode::Delete;
+        let mut version = FORMAT_VERSION;
+        if pager.page_count()? > 0 {
+            (database.tables, database.fts_tables, database.views, mode, version) = read_header(&mut pager)?;
+        }
+        pager.set_journal_mode(mode)?;
+        database.pager = Some(pager);
+        if version < FORMAT_VERSION {
+            database.migrate(version)?;
+        }
+        Ok(database)
+    }
+
+    /// Réécrit au format courant des tables écrites dans un format précédent
+    fn migrate(&mut self, version: i64) -> Result<(), StorageError> {
+        fn read<D: Deserializable>(
+            table: &Table,
+            record: fn(D) -> Record,
+        ) -> Result<Vec<Record>, SelectError> {
+            table.cursor::<D>().map(|row| row.map(record)).collect()
+        }
+        for (name, table) in self.tables.iter_mut() {
+            // les tables virtuelles sont apparues au format 4, le format 5
+            // range une liste par terme au lieu d'un index par document
+            if let TableName::Virtual(name) = name {
+                let fts = self
+                    .fts_tables
+                    .get_mut(name)
+                    .ok_or_else(|| StorageError::Corrupted(format!("unknown table {name}")))?;
+                *table = fts.migrate(table)?;
+                continue;
+            }
+            let records = match (version, name) {
+                // le format 4 ne change que les tables virtuelles
+                (4, _) => continue,
+                (1, TableName::User) => read(table, |UserV1(user)| Record::User(user)),
+                (1, TableName::Car) => read(table, |CarV1(car)| Record::Car(car)),
+                (2, TableName::User) => read(table, |UserV2(user)| Record::User(user)),
+                (2, TableName::Car) => read(table, |CarV2(car)| Record::Car(car)),
+                (_, TableName::User) => read(table, |UserV3(user)| Record::User(user)),
+                (_, TableName::Car) => read(table, |CarV3(car)| Record::Car(car)),
+                (_, TableName::Virtual(_)) => continue,
+            }
+            .map_err(|err| StorageError::Corrupted(err.to_string()))?;
+            let mut migrated = Table::new(table.root_page());
+            migrated.set_strict(table.is_strict());
+            for record in records {
+                match record {
+                    Record::User(user) => migrated.insert(user),
+                    Record::Car(car) => migrated.insert(car),
+                }
+                .map_err(|err| StorageError::Corrupted(err.to_string()))?;
+            }
+            *table = migrated;
+        }
+        self.flush()
+    }
+
+    /// Mode de journalisation, absent pour une base en mémoire
+    pub fn journal_mode(&self) -> Option<JournalMode> {
+        self.pager.as_ref().map(Pager::journal_mode)
+    }
+
+    /// Change le mode de journalisation, le choix est conservé dans le fichier
+    pub fn set_journal_mode(&mut self, mode: JournalMode) -> Result<(), StorageError> {
+        if self.in_transaction() {
+            return Err(StorageError::InTransaction);
+        }
+        let Some(pager) = self.pager.as_mut() else {
+            return Ok(());
+        };
+        let previous = pager.journal_mode();
+        pager.set_journal_mode(mode)?;
+        if let Err(err) = self.flush() {
+            let _ = self.pager.as_mut().map(|pager| pager.set_journal_mode(previous));
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    /// Recopie le journal WAL dans le fichier principal
+    pub fn checkpoint(&mut self) -> Result<(), StorageError> {
+        match self.pager.as_mut() {
+            Some(pager) => pager.checkpoint(),
+            None => Ok(()),
+        }
+    }
+
+    /// Enregistre sur disque les pages modifiées
+    fn flush(&mut self) -> Result<(), StorageError> {
+        let Some(pager) = self.pager.as_mut() else {
+            return Ok(());
+        };
+        let header = write_header(&self.tables, &self.fts_tables, &self.views, pager.journal_mode())
+            .map_err(StorageError::Serialization)?;
+        let mut pages = vec![(0, &header[..])];
+        for table in self.tables.values() {
+            pages.extend(table.dirty_pages());
+        }
+        pager.commit(&pages)?;
+        self.tables.values_mut().for_each(Table::mark_synced);
+        Ok(())
+    }
+
+    /// Exécute une commande hors transaction comme une transaction implicite :
+    /// elle est enregistrée dès qu'elle se termine, ou annulée si l'écriture échoue
+    fn autocommit<T, E>(
+        &mut self,
+        command: impl FnOnce(&mut Self) -> Result<T, E>,
+        storage_error: fn(StorageError) -> E,
+    ) -> Result<T, E> {
+        if self.in_transaction() {
+            return command(self);
+        }
+        let snapshot = Snapshot::take(&self.tables, &self.views);
+        let result = command(self)?;
+        if let Err(err) = self.flush() {
+            snapshot.restore(&mut self.tables, &mut self.views);
+            return Err(storage_error(err));
+        }
+        Ok(result)
+    }
+
+    pub fn in_transaction(&self) -> bool {
+        self.transaction.is_some()
+    }
+
+    /// Démarre une transaction, les modifications suivantes ne seront
+    /// définitives qu'au `commit`
+    pub fn begin(&mut self) -> Result<(), TransactionError> {
+        if self.in_transaction() {
+            return Err(TransactionError::AlreadyStarted);
+        }
+        self.transaction = Some(Transaction::new(Snapshot::take(&self.tables, &self.views), false));
+        Ok(())
+    }
+
+    pub fn commit(&mut self) -> Result<(), TransactionError> {
+        let transaction = self
+            .transaction
+            .take()
+            .ok_or(TransactionError::NoActiveTransaction)?;
+        if let Err(err) = self.flush() {
+            transaction.snapshot.restore(&mut self.tables, &mut self.views);
+            return Err(TransactionError::Storage(err));
+        }
+        Ok(())
+    }
+
+    /// Annule toutes les modifications faites depuis le `begin`
+    pub fn rollback(&mut self) -> Result<(), TransactionError> {
+        let transaction = self
+            .transaction
+            .take()
+            .ok_or(TransactionError::NoActiveTransaction)?;
+        transaction.snapshot.restore(&mut self.tables, &mut self.views);
+        Ok(())
+    }
+
+    /// Pose un point de sauvegarde, en démarrant une transaction si besoin
+    pub fn savepoint(&mut self, name: String) -> Result<(), TransactionError> {
+        let snapshot = Snapshot::take(&self.tables, &self.views);
+        self.transaction
+            .get_or_insert_with(|| Transaction::new(snapshot.clone(), true))
+            .savepoint(name, snapshot);
+        Ok(())
+    }
+
+    /// Oublie un point de sauvegarde en conservant les modifications faites
+    /// depuis, la transaction est validée si elle avait été démarrée par lui
+    pub fn release(&mut self, name: &str) -> Result<(), TransactionError> {
+        let transaction = self
+            .transaction
+            .as_mut()
+            .ok_or_else(|| TransactionError::NoSuchSavepoint(name.to_string()))?;
+        if transaction.release(name)? {
+            self.commit()?;
+        }
+        Ok(())
+    }
+
+    /// Annule les modifications faites depuis le point de sauvegarde,
+    /// la transaction continue
+    pub fn rollback_to(&mut self, name: &str) -> Result<(), TransactionError> {
+        let transaction = self
+            .transaction
+            .as_mut()
+            .ok_or_else(|| TransactionError::NoSuchSavepoint(name.to_string()))?;
+        transaction.rollback_to(name)?.restore(&mut self.tables, &mut self.views);
+        Ok(())
+    }
+
 
     pub fn create_table(&mut self, table_name: TableName) -> Result<(), CreationError> {
+        self.autocommit(|database| database.create(table_name, false), CreationError::Storage)
+    }
+
+    /// Crée une table qui refuse les valeurs qu'il faudrait convertir pour
+    /// les ranger dans leur colonne
+    pub fn create_strict_table(&mut self, table_name: TableName) -> Result<(), CreationError> {
+        self.autocommit(|database| database.create(table_name, true), CreationError::Storage)
+    }
+
+    fn create(&mut self, table_name: TableName, strict: bool) -> Result<(), CreationError> {
         if self.tables.contains_key(&table_name) {
             return Err(CreationError::TableAlreadyExist(table_name))
         }
-        self.tables.insert(table_name, Table::new());
+        // la page 0 contient l'en-tête, puis chaque table a sa plage de pages
+        let root_page = 1 + self.tables.len() as u32 * TABLE_PAGES;
+        let mut table = Table::new(root_page);
+        table.set_strict(strict);
+        self.tables.insert(table_name, table);
         Ok(())
     }
 
+    /// Crée une table virtuelle gérée par le module `fts`, seul connu pour
+    /// l'instant, à qui sont passés les arguments de `USING fts(...)`
+    pub fn create_virtual_table(&mut self, name: &str, module: &str, arguments: &[String]) -> Result<(), CreationError> {
+        if let Ok(table_name) = TableName::from_str(name) {
+            return Err(CreationError::TableAlreadyExist(table_name));
+        }
+        let name = normalize(name);
+        if self.virtual_tables.get(&name).is_some() {
+            return Err(CreationError::TableAlreadyExist(TableName::Virtual(name)));
+        }
+        if self.views.contains_key(&name) {
+            return Err(CreationError::ViewAlreadyExist(name));
+        }
+        if !module.eq_ignore_ascii_case("fts") {
+            return Err(CreationError::UnknownModule(module.to_string()));
+        }
+        let fts = Fts::new(arguments)?;
+        self.autocommit(
+            |database| {
+                let table_name = TableName::Virtual(name.clone());
+                database.create(table_name.clone(), false)?;
+                database.fts_tables.insert(name.clone(), fts);
+                if !database.catalog_fits() {
+                    database.tables.remove(&table_name);
+                    database.fts_tables.remove(&name);
+                    return Err(CreationError::CatalogFull);
+                }
+                Ok(())
+            },
+            CreationError::Storage,
+        )
+    }
+
+    /// Définition et lignes d'une table virtuelle
+    pub(crate) fn fts_table(&self, name: &str) -> Option<(&Fts, &Table)> {
+        let name = normalize(name);
+        let table = self.tables.get(&TableName::Virtual(name.clone()))?;
+        Some((self.fts_tables.get(&name)?, table))
+    }
+
+    /// Enregistre une vue, `select` étant la requête qui la définit, déjà
+    /// vérifiée par l'appelant
+    pub fn create_view(&mut self, name: &str, select: &str) -> Result<(), CreationError> {
+        let name = normalize(name);
+        let table_name = TableName::from_name(&name);
+        if self.tables.contains_key(&table_name) || self.virtual_tables.get(&name).is_some() {
+            return Err(CreationError::TableAlreadyExist(table_name));
+        }
+        if self.views.contains_key(&name) {
+            return Err(CreationError::ViewAlreadyExist(name));
+        }
+        self.autocommit(
+            |database| {
+                database.views.insert(name.clone(), select.to_string());
+                if !database.catalog_fits() {
+                    database.views.remove(&name);
+                    return Err(CreationError::CatalogFull);
+                }
+                Ok(())
+            },
+            CreationError::Storage,
+        )
+    }
+
+    /// La page d'en-tête peut encore décrire toutes les tables et les vues
+    fn catalog_fits(&self) -> bool {
+        let mode = self.journal_mode().unwrap_or(JournalMode::Delete);
+        write_header(&self.tables, &self.fts_tables, &self.views, mode).is_ok()
+    }
+
+    pub fn drop_view(&mut self, name: &str) -> Result<(), CreationError> {
+        let name = normalize(name);
+        if !self.views.contains_key(&name) {
+            return Err(CreationError::UnknownView(name));
+        }
+        self.autocommit(
+            |database| {
+                database.views.remove(&name);
+                Ok(())
+            },
+            CreationError::Storage,
+        )
+    }
+
+    /// Requête qui définit la vue
+    pub fn view(&self, name: &str) -> Option<&str> {
+        self.views.get(&normalize(name)).map(String::as_str)
+    }
+
+    /// Commandes qui recréeraient les tables puis les vues, par nom
+    pub fn schema(&self) -> Vec<String> {
+        let mut tables = self
+            .tables
+            .iter()
+            .map(|(name, table)| {
+                let command = match name {
+                    TableName::Virtual(name) => {
+                        let module = self.fts_tables.get(name).map(Fts::schema).unwrap_or_default();
+                        format!("create virtual table {name} using {module}")
+                    }
+                    name if table.is_strict() => format!("create {} strict", name.as_str()),
+                    name => format!("create {}", name.as_str()),
+                };
+                (name.as_str(), command)
+            })
+            .collect::<Vec<_>>();
+        tables.sort();
+        let views = self.views.iter().map(|(name, select)| format!("create view {name} as {select}"));
+        tables.into_iter().map(|(_, command)| command).chain(views).collect()
+    }
+
+    /// Ajoute une ligne à une table virtuelle, une valeur par colonne
+    pub fn insert_virtual(&mut self, name: &str, values: &[Value]) -> Result<(), InsertionError> {
+        self.autocommit(
+            |database| {
+                let name = normalize(name);
+                let table_name = TableName::Virtual(name.clone());
+                let (Some(fts), Some(table)) = (database.fts_tables.get_mut(&name), database.tables.get_mut(&table_name)) else {
+                    return Err(InsertionError::TableNotExist(table_name));
+                };
+                fts.insert(table, values)
+            },
+            InsertionError::Storage,
+        )
+    }
+
+    /// La table existe et a été créée STRICT
+    pub fn is_strict(&self, table_name: &TableName) -> bool {
+        self.tables.get(table_name).is_some_and(Table::is_strict)
+    }
+
+    pub fn functions(&self) -> &Functions {
+        &self.functions
+    }
+
+    /// Pour enregistrer les fonctions de l'application
+    pub fn functions_mut(&mut self) -> &mut Functions {
+        &mut self.functions
+    }
+
+    pub fn virtual_tables(&self) -> &VirtualTables {
+        &self.virtual_tables
+    }
+
+    /// Pour enregistrer les tables virtuelles de l'application
+    pub fn virtual_tables_mut(&mut self) -> &mut VirtualTables {
+        &mut self.virtual_tables
+    }
+
+    /// Ajoute un enregistrement, dont les champs ont déjà le type de leur
+    /// colonne
     pub fn insert(&mut self, data: Record) -> Result<(), InsertionError> {
-        let table_key = match data {
-            Record::User(_) => TableName::User,
-            Record::Car(_) => TableName::Car,
-        };
+        self.insert_converted(data, false)
+    }
+
+    /// Ajoute un enregistrement construit à partir de valeurs SQL,
+    /// `converted` indiquant qu'une valeur a dû changer de type : une table
+    /// STRICT le refuse alors
+    pub fn insert_converted(&mut self, data: Record, converted: bool) -> Result<(), InsertionError> {
+        self.autocommit(|database| database.insert_record(data, converted), InsertionError::Storage)
+    }
+
+    fn insert_record(&mut self, data: Record, converted: bool) -> Result<(), InsertionError> {
+        let table_key = data.table_name();
 
         match self.tables.get_mut(&table_key) {
+            Some(table) if converted && table.is_strict() => {
+                Err(InsertionError::TypeMismatch(table_key))?;
+            }
             Some(table) => match data {
                 Record::User(user) => {
                     table.insert(user)?;
@@ -48,30 +474,241 @@ impl Database {
     }
 
 
-    pub fn select(&mut self, table_name: TableName) -> Result<Vec<Record>, SelectError> {
+    /// Ouvre un curseur sur les enregistrements d'une table
+    pub fn cursor(&self, table_name: TableName) -> Result<RecordCursor<'_>, SelectError> {
         match self.tables.get(&table_name) {
             Some(table) => match table_name {
-                TableName::User => Ok(table
-                    .select::<User>()?
-                    .into_iter()
-                    .map(Record::User)
-                    .collect::<Vec<_>>()),
-                TableName::Car => Ok(table
-                    .select::<Car>()?
-                    .into_iter()
-                    .map(Record::Car)
-                    .collect::<Vec<_>>()),
+                TableName::User => Ok(RecordCursor::User(table.cursor())),
+                TableName::Car => Ok(RecordCursor::Car(table.cursor())),
+                // les lignes d'une table virtuelle ne sont pas des enregistrements
+                TableName::Virtual(_) => Err(SelectError::TableNotExist(table_name)),
             },
             None => Err(SelectError::TableNotExist(table_name))?,
         }
     }
 
+    pub fn select(&self, table_name: TableName) -> Result<Vec<Record>, SelectError> {
+        self.cursor(table_name)?.collect()
+    }
+
+    /// État de la table au dernier commit, sans les modifications de la
+    /// transaction en cours
+    pub(crate) fn committed_state(&self, table_name: &TableName) -> Option<TableState> {
+        match &self.transaction {
+            Some(transaction) => transaction.snapshot.state(table_name),
+            None => self.tables.get(table_name).map(Table::state),
+        }
+    }
+
+    /// Vue figée de la base au dernier commit
+    pub fn read_view(&self) -> ReadView {
+        ReadView::new(
+            self.tables
+                .iter()
+                .filter_map(|(name, table)| {
+                    let state = self.committed_state(name)?;
+                    Some((name.clone(), table.version(state)))
+                })
+                .collect(),
+        )
+    }
+
+    /// Copie de la base au dernier commit, pour lire sans voir la
+    /// transaction en cours. Les tables partagent leurs pages avec
+    /// l'originale et la copie n'écrit jamais sur le disque.
+    pub fn committed(&self) -> Database {
+        let mut tables = self.tables.clone();
+        let mut views = self.views.clone();
+        if let Some(transaction) = &self.transaction {
+            transaction.snapshot.restore(&mut tables, &mut views);
+        }
+        Database {
+            tables,
+            transaction: None,
+            pager: None,
+            functions: self.functions.clone(),
+            fts_tables: self.fts_tables.clone(),
+            virtual_tables: self.virtual_tables.clone(),
+            views,
+        }
+    }
+
+}
+
+/// Nom d'une vue ou d'une table virtuelle, qui ne tient pas compte de la
+/// casse comme celui des tables de l'application
+fn normalize(name: &str) -> String {
+    name.to_ascii_lowercase()
+}
+
+/// Écrit la page d'en-tête : signature, description de chaque table,
+/// mode de journalisation, version du format, nombre de pages de débordement
+/// de chaque table puis ses options. Les entiers y gardent une taille fixe pour
+/// que les fichiers des versions précédentes restent lisibles.
+fn write_header(
+    tables: &HashMap<TableName, Table>,
+    fts_tables: &HashMap<String, Fts>,
+    views: &BTreeMap<String, String>,
+    mode: JournalMode,
+) -> Result<Vec<u8>, SerializationError> {
+    let mut page = vec![0_u8; PAGE_SIZE];
+    let mut writer = std::io::Cursor::new(&mut page[..]);
+    MAGIC.to_string().serialize(&mut writer)?;
+    FixedI64(tables.len() as i64).serialize(&mut writer)?;
+    let mut entries = tables.iter().collect::<Vec<_>>();
+    entries.sort_by_key(|(_, table)| table.root_page());
+    for (name, table) in &entries {
+        let state = table.state();
+        name.as_str().to_string().serialize(&mut writer)?;
+        FixedI64(table.root_page() as i64).serialize(&mut writer)?;
+        FixedI64(state.offset as i64).serialize(&mut writer)?;
+        FixedI64(state.row_number as i64).serialize(&mut writer)?;
+    }
+    mode.as_str().to_string().serialize(&mut writer)?;
+    FixedI64(FORMAT_VERSION).serialize(&mut writer)?;
+    for (_, table) in &entries {
+        FixedI64(table.state().overflow_pages() as i64).serialize(&mut writer)?;
+    }
+    for (_, table) in &entries {
+        FixedI64(table.is_strict() as i64).serialize(&mut writer)?;
+    }
+    // vide pour une table ordinaire
+    for (name, _) in &entries {
+        let schema = match name {
+            TableName::Virtual(name) => fts_tables.get(name).map(Fts::schema).unwrap_or_default(),
+            _ => String::new(),
+        };
+        schema.serialize(&mut writer)?;
+    }
+    FixedI64(views.len() as i64).serialize(&mut writer)?;
+    for (name, select) in views {
+        name.serialize(&mut writer)?;
+        select.serialize(&mut writer)?;
+    }
+    Ok(page)
+}
+
+/// Relit la page d'en-tête et charge les pages de chaque table, ainsi que
+/// la définition des tables virtuelles et des vues
+#[allow(clippy::type_complexity)]
+fn read_header(
+    pager: &mut Pager,
+) -> Result<
+    (HashMap<TableName, Table>, HashMap<String, Fts>, BTreeMap<String, String>, JournalMode, i64),
+    StorageError,
+> {
+    pager.begin_read()?;
+    let mut header = vec![0_u8; PAGE_SIZE];
+    pager.read_page(0, &mut header)?;
+    let mut reader = Reader::new(&header);
+    let magic = String::deserialize(&mut reader).map_err(StorageError::Deserialization)?;
+    if magic != MAGIC {
+        return Err(StorageError::Corrupted("not a database file".to_string()));
+    }
+    let count = FixedI64::deserialize(&mut reader).map_err(StorageError::Deserialization)?.0;
+    let mut entries = vec![];
+    for _ in 0..count {
+        let name = String::deserialize(&mut reader).map_err(StorageError::Deserialization)?;
+        let name = TableName::from_name(&normalize(&name));
+        let root_page = FixedI64::deserialize(&mut reader).map_err(StorageError::Deserialization)?.0;
+        let offset = FixedI64::deserialize(&mut reader).map_err(StorageError::Deserialization)?.0;
+        let row_number = FixedI64::deserialize(&mut reader).map_err(StorageError::Deserialization)?.0;
+        entries.push((name, root_page, offset, row_number));
+    }
+    let mode = String::deserialize(&mut reader).map_err(StorageError::Deserialization)?;
+    let mode = JournalMode::from_str(&mode).unwrap_or(JournalMode::Delete);
+    // les premiers fichiers ne portaient pas de version
+    let version = FixedI64::deserialize(&mut reader).map_err(StorageError::Deserialization)?.0;
+    // absents des premières versions du format, lus comme zéro
+    let mut overflow_pages = vec![];
+    for _ in &entries {
+        overflow_pages.push(FixedI64::deserialize(&mut reader).map_err(StorageError::Deserialization)?.0);
+    }
+    let mut strict = vec![];
+    for _ in &entries {
+        strict.push(FixedI64::deserialize(&mut reader).map_err(StorageError::Deserialization)?.0 != 0);
+    }
+    let mut schemas = vec![];
+    for _ in &entries {
+        schemas.push(String::deserialize(&mut reader).map_err(StorageError::Deserialization)?);
+    }
+    let mut fts_tables = HashMap::new();
+    for ((name, ..), schema) in entries.iter().zip(&schemas) {
+        if let TableName::Virtual(name) = name {
+            let fts = Fts::from_schema(schema)
+                .ok_or_else(|| StorageError::Corrupted(format!("unknown table {name}")))?;
+            fts_tables.insert(name.clone(), fts);
+        }
+    }
+    let mut views = BTreeMap::new();
+    let count = FixedI64::deserialize(&mut reader).map_err(StorageError::Deserialization)?.0;
+    for _ in 0..count {
+        let name = String::deserialize(&mut reader).map_err(StorageError::Deserialization)?;
+        let select = String::deserialize(&mut reader).map_err(StorageError::Deserialization)?;
+        views.insert(normalize(&name), select);
+    }
+    let mut tables = HashMap::new();
+    for (((name, root_page, offset, row_number), overflow_pages), strict) in
+        entries.into_iter().zip(overflow_pages).zip(strict)
+    {
+        if u32::try_from(root_page).map_or(true, |root_page| root_page == 0)
+            || offset < 0
+            || row_number < 0
+            || overflow_pages < 0
+            || offset as usize + overflow_pages as usize * PAGE_SIZE > TABLE_PAGES as usize * PAGE_SIZE
+        {
+            return Err(StorageError::Corrupted(format!("invalid table {name:?}")));
+        }
+        let state = TableState {
+            offset: offset as usize,
+            row_number: row_number as usize,
+            overflow: (TABLE_PAGES as usize - overflow_pages as usize) * PAGE_SIZE,
+        };
+        let mut table = Table::load(root_page as u32, state, |page, data| pager.read_page(page, data))?;
+        table.set_strict(strict);
+        tables.insert(name, table);
+    }
+    // le répertoire des tables fts se refait en parcourant leurs lignes,
+    // celles d'un format précédent le sont à leur migration
+    if version >= FORMAT_VERSION {
+        for (name, fts) in fts_tables.iter_mut() {
+            if let Some(table) = tables.get(&TableName::Virtual(name.clone())) {
+                fts.load(table).map_err(|err| StorageError::Corrupted(err.to_string()))?;
+            }
+        }
+    }
+    Ok((tables, fts_tables, views, mode, version.max(1)))
+}
+
+/// Curseur typé selon la table parcourue, qui produit des `Record`
+pub enum RecordCursor<'a> {
+    User(Cursor<'a, User>),
+    Car(Cursor<'a, Car>),
+}
+
+impl Iterator for RecordCursor<'_> {
+    type Item = Result<Record, SelectError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            RecordCursor::User(cursor) => cursor.next().map(|row| row.map(Record::User)),
+            RecordCursor::Car(cursor) => cursor.next().map(|row| row.map(Record::Car)),
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match self {
+            RecordCursor::User(cursor) => cursor.size_hint(),
+            RecordCursor::Car(cursor) => cursor.size_hint(),
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::data::User;
+    use crate::vfs::memory::MemoryVfs;
     #[test]
     fn test_database() {
         let mut database = Database::new();
@@ -94,6 +731,510 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_integer_size() {
+        let mut database = Database::new();
+        database.create_table(TableName::User).expect("Creation failed");
+        let offset = |database: &Database| {
+            database
+                .committed_state(&TableName::User)
+                .expect("missing table")
+                .offset
+        };
+        for i in 2..50 {
+            let user = User::new(i, "a".to_string(), "b".to_string());
+            database.insert(Record::User(user)).expect("insert user failed");
+        }
+        // en-tête de 4 octets, puis 1 octet pour l'id et pour chaque texte
+        assert_eq!(offset(&database), 48 * (4 + 1 + 1 + 1));
+        // le plus grand id prend 8 octets
+        let user = User::new(i64::MAX, "a".to_string(), "b".to_string());
+        database.insert(Record::User(user)).expect("insert user failed");
+        assert_eq!(offset(&database), 48 * 7 + 4 + 8 + 1 + 1);
+    }
+
+    #[test]
+    fn test_cursor_limit() {
+        let mut database = Database::new();
+        database.create_table(TableName::User).expect("Creation failed");
+        for i in 0..50 {
+            let user = User::new(i, format!("test_{i}"), format!("email_{i}@example.com"));
+            database
+                .insert(Record::User(user))
+                .expect("insert user failed");
+        }
+        let rows = database
+            .cursor(TableName::User)
+            .expect("cursor failed")
+            .take(3)
+            .collect::<Result<Vec<_>, _>>()
+            .expect("select failed");
+        assert_eq!(rows.len(), 3);
+        assert_eq!(
+            rows[2],
+            Record::User(User::new(2, "test_2".to_string(), "email_2@example.com".to_string()))
+        );
+        assert_eq!(
+            database.cursor(TableName::Car).err(),
+            Some(SelectError::TableNotExist(TableName::Car))
+        );
+    }
+
+    #[test]
+    fn test_transaction() {
+        let mut database = Database::new();
+        database.create_table(TableName::User).expect("Creation failed");
+        database
+            .insert(Record::User(User::new(0, "test_0".to_string(), "email_0".to_string())))
+            .expect("insert user failed");
+
+        // les insertions annulées disparaissent, ainsi que les tables créées
+        database.begin().expect("begin failed");
+        assert_eq!(database.begin(), Err(TransactionError::AlreadyStarted));
+        database.create_table(TableName::Car).expect("Creation failed");
+        for i in 1..10 {
+            let user = User::new(i, format!("test_{i}"), format!("email_{i}"));
+            database
+                .insert(Record::User(user))
+                .expect("insert user failed");
+        }
+        assert_eq!(database.select(TableName::User).expect("select failed").len(), 10);
+        database.rollback().expect("rollback failed");
+        assert_eq!(database.select(TableName::User).expect("select failed").len(), 1);
+        assert_eq!(
+            database.select(TableName::Car),
+            Err(SelectError::TableNotExist(TableName::Car))
+        );
+
+        // les insertions validées sont conservées
+        database.begin().expect("begin failed");
+        database
+            .insert(Record::User(User::new(1, "test_1".to_string(), "email_1".to_string())))
+            .expect("insert user failed");
+        database.commit().expect("commit failed");
+        assert_eq!(database.rollback(), Err(TransactionError::NoActiveTransaction));
+        assert_eq!(database.commit(), Err(TransactionError::NoActiveTransaction));
+        let rows = database.select(TableName::User).expect("select failed");
+        assert_eq!(
+            rows,
+            vec![
+                Record::User(User::new(0, "test_0".to_string(), "email_0".to_string())),
+                Record::User(User::new(1, "test_1".to_string(), "email_1".to_string())),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_reopen() {
+        let file = TempFile(std::env::temp_dir().join(format!("rusty-sqlite-{}.db", std::process::id())));
+        let path = file.0.clone();
+        {
+            let mut database = Database::open(&path).expect("open failed");
+            database.create_table(TableName::User).expect("Creation failed");
+            database.create_table(TableName::Car).expect("Creation failed");
+            // assez de lignes pour occuper plusieurs pages
+            for i in 0..500 {
+                let user = User::new(i, format!("test_{i}"), format!("email_{i}@example.com"));
+                database
+                    .insert(Record::User(user))
+                    .expect("insert user failed");
+            }
+            database
+                .insert(Record::Car(Car::new("AB-123-CD".to_string(), "Renault".to_string())))
+                .expect("insert car failed");
+            // une transaction non validée n'est pas enregistrée
+            database.begin().expect("begin failed");
+            database
+                .insert(Record::Car(Car::new("EF-456-GH".to_string(), "Peugeot".to_string())))
+                .expect("insert car failed");
+        }
+        let database = Database::open(&path).expect("reopen failed");
+        let rows = database.select(TableName::User).expect("select failed");
+        assert_eq!(rows.len(), 500);
+        assert_eq!(
+            rows[499],
+            Record::User(User::new(499, "test_499".to_string(), "email_499@example.com".to_string()))
+        );
+        assert_eq!(
+            database.select(TableName::Car),
+            Ok(vec![Record::Car(Car::new("AB-123-CD".to_string(), "Renault".to_string()))])
+        );
+    }
+
+    /// Fichier de test supprimé avec ses journaux, même si le test échoue
+    struct TempFile(std::path::PathBuf);
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            for suffix in ["", "-journal", "-wal"] {
+                let mut path = self.0.as_os_str().to_owned();
+                path.push(suffix);
+                let _ = std::fs::remove_file(path);
+            }
+        }
+    }
+
+    #[test]
+    fn test_not_a_database() {
+        let vfs = MemoryVfs::new();
+        let mut file = vfs.open(Path::new("test.db")).expect("open failed");
+        file.write_at(0, b"garbage").expect("write failed");
+        assert!(Database::open_with(Arc::new(vfs), "test.db").is_err());
+    }
+
+    #[test]
+    fn test_invalid_root_page() {
+        for root_page in [-1, 0, 1 << 40] {
+            let mut header = vec![0_u8; PAGE_SIZE];
+            let mut writer = std::io::Cursor::new(&mut header[..]);
+            MAGIC.to_string().serialize(&mut writer).expect("serialization error");
+            FixedI64(1).serialize(&mut writer).expect("serialization error");
+            "user".to_string().serialize(&mut writer).expect("serialization error");
+            FixedI64(root_page).serialize(&mut writer).expect("serialization error");
+            let vfs = MemoryVfs::new();
+            let mut file = vfs.open(Path::new("test.db")).expect("open failed");
+            file.write_at(0, &header).expect("write failed");
+            drop(file);
+            assert!(matches!(
+                Database::open_with(Arc::new(vfs), "test.db"),
+                Err(StorageError::Corrupted(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_crash_at_every_write_point() {
+        let vfs = MemoryVfs::new();
+        let mut database = Database::open_with(Arc::new(vfs.clone()), "test.db").expect("open failed");
+        database.create_table(TableName::User).expect("Creation failed");
+        for i in 0..100 {
+            let user = User::new(i, format!("test_{i}"), format!("email_{i}@example.com"));
+            database
+                .insert(Record::User(user))
+                .expect("insert user failed");
+        }
+        drop(database);
+
+        let mut crash_at = 0;
+        loop {
+            let mut database =
+                Database::open_with(Arc::new(vfs.clone()), "test.db").expect("open failed");
+            vfs.crash_after(crash_at);
+            let result = (|| {
+                database.begin()?;
+                database.create_table(TableName::Car).expect("Creation failed");
+                for i in 100..200 {
+                    let user = User::new(i, format!("test_{i}"), format!("email_{i}@example.com"));
+                    database
+                        .insert(Record::User(user))
+                        .expect("insert user failed");
+                }
+                database.commit()
+            })();
+            vfs.crash();
+            drop(database);
+
+            let database =
+                Database::open_with(Arc::new(vfs.clone()), "test.db").expect("recovery failed");
+            let users = database.select(TableName::User).expect("select failed");
+            if result.is_ok() {
+                assert_eq!(users.len(), 200);
+                assert_eq!(database.select(TableName::Car), Ok(vec![]));
+                break;
+            }
+            // le commit interrompu ne laisse aucune trace
+            assert_eq!(users.len(), 100, "crash at write {crash_at}");
+            assert_eq!(
+                database.select(TableName::Car),
+                Err(SelectError::TableNotExist(TableName::Car)),
+                "crash at write {crash_at}"
+            );
+            crash_at += 1;
+        }
+        assert!(crash_at > 0);
+    }
+
+    #[test]
+    fn test_journal_mode() {
+        let vfs = MemoryVfs::new();
+        let mut database = Database::open_with(Arc::new(vfs.clone()), "test.db").expect("open failed");
+        assert_eq!(database.journal_mode(), Some(JournalMode::Delete));
+        database.set_journal_mode(JournalMode::Wal).expect("mode failed");
+        database.create_table(TableName::User).expect("Creation failed");
+        database.begin().expect("begin failed");
+        assert_eq!(
+            database.set_journal_mode(JournalMode::Delete),
+            Err(StorageError::InTransaction)
+        );
+        for i in 0..10 {
+            let user = User::new(i, format!("test_{i}"), format!("email_{i}@example.com"));
+            database
+                .insert(Record::User(user))
+                .expect("insert user failed");
+        }
+        database.commit().expect("commit failed");
+        assert!(vfs.exists(Path::new("test.db-wal")));
+        drop(database);
+
+        // le mode est conservé dans le fichier
+        let mut database = Database::open_with(Arc::new(vfs.clone()), "test.db").expect("open failed");
+        assert_eq!(database.journal_mode(), Some(JournalMode::Wal));
+        assert_eq!(database.select(TableName::User).expect("select failed").len(), 10);
+        database.checkpoint().expect("checkpoint failed");
+        database.set_journal_mode(JournalMode::Delete).expect("mode failed");
+        assert!(!vfs.exists(Path::new("test.db-wal")));
+        drop(database);
+
+        let database = Database::open_with(Arc::new(vfs), "test.db").expect("open failed");
+        assert_eq!(database.journal_mode(), Some(JournalMode::Delete));
+        assert_eq!(database.select(TableName::User).expect("select failed").len(), 10);
+        // une base en mémoire n'a pas de journal
+        assert_eq!(Database::new().journal_mode(), None);
+    }
+
+    #[test]
+    fn test_savepoints() {
+        let mut database = Database::new();
+        database.create_table(TableName::User).expect("Creation failed");
+        let insert = |database: &mut Database, i: i64| {
+            database
+                .insert(Record::User(User::new(i, format!("test_{i}"), format!("email_{i}"))))
+                .expect("insert user failed");
+        };
+        let count = |database: &Database| database.select(TableName::User).expect("select failed").len();
+
+        database.begin().expect("begin failed");
+        insert(&mut database, 0);
+        database.savepoint("a".to_string()).expect("savepoint failed");
+        insert(&mut database, 1);
+        database.savepoint("b".to_string()).expect("savepoint failed");
+        insert(&mut database, 2);
+        database.create_table(TableName::Car).expect("Creation failed");
+        assert_eq!(count(&database), 3);
+
+        // retour au point b, qui reste utilisable
+        database.rollback_to("B").expect("rollback to failed");
+        assert_eq!(count(&database), 2);
+        assert!(database.select(TableName::Car).is_err());
+        insert(&mut database, 3);
+        database.rollback_to("b").expect("rollback to failed");
+        assert_eq!(count(&database), 2);
+
+        // relâcher a oublie aussi b
+        database.release("a").expect("release failed");
+        assert_eq!(
+            database.rollback_to("b"),
+            Err(TransactionError::NoSuchSavepoint("b".to_string()))
+        );
+        assert!(database.in_transaction());
+        database.rollback().expect("rollback failed");
+        assert_eq!(count(&database), 0);
+
+        // un savepoint hors transaction en démarre une, validée par son release
+        database.savepoint("outer".to_string()).expect("savepoint failed");
+        insert(&mut database, 0);
+        database.savepoint("inner".to_string()).expect("savepoint failed");
+        insert(&mut database, 1);
+        database.release("inner").expect("release failed");
+        assert!(database.in_transaction());
+        database.release("outer").expect("release failed");
+        assert!(!database.in_transaction());
+        assert_eq!(count(&database), 2);
+        assert_eq!(
+            database.release("outer"),
+            Err(TransactionError::NoSuchSavepoint("outer".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_migrate_v1() {
+        fn string_v1(page: &mut Vec<u8>, data: &str) {
+            page.push(data.len() as u8);
+            page.extend_from_slice(data.as_bytes());
+        }
+        // table au format 1, avec une chaîne dont la longueur a le bit de poids fort
+        let long_name = "x".repeat(200);
+        let mut rows = vec![];
+        rows.extend_from_slice(&1_i64.to_le_bytes());
+        string_v1(&mut rows, &long_name);
+        string_v1(&mut rows, "one@example.com");
+        rows.extend_from_slice(&2_i64.to_le_bytes());
+        string_v1(&mut rows, "two");
+        string_v1(&mut rows, "two@example.com");
+        // en-tête sans mode de journalisation ni version
+        let mut header = vec![];
+        string_v1(&mut header, MAGIC);
+        header.extend_from_slice(&1_i64.to_le_bytes());
+        string_v1(&mut header, "user");
+        header.extend_from_slice(&1_i64.to_le_bytes());
+        header.extend_from_slice(&(rows.len() as i64).to_le_bytes());
+        header.extend_from_slice(&2_i64.to_le_bytes());
+
+        let vfs = MemoryVfs::new();
+        let mut file = vfs.open(Path::new("test.db")).expect("open failed");
+        file.write_at(0, &header).expect("write failed");
+        file.write_at(PAGE_SIZE as u64, &rows).expect("write failed");
+        drop(file);
+
+        let expected = vec![
+            Record::User(User::new(1, long_name.clone(), "one@example.com".to_string())),
+            Record::User(User::new(2, "two".to_string(), "two@example.com".to_string())),
+        ];
+        let database = Database::open_with(Arc::new(vfs.clone()), "test.db").expect("open failed");
+        assert_eq!(database.select(TableName::User), Ok(expected.clone()));
+        drop(database);
+        // le fichier a été réécrit au format courant
+        let database = Database::open_with(Arc::new(vfs), "test.db").expect("open failed");
+        assert_eq!(database.select(TableName::User), Ok(expected));
+    }
+
+    #[test]
+    fn test_migrate_v2() {
+        fn string_v2(page: &mut Vec<u8>, data: &str) {
+            // longueur en varint sur deux octets au plus
+            if data.len() < 0x80 {
+                page.push(data.len() as u8);
+            } else {
+                page.extend_from_slice(&[data.len() as u8 | 0x80, (data.len() >> 7) as u8]);
+            }
+            page.extend_from_slice(data.as_bytes());
+        }
+        // au format 2 les grandes chaînes restaient dans la ligne
+        let long_name = "x".repeat(2_000);
+        let mut rows = vec![];
+        rows.extend_from_slice(&1_i64.to_le_bytes());
+        string_v2(&mut rows, &long_name);
+        string_v2(&mut rows, "one@example.com");
+        let mut header = vec![];
+        string_v2(&mut header, MAGIC);
+        header.extend_from_slice(&1_i64.to_le_bytes());
+        string_v2(&mut header, "user");
+        header.extend_from_slice(&1_i64.to_le_bytes());
+        header.extend_from_slice(&(rows.len() as i64).to_le_bytes());
+        header.extend_from_slice(&1_i64.to_le_bytes());
+        string_v2(&mut header, "delete");
+        header.extend_from_slice(&2_i64.to_le_bytes());
+
+        let vfs = MemoryVfs::new();
+        let mut file = vfs.open(Path::new("test.db")).expect("open failed");
+        file.write_at(0, &header).expect("write failed");
+        file.write_at(PAGE_SIZE as u64, &rows).expect("write failed");
+        drop(file);
+
+        let expected = vec![Record::User(User::new(1, long_name, "one@example.com".to_string()))];
+        let database = Database::open_with(Arc::new(vfs.clone()), "test.db").expect("open failed");
+        assert_eq!(database.select(TableName::User), Ok(expected.clone()));
+        drop(database);
+        let database = Database::open_with(Arc::new(vfs), "test.db").expect("open failed");
+        assert_eq!(database.select(TableName::User), Ok(expected));
+    }
+
+    #[test]
+    fn test_migrate_v3() {
+        // au format 3 les champs se suivaient sans en-tête de types
+        let mut rows = vec![];
+        rows.extend_from_slice(&1_i64.to_le_bytes());
+        for data in ["one", "one@example.com"] {
+            rows.push(data.len() as u8);
+            rows.extend_from_slice(data.as_bytes());
+        }
+        let mut header = vec![0_u8; PAGE_SIZE];
+        let mut writer = std::io::Cursor::new(&mut header[..]);
+        MAGIC.to_string().serialize(&mut writer).expect("serialization error");
+        FixedI64(1_i64).serialize(&mut writer).expect("serialization error");
+        "user".to_string().serialize(&mut writer).expect("serialization error");
+        FixedI64(1_i64).serialize(&mut writer).expect("serialization error");
+        FixedI64(rows.len() as i64).serialize(&mut writer).expect("serialization error");
+        FixedI64(1_i64).serialize(&mut writer).expect("serialization error");
+        "delete".to_string().serialize(&mut writer).expect("serialization error");
+        FixedI64(3_i64).serialize(&mut writer).expect("serialization error");
+
+        let vfs = MemoryVfs::new();
+        let mut file = vfs.open(Path::new("test.db")).expect("open failed");
+        file.write_at(0, &header).expect("write failed");
+        file.write_at(PAGE_SIZE as u64, &rows).expect("write failed");
+        drop(file);
+
+        let database = Database::open_with(Arc::new(vfs), "test.db").expect("open failed");
+        assert_eq!(
+            database.select(TableName::User),
+            Ok(vec![Record::User(User::new(1, "one".to_string(), "one@example.com".to_string()))])
+        );
+    }
+
+    #[test]
+    fn test_migrate_v4() {
+        let vfs = MemoryVfs::new();
+        let expected = [
+            Record::User(User::new(1, "alice".to_string(), "alice@x.com".to_string())),
+            Record::User(User::new(2, "bob".to_string(), "bob@x.com".to_string())),
+            Record::Car(Car::new("AB-123-CD".to_string(), "Renault".to_string())),
+        ];
+        {
+            let mut database = Database::open_with(Arc::new(vfs.clone()), "test.db").expect("open failed");
+            database.create_table(TableName::User).expect("Creation failed");
+            database.create_table(TableName::Car).expect("Creation failed");
+            for record in expected.clone() {
+                database.insert(record).expect("insert failed");
+            }
+        }
+        // au format 4 les lignes avaient déjà leur en-tête de types : seule
+        // la version de l'en-tête change
+        let mut file = vfs.open(Path::new("test.db")).expect("open failed");
+        let mut header = vec![0_u8; PAGE_SIZE];
+        file.read_at(0, &mut header).expect("read failed");
+        let mut reader = Reader::new(&header);
+        String::deserialize(&mut reader).expect("deserialization error");
+        // nom puis racine, taille et nombre de lignes de chaque table, puis le mode
+        for _ in 0..FixedI64::deserialize(&mut reader).expect("deserialization error").0 {
+            String::deserialize(&mut reader).expect("deserialization error");
+            for _ in 0..3 {
+                FixedI64::deserialize(&mut reader).expect("deserialization error");
+            }
+        }
+        String::deserialize(&mut reader).expect("deserialization error");
+        let position = reader.position() as usize;
+        let mut version = vec![0_u8; 8];
+        FixedI64(4).serialize(&mut std::io::Cursor::new(&mut version[..])).expect("serialization error");
+        file.write_at(position as u64, &version).expect("write failed");
+        drop(file);
+
+        for _ in 0..2 {
+            let database = Database::open_with(Arc::new(vfs.clone()), "test.db").expect("open failed");
+            assert_eq!(database.select(TableName::User), Ok(expected[..2].to_vec()));
+            assert_eq!(database.select(TableName::Car), Ok(expected[2..].to_vec()));
+        }
+    }
+
+    #[test]
+    fn test_overflow_reopen() {
+        let vfs = MemoryVfs::new();
+        let document = "lorem ipsum ".repeat(10_000);
+        let expected = vec![
+            Record::User(User::new(1, "one".to_string(), document.clone())),
+            Record::User(User::new(2, "two".to_string(), "two@example.com".to_string())),
+        ];
+        {
+            let mut database = Database::open_with(Arc::new(vfs.clone()), "test.db").expect("open failed");
+            database.create_table(TableName::User).expect("Creation failed");
+            for record in expected.clone() {
+                database.insert(record).expect("insert failed");
+            }
+            // les pages allouées par une transaction annulée sont libérées
+            database.begin().expect("begin failed");
+            database
+                .insert(Record::User(User::new(3, "three".to_string(), document.clone())))
+                .expect("insert failed");
+            database.rollback().expect("rollback failed");
+        }
+        let database = Database::open_with(Arc::new(vfs), "test.db").expect("open failed");
+        assert_eq!(database.select(TableName::User), Ok(expected));
+        assert_eq!(
+            database.tables[&TableName::User].state().overflow_pages(),
+            document.len().div_ceil(PAGE_SIZE - 8)
+        );
+    }
+
     #[test]
     fn recreate_table() {
         let mut database = Database::new();
@@ -103,5 +1244,111 @@ mod tests {
             Err(CreationError::TableAlreadyExist(TableName::User))
         );
     }
+
+    #[test]
+    fn test_virtual_table_reopen() {
+        let vfs = MemoryVfs::new();
+        let columns = ["title".to_string(), "body".to_string()];
+        let text = |text: &str| Value::Text(text.to_string());
+        {
+            let mut database = Database::open_with(Arc::new(vfs.clone()), "test.db").expect("open failed");
+            database.create_table(TableName::User).expect("Creation failed");
+            assert_eq!(
+                database.create_virtual_table("user", "fts", &columns),
+                Err(CreationError::TableAlreadyExist(TableName::User))
+            );
+            assert_eq!(
+                database.create_virtual_table("docs", "rtree", &columns),
+                Err(CreationError::UnknownModule("rtree".to_string()))
+            );
+            database.create_virtual_table("docs", "fts", &columns).expect("Creation failed");
+            database.insert_virtual("docs", &[text("Rust"), text("Systems programming")]).expect("insert failed");
+            // une table virtuelle créée dans une transaction annulée disparaît
+            database.begin().expect("begin failed");
+            database.create_virtual_table("notes", "fts", &columns[..1]).expect("Creation failed");
+            database.rollback().expect("rollback failed");
+            assert!(database.fts_table("notes").is_none());
+        }
+        let database = Database::open_with(Arc::new(vfs), "test.db").expect("open failed");
+        assert!(database.fts_table("notes").is_none());
+        let (fts, table) = database.fts_table("docs").expect("missing table");
+        assert_eq!(fts.columns(), columns);
+        assert_eq!(
+            fts.rows(table, None),
+            Ok(vec![vec![text("Rust"), text("Systems programming"), Value::Integer(1), Value::Null]])
+        );
+    }
+
+    #[test]
+    fn test_catalog() {
+        let mut database = Database::new();
+        // les noms des vues et des tables virtuelles ignorent la casse
+        database.create_view("Recent", "select id from user").expect("Creation failed");
+        assert_eq!(database.view("RECENT"), Some("select id from user"));
+        assert_eq!(
+            database.create_view("recent", "select id from car"),
+            Err(CreationError::ViewAlreadyExist("recent".to_string()))
+        );
+        database
+            .create_virtual_table("Docs", "fts", &["body".to_string()])
+            .expect("Creation failed");
+        assert!(database.fts_table("DOCS").is_some());
+        database.insert_virtual("docs", &[Value::Text("x".to_string())]).expect("insert failed");
+        database.drop_view("rEcEnT").expect("drop failed");
+        // la page d'en-tête décrit toutes les tables et les vues
+        let long = format!("select '{}'", "x".repeat(5000));
+        assert_eq!(database.create_view("long", &long), Err(CreationError::CatalogFull));
+        assert_eq!(database.view("long"), None);
+        let mut count = 0;
+        while database.create_view(&format!("view{count}"), "select id, name from user where id > 1").is_ok() {
+            count += 1;
+        }
+        assert!(count > 10);
+        assert_eq!(
+            database.create_virtual_table("notes", "fts", &["body".to_string()]),
+            Err(CreationError::CatalogFull)
+        );
+        assert!(database.fts_table("notes").is_none());
+        assert_eq!(database.schema().len(), count + 1);
+    }
+
+    #[test]
+    fn test_views_reopen() {
+        let vfs = MemoryVfs::new();
+        {
+            let mut database = Database::open_with(Arc::new(vfs.clone()), "test.db").expect("open failed");
+            database.create_strict_table(TableName::User).expect("Creation failed");
+            database.create_table(TableName::Car).expect("Creation failed");
+            database
+                .create_virtual_table("docs", "fts", &["body".to_string()])
+                .expect("Creation failed");
+            database.create_view("ids", "select id from user").expect("Creation failed");
+            assert_eq!(
+                database.create_view("ids", "select id from car"),
+                Err(CreationError::ViewAlreadyExist("ids".to_string()))
+            );
+            assert_eq!(
+                database.create_view("car", "select id from car"),
+                Err(CreationError::TableAlreadyExist(TableName::Car))
+            );
+            // la création et la suppression d'une vue suivent la transaction
+            database.begin().expect("begin failed");
+            database.create_view("brands", "select brand from car").expect("Creation failed");
+            database.drop_view("ids").expect("drop failed");
+            database.rollback().expect("rollback failed");
+            assert_eq!(database.drop_view("brands"), Err(CreationError::UnknownView("brands".to_string())));
+        }
+        let database = Database::open_with(Arc::new(vfs), "test.db").expect("open failed");
+        assert_eq!(database.view("ids"), Some("select id from user"));
+        assert_eq!(
+            database.schema(),
+            [
+                "create car",
+                "create virtual table docs using fts(body)",
+                "create user strict",
+                "create view ids as select id from user",
+            ]
+        );
+    }
 }
 
@@ -1,13 +1,18 @@
 use std::error::Error;
 use std::fmt::Display;
 use std::string::FromUtf8Error;
-use crate::data::TableName;
+use crate::data::{TableName, Value};
 
 #[derive(Debug, PartialEq)]
 pub enum ExecutionError {
     Insertion(InsertionError),
     Select(SelectError),
     Create(CreationError),
+    Transaction(TransactionError),
+    Storage(StorageError),
+    Command(CommandError),
+    Row(RowError),
+    Evaluation(EvaluationError),
 }
 
 impl Display for ExecutionError {
@@ -24,7 +29,25 @@ pub enum CommandError {
     TooManyArguments,
     ExpectingInteger,
     /// La table n'existe pas
-    UnknownTable(String)
+    UnknownTable(String),
+    UnknownPragma(String),
+    InvalidPragmaValue(String),
+    /// Littéral mal écrit : chaîne non terminée, date invalide...
+    InvalidLiteral(String),
+    UnexpectedToken(String),
+    ExpectingText,
+    /// Aucune commande SQL ne commence ainsi
+    UnknownCommand(String),
+    /// Le nombre de valeurs liées ne correspond pas aux paramètres
+    ParameterCount { expected: usize, given: usize },
+    /// Aucun paramètre ne porte ce nom
+    UnknownParameter(String),
+    /// Plus de paramètres que la limite `MAX_PARAMETERS`
+    TooManyParameters,
+    /// Une vue se définit par un select sans paramètre
+    InvalidView(String),
+    /// Expression imbriquée sur plus de niveaux que la limite `MAX_DEPTH`
+    ExpressionTooDeep,
 }
 
 impl Display for CommandError {
@@ -50,6 +73,14 @@ pub enum BufferError {
 pub enum DeserializationError {
     UnableToDeserializeString(FromUtf8Error),
     UnableToDeserializeInteger,
+    /// Entier de taille variable qui ne se termine pas
+    UnableToDeserializeVarint,
+    /// Chaîne de pages de débordement incohérente
+    UnableToReadOverflow,
+    /// Valeur NULL lue dans une colonne qui ne l'accepte pas
+    UnexpectedNull,
+    /// Code de type inconnu ou incompatible avec la colonne lue
+    UnexpectedType(u64),
     Buffer(BufferError),
 }
 
@@ -81,6 +112,9 @@ impl Error for SerializationError {}
 pub enum InsertionError {
     Serialization(SerializationError),
     TableNotExist(TableName),
+    Storage(StorageError),
+    /// Une table STRICT refuse une valeur qui n'a pas le type de sa colonne
+    TypeMismatch(TableName),
 }
 
 impl Display for InsertionError {
@@ -111,6 +145,17 @@ impl Error for SelectError {}
 #[derive(Debug, PartialEq)]
 pub enum CreationError {
     TableAlreadyExist(TableName),
+    Storage(StorageError),
+    /// Aucun module de table virtuelle ne porte ce nom
+    UnknownModule(String),
+    /// Le module refuse cet argument
+    InvalidModuleArguments(String),
+    ViewAlreadyExist(String),
+    /// `drop view` d'une vue qui n'existe pas
+    UnknownView(String),
+    /// La page d'en-tête n'a plus la place de décrire une table ou une vue
+    /// de plus
+    CatalogFull,
 }
 
 impl Display for CreationError {
@@ -121,3 +166,123 @@ impl Display for CreationError {
 
 impl Error for CreationError {}
 
+
+//--------------------
+// Transaction error
+//--------------------
+#[derive(Debug, PartialEq)]
+pub enum TransactionError {
+    /// Une transaction est déjà en cours
+    AlreadyStarted,
+    /// Aucune transaction n'est en cours
+    NoActiveTransaction,
+    /// Aucun point de sauvegarde ne porte ce nom
+    NoSuchSavepoint(String),
+    /// La table a été créée par une autre transaction validée entre-temps
+    Conflict(TableName),
+    Insertion(InsertionError),
+    Creation(CreationError),
+    Storage(StorageError),
+}
+
+impl TransactionError {
+    /// La transaction peut réussir si elle est rejouée, une fois la
+    /// transaction de l'autre connexion terminée pour `Busy`
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            TransactionError::Conflict(_) | TransactionError::Storage(StorageError::Busy)
+        )
+    }
+}
+
+impl Display for TransactionError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl Error for TransactionError {}
+
+//-------------------
+// Evaluation error
+//-------------------
+#[derive(Debug, PartialEq)]
+pub enum EvaluationError {
+    /// Aucune fonction ne porte ce nom
+    UnknownFunction(String),
+    /// La fonction existe, mais pas avec ce nombre d'arguments
+    WrongArgumentCount { function: String, given: usize },
+    /// Fonction d'agrégat appelée hors d'un select, ou dans une autre
+    MisusedAggregate(String),
+    /// La colonne n'existe pas dans la table lue
+    UnknownColumn(String),
+    /// Le calcul dépasse les bornes d'un entier sur 64 bits
+    IntegerOverflow,
+    /// Erreur signalée par une fonction
+    Function(String),
+}
+
+impl Display for EvaluationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl Error for EvaluationError {}
+
+//------------
+// Row error
+//------------
+#[derive(Debug, PartialEq)]
+pub enum RowError {
+    /// Aucune colonne ne porte ce nom
+    UnknownColumn(String),
+    /// La ligne a moins de colonnes
+    IndexOutOfRange(usize),
+    /// La valeur de la colonne ne peut pas être lue dans le type attendu
+    InvalidType {
+        column: String,
+        expected: &'static str,
+        value: Value,
+    },
+}
+
+impl Display for RowError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl Error for RowError {}
+
+//----------------
+// Storage error
+//----------------
+#[derive(Debug, PartialEq)]
+pub enum StorageError {
+    /// Erreur d'entrée/sortie sur le fichier
+    Io(String),
+    /// Le fichier n'est pas une base de données valide
+    Corrupted(String),
+    Serialization(SerializationError),
+    Deserialization(DeserializationError),
+    /// Opération impossible pendant une transaction
+    InTransaction,
+    /// Une autre connexion a une transaction en cours sur la base
+    Busy,
+}
+
+impl StorageError {
+    pub fn io(error: std::io::Error) -> Self {
+        StorageError::Io(error.to_string())
+    }
+}
+
+impl Display for StorageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl Error for StorageError {}
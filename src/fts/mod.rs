@@ -0,0 +1,526 @@
+//! Tables virtuelles de recherche plein texte, créées par
+//! `CREATE VIRTUAL TABLE docs USING fts(title, body)`.
+//!
+//! Les documents sont rangés dans les pages d'une table ordinaire, qui ne
+//! fait qu'ajouter des lignes : chaque insertion ajoute le document, puis
+//! pour chacun de ses termes et chaque colonne où il apparaît une ligne de
+//! la liste du terme, avec ses positions. Un répertoire en mémoire, refait
+//! au chargement de la table, retient où se trouvent le document et les
+//! lignes de chaque terme : une recherche ne relit que les listes des
+//! termes de la requête, garde les documents qui contiennent toutes ses
+//! phrases et les classe selon BM25.
+
+mod query;
+mod tokenizer;
+
+use crate::data::{write_record, Column, Deserializable, Reader, RecordReader, Serializable, Value};
+use crate::errors::{
+    CreationError, DeserializationError, EvaluationError, ExecutionError, InsertionError, SelectError, SerializationError,
+    StorageError,
+};
+use crate::table::Table;
+use query::Phrase;
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use tokenizer::tokenize;
+
+/// Colonnes cachées, lisibles par leur nom mais absentes de `*` : le numéro
+/// du document et son classement, d'autant meilleur qu'il est petit
+pub const HIDDEN_COLUMNS: [&str; 2] = ["rowid", "rank"];
+
+/// Paramètres de BM25, ceux de SQLite
+const K1: f64 = 1.2;
+const B: f64 = 0.75;
+
+/// Première colonne d'une ligne de la table, qui en donne la nature
+const DOCUMENT: i64 = 0;
+const POSTING: i64 = 1;
+
+/// Erreur d'un `MATCH` ailleurs que dans le filtre d'une table fts
+pub fn misused_match() -> EvaluationError {
+    EvaluationError::Function("unable to use function MATCH in the requested context".to_string())
+}
+
+/// Table fts : ses colonnes, toutes indexées, et le répertoire de ses lignes
+#[derive(Debug, PartialEq, Clone)]
+pub struct Fts {
+    columns: Vec<String>,
+    directory: Directory,
+}
+
+/// Recherche du `MATCH`, dans toutes les colonnes ou dans une seule
+pub struct Search<'a> {
+    pub query: &'a str,
+    pub column: Option<usize>,
+}
+
+impl Fts {
+    /// Table décrite par les arguments du module, les noms de ses colonnes
+    pub fn new(arguments: &[String]) -> Result<Self, CreationError> {
+        let mut names = HashSet::new();
+        for argument in arguments {
+            let valid = !argument.is_empty() && argument.chars().all(|c| c.is_alphanumeric() || c == '_');
+            let hidden = HIDDEN_COLUMNS.iter().any(|column| column.eq_ignore_ascii_case(argument));
+            if !valid || hidden || !names.insert(argument.to_ascii_lowercase()) {
+                return Err(CreationError::InvalidModuleArguments(argument.clone()));
+            }
+        }
+        match arguments.is_empty() {
+            true => Err(CreationError::InvalidModuleArguments("fts".to_string())),
+            false => Ok(Self {
+                columns: arguments.to_vec(),
+                directory: Directory::default(),
+            }),
+        }
+    }
+
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    /// Description gardée dans l'en-tête du fichier : `fts(title, body)`
+    pub fn schema(&self) -> String {
+        format!("fts({})", self.columns.join(", "))
+    }
+
+    pub fn from_schema(schema: &str) -> Option<Self> {
+        let arguments = schema.strip_prefix("fts(")?.strip_suffix(')')?;
+        let arguments = arguments.split(", ").map(str::to_string).collect::<Vec<_>>();
+        Self::new(&arguments).ok()
+    }
+
+    /// Refait le répertoire en parcourant les lignes de la table
+    pub fn load(&mut self, table: &Table) -> Result<(), SelectError> {
+        self.directory = Directory::default();
+        let mut cursor = table.cursor::<Entry>();
+        loop {
+            let offset = cursor.offset();
+            match cursor.next().transpose()? {
+                Some(Entry::Document(document)) => self.directory.documents.push((offset, document.lengths)),
+                Some(Entry::Posting(posting)) => self.directory.terms.entry(posting.term).or_default().push(offset),
+                None => return Ok(()),
+            }
+        }
+    }
+
+    /// Ajoute un document à la table, une valeur par colonne. La table est
+    /// laissée intacte si une de ses lignes ne peut pas être écrite.
+    pub fn insert(&mut self, table: &mut Table, values: &[Value]) -> Result<(), InsertionError> {
+        let state = table.state();
+        // les lignes oubliées par l'annulation d'une transaction sortent du
+        // répertoire avant que leur place ne soit réutilisée
+        self.directory.truncate(state.offset);
+        let values = values
+            .iter()
+            .map(|value| match value {
+                Value::Null => None,
+                value => Some(value.to_string()),
+            })
+            .collect::<Vec<_>>();
+        let rowid = self.directory.documents.len() as i64 + 1;
+        let mut terms = BTreeMap::<(String, usize), Vec<usize>>::new();
+        let mut lengths = vec![0; values.len()];
+        for (column, value) in values.iter().enumerate() {
+            for (position, term) in tokenize(value.as_deref().unwrap_or_default()) {
+                terms.entry((term, column)).or_default().push(position);
+                lengths[column] += 1;
+            }
+        }
+        let mut offsets = vec![];
+        let document = Document { rowid, lengths: lengths.clone(), values };
+        let mut inserted = table.insert(Entry::Document(document));
+        for ((term, column), positions) in terms {
+            if inserted.is_err() {
+                break;
+            }
+            offsets.push((term.clone(), table.state().offset));
+            inserted = table.insert(Entry::Posting(Posting { term, rowid, column, positions }));
+        }
+        if let Err(err) = inserted {
+            table.restore(state);
+            return Err(err);
+        }
+        self.directory.documents.push((state.offset, lengths));
+        for (term, offset) in offsets {
+            self.directory.terms.entry(term).or_default().push(offset);
+        }
+        Ok(())
+    }
+
+    /// Lignes de la table, suivies de leurs colonnes cachées. Avec une
+    /// recherche, seuls les documents trouvés sont gardés, les mieux
+    /// classés d'abord.
+    pub fn rows(&self, table: &Table, search: Option<Search>) -> Result<Vec<Vec<Value>>, ExecutionError> {
+        // le répertoire peut encore citer des lignes d'une transaction annulée
+        let end = table.state().offset;
+        let documents = &self.directory.documents[..self.directory.documents.partition_point(|(offset, _)| *offset < end)];
+        let document = |rowid: i64| match table.row::<Entry>(documents[rowid as usize - 1].0) {
+            Ok(Entry::Document(document)) => Ok(document),
+            Ok(Entry::Posting(_)) => Err(misplaced(POSTING)),
+            Err(err) => Err(ExecutionError::Select(err)),
+        };
+        let Some(search) = search else {
+            return (1..=documents.len() as i64)
+                .map(|rowid| Ok(document(rowid)?.row(Value::Null)))
+                .collect();
+        };
+        let phrases = query::parse(search.query).map_err(ExecutionError::Evaluation)?;
+        let columns = |column: usize| search.column.is_none_or(|searched| searched == column);
+        // occurrences de chaque phrase dans les documents où elle apparaît
+        let mut counts = BTreeMap::<i64, Vec<usize>>::new();
+        for (index, phrase) in phrases.iter().enumerate() {
+            for (rowid, count) in self.occurrences(table, end, phrase, columns)? {
+                counts.entry(rowid).or_insert_with(|| vec![0; phrases.len()])[index] = count;
+            }
+        }
+        // longueur des documents en termes, lue dans le répertoire
+        let length = |lengths: &Vec<usize>| {
+            lengths.iter().enumerate().filter(|(column, _)| columns(*column)).map(|(_, length)| length).sum::<usize>() as f64
+        };
+        let total = documents.len() as f64;
+        let average = (documents.iter().map(|(_, lengths)| length(lengths)).sum::<f64>() / total).max(1.0);
+        let idf = (0..phrases.len())
+            .map(|index| {
+                let found = counts.values().filter(|counts| counts[index] > 0).count() as f64;
+                // comme SQLite, un terme présent partout compte encore un peu
+                ((total - found + 0.5) / (found + 0.5)).ln().max(1e-6)
+            })
+            .collect::<Vec<_>>();
+        let mut found = counts
+            .iter()
+            .filter(|(_, counts)| counts.iter().all(|count| *count > 0))
+            .map(|(rowid, counts)| {
+                let length = length(&documents[*rowid as usize - 1].1);
+                let score = counts
+                    .iter()
+                    .zip(&idf)
+                    .map(|(count, idf)| {
+                        let count = *count as f64;
+                        idf * count * (K1 + 1.0) / (count + K1 * (1.0 - B + B * length / average))
+                    })
+                    .sum::<f64>();
+                (-score, *rowid)
+            })
+            .collect::<Vec<_>>();
+        // à score égal, dans l'ordre des documents
+        found.sort_by(|(left, _), (right, _)| left.total_cmp(right));
+        found
+            .into_iter()
+            .map(|(rank, rowid)| Ok(document(rowid)?.row(Value::Real(rank))))
+            .collect()
+    }
+
+    /// Nombre de fois où la phrase apparaît dans les colonnes retenues de
+    /// chaque document, en ne lisant que les listes de ses termes
+    fn occurrences(
+        &self,
+        table: &Table,
+        end: usize,
+        phrase: &Phrase,
+        columns: impl Fn(usize) -> bool,
+    ) -> Result<BTreeMap<i64, usize>, ExecutionError> {
+        let mut terms = BTreeSet::new();
+        for (index, term) in phrase.terms.iter().enumerate() {
+            match phrase.prefix && index + 1 == phrase.terms.len() {
+                true => terms.extend(
+                    self.directory
+                        .terms
+                        .range(term.clone()..)
+                        .take_while(|(found, _)| found.starts_with(term.as_str())),
+                ),
+                false => terms.extend(self.directory.terms.get_key_value(term)),
+            }
+        }
+        // positions acceptées pour chaque terme de la phrase, par document et
+        // par colonne
+        let mut positions = BTreeMap::<(i64, usize), Vec<HashSet<usize>>>::new();
+        for (term, offsets) in terms {
+            for offset in offsets.iter().take_while(|offset| **offset < end) {
+                let posting = match table.row::<Entry>(*offset) {
+                    Ok(Entry::Posting(posting)) => posting,
+                    Ok(Entry::Document(_)) => return Err(misplaced(DOCUMENT)),
+                    Err(err) => return Err(ExecutionError::Select(err)),
+                };
+                if !columns(posting.column) {
+                    continue;
+                }
+                let sets = positions
+                    .entry((posting.rowid, posting.column))
+                    .or_insert_with(|| vec![HashSet::new(); phrase.terms.len()]);
+                for (index, set) in sets.iter_mut().enumerate() {
+                    if phrase.accepts(index, term) {
+                        set.extend(&posting.positions);
+                    }
+                }
+            }
+        }
+        let mut counts = BTreeMap::new();
+        for ((rowid, _), sets) in positions {
+            let count = sets[0]
+                .iter()
+                .filter(|start| sets.iter().enumerate().all(|(index, set)| set.contains(&(*start + index))))
+                .count();
+            if count > 0 {
+                *counts.entry(rowid).or_default() += count;
+            }
+        }
+        Ok(counts)
+    }
+
+    /// Recopie une table écrite au format 4, où chaque document portait son
+    /// propre index, dans une table au format courant
+    pub fn migrate(&mut self, table: &Table) -> Result<Table, StorageError> {
+        self.directory = Directory::default();
+        let mut migrated = Table::new(table.root_page());
+        for document in table.cursor::<DocumentV4>() {
+            let DocumentV4(values) = document.map_err(|err| StorageError::Corrupted(err.to_string()))?;
+            self.insert(&mut migrated, &values)
+                .map_err(|err| StorageError::Corrupted(err.to_string()))?;
+        }
+        Ok(migrated)
+    }
+}
+
+/// Le répertoire désigne une ligne de l'autre nature, `kind` étant celle lue
+fn misplaced(kind: i64) -> ExecutionError {
+    ExecutionError::Select(SelectError::Deserialization(DeserializationError::UnexpectedType(kind as u64)))
+}
+
+/// Position dans les pages de la table des lignes utiles aux recherches,
+/// dans l'ordre où elles ont été écrites
+#[derive(Debug, PartialEq, Clone, Default)]
+struct Directory {
+    /// chaque document, par numéro, et son nombre de termes par colonne
+    documents: Vec<(usize, Vec<usize>)>,
+    /// lignes de la liste de chaque terme
+    terms: BTreeMap<String, Vec<usize>>,
+}
+
+impl Directory {
+    /// Oublie les lignes écrites à partir de `end`
+    fn truncate(&mut self, end: usize) {
+        let documents = self.documents.partition_point(|(offset, _)| *offset < end);
+        self.documents.truncate(documents);
+        self.terms.retain(|_, offsets| {
+            offsets.truncate(offsets.partition_point(|offset| *offset < end));
+            !offsets.is_empty()
+        });
+    }
+}
+
+/// Ligne d'une table fts
+#[derive(Debug, PartialEq)]
+enum Entry {
+    Document(Document),
+    Posting(Posting),
+}
+
+/// Document d'une table fts : son numéro, ses valeurs et leur nombre de
+/// termes
+#[derive(Debug, PartialEq)]
+struct Document {
+    rowid: i64,
+    lengths: Vec<usize>,
+    values: Vec<Option<String>>,
+}
+
+impl Document {
+    fn row(&self, rank: Value) -> Vec<Value> {
+        let mut row = self
+            .values
+            .iter()
+            .map(|value| value.clone().map_or(Value::Null, Value::Text))
+            .collect::<Vec<_>>();
+        row.extend([Value::Integer(self.rowid), rank]);
+        row
+    }
+}
+
+/// Ligne de la liste d'un terme : ses positions dans une colonne d'un document
+#[derive(Debug, PartialEq)]
+struct Posting {
+    term: String,
+    rowid: i64,
+    column: usize,
+    positions: Vec<usize>,
+}
+
+/// Un document est écrit `DOCUMENT, numéro, valeurs..., longueurs...`, une
+/// ligne de liste `POSTING, terme, numéro, colonne, positions...`
+impl Serializable for Entry {
+    fn serialize(&self, cursor: &mut std::io::Cursor<&mut [u8]>) -> Result<(), SerializationError> {
+        match self {
+            Entry::Document(document) => {
+                let lengths = document.lengths.iter().map(|length| *length as i64).collect::<Vec<_>>();
+                let mut columns: Vec<&dyn Column> = vec![&DOCUMENT, &document.rowid];
+                columns.extend(document.values.iter().map(|value| value as &dyn Column));
+                columns.extend(lengths.iter().map(|length| length as &dyn Column));
+                write_record(cursor, &columns)
+            }
+            Entry::Posting(posting) => {
+                let column = posting.column as i64;
+                let positions = posting.positions.iter().map(|position| *position as i64).collect::<Vec<_>>();
+                let mut columns: Vec<&dyn Column> = vec![&POSTING, &posting.term, &posting.rowid, &column];
+                columns.extend(positions.iter().map(|position| position as &dyn Column));
+                write_record(cursor, &columns)
+            }
+        }
+    }
+}
+
+impl Deserializable for Entry {
+    fn deserialize(cursor: &mut Reader<'_>) -> Result<Self, DeserializationError> {
+        let mut record = RecordReader::new(cursor)?;
+        let integer = |record: &mut RecordReader, cursor: &mut Reader<'_>| {
+            usize::try_from(record.column::<i64>(cursor)?).map_err(|_| DeserializationError::UnableToDeserializeInteger)
+        };
+        let entry = match record.column::<i64>(cursor)? {
+            DOCUMENT => {
+                let rowid = record.column(cursor)?;
+                let count = record.len().saturating_sub(2) / 2;
+                let values = (0..count).map(|_| record.column(cursor)).collect::<Result<_, _>>()?;
+                let lengths = (0..count).map(|_| integer(&mut record, cursor)).collect::<Result<_, _>>()?;
+                Entry::Document(Document { rowid, lengths, values })
+            }
+            POSTING => {
+                let term = record.column(cursor)?;
+                let rowid = record.column(cursor)?;
+                let column = integer(&mut record, cursor)?;
+                let positions = (4..record.len()).map(|_| integer(&mut record, cursor)).collect::<Result<_, _>>()?;
+                Entry::Posting(Posting { term, rowid, column, positions })
+            }
+            other => return Err(DeserializationError::UnexpectedType(other as u64)),
+        };
+        record.finish(cursor)?;
+        Ok(entry)
+    }
+}
+
+/// Document au format 4 : son numéro, son index puis ses valeurs
+struct DocumentV4(Vec<Value>);
+
+impl Deserializable for DocumentV4 {
+    fn deserialize(cursor: &mut Reader<'_>) -> Result<Self, DeserializationError> {
+        let mut record = RecordReader::new(cursor)?;
+        record.column::<i64>(cursor)?;
+        record.column::<Vec<u8>>(cursor)?;
+        let values = (2..record.len())
+            .map(|_| record.column::<Option<String>>(cursor).map(|value| value.map_or(Value::Null, Value::Text)))
+            .collect::<Result<_, _>>()?;
+        record.finish(cursor)?;
+        Ok(Self(values))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::errors::EvaluationError;
+
+    fn fts() -> Fts {
+        Fts::new(&["title".to_string(), "body".to_string()]).unwrap()
+    }
+
+    #[test]
+    fn test_schema() {
+        assert_eq!(fts().schema(), "fts(title, body)");
+        assert_eq!(Fts::from_schema("fts(title, body)"), Some(fts()));
+        assert_eq!(Fts::from_schema("other(a)"), None);
+        assert!(Fts::new(&[]).is_err());
+        assert!(Fts::new(&["a".to_string(), "A".to_string()]).is_err());
+        assert!(Fts::new(&["rank".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_insert() {
+        let mut fts = fts();
+        let mut table = Table::new(1);
+        fts.insert(&mut table, &[Value::Text("Hello hello".to_string()), Value::Null])
+            .expect("insert failed");
+        let entries = table.cursor::<Entry>().collect::<Result<Vec<_>, _>>().expect("select failed");
+        assert_eq!(
+            entries,
+            [
+                Entry::Document(Document {
+                    rowid: 1,
+                    lengths: vec![2, 0],
+                    values: vec![Some("Hello hello".to_string()), None],
+                }),
+                Entry::Posting(Posting {
+                    term: "hello".to_string(),
+                    rowid: 1,
+                    column: 0,
+                    positions: vec![0, 1],
+                }),
+            ]
+        );
+        // le répertoire se refait à l'identique à partir des lignes
+        let mut loaded = self::fts();
+        loaded.load(&table).expect("load failed");
+        assert_eq!(loaded, fts);
+        // les lignes d'une insertion annulée sont oubliées, et leur place reprise
+        let state = table.state();
+        fts.insert(&mut table, &[Value::Text("a".to_string()), Value::Null]).expect("insert failed");
+        table.restore(state);
+        assert_eq!(fts.rows(&table, None).map(|rows| rows.len()), Ok(1));
+        fts.insert(&mut table, &[Value::Text("b".to_string()), Value::Null]).expect("insert failed");
+        assert_eq!(fts.directory.terms.keys().collect::<Vec<_>>(), ["b", "hello"]);
+        let rowids = fts.rows(&table, None).map(|rows| rows.iter().map(|row| row[2].clone()).collect::<Vec<_>>());
+        assert_eq!(rowids, Ok(vec![Value::Integer(1), Value::Integer(2)]));
+        // une table pleine refuse le document entier
+        let long = Value::Text("x ".repeat(600_000));
+        assert!(fts.insert(&mut table, &[long.clone(), long]).is_err());
+        assert_eq!(fts.rows(&table, None).map(|rows| rows.len()), Ok(2));
+    }
+
+    #[test]
+    fn test_migrate() {
+        // document au format 4 : numéro, index, puis valeurs
+        struct Written(i64, Option<String>);
+        impl Serializable for Written {
+            fn serialize(&self, cursor: &mut std::io::Cursor<&mut [u8]>) -> Result<(), SerializationError> {
+                write_record(cursor, &[&self.0, &vec![1_u8, 2, 3], &self.1, &None::<String>])
+            }
+        }
+        let mut table = Table::new(1);
+        table.insert(Written(1, Some("Rust".to_string()))).expect("insert failed");
+        table.insert(Written(2, None)).expect("insert failed");
+        let mut fts = fts();
+        let migrated = fts.migrate(&table).expect("migration failed");
+        assert_eq!(
+            fts.rows(&migrated, Some(Search { query: "rust", column: None })).map(|rows| rows[0][..3].to_vec()),
+            Ok(vec![Value::Text("Rust".to_string()), Value::Null, Value::Integer(1)])
+        );
+        assert_eq!(fts.rows(&migrated, None).map(|rows| rows.len()), Ok(2));
+    }
+
+    #[test]
+    fn test_search() {
+        let mut fts = fts();
+        let mut table = Table::new(1);
+        let documents = [
+            ("Rust", "Rust is a systems programming language"),
+            ("Databases", "SQLite is a small database engine written in C"),
+            ("Search", "Full text search in a database, written in Rust, for Rust users"),
+        ];
+        for (title, body) in documents {
+            let values = [Value::Text(title.to_string()), Value::Text(body.to_string())];
+            fts.insert(&mut table, &values).expect("insert failed");
+        }
+        let rowids = |query: &str, column: Option<usize>| {
+            fts.rows(&table, Some(Search { query, column }))
+                .map(|rows| rows.iter().map(|row| row[2].clone()).collect::<Vec<_>>())
+        };
+        // à nombre d'occurrences égal, le document le plus court passe en premier
+        assert_eq!(rowids("rust", None), Ok(vec![Value::Integer(1), Value::Integer(3)]));
+        assert_eq!(rowids("rust", Some(0)), Ok(vec![Value::Integer(1)]));
+        assert_eq!(rowids("\"written in rust\"", None), Ok(vec![Value::Integer(3)]));
+        assert_eq!(rowids("\"in rust written\"", None), Ok(vec![]));
+        assert_eq!(rowids("data*", None), Ok(vec![Value::Integer(2), Value::Integer(3)]));
+        assert_eq!(rowids("database written", None), Ok(vec![Value::Integer(2), Value::Integer(3)]));
+        assert_eq!(fts.rows(&table, None).map(|rows| rows.len()), Ok(3));
+        assert!(matches!(
+            rowids("\"", None),
+            Err(ExecutionError::Evaluation(EvaluationError::Function(_)))
+        ));
+    }
+}
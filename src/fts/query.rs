@@ -0,0 +1,89 @@
+//! Requêtes du `MATCH` d'une table fts : des termes et des phrases entre
+//! guillemets, qui doivent tous se trouver dans le document. Un `*` final
+//! cherche les termes qui commencent ainsi.
+
+use crate::errors::EvaluationError;
+use crate::fts::tokenizer::tokenize;
+
+/// Suite de termes qui doivent se suivre dans une même colonne
+#[derive(Debug, PartialEq, Clone)]
+pub struct Phrase {
+    pub terms: Vec<String>,
+    /// le dernier terme est un préfixe
+    pub prefix: bool,
+}
+
+impl Phrase {
+    /// Le terme de l'index correspond au terme de la phrase à cette place
+    pub fn accepts(&self, index: usize, term: &str) -> bool {
+        match self.prefix && index + 1 == self.terms.len() {
+            true => term.starts_with(&self.terms[index]),
+            false => term == self.terms[index],
+        }
+    }
+}
+
+/// Analyse une requête, les phrases sont toutes exigées. `AND` entre deux
+/// phrases est accepté et ne change rien.
+pub fn parse(query: &str) -> Result<Vec<Phrase>, EvaluationError> {
+    let error = || EvaluationError::Function(format!("fts: syntax error near {query:?}"));
+    let mut phrases = vec![];
+    let mut rest = query.trim_start();
+    while !rest.is_empty() {
+        let (text, after) = match rest.strip_prefix('"') {
+            Some(quoted) => {
+                let end = quoted.find('"').ok_or_else(error)?;
+                (&quoted[..end], &quoted[end + 1..])
+            }
+            None => {
+                let end = rest.find(|c: char| c.is_whitespace() || c == '"').unwrap_or(rest.len());
+                (&rest[..end], &rest[end..])
+            }
+        };
+        let (text, prefix, after) = match (text.strip_suffix('*'), after.strip_prefix('*')) {
+            (Some(text), _) => (text, true, after),
+            (None, Some(after)) => (text, true, after),
+            (None, None) => (text, false, after),
+        };
+        rest = after.trim_start();
+        if text == "AND" && !prefix {
+            continue;
+        }
+        let terms = tokenize(text).map(|(_, term)| term).collect::<Vec<_>>();
+        if terms.is_empty() {
+            return Err(error());
+        }
+        phrases.push(Phrase { terms, prefix });
+    }
+    match phrases.is_empty() {
+        true => Err(error()),
+        false => Ok(phrases),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let phrase = |terms: &[&str], prefix: bool| Phrase {
+            terms: terms.iter().map(|term| term.to_string()).collect(),
+            prefix,
+        };
+        assert_eq!(
+            parse(r#"Rust AND "hello, world" data* "base de"*"#),
+            Ok(vec![
+                phrase(&["rust"], false),
+                phrase(&["hello", "world"], false),
+                phrase(&["data"], true),
+                phrase(&["base", "de"], true),
+            ])
+        );
+        // un mot coupé par la ponctuation est une phrase
+        assert_eq!(parse("e-mail"), Ok(vec![phrase(&["e", "mail"], false)]));
+        assert!(parse("").is_err());
+        assert!(parse("\"open").is_err());
+        assert!(parse("rust !").is_err());
+    }
+}
@@ -0,0 +1,30 @@
+/// Découpe un texte en termes : les suites de lettres et de chiffres, en
+/// minuscules. Chaque terme est accompagné de sa position dans le texte,
+/// comptée en termes.
+pub fn tokenize(text: &str) -> impl Iterator<Item = (usize, String)> + '_ {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .enumerate()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tokenize() {
+        let terms = tokenize("L'été, 2024 : Crème-BRÛLÉE!").collect::<Vec<_>>();
+        assert_eq!(
+            terms,
+            [
+                (0, "l".to_string()),
+                (1, "été".to_string()),
+                (2, "2024".to_string()),
+                (3, "crème".to_string()),
+                (4, "brûlée".to_string()),
+            ]
+        );
+        assert_eq!(tokenize(" ,; ").count(), 0);
+    }
+}
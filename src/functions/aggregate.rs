@@ -0,0 +1,228 @@
+//! Fonctions d'agrégat de base : `count`, `sum`, `total`, `avg`, `min`,
+//! `max` et `group_concat`. Comme dans SQLite, les valeurs NULL sont
+//! ignorées.
+
+use crate::data::{Affinity, Value};
+use crate::errors::EvaluationError;
+use crate::functions::{Accumulator, Function, Functions};
+use std::cmp::Ordering;
+use std::sync::Arc;
+
+pub fn register(functions: &mut Functions) {
+    let mut aggregate = |name: &str, arity: i32, factory: fn() -> Box<dyn Accumulator>| {
+        functions.register(name, arity, Function::Aggregate(Arc::new(factory)));
+    };
+    // count(*) compte les lignes, count(x) les valeurs non NULL
+    aggregate("count", 0, || Box::new(Count(0)));
+    aggregate("count", 1, || Box::new(Count(0)));
+    aggregate("sum", 1, || Box::new(Sum::default()));
+    aggregate("total", 1, || Box::new(Total::default()));
+    aggregate("avg", 1, || Box::new(Total::average()));
+    aggregate("min", 1, || Box::new(Extremum::new(Ordering::Less)));
+    aggregate("max", 1, || Box::new(Extremum::new(Ordering::Greater)));
+    aggregate("group_concat", 1, || Box::new(GroupConcat::default()));
+    aggregate("group_concat", 2, || Box::new(GroupConcat::default()));
+}
+
+struct Count(i64);
+
+impl Accumulator for Count {
+    fn step(&mut self, arguments: &[Value]) -> Result<(), EvaluationError> {
+        if arguments.first() != Some(&Value::Null) {
+            self.0 += 1;
+        }
+        Ok(())
+    }
+
+    fn finalize(self: Box<Self>) -> Result<Value, EvaluationError> {
+        Ok(Value::Integer(self.0))
+    }
+}
+
+/// Somme entière tant qu'aucun flottant n'est vu, NULL sans valeur
+#[derive(Default)]
+struct Sum {
+    sum: Option<Value>,
+}
+
+impl Accumulator for Sum {
+    fn step(&mut self, arguments: &[Value]) -> Result<(), EvaluationError> {
+        let value = match &arguments[0] {
+            Value::Null => return Ok(()),
+            value => value.clone().cast(Affinity::Numeric),
+        };
+        self.sum = Some(match (self.sum.take(), value) {
+            (None, value) => value,
+            (Some(Value::Integer(sum)), Value::Integer(value)) => {
+                Value::Integer(sum.checked_add(value).ok_or(EvaluationError::IntegerOverflow)?)
+            }
+            (Some(sum), value) => Value::Real(real(&sum) + real(&value)),
+        });
+        Ok(())
+    }
+
+    fn finalize(self: Box<Self>) -> Result<Value, EvaluationError> {
+        Ok(self.sum.unwrap_or(Value::Null))
+    }
+}
+
+/// Somme en flottant, 0.0 sans valeur, ou moyenne, NULL sans valeur
+#[derive(Default)]
+struct Total {
+    sum: f64,
+    count: usize,
+    average: bool,
+}
+
+impl Total {
+    fn average() -> Self {
+        Self {
+            average: true,
+            ..Self::default()
+        }
+    }
+}
+
+impl Accumulator for Total {
+    fn step(&mut self, arguments: &[Value]) -> Result<(), EvaluationError> {
+        if arguments[0] != Value::Null {
+            self.sum += real(&arguments[0].clone().cast(Affinity::Numeric));
+            self.count += 1;
+        }
+        Ok(())
+    }
+
+    fn finalize(self: Box<Self>) -> Result<Value, EvaluationError> {
+        Ok(match (self.average, self.count) {
+            (false, _) => Value::Real(self.sum),
+            (true, 0) => Value::Null,
+            (true, count) => Value::Real(self.sum / count as f64),
+        })
+    }
+}
+
+fn real(value: &Value) -> f64 {
+    match value {
+        Value::Integer(value) => *value as f64,
+        Value::Real(value) => *value,
+        _ => 0.0,
+    }
+}
+
+/// Plus petite ou plus grande valeur, selon l'ordre de `Value::compare`
+struct Extremum {
+    kept: Ordering,
+    value: Value,
+}
+
+impl Extremum {
+    fn new(kept: Ordering) -> Self {
+        Self {
+            kept,
+            value: Value::Null,
+        }
+    }
+}
+
+impl Accumulator for Extremum {
+    fn step(&mut self, arguments: &[Value]) -> Result<(), EvaluationError> {
+        let value = &arguments[0];
+        if *value != Value::Null
+            && (self.value == Value::Null || value.compare(&self.value) == Some(self.kept))
+        {
+            self.value = value.clone();
+        }
+        Ok(())
+    }
+
+    fn finalize(self: Box<Self>) -> Result<Value, EvaluationError> {
+        Ok(self.value)
+    }
+}
+
+/// Valeurs mises bout à bout, séparées par une virgule par défaut
+#[derive(Default)]
+struct GroupConcat {
+    text: Option<String>,
+}
+
+impl Accumulator for GroupConcat {
+    fn step(&mut self, arguments: &[Value]) -> Result<(), EvaluationError> {
+        if arguments[0] == Value::Null {
+            return Ok(());
+        }
+        let separator = match arguments.get(1) {
+            Some(Value::Null) => String::new(),
+            Some(separator) => separator.to_string(),
+            None => ",".to_string(),
+        };
+        match &mut self.text {
+            Some(text) => {
+                text.push_str(&separator);
+                text.push_str(&arguments[0].to_string());
+            }
+            None => self.text = Some(arguments[0].to_string()),
+        }
+        Ok(())
+    }
+
+    fn finalize(self: Box<Self>) -> Result<Value, EvaluationError> {
+        Ok(self.text.map_or(Value::Null, Value::Text))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn aggregate(name: &str, rows: &[&[Value]]) -> Result<Value, EvaluationError> {
+        let functions = Functions::new();
+        let count = rows.first().map_or(1, |row| row.len());
+        let Function::Aggregate(factory) = functions.find(name, count)? else {
+            panic!("{name} is not an aggregate");
+        };
+        let mut accumulator = factory();
+        for row in rows {
+            accumulator.step(row)?;
+        }
+        accumulator.finalize()
+    }
+
+    #[test]
+    fn test_aggregates() {
+        let values: &[&[Value]] = &[
+            &[Value::Integer(3)],
+            &[Value::Null],
+            &[Value::Text("4".to_string())],
+            &[Value::Integer(-1)],
+        ];
+        assert_eq!(aggregate("count", &[&[], &[]]), Ok(Value::Integer(2)));
+        assert_eq!(aggregate("count", values), Ok(Value::Integer(3)));
+        assert_eq!(aggregate("sum", values), Ok(Value::Integer(6)));
+        assert_eq!(aggregate("total", values), Ok(Value::Real(6.0)));
+        assert_eq!(aggregate("avg", values), Ok(Value::Real(2.0)));
+        assert_eq!(aggregate("min", values), Ok(Value::Integer(-1)));
+        // un texte est plus grand que tout nombre
+        assert_eq!(aggregate("max", values), Ok(Value::Text("4".to_string())));
+        assert_eq!(aggregate("group_concat", values), Ok(Value::Text("3,4,-1".to_string())));
+        assert_eq!(
+            aggregate(
+                "group_concat",
+                &[&[Value::Integer(1), Value::Text("; ".to_string())], &[Value::Integer(2), Value::Text("; ".to_string())]]
+            ),
+            Ok(Value::Text("1; 2".to_string()))
+        );
+        // sans valeur
+        assert_eq!(aggregate("sum", &[&[Value::Null]]), Ok(Value::Null));
+        assert_eq!(aggregate("total", &[&[Value::Null]]), Ok(Value::Real(0.0)));
+        assert_eq!(aggregate("avg", &[&[Value::Null]]), Ok(Value::Null));
+        assert_eq!(
+            aggregate("sum", &[&[Value::Integer(i64::MAX)], &[Value::Integer(1)]]),
+            Err(EvaluationError::IntegerOverflow)
+        );
+        assert_eq!(
+            aggregate("sum", &[&[Value::Integer(1)], &[Value::Real(0.5)]]),
+            Ok(Value::Real(1.5))
+        );
+    }
+}
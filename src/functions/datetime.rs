@@ -0,0 +1,884 @@
+//! Fonctions de date et d'heure de SQLite : `date`, `time`, `datetime`,
+//! `julianday`, `unixepoch` et `strftime`.
+//!
+//! Chaque fonction lit un instant, éventuellement transformé par des
+//! modificateurs (`'+1 day'`, `'start of month'`, `'localtime'`...), puis
+//! l'écrit. Les instants sont comptés en millisecondes depuis le 1er janvier
+//! 1970 UTC. Une valeur ou un modificateur invalide donne NULL.
+
+use crate::data::{Date, Time, Timestamp, Value};
+use crate::functions::Functions;
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const MILLISECONDS_PER_DAY: i64 = 24 * 60 * 60 * 1000;
+
+/// Jour julien du 1er janvier 1970 à minuit
+const UNIX_EPOCH_JULIAN_DAY: f64 = 2_440_587.5;
+
+/// Instants acceptés, comme dans SQLite : du jour julien 0 (24 novembre
+/// -4713 à midi) au 31 décembre 9999 à 23:59:59.999
+const MIN_MILLISECONDS: i64 = -210_866_760_000_000;
+const MAX_MILLISECONDS: i64 = 253_402_300_799_999;
+
+/// Écriture du résultat d'une fonction
+#[derive(Clone, Copy)]
+enum Output {
+    Date,
+    Time,
+    DateTime,
+    JulianDay,
+    UnixEpoch,
+}
+
+pub fn register(functions: &mut Functions) {
+    let outputs = [
+        ("date", Output::Date),
+        ("time", Output::Time),
+        ("datetime", Output::DateTime),
+        ("julianday", Output::JulianDay),
+        ("unixepoch", Output::UnixEpoch),
+    ];
+    // 'now' ne change pas au cours d'une requête : un appel sur des
+    // constantes peut n'être calculé qu'une fois
+    for (name, output) in outputs {
+        functions.register_scalar(name, -1, true, move |arguments| {
+            Ok(write(output, arguments, local()))
+        });
+    }
+    functions.register_scalar("strftime", -1, true, |arguments| {
+        Ok(strftime(arguments, local()))
+    });
+}
+
+/// Fuseau horaire du système, lu une seule fois
+fn local() -> &'static TimeZone {
+    static LOCAL: OnceLock<TimeZone> = OnceLock::new();
+    LOCAL.get_or_init(TimeZone::local)
+}
+
+fn write(output: Output, arguments: &[Value], zone: &TimeZone) -> Value {
+    let Some(moment) = Moment::read(arguments, zone) else {
+        return Value::Null;
+    };
+    let format = match output {
+        Output::Date => "%F",
+        Output::Time if moment.subsec => "%H:%M:%f",
+        Output::Time => "%T",
+        Output::DateTime if moment.subsec => "%F %H:%M:%f",
+        Output::DateTime => "%F %T",
+        Output::JulianDay => return Value::Real(moment.julian_day()),
+        Output::UnixEpoch if moment.subsec => return Value::Real(moment.milliseconds as f64 / 1000.0),
+        Output::UnixEpoch => return Value::Integer(moment.milliseconds.div_euclid(1000)),
+    };
+    moment.format(format).map_or(Value::Null, Value::Text)
+}
+
+/// `strftime(format, instant, modificateurs...)`
+fn strftime(arguments: &[Value], zone: &TimeZone) -> Value {
+    let Some(Value::Text(format)) = arguments.first() else {
+        return Value::Null;
+    };
+    Moment::read(&arguments[1..], zone)
+        .and_then(|moment| moment.format(format))
+        .map_or(Value::Null, Value::Text)
+}
+
+/// Instant lu dans les arguments d'une fonction
+struct Moment {
+    milliseconds: i64,
+    /// écrire les millisecondes, avec le modificateur `subsec`
+    subsec: bool,
+}
+
+impl Moment {
+    /// Instant donné par le premier argument, 'now' par défaut, transformé
+    /// par les modificateurs qui suivent
+    fn read(arguments: &[Value], zone: &TimeZone) -> Option<Moment> {
+        let now = Value::Text("now".to_string());
+        // un nombre est un jour julien, sauf modificateur 'unixepoch' ou 'auto'
+        let mut number = None;
+        let milliseconds = match arguments.first().unwrap_or(&now) {
+            Value::Integer(value) => {
+                number = Some(*value as f64);
+                from_julian_day(*value as f64)?
+            }
+            Value::Real(value) => {
+                number = Some(*value);
+                from_julian_day(*value)?
+            }
+            Value::Timestamp(Timestamp(seconds)) => seconds.checked_mul(1000)?,
+            Value::Date(Date(days)) => days.checked_mul(MILLISECONDS_PER_DAY)?,
+            Value::Time(Time(seconds)) => seconds.checked_mul(1000)?.checked_add(default_day())?,
+            Value::Text(text) if text.trim().eq_ignore_ascii_case("now") => now_milliseconds(),
+            Value::Text(text) => match parse(text.trim()) {
+                Some(milliseconds) => milliseconds,
+                None => {
+                    let value = text.trim().parse::<f64>().ok()?;
+                    number = Some(value);
+                    from_julian_day(value)?
+                }
+            },
+            _ => return None,
+        };
+        let mut moment = Moment {
+            milliseconds,
+            subsec: false,
+        };
+        for (position, modifier) in arguments.iter().skip(1).enumerate() {
+            let Value::Text(modifier) = modifier else {
+                return None;
+            };
+            // ces modificateurs disent comment lire le nombre, ils doivent
+            // donc le suivre immédiatement
+            let number = number.filter(|_| position == 0);
+            moment.modify(&modifier.trim().to_ascii_lowercase(), number, zone)?;
+        }
+        // hors des instants acceptés, le résultat vaut NULL
+        (MIN_MILLISECONDS..=MAX_MILLISECONDS).contains(&moment.milliseconds).then_some(moment)
+    }
+
+    fn modify(&mut self, modifier: &str, number: Option<f64>, zone: &TimeZone) -> Option<()> {
+        let (date, time) = self.split();
+        match modifier {
+            "unixepoch" => self.milliseconds = rounded(number? * 1000.0)?,
+            "julianday" => {
+                number?;
+            }
+            "auto" => {
+                let number = number?;
+                if !(0.0..5_373_484.5).contains(&number) {
+                    self.milliseconds = rounded(number * 1000.0)?;
+                }
+            }
+            "subsec" | "subsecond" => self.subsec = true,
+            "localtime" => {
+                let offset = zone.offset(self.milliseconds.div_euclid(1000));
+                self.milliseconds = self.milliseconds.checked_add(offset * 1000)?;
+            }
+            "utc" => {
+                // le décalage est celui de l'instant UTC, qu'on approche à
+                // partir de l'heure locale
+                let local = self.milliseconds.div_euclid(1000);
+                let guess = local - zone.offset(local);
+                self.milliseconds = self.milliseconds.checked_sub(zone.offset(guess) * 1000)?;
+            }
+            "start of day" => self.milliseconds = date.0 * MILLISECONDS_PER_DAY,
+            "start of month" => {
+                let (year, month, _) = date.ymd();
+                self.milliseconds = Date::from_ymd(year, month, 1)?.0 * MILLISECONDS_PER_DAY;
+            }
+            "start of year" => {
+                let (year, _, _) = date.ymd();
+                self.milliseconds = Date::from_ymd(year, 1, 1)?.0 * MILLISECONDS_PER_DAY;
+            }
+            _ => {
+                if let Some(weekday) = modifier.strip_prefix("weekday ") {
+                    // prochain jour de la semaine donné, 0 pour dimanche
+                    let weekday = weekday.trim().parse::<i64>().ok().filter(|day| (0..7).contains(day))?;
+                    let days = (weekday - date.weekday()).rem_euclid(7);
+                    self.milliseconds = self.milliseconds.checked_add(days * MILLISECONDS_PER_DAY)?;
+                } else if let Some((amount, unit)) = modifier.split_once(char::is_whitespace) {
+                    self.add(amount.parse().ok()?, unit.trim(), date, time)?;
+                } else {
+                    // ±HH:MM[:SS[.SSS]]
+                    let sign = match modifier.chars().next()? {
+                        '+' => 1,
+                        '-' => -1,
+                        _ => return None,
+                    };
+                    self.milliseconds = self.milliseconds.checked_add(sign * parse_time(&modifier[1..])?)?;
+                }
+            }
+        }
+        Some(())
+    }
+
+    /// `+N days`, `-N months`... : les mois et les années se comptent dans le
+    /// calendrier, un jour qui n'existe pas déborde sur le mois suivant.
+    /// `None` si le résultat sort des instants acceptés.
+    fn add(&mut self, amount: f64, unit: &str, date: Date, time: i64) -> Option<()> {
+        let unit = unit.strip_suffix('s').unwrap_or(unit);
+        let milliseconds = match unit {
+            "day" => MILLISECONDS_PER_DAY as f64,
+            "hour" => 3_600_000.0,
+            "minute" => 60_000.0,
+            "second" => 1000.0,
+            "month" | "year" => {
+                let (year, month, day) = date.ymd();
+                let whole = rounded(amount.trunc())?;
+                let months = match unit {
+                    "month" => (year * 12 + month as i64 - 1).checked_add(whole)?,
+                    _ => year.checked_add(whole)?.checked_mul(12)? + month as i64 - 1,
+                };
+                // au-delà, l'instant est de toute façon refusé
+                if !(-4714 * 12..=10_000 * 12).contains(&months) {
+                    return None;
+                }
+                let first = Date::from_ymd(months.div_euclid(12), months.rem_euclid(12) as u32 + 1, 1)?;
+                self.milliseconds = (first.0 + day as i64 - 1) * MILLISECONDS_PER_DAY + time;
+                // une fraction compte des mois de 30 jours, des années de 365
+                let days = if unit == "month" { 30.0 } else { 365.0 };
+                let fraction = rounded(amount.fract() * days * MILLISECONDS_PER_DAY as f64)?;
+                self.milliseconds = self.milliseconds.checked_add(fraction)?;
+                return Some(());
+            }
+            _ => return None,
+        };
+        self.milliseconds = self.milliseconds.checked_add(rounded(amount * milliseconds)?)?;
+        Some(())
+    }
+
+    /// Jour et millisecondes depuis minuit
+    fn split(&self) -> (Date, i64) {
+        (
+            Date(self.milliseconds.div_euclid(MILLISECONDS_PER_DAY)),
+            self.milliseconds.rem_euclid(MILLISECONDS_PER_DAY),
+        )
+    }
+
+    fn julian_day(&self) -> f64 {
+        self.milliseconds as f64 / MILLISECONDS_PER_DAY as f64 + UNIX_EPOCH_JULIAN_DAY
+    }
+
+    /// Écrit l'instant selon un format de `strftime`, `None` hors des années
+    /// 0000 à 9999 ou pour une conversion inconnue
+    fn format(&self, format: &str) -> Option<String> {
+        let (date, time) = self.split();
+        let (year, month, day) = date.ymd();
+        if !(0..=9999).contains(&year) {
+            return None;
+        }
+        let (hour, minute, second) = Time(time / 1000).hms();
+        let milliseconds = time % 1000;
+        let day_of_year = date.0 - Date::from_ymd(year, 1, 1)?.0;
+        let weekday = date.weekday();
+        let twelve = |hour: u32| match hour % 12 {
+            0 => 12,
+            hour => hour,
+        };
+        let mut output = String::new();
+        let mut chars = format.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                output.push(c);
+                continue;
+            }
+            let conversion = chars.next()?;
+            let converted = match conversion {
+                'd' => format!("{day:02}"),
+                'e' => format!("{day:2}"),
+                'f' => format!("{second:02}.{milliseconds:03}"),
+                'F' => format!("{year:04}-{month:02}-{day:02}"),
+                'H' => format!("{hour:02}"),
+                'I' => format!("{:02}", twelve(hour)),
+                'k' => format!("{hour:2}"),
+                'l' => format!("{:2}", twelve(hour)),
+                'j' => format!("{:03}", day_of_year + 1),
+                'J' => format!("{}", self.julian_day()),
+                'm' => format!("{month:02}"),
+                'M' => format!("{minute:02}"),
+                'p' => if hour < 12 { "AM" } else { "PM" }.to_string(),
+                'P' => if hour < 12 { "am" } else { "pm" }.to_string(),
+                'R' => format!("{hour:02}:{minute:02}"),
+                's' => format!("{}", self.milliseconds.div_euclid(1000)),
+                'S' => format!("{second:02}"),
+                'T' => format!("{hour:02}:{minute:02}:{second:02}"),
+                'u' => format!("{}", if weekday == 0 { 7 } else { weekday }),
+                'w' => format!("{weekday}"),
+                // semaines commençant le dimanche ou le lundi, la semaine 00
+                // précédant le premier de ces jours
+                'U' => format!("{:02}", (day_of_year + 7 - weekday) / 7),
+                'W' => format!("{:02}", (day_of_year + 7 - (weekday + 6) % 7) / 7),
+                'G' | 'g' | 'V' => {
+                    let (iso_year, week) = date.iso_week();
+                    match conversion {
+                        'G' => format!("{iso_year:04}"),
+                        'g' => format!("{:02}", iso_year % 100),
+                        _ => format!("{week:02}"),
+                    }
+                }
+                'Y' => format!("{year:04}"),
+                '%' => "%".to_string(),
+                _ => return None,
+            };
+            output.push_str(&converted);
+        }
+        Some(output)
+    }
+}
+
+impl Date {
+    /// Jour de la semaine, 0 pour dimanche
+    fn weekday(&self) -> i64 {
+        // le 1er janvier 1970 était un jeudi
+        (self.0 + 4).rem_euclid(7)
+    }
+
+    /// Année et semaine ISO 8601 : la semaine 1 contient le premier jeudi
+    fn iso_week(&self) -> (i64, i64) {
+        let thursday = Date(self.0 - (self.weekday() + 6) % 7 + 3);
+        let (year, _, _) = thursday.ymd();
+        let first = Date::from_ymd(year, 1, 1).map_or(thursday.0, |first| first.0);
+        (year, (thursday.0 - first) / 7 + 1)
+    }
+}
+
+fn from_julian_day(julian_day: f64) -> Option<i64> {
+    rounded((julian_day - UNIX_EPOCH_JULIAN_DAY) * MILLISECONDS_PER_DAY as f64)
+}
+
+/// Entier le plus proche, `None` s'il ne tient pas sur 64 bits
+fn rounded(value: f64) -> Option<i64> {
+    let value = value.round();
+    // 2^63 s'écrit exactement, contrairement à i64::MAX
+    (value >= i64::MIN as f64 && value < -(i64::MIN as f64)).then_some(value as i64)
+}
+
+fn now_milliseconds() -> i64 {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(elapsed) => elapsed.as_millis() as i64,
+        Err(error) => -(error.duration().as_millis() as i64),
+    }
+}
+
+/// Une heure seule se rapporte au 1er janvier 2000, comme dans SQLite
+fn default_day() -> i64 {
+    Date::from_ymd(2000, 1, 1).map_or(0, |date| date.0 * MILLISECONDS_PER_DAY)
+}
+
+/// `AAAA-MM-JJ[( |T)HH:MM[:SS[.SSS]][Z|±HH:MM]]` ou `HH:MM[:SS[.SSS]]`
+fn parse(text: &str) -> Option<i64> {
+    if let Some(time) = parse_time(text) {
+        return Some(default_day() + time);
+    }
+    let date = text.get(..10)?.parse::<Date>().ok()?;
+    let day = date.0 * MILLISECONDS_PER_DAY;
+    let rest = &text[10..];
+    if rest.is_empty() {
+        return Some(day);
+    }
+    let rest = rest.strip_prefix([' ', 'T'])?.trim_start();
+    // un fuseau horaire en fin d'heure ramène l'instant en UTC
+    let (time, offset) = match rest.find(['Z', 'z', '+', '-']) {
+        Some(index) => {
+            let offset = match &rest[index..] {
+                "Z" | "z" => 0,
+                zone => {
+                    let sign = if zone.starts_with('-') { -1 } else { 1 };
+                    sign * parse_time(&zone[1..])?
+                }
+            };
+            (rest[..index].trim_end(), offset)
+        }
+        None => (rest, 0),
+    };
+    Some(day + parse_time(time)? - offset)
+}
+
+/// `HH:MM[:SS[.SSS]]`, en millisecondes
+fn parse_time(text: &str) -> Option<i64> {
+    let (hms, fraction) = match text.split_once('.') {
+        Some((hms, fraction)) => (hms, Some(fraction)),
+        None => (text, None),
+    };
+    let mut parts = hms.split(':');
+    let mut next = || {
+        parts
+            .next()
+            .filter(|part| part.len() == 2 && part.bytes().all(|byte| byte.is_ascii_digit()))
+            .and_then(|part| part.parse::<i64>().ok())
+    };
+    let (hour, minute) = (next()?, next()?);
+    let second = match text.matches(':').count() {
+        2 => next()?,
+        1 if fraction.is_none() => 0,
+        _ => return None,
+    };
+    if hour > 23 || minute > 59 || second > 59 {
+        return None;
+    }
+    let milliseconds = match fraction {
+        Some(fraction) if !fraction.is_empty() && fraction.bytes().all(|byte| byte.is_ascii_digit()) => {
+            format!("0.{fraction}").parse::<f64>().ok()? * 1000.0
+        }
+        Some(_) => return None,
+        None => 0.0,
+    };
+    Some((hour * 3600 + minute * 60 + second) * 1000 + milliseconds.round() as i64)
+}
+
+/// Décalages d'un fuseau horaire, lus dans un fichier TZif de la base de
+/// fuseaux du système. Après le dernier changement connu, la règle POSIX de
+/// fin de fichier donne les décalages des années suivantes.
+struct TimeZone {
+    /// instant UTC, en secondes, à partir duquel s'applique un décalage
+    transitions: Vec<(i64, i64)>,
+    /// décalage avant le premier changement
+    initial: i64,
+    /// règle qui suit le dernier changement, à partir de la version 2
+    rule: Option<Rule>,
+}
+
+impl TimeZone {
+    fn utc() -> Self {
+        Self {
+            transitions: vec![],
+            initial: 0,
+            rule: None,
+        }
+    }
+
+    /// Fuseau de la variable TZ, ou à défaut de /etc/localtime, UTC si
+    /// aucun ne peut être lu
+    fn local() -> Self {
+        let path = match std::env::var("TZ") {
+            Ok(zone) => {
+                let zone = zone.strip_prefix(':').unwrap_or(&zone);
+                match zone.starts_with('/') {
+                    true => zone.to_string(),
+                    false => format!("/usr/share/zoneinfo/{zone}"),
+                }
+            }
+            Err(_) => "/etc/localtime".to_string(),
+        };
+        std::fs::read(path)
+            .ok()
+            .and_then(|bytes| TimeZone::from_tzif(&bytes))
+            .unwrap_or_else(TimeZone::utc)
+    }
+
+    /// Lit un fichier TZif, les instants sur 64 bits à partir de la version 2
+    fn from_tzif(bytes: &[u8]) -> Option<Self> {
+        if bytes.get(..4)? != b"TZif" {
+            return None;
+        }
+        let version = *bytes.get(4)?;
+        let (header, size) = match version {
+            0 => (0, 4),
+            _ => {
+                // le bloc 64 bits suit le bloc 32 bits de la version 1
+                let counts = Counts::read(bytes)?;
+                (44 + counts.data_size(4), 8)
+            }
+        };
+        let counts = Counts::read(bytes.get(header..)?)?;
+        // la règle POSIX suit le bloc 64 bits, entre deux retours à la ligne
+        let footer = header + 44 + counts.data_size(size);
+        let rule = match size {
+            8 => bytes
+                .get(footer..)
+                .and_then(|footer| footer.strip_prefix(b"\n"))
+                .and_then(|footer| footer.split(|byte| *byte == b'\n').next())
+                .and_then(|footer| std::str::from_utf8(footer).ok())
+                .and_then(Rule::parse),
+            _ => None,
+        };
+        let mut position = header + 44;
+        let mut take = |length: usize| {
+            let slice = bytes.get(position..position + length);
+            position += length;
+            slice
+        };
+        let times = take(counts.transitions * size)?;
+        let indices = take(counts.transitions)?;
+        let types = take(counts.types * 6)?;
+        let offset = |index: usize| {
+            let offset = types.get(index * 6..index * 6 + 4)?;
+            Some(i32::from_be_bytes(offset.try_into().ok()?) as i64)
+        };
+        let transitions = times
+            .chunks(size)
+            .zip(indices)
+            .map(|(time, index)| {
+                let time = match size {
+                    4 => i32::from_be_bytes(time.try_into().ok()?) as i64,
+                    _ => i64::from_be_bytes(time.try_into().ok()?),
+                };
+                Some((time, offset(*index as usize)?))
+            })
+            .collect::<Option<Vec<_>>>()?;
+        Some(Self {
+            transitions,
+            initial: offset(0)?,
+            rule,
+        })
+    }
+
+    /// Décalage en secondes de l'heure locale sur l'heure UTC à cet instant
+    fn offset(&self, seconds: i64) -> i64 {
+        let index = self.transitions.partition_point(|(time, _)| *time <= seconds);
+        match (&self.rule, index) {
+            (Some(rule), index) if index == self.transitions.len() => rule.offset(seconds),
+            (_, 0) => self.initial,
+            (_, index) => self.transitions[index - 1].1,
+        }
+    }
+}
+
+/// Règle POSIX d'un fuseau horaire, comme `CET-1CEST,M3.5.0,M10.5.0/3` :
+/// nom et décalage de l'heure normale, puis ceux de l'heure d'été avec ses
+/// dates de début et de fin. Les décalages sont ici comptés vers l'est.
+struct Rule {
+    standard: i64,
+    daylight: Option<Daylight>,
+}
+
+/// Heure d'été d'une règle POSIX
+struct Daylight {
+    offset: i64,
+    /// changements en heure locale : jour et secondes depuis minuit
+    start: (RuleDay, i64),
+    end: (RuleDay, i64),
+}
+
+/// Jour d'un changement dans une règle POSIX
+enum RuleDay {
+    /// `Jn` : de 1 à 365, sans compter le 29 février
+    Julian(i64),
+    /// `n` : de 0 à 365, en comptant le 29 février
+    Zero(i64),
+    /// `Mm.w.d` : jour `d` (0 pour dimanche) de la semaine `w` du mois `m`,
+    /// la semaine 5 étant la dernière
+    Month { month: u32, week: i64, weekday: i64 },
+}
+
+impl Rule {
+    /// Lit une règle POSIX, `None` si elle est mal formée. Une heure d'été
+    /// sans dates de changement est ignorée.
+    fn parse(text: &str) -> Option<Self> {
+        let (standard, rest) = rule_offset(rule_name(text)?)?;
+        let standard = -standard;
+        if rest.is_empty() {
+            return Some(Self { standard, daylight: None });
+        }
+        let rest = rule_name(rest)?;
+        // une heure d'été sans décalage avance d'une heure
+        let (offset, rest) = match rest.starts_with(',') || rest.is_empty() {
+            true => (standard + 3600, rest),
+            false => rule_offset(rest).map(|(offset, rest)| (-offset, rest))?,
+        };
+        let Some(rest) = rest.strip_prefix(',') else {
+            return rest.is_empty().then_some(Self { standard, daylight: None });
+        };
+        let (start, rest) = RuleDay::parse(rest)?;
+        let (end, rest) = RuleDay::parse(rest.strip_prefix(',')?)?;
+        rest.is_empty().then_some(Self {
+            standard,
+            daylight: Some(Daylight { offset, start, end }),
+        })
+    }
+
+    /// Décalage en secondes de l'heure locale sur l'heure UTC à cet instant
+    fn offset(&self, seconds: i64) -> i64 {
+        let Some(daylight) = &self.daylight else {
+            return self.standard;
+        };
+        let seconds_per_day = MILLISECONDS_PER_DAY / 1000;
+        let (year, _, _) = Date((seconds + self.standard).div_euclid(seconds_per_day)).ymd();
+        // l'été commence en heure normale et finit en heure d'été
+        let change = |(day, time): &(RuleDay, i64), offset: i64| Some(day.date(year)? * seconds_per_day + time - offset);
+        let (Some(start), Some(end)) = (change(&daylight.start, self.standard), change(&daylight.end, daylight.offset)) else {
+            return self.standard;
+        };
+        // dans l'hémisphère sud, l'été chevauche deux années
+        let summer = match start < end {
+            true => (start..end).contains(&seconds),
+            false => seconds < end || seconds >= start,
+        };
+        match summer {
+            true => daylight.offset,
+            false => self.standard,
+        }
+    }
+}
+
+impl RuleDay {
+    /// Lit un jour et son heure facultative, 2 heures par défaut
+    fn parse(text: &str) -> Option<((Self, i64), &str)> {
+        let (day, rest) = match text.as_bytes().first()? {
+            b'J' => {
+                let (day, rest) = rule_number(&text[1..])?;
+                ((1..=365).contains(&day).then_some(RuleDay::Julian(day))?, rest)
+            }
+            b'M' => {
+                let (month, rest) = rule_number(&text[1..])?;
+                let (week, rest) = rule_number(rest.strip_prefix('.')?)?;
+                let (weekday, rest) = rule_number(rest.strip_prefix('.')?)?;
+                if !(1..=12).contains(&month) || !(1..=5).contains(&week) || !(0..7).contains(&weekday) {
+                    return None;
+                }
+                let month = month as u32;
+                (RuleDay::Month { month, week, weekday }, rest)
+            }
+            _ => {
+                let (day, rest) = rule_number(text)?;
+                ((0..=365).contains(&day).then_some(RuleDay::Zero(day))?, rest)
+            }
+        };
+        match rest.strip_prefix('/') {
+            Some(rest) => rule_offset(rest).map(|(time, rest)| ((day, time), rest)),
+            None => Some(((day, 7200), rest)),
+        }
+    }
+
+    /// Jour de l'année donnée, compté depuis le 1er janvier 1970
+    fn date(&self, year: i64) -> Option<i64> {
+        let first = Date::from_ymd(year, 1, 1)?.0;
+        match *self {
+            RuleDay::Julian(day) => {
+                let leap = Date::from_ymd(year, 2, 29).is_some();
+                Some(first + day - 1 + (leap && day >= 60) as i64)
+            }
+            RuleDay::Zero(day) => Some(first + day),
+            RuleDay::Month { month, week, weekday } => {
+                let first = Date::from_ymd(year, month, 1)?;
+                let mut day = first.0 + (weekday - first.weekday()).rem_euclid(7) + (week - 1) * 7;
+                // la semaine 5 peut déborder sur le mois suivant
+                while Date(day).ymd().1 != month {
+                    day -= 7;
+                }
+                Some(day)
+            }
+        }
+    }
+}
+
+/// Passe le nom d'un fuseau : au moins trois lettres, ou un texte entre `<>`
+fn rule_name(text: &str) -> Option<&str> {
+    if let Some(quoted) = text.strip_prefix('<') {
+        return quoted.split_once('>').map(|(_, rest)| rest);
+    }
+    let length = text.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(text.len());
+    (length >= 3).then_some(&text[length..])
+}
+
+/// `[+-]hh[:mm[:ss]]`, en secondes
+fn rule_offset(text: &str) -> Option<(i64, &str)> {
+    let (sign, text) = match text.strip_prefix('-') {
+        Some(text) => (-1, text),
+        None => (1, text.strip_prefix('+').unwrap_or(text)),
+    };
+    let (hours, mut rest) = rule_number(text)?;
+    let mut seconds = hours * 3600;
+    for unit in [60, 1] {
+        let Some(next) = rest.strip_prefix(':') else {
+            break;
+        };
+        let (value, next) = rule_number(next)?;
+        seconds += value * unit;
+        rest = next;
+    }
+    Some((sign * seconds, rest))
+}
+
+/// Nombre décimal en tête du texte
+fn rule_number(text: &str) -> Option<(i64, &str)> {
+    let length = text.find(|c: char| !c.is_ascii_digit()).unwrap_or(text.len());
+    // au plus 167 heures dans une règle, bien loin du débordement
+    let number = text.get(..length).filter(|digits| (1..=3).contains(&digits.len()))?.parse().ok()?;
+    Some((number, &text[length..]))
+}
+
+/// Nombres d'éléments annoncés par l'en-tête d'un fichier TZif
+struct Counts {
+    utc_indicators: usize,
+    standard_indicators: usize,
+    leap_seconds: usize,
+    transitions: usize,
+    types: usize,
+    characters: usize,
+}
+
+impl Counts {
+    fn read(bytes: &[u8]) -> Option<Self> {
+        let count = |index: usize| {
+            let start = 20 + index * 4;
+            Some(u32::from_be_bytes(bytes.get(start..start + 4)?.try_into().ok()?) as usize)
+        };
+        Some(Self {
+            utc_indicators: count(0)?,
+            standard_indicators: count(1)?,
+            leap_seconds: count(2)?,
+            transitions: count(3)?,
+            types: count(4)?,
+            characters: count(5)?,
+        })
+    }
+
+    /// Taille des données qui suivent l'en-tête, pour des instants de
+    /// `size` octets
+    fn data_size(&self, size: usize) -> usize {
+        self.transitions * (size + 1)
+            + self.types * 6
+            + self.characters
+            + self.leap_seconds * (size + 4)
+            + self.standard_indicators
+            + self.utc_indicators
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(text: &str) -> Value {
+        Value::Text(text.to_string())
+    }
+
+    fn call(output: Output, arguments: &[&str]) -> Value {
+        let arguments = arguments.iter().map(|argument| text(argument)).collect::<Vec<_>>();
+        write(output, &arguments, &TimeZone::utc())
+    }
+
+    #[test]
+    fn test_read() {
+        assert_eq!(call(Output::DateTime, &["2024-02-29T13:05"]), text("2024-02-29 13:05:00"));
+        assert_eq!(call(Output::Date, &["2024-02-29 13:05:07.250"]), text("2024-02-29"));
+        assert_eq!(call(Output::Time, &["13:05:07.25", "subsec"]), text("13:05:07.250"));
+        assert_eq!(call(Output::DateTime, &["13:05"]), text("2000-01-01 13:05:00"));
+        // un fuseau horaire ramène à UTC
+        assert_eq!(call(Output::DateTime, &["2024-01-01 01:30+02:00"]), text("2023-12-31 23:30:00"));
+        assert_eq!(call(Output::JulianDay, &["2000-01-01 12:00"]), Value::Real(2_451_545.0));
+        assert_eq!(call(Output::UnixEpoch, &["1970-01-02"]), Value::Integer(86_400));
+        assert_eq!(call(Output::DateTime, &["1700000000", "unixepoch"]), text("2023-11-14 22:13:20"));
+        assert_eq!(call(Output::Date, &["2451545"]), text("2000-01-01"));
+        assert_eq!(call(Output::Date, &["1700000000", "auto"]), text("2023-11-14"));
+        assert_eq!(
+            write(Output::DateTime, &[Value::Timestamp(Timestamp(86_461))], &TimeZone::utc()),
+            text("1970-01-02 00:01:01")
+        );
+        assert_eq!(
+            write(Output::Date, &[Value::Real(2_460_000.5)], &TimeZone::utc()),
+            text("2023-02-25")
+        );
+        // invalides
+        assert_eq!(call(Output::Date, &["2024-02-30"]), Value::Null);
+        assert_eq!(call(Output::Date, &["24:00"]), Value::Null);
+        assert_eq!(call(Output::Date, &["2024-01-01", "unixepoch"]), Value::Null);
+        assert_eq!(call(Output::Date, &["2024-01-01", "+1 fortnight"]), Value::Null);
+        assert_eq!(write(Output::Date, &[Value::Null], &TimeZone::utc()), Value::Null);
+        // sans argument, l'instant présent
+        assert!(matches!(write(Output::UnixEpoch, &[], &TimeZone::utc()), Value::Integer(now) if now > 1_700_000_000));
+    }
+
+    #[test]
+    fn test_modifiers() {
+        assert_eq!(call(Output::Date, &["2024-01-31", "+1 month"]), text("2024-03-02"));
+        assert_eq!(call(Output::Date, &["2024-02-29", "+1 year"]), text("2025-03-01"));
+        assert_eq!(
+            call(Output::Date, &["2024-03-15", "start of month", "+1 month", "-1 day"]),
+            text("2024-03-31")
+        );
+        assert_eq!(call(Output::DateTime, &["2024-03-15 10:20:30", "start of year"]), text("2024-01-01 00:00:00"));
+        assert_eq!(call(Output::DateTime, &["2024-03-15 10:20:30", "start of day"]), text("2024-03-15 00:00:00"));
+        assert_eq!(call(Output::DateTime, &["2024-03-15 23:00", "+90 minutes"]), text("2024-03-16 00:30:00"));
+        assert_eq!(call(Output::DateTime, &["2024-03-15 23:00", "-1.5 hours"]), text("2024-03-15 21:30:00"));
+        assert_eq!(call(Output::DateTime, &["2024-03-15 23:00", "+01:30:15"]), text("2024-03-16 00:30:15"));
+        // le 1er janvier 2024 est un lundi
+        assert_eq!(call(Output::Date, &["2024-01-01", "weekday 0"]), text("2024-01-07"));
+        assert_eq!(call(Output::Date, &["2024-01-01", "weekday 1"]), text("2024-01-01"));
+        assert_eq!(call(Output::Date, &["2024-01-01", "WEEKDAY 7"]), Value::Null);
+        // hors des instants acceptés
+        assert_eq!(call(Output::Date, &["2024-01-01", "+9e18 seconds"]), Value::Null);
+        assert_eq!(call(Output::Date, &["2024-01-01", "+99999999999999999 months"]), Value::Null);
+        assert_eq!(call(Output::Date, &["2024-01-01", "+1e300 years"]), Value::Null);
+        assert_eq!(call(Output::Date, &["2024-01-01", "-1e300 days"]), Value::Null);
+        assert_eq!(call(Output::Date, &["2024-01-01", "+NaN days"]), Value::Null);
+        assert_eq!(call(Output::Date, &["9999-12-31", "+1 day"]), Value::Null);
+        assert_eq!(call(Output::UnixEpoch, &["1e300", "unixepoch"]), Value::Null);
+        assert_eq!(call(Output::JulianDay, &["-1"]), Value::Null);
+    }
+
+    #[test]
+    fn test_time_zones() {
+        let paris = TimeZone {
+            transitions: vec![(1_711_846_800, 7200), (1_729_990_800, 3600)],
+            initial: 3600,
+            rule: None,
+        };
+        let arguments = |moment: &str, modifier: &str| [text(moment), text(modifier)];
+        assert_eq!(
+            write(Output::DateTime, &arguments("2024-03-01 12:00", "localtime"), &paris),
+            text("2024-03-01 13:00:00")
+        );
+        assert_eq!(
+            write(Output::DateTime, &arguments("2024-07-01 12:00", "localtime"), &paris),
+            text("2024-07-01 14:00:00")
+        );
+        assert_eq!(
+            write(Output::DateTime, &arguments("2024-07-01 14:00", "utc"), &paris),
+            text("2024-07-01 12:00:00")
+        );
+
+        // fichier TZif version 1 : deux types, un changement en 1970
+        let mut tzif = b"TZif".to_vec();
+        tzif.extend([0; 16]);
+        for count in [0u32, 0, 0, 1, 2, 4] {
+            tzif.extend(count.to_be_bytes());
+        }
+        tzif.extend(3600i32.to_be_bytes());
+        tzif.push(1);
+        tzif.extend(0i32.to_be_bytes());
+        tzif.extend([0, 0]);
+        tzif.extend((-3600i32).to_be_bytes());
+        tzif.extend([0, 0]);
+        tzif.extend(b"XXX\0");
+        let zone = TimeZone::from_tzif(&tzif).expect("invalid TZif");
+        assert_eq!(zone.offset(0), 0);
+        assert_eq!(zone.offset(3600), -3600);
+        assert_eq!(zone.offset(7200), -3600);
+        assert!(TimeZone::from_tzif(b"TZif2").is_none());
+
+        // version 2 : bloc 32 bits vide, un changement en 1970 dans le bloc
+        // 64 bits, puis la règle de l'Europe centrale
+        let mut tzif = b"TZif2".to_vec();
+        tzif.extend([0; 39]);
+        tzif.extend(b"TZif2");
+        tzif.extend([0; 15]);
+        for count in [0u32, 0, 0, 1, 1, 4] {
+            tzif.extend(count.to_be_bytes());
+        }
+        tzif.extend(0i64.to_be_bytes());
+        tzif.push(0);
+        tzif.extend(3600i32.to_be_bytes());
+        tzif.extend([0, 0]);
+        tzif.extend(b"CET\0");
+        tzif.extend(b"\nCET-1CEST,M3.5.0,M10.5.0/3\n");
+        let zone = TimeZone::from_tzif(&tzif).expect("invalid TZif");
+        // le dernier dimanche de mars 2030 est le 31, celui d'octobre le 27
+        let march = 1_901_149_200;
+        let october = 1_919_293_200;
+        assert_eq!(zone.offset(march - 1), 3600);
+        assert_eq!(zone.offset(march), 7200);
+        assert_eq!(zone.offset(october - 1), 7200);
+        assert_eq!(zone.offset(october), 3600);
+        assert_eq!(
+            write(Output::DateTime, &arguments("2030-07-01 12:00", "localtime"), &zone),
+            text("2030-07-01 14:00:00")
+        );
+        // dans l'hémisphère sud, l'été chevauche le changement d'année
+        let sydney = Rule::parse("AEST-10AEDT,M10.1.0,M4.1.0/3").expect("invalid rule");
+        assert_eq!(sydney.offset(1_893_456_000), 11 * 3600);
+        assert_eq!(sydney.offset(1_907_000_000), 10 * 3600);
+        assert_eq!(Rule::parse("<+03>-3").map(|rule| rule.offset(0)), Some(3 * 3600));
+        assert!(Rule::parse("CET-1CEST,M13.5.0,M10.5.0").is_none());
+    }
+
+    #[test]
+    fn test_strftime() {
+        let strftime = |format: &str, moment: &str| super::strftime(&[text(format), text(moment)], &TimeZone::utc());
+        assert_eq!(
+            strftime("%Y-%m-%d %H:%M:%f %j %w %u %s", "2024-03-10 09:08:07.5"),
+            text("2024-03-10 09:08:07.500 070 0 7 1710061687")
+        );
+        assert_eq!(strftime("%I %l %p %P %k %e", "2024-03-05 00:30"), text("12 12 AM am  0  5"));
+        assert_eq!(strftime("%R %T %F %%", "2024-03-05 15:30"), text("15:30 15:30:00 2024-03-05 %"));
+        assert_eq!(strftime("%J", "2000-01-01 12:00"), text("2451545"));
+        // semaines : le 1er janvier 2021 est un vendredi de la semaine ISO 53 de 2020
+        assert_eq!(strftime("%W %U %V %G %g", "2021-01-01"), text("00 00 53 2020 20"));
+        assert_eq!(strftime("%W %U %V", "2024-12-30"), text("53 52 01"));
+        assert_eq!(strftime("%Q", "2024-01-01"), Value::Null);
+        assert_eq!(super::strftime(&[Value::Null, text("now")], &TimeZone::utc()), Value::Null);
+    }
+}
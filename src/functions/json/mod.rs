@@ -0,0 +1,483 @@
+//! Fonctions JSON de SQLite, sur un analyseur JSON intégré. Un document
+//! JSON est un texte ; le texte renvoyé par une fonction JSON est repris
+//! tel quel par une autre, plutôt que comme une chaîne à citer.
+
+mod value;
+
+use crate::data::Value;
+use crate::errors::EvaluationError;
+use crate::functions::{Accumulator, Function, Functions, JsonFunction};
+use std::sync::Arc;
+use value::{malformed, parse_path, path_key, Index, Json, Mode, Step};
+
+/// Colonnes des fonctions tables `json_each` et `json_tree`
+const COLUMNS: [&str; 8] = ["key", "value", "type", "atom", "id", "parent", "fullkey", "path"];
+
+pub fn register(functions: &mut Functions) {
+    // `returns_json` : le texte renvoyé est du JSON
+    let mut json = |name: &str, arities: &[i32], function: JsonFunction, returns_json: bool| {
+        for arity in arities {
+            functions.register(name, *arity, Function::Json(function));
+            if returns_json {
+                functions.returns_json(name, *arity);
+            }
+        }
+    };
+    json("json", &[1], |arguments, _| text(document(&arguments[0])?), true);
+    json("json_extract", &[-1], extract, true);
+    json("->", &[2], |arguments, _| arrow(arguments, |found| Value::Text(found.to_string())), true);
+    json("->>", &[2], |arguments, _| arrow(arguments, to_sql), false);
+    json("json_set", &[-1], |arguments, json| modify(arguments, json, Mode::Set), true);
+    json("json_insert", &[-1], |arguments, json| modify(arguments, json, Mode::Insert), true);
+    json("json_replace", &[-1], |arguments, json| modify(arguments, json, Mode::Replace), true);
+    json("json_remove", &[-1], remove, true);
+    json("json_array", &[-1], array, true);
+    json("json_object", &[-1], object, true);
+    json("json_quote", &[1], |arguments, json| Ok(Value::Text(to_json(&arguments[0], json[0])?.to_string())), true);
+    json("json_valid", &[1], |arguments, _| {
+        Ok(match &arguments[0] {
+            Value::Null => Value::Null,
+            value => Value::Integer(document(value).is_ok() as i64),
+        })
+    }, false);
+    json("json_type", &[1, 2], |arguments, _| {
+        Ok(match element(arguments)? {
+            Some(found) => Value::Text(found.type_name().to_string()),
+            None => Value::Null,
+        })
+    }, false);
+    json("json_array_length", &[1, 2], |arguments, _| {
+        Ok(match element(arguments)? {
+            Some(Json::Array(items)) => Value::Integer(items.len() as i64),
+            Some(_) => Value::Integer(0),
+            None => Value::Null,
+        })
+    }, false);
+    let group_array = || -> Box<dyn Accumulator> { Box::new(GroupArray(vec![])) };
+    functions.register("json_group_array", 1, Function::Aggregate(Arc::new(group_array)));
+    functions.returns_json("json_group_array", 1);
+    let group_object = || -> Box<dyn Accumulator> { Box::new(GroupObject(vec![])) };
+    functions.register("json_group_object", 2, Function::Aggregate(Arc::new(group_object)));
+    functions.returns_json("json_group_object", 2);
+    functions.register_table("json_each", &COLUMNS, |arguments| table("json_each", arguments, false));
+    functions.register_table("json_tree", &COLUMNS, |arguments| table("json_tree", arguments, true));
+}
+
+/// Document JSON passé en premier argument, `None` pour NULL
+fn document(value: &Value) -> Result<Option<Json>, EvaluationError> {
+    Ok(Some(match value {
+        Value::Null => return Ok(None),
+        Value::Integer(value) => Json::Integer(*value),
+        Value::Real(value) => Json::Real(*value),
+        Value::Boolean(value) => Json::Boolean(*value),
+        Value::Blob(_) => return Err(malformed()),
+        value => Json::parse(&value.to_string())?,
+    }))
+}
+
+/// Valeur SQL à inclure dans un document. Un texte JSON produit par une
+/// autre fonction JSON est repris tel quel, s'il est bien formé.
+fn to_json(value: &Value, is_json: bool) -> Result<Json, EvaluationError> {
+    Ok(match value {
+        Value::Null => Json::Null,
+        Value::Integer(value) => Json::Integer(*value),
+        Value::Real(value) => Json::Real(*value),
+        Value::Boolean(value) => Json::Boolean(*value),
+        Value::Blob(_) => return Err(EvaluationError::Function("JSON cannot hold BLOB values".to_string())),
+        Value::Text(text) if is_json => Json::parse(text).unwrap_or_else(|_| Json::String(text.clone())),
+        value => Json::String(value.to_string()),
+    })
+}
+
+/// Valeur SQL d'une partie d'un document : un booléen devient 1 ou 0, un
+/// tableau ou un objet son texte JSON
+fn to_sql(json: &Json) -> Value {
+    match json {
+        Json::Null => Value::Null,
+        Json::Boolean(value) => Value::Integer(*value as i64),
+        Json::Integer(value) => Value::Integer(*value),
+        Json::Real(value) => Value::Real(*value),
+        Json::String(text) => Value::Text(text.clone()),
+        json => Value::Text(json.to_string()),
+    }
+}
+
+fn text(json: Option<Json>) -> Result<Value, EvaluationError> {
+    Ok(json.map_or(Value::Null, |json| Value::Text(json.to_string())))
+}
+
+/// Chemin passé en argument, `None` pour NULL
+fn path(value: &Value) -> Result<Option<Vec<Step>>, EvaluationError> {
+    match value {
+        Value::Null => Ok(None),
+        Value::Text(path) => parse_path(path).map(Some),
+        value => Err(EvaluationError::Function(format!("bad JSON path: {:?}", value.to_string()))),
+    }
+}
+
+fn at_least(name: &str, arguments: &[Value], count: usize) -> Result<(), EvaluationError> {
+    match arguments.len() < count {
+        true => Err(EvaluationError::WrongArgumentCount {
+            function: name.to_string(),
+            given: arguments.len(),
+        }),
+        false => Ok(()),
+    }
+}
+
+/// Partie désignée par le chemin facultatif du second argument
+fn element(arguments: &[Value]) -> Result<Option<Json>, EvaluationError> {
+    let Some(json) = document(&arguments[0])? else {
+        return Ok(None);
+    };
+    let steps = match arguments.get(1) {
+        Some(value) => match path(value)? {
+            Some(steps) => steps,
+            None => return Ok(None),
+        },
+        None => vec![],
+    };
+    Ok(json.get(&steps).cloned())
+}
+
+/// `json_extract(json, chemin, ...)` : la valeur SQL au chemin, ou avec
+/// plusieurs chemins le tableau JSON des valeurs trouvées
+fn extract(arguments: &[Value], _: &[bool]) -> Result<Value, EvaluationError> {
+    at_least("json_extract", arguments, 2)?;
+    let Some(json) = document(&arguments[0])? else {
+        return Ok(Value::Null);
+    };
+    let mut found = vec![];
+    for argument in &arguments[1..] {
+        let Some(steps) = path(argument)? else {
+            return Ok(Value::Null);
+        };
+        found.push(json.get(&steps).cloned());
+    }
+    Ok(match <[_; 1]>::try_from(found) {
+        Ok([found]) => found.as_ref().map_or(Value::Null, to_sql),
+        Err(found) => Value::Text(Json::Array(found.into_iter().map(|found| found.unwrap_or(Json::Null)).collect()).to_string()),
+    })
+}
+
+/// `json -> chemin` et `json ->> chemin`. À droite, un entier désigne un
+/// élément de tableau, compté depuis la fin s'il est négatif, et un texte
+/// sans `$` une clé d'objet.
+fn arrow(arguments: &[Value], result: fn(&Json) -> Value) -> Result<Value, EvaluationError> {
+    let Some(json) = document(&arguments[0])? else {
+        return Ok(Value::Null);
+    };
+    let steps = match &arguments[1] {
+        Value::Null => return Ok(Value::Null),
+        Value::Integer(index) if *index < 0 => vec![Step::Index(Index::End(index.unsigned_abs() as usize))],
+        Value::Integer(index) => vec![Step::Index(Index::Start(*index as usize))],
+        Value::Text(text) if text.starts_with('$') => parse_path(text)?,
+        value => vec![Step::Key(value.to_string())],
+    };
+    Ok(json.get(&steps).map_or(Value::Null, result))
+}
+
+/// `json_set`, `json_insert` et `json_replace` : `(json, chemin, valeur, ...)`
+fn modify(arguments: &[Value], json: &[bool], mode: Mode) -> Result<Value, EvaluationError> {
+    if arguments.len().is_multiple_of(2) {
+        let name = match mode {
+            Mode::Set => "json_set",
+            Mode::Insert => "json_insert",
+            Mode::Replace => "json_replace",
+        };
+        return Err(EvaluationError::WrongArgumentCount {
+            function: name.to_string(),
+            given: arguments.len(),
+        });
+    }
+    let Some(mut document) = document(&arguments[0])? else {
+        return Ok(Value::Null);
+    };
+    for index in (1..arguments.len()).step_by(2) {
+        let Some(steps) = path(&arguments[index])? else {
+            return Ok(Value::Null);
+        };
+        document.set(&steps, to_json(&arguments[index + 1], json[index + 1])?, mode);
+    }
+    text(Some(document))
+}
+
+/// `json_remove(json, chemin, ...)`, NULL si le document entier est retiré
+fn remove(arguments: &[Value], _: &[bool]) -> Result<Value, EvaluationError> {
+    at_least("json_remove", arguments, 1)?;
+    let Some(mut document) = document(&arguments[0])? else {
+        return Ok(Value::Null);
+    };
+    for argument in &arguments[1..] {
+        match path(argument)? {
+            Some(steps) if steps.is_empty() => return Ok(Value::Null),
+            Some(steps) => document.remove(&steps),
+            None => return Ok(Value::Null),
+        }
+    }
+    text(Some(document))
+}
+
+fn array(arguments: &[Value], json: &[bool]) -> Result<Value, EvaluationError> {
+    let items = arguments
+        .iter()
+        .zip(json)
+        .map(|(argument, is_json)| to_json(argument, *is_json))
+        .collect::<Result<_, _>>()?;
+    text(Some(Json::Array(items)))
+}
+
+/// `json_object(clé, valeur, ...)`
+fn object(arguments: &[Value], json: &[bool]) -> Result<Value, EvaluationError> {
+    if !arguments.len().is_multiple_of(2) {
+        return Err(EvaluationError::WrongArgumentCount {
+            function: "json_object".to_string(),
+            given: arguments.len(),
+        });
+    }
+    let mut members = vec![];
+    for index in (0..arguments.len()).step_by(2) {
+        members.push((label(&arguments[index])?, to_json(&arguments[index + 1], json[index + 1])?));
+    }
+    text(Some(Json::Object(members)))
+}
+
+/// Clé d'un membre d'objet, qui doit être un texte
+fn label(value: &Value) -> Result<String, EvaluationError> {
+    match value {
+        Value::Text(text) => Ok(text.clone()),
+        _ => Err(EvaluationError::Function("json_object() labels must be TEXT".to_string())),
+    }
+}
+
+/// `json_group_array(valeur)`
+struct GroupArray(Vec<Json>);
+
+impl Accumulator for GroupArray {
+    fn step(&mut self, arguments: &[Value]) -> Result<(), EvaluationError> {
+        self.step_json(arguments, &[false])
+    }
+
+    fn step_json(&mut self, arguments: &[Value], json: &[bool]) -> Result<(), EvaluationError> {
+        self.0.push(to_json(&arguments[0], json[0])?);
+        Ok(())
+    }
+
+    fn finalize(self: Box<Self>) -> Result<Value, EvaluationError> {
+        text(Some(Json::Array(self.0)))
+    }
+}
+
+/// `json_group_object(clé, valeur)`, les lignes de clé NULL sont ignorées
+struct GroupObject(Vec<(String, Json)>);
+
+impl Accumulator for GroupObject {
+    fn step(&mut self, arguments: &[Value]) -> Result<(), EvaluationError> {
+        self.step_json(arguments, &[false, false])
+    }
+
+    fn step_json(&mut self, arguments: &[Value], json: &[bool]) -> Result<(), EvaluationError> {
+        if arguments[0] != Value::Null {
+            self.0.push((label(&arguments[0])?, to_json(&arguments[1], json[1])?));
+        }
+        Ok(())
+    }
+
+    fn finalize(self: Box<Self>) -> Result<Value, EvaluationError> {
+        text(Some(Json::Object(self.0)))
+    }
+}
+
+/// `json_each(json[, chemin])` : une ligne par enfant de la partie
+/// désignée, ou pour la partie elle-même si elle n'en a pas.
+/// `json_tree(json[, chemin])` : une ligne par partie, enfants compris, en
+/// profondeur d'abord.
+fn table(name: &str, arguments: &[Value], recursive: bool) -> Result<Vec<Vec<Value>>, EvaluationError> {
+    if !(1..=2).contains(&arguments.len()) {
+        return Err(EvaluationError::WrongArgumentCount {
+            function: name.to_string(),
+            given: arguments.len(),
+        });
+    }
+    let Some(json) = document(&arguments[0])? else {
+        return Ok(vec![]);
+    };
+    let steps = match arguments.get(1) {
+        Some(value) => match path(value)? {
+            Some(steps) => steps,
+            None => return Ok(vec![]),
+        },
+        None => vec![],
+    };
+    let Some(root) = json.get(&steps) else {
+        return Ok(vec![]);
+    };
+    let mut walk = Walk {
+        rows: vec![],
+        recursive,
+    };
+    let fullkey = steps_text(&steps);
+    match root {
+        Json::Array(_) | Json::Object(_) if !recursive => walk.children(root, None, &fullkey),
+        _ => {
+            let parent = steps_text(&steps[..steps.len().saturating_sub(1)]);
+            // la clé d'un élément de tableau est sa position
+            let key = match (steps.last(), json.get(&steps[..steps.len().saturating_sub(1)])) {
+                (Some(Step::Key(key)), _) => Value::Text(key.clone()),
+                (Some(Step::Index(index)), Some(Json::Array(items))) => {
+                    index.resolve(items.len()).map_or(Value::Null, |position| Value::Integer(position as i64))
+                }
+                _ => Value::Null,
+            };
+            walk.visit(key, root, None, fullkey, &parent);
+        }
+    }
+    Ok(walk.rows)
+}
+
+/// Écriture d'un chemin à partir de ses étapes
+fn steps_text(steps: &[Step]) -> String {
+    let mut text = "$".to_string();
+    for step in steps {
+        match step {
+            Step::Key(key) => text.push_str(&path_key(key)),
+            Step::Index(Index::Start(index)) => text.push_str(&format!("[{index}]")),
+            Step::Index(Index::End(0)) => text.push_str("[#]"),
+            Step::Index(Index::End(offset)) => text.push_str(&format!("[#-{offset}]")),
+        }
+    }
+    text
+}
+
+/// Parcours d'un document pour `json_each` et `json_tree`, `id` est le
+/// numéro de la ligne dans l'ordre du parcours
+struct Walk {
+    rows: Vec<Vec<Value>>,
+    recursive: bool,
+}
+
+impl Walk {
+    fn visit(&mut self, key: Value, json: &Json, parent: Option<i64>, fullkey: String, path: &str) {
+        let id = self.rows.len() as i64;
+        let atom = match json {
+            Json::Array(_) | Json::Object(_) => Value::Null,
+            json => to_sql(json),
+        };
+        self.rows.push(vec![
+            key,
+            to_sql(json),
+            Value::Text(json.type_name().to_string()),
+            atom,
+            Value::Integer(id),
+            parent.map_or(Value::Null, Value::Integer),
+            Value::Text(fullkey.clone()),
+            Value::Text(path.to_string()),
+        ]);
+        if self.recursive {
+            self.children(json, Some(id), &fullkey);
+        }
+    }
+
+    fn children(&mut self, json: &Json, parent: Option<i64>, fullkey: &str) {
+        match json {
+            Json::Array(items) => {
+                for (index, item) in items.iter().enumerate() {
+                    self.visit(Value::Integer(index as i64), item, parent, format!("{fullkey}[{index}]"), fullkey);
+                }
+            }
+            Json::Object(members) => {
+                for (name, value) in members {
+                    self.visit(Value::Text(name.clone()), value, parent, format!("{fullkey}{}", path_key(name)), fullkey);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(name: &str, arguments: &[&str]) -> Result<Value, EvaluationError> {
+        let arguments = arguments.iter().map(|text| Value::Text(text.to_string())).collect::<Vec<_>>();
+        Functions::new().call(name, &arguments)
+    }
+
+    fn text(text: &str) -> Result<Value, EvaluationError> {
+        Ok(Value::Text(text.to_string()))
+    }
+
+    #[test]
+    fn test_extract() {
+        let document = r#"{"a":{"b":[1,2.5,"x",true,null]}}"#;
+        assert_eq!(call("json", &[" [ 1 , {\"a\" : 2} ] "]), text(r#"[1,{"a":2}]"#));
+        assert_eq!(call("json", &["{"]), Err(malformed()));
+        assert_eq!(call("json_extract", &[document, "$.a.b[2]"]), text("x"));
+        assert_eq!(call("json_extract", &[document, "$.a.b[3]"]), Ok(Value::Integer(1)));
+        assert_eq!(call("json_extract", &[document, "$.a.b[#-1]"]), Ok(Value::Null));
+        assert_eq!(call("json_extract", &[document, "$.a"]), text(r#"{"b":[1,2.5,"x",true,null]}"#));
+        assert_eq!(call("json_extract", &[document, "$.a.b[0]", "$.c"]), text("[1,null]"));
+        assert_eq!(call("->", &[document, "$.a.b[2]"]), text("\"x\""));
+        assert_eq!(call("->>", &[document, "a"]), text(r#"{"b":[1,2.5,"x",true,null]}"#));
+        assert_eq!(
+            Functions::new().call("->>", &[Value::Text("[1,2,3]".to_string()), Value::Integer(-1)]),
+            Ok(Value::Integer(3))
+        );
+        assert_eq!(
+            call("json_extract", &[document, "a"]),
+            Err(EvaluationError::Function("bad JSON path: \"a\"".to_string()))
+        );
+        assert_eq!(call("json_type", &[document, "$.a.b[1]"]), text("real"));
+        assert_eq!(call("json_array_length", &[document, "$.a.b"]), Ok(Value::Integer(5)));
+        assert_eq!(call("json_valid", &["[1,"]), Ok(Value::Integer(0)));
+    }
+
+    #[test]
+    fn test_modify() {
+        let document = r#"{"a":1,"b":[1,2]}"#;
+        assert_eq!(call("json_set", &[document, "$.a", "x", "$.c.d", "y"]), text(r#"{"a":"x","b":[1,2],"c":{"d":"y"}}"#));
+        assert_eq!(call("json_insert", &[document, "$.a", "x", "$.b[#]", "y"]), text(r#"{"a":1,"b":[1,2,"y"]}"#));
+        assert_eq!(call("json_replace", &[document, "$.a", "x", "$.c", "y"]), text(r#"{"a":"x","b":[1,2]}"#));
+        assert_eq!(call("json_remove", &[document, "$.b[0]", "$.a"]), text(r#"{"b":[2]}"#));
+        assert_eq!(call("json_remove", &[document, "$"]), Ok(Value::Null));
+        assert!(call("json_set", &[document, "$.a"]).is_err());
+    }
+
+    #[test]
+    fn test_build() {
+        let functions = Functions::new();
+        let array = [Value::Integer(1), Value::Text("[2]".to_string()), Value::Null];
+        assert_eq!(functions.call("json_array", &array), text(r#"[1,"[2]",null]"#));
+        // un texte JSON venu d'une autre fonction JSON est inclus tel quel
+        assert_eq!(functions.call_json("json_array", &array, &[false, true, false]), text("[1,[2],null]"));
+        assert_eq!(call("json_object", &["a", "b\"c"]), text(r#"{"a":"b\"c"}"#));
+        assert!(functions.call("json_object", &[Value::Integer(1), Value::Null]).is_err());
+        assert_eq!(call("json_quote", &["it's"]), text("\"it's\""));
+        let mut accumulator = GroupObject(vec![]);
+        accumulator.step(&[Value::Text("a".to_string()), Value::Integer(1)]).unwrap();
+        accumulator.step(&[Value::Null, Value::Integer(2)]).unwrap();
+        accumulator.step_json(&[Value::Text("b".to_string()), Value::Text("{}".to_string())], &[false, true]).unwrap();
+        assert_eq!(Box::new(accumulator).finalize(), text(r#"{"a":1,"b":{}}"#));
+    }
+
+    #[test]
+    fn test_each_tree() {
+        let functions = Functions::new();
+        let each = functions.table("json_each").unwrap();
+        let rows = (each.function)(&[Value::Text(r#"{"a":[1],"b c":2}"#.to_string())]).unwrap();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0][..4], [text("a").unwrap(), text("[1]").unwrap(), text("array").unwrap(), Value::Null]);
+        assert_eq!(rows[1][6..], [text("$.\"b c\"").unwrap(), text("$").unwrap()]);
+        let tree = functions.table("json_tree").unwrap();
+        let rows = (tree.function)(&[Value::Text(r#"{"a":[1]}"#.to_string())]).unwrap();
+        let fullkeys = rows.iter().map(|row| row[6].clone()).collect::<Vec<_>>();
+        assert_eq!(fullkeys, [text("$").unwrap(), text("$.a").unwrap(), text("$.a[0]").unwrap()]);
+        // chaque ligne désigne son parent par son id
+        assert_eq!(rows[2][5], rows[1][4]);
+        let rows = (each.function)(&[Value::Text("[[1,2]]".to_string()), Value::Text("$[0]".to_string())]).unwrap();
+        assert_eq!(rows[1][..2], [Value::Integer(1), Value::Integer(2)]);
+        let rows = (each.function)(&[Value::Text("[1,2]".to_string()), Value::Text("$[#-1]".to_string())]).unwrap();
+        assert_eq!(rows[0][..2], [Value::Integer(1), Value::Integer(2)]);
+    }
+}
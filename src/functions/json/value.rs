@@ -0,0 +1,496 @@
+//! Valeur JSON, son analyse et son écriture minimale, et les chemins
+//! `$.a.b[0]` qui en désignent une partie.
+
+use crate::errors::EvaluationError;
+use std::fmt::{Display, Formatter, Write};
+
+/// Profondeur d'imbrication au-delà de laquelle un texte est refusé. SQLite
+/// va jusqu'à 1000, mais l'analyse récursive doit tenir dans la pile d'un
+/// fil d'exécution.
+const MAX_DEPTH: usize = 500;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Json {
+    Null,
+    Boolean(bool),
+    Integer(i64),
+    Real(f64),
+    String(String),
+    Array(Vec<Json>),
+    /// les membres gardent leur ordre d'écriture
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn parse(input: &str) -> Result<Json, EvaluationError> {
+        let mut reader = Reader { input, position: 0 };
+        let json = reader.value(0)?;
+        reader.blanks();
+        match reader.position == input.len() {
+            true => Ok(json),
+            false => Err(malformed()),
+        }
+    }
+
+    /// Nom du type, tel que renvoyé par `json_type`
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Json::Null => "null",
+            Json::Boolean(true) => "true",
+            Json::Boolean(false) => "false",
+            Json::Integer(_) => "integer",
+            Json::Real(_) => "real",
+            Json::String(_) => "text",
+            Json::Array(_) => "array",
+            Json::Object(_) => "object",
+        }
+    }
+
+    pub fn get(&self, path: &[Step]) -> Option<&Json> {
+        let Some((step, rest)) = path.split_first() else {
+            return Some(self);
+        };
+        match (self, step) {
+            (Json::Object(members), Step::Key(key)) => members
+                .iter()
+                .find(|(name, _)| name == key)
+                .and_then(|(_, value)| value.get(rest)),
+            (Json::Array(items), Step::Index(index)) => items.get(index.resolve(items.len())?)?.get(rest),
+            _ => None,
+        }
+    }
+
+    /// Écrit `value` au chemin. Selon `mode`, une valeur existante est
+    /// remplacée ou gardée, et une valeur absente est ajoutée ou non, les
+    /// objets parents manquants étant alors créés.
+    pub fn set(&mut self, path: &[Step], value: Json, mode: Mode) {
+        let Some((step, rest)) = path.split_first() else {
+            if mode != Mode::Insert {
+                *self = value;
+            }
+            return;
+        };
+        let missing = |value: Json| match rest.is_empty() {
+            true => value,
+            false => {
+                let mut parent = Json::Object(vec![]);
+                parent.set(rest, value, mode);
+                parent
+            }
+        };
+        match (self, step) {
+            (Json::Object(members), Step::Key(key)) => match members.iter_mut().find(|(name, _)| name == key) {
+                Some((_, member)) => member.set(rest, value, mode),
+                None if mode != Mode::Replace => members.push((key.clone(), missing(value))),
+                None => {}
+            },
+            (Json::Array(items), Step::Index(index)) => match index.resolve(items.len()) {
+                Some(position) => items[position].set(rest, value, mode),
+                // seul l'indice qui suit le dernier élément ajoute un élément
+                None if mode != Mode::Replace && index.position(items.len()) == Some(items.len()) => {
+                    items.push(missing(value))
+                }
+                None => {}
+            },
+            _ => {}
+        }
+    }
+
+    /// Retire la valeur au chemin, sans effet si elle n'existe pas
+    pub fn remove(&mut self, path: &[Step]) {
+        let Some((step, rest)) = path.split_first() else {
+            return;
+        };
+        match (self, step) {
+            (Json::Object(members), Step::Key(key)) => {
+                if let Some(position) = members.iter().position(|(name, _)| name == key) {
+                    match rest.is_empty() {
+                        true => drop(members.remove(position)),
+                        false => members[position].1.remove(rest),
+                    }
+                }
+            }
+            (Json::Array(items), Step::Index(index)) => {
+                if let Some(position) = index.resolve(items.len()) {
+                    match rest.is_empty() {
+                        true => drop(items.remove(position)),
+                        false => items[position].remove(rest),
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Écriture minimale, sans espace
+impl Display for Json {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Boolean(value) => write!(f, "{value}"),
+            Json::Integer(value) => write!(f, "{value}"),
+            Json::Real(value) if value.is_nan() => write!(f, "null"),
+            // JSON n'a pas d'infini, SQLite écrit un nombre trop grand
+            Json::Real(value) if value.is_infinite() => write!(f, "{}9e999", if *value < 0.0 { "-" } else { "" }),
+            Json::Real(value) => write!(f, "{value:?}"),
+            Json::String(text) => quote(f, text),
+            Json::Array(items) => {
+                f.write_char('[')?;
+                for (index, item) in items.iter().enumerate() {
+                    if index > 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "{item}")?;
+                }
+                f.write_char(']')
+            }
+            Json::Object(members) => {
+                f.write_char('{')?;
+                for (index, (name, value)) in members.iter().enumerate() {
+                    if index > 0 {
+                        f.write_char(',')?;
+                    }
+                    quote(f, name)?;
+                    write!(f, ":{value}")?;
+                }
+                f.write_char('}')
+            }
+        }
+    }
+}
+
+fn quote(f: &mut Formatter<'_>, text: &str) -> std::fmt::Result {
+    f.write_char('"')?;
+    for c in text.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            '\u{8}' => f.write_str("\\b")?,
+            '\u{c}' => f.write_str("\\f")?,
+            c if c.is_control() && (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+/// Quatre chiffres hexadécimaux d'un échappement `\uXXXX`
+fn unit(chars: &mut impl Iterator<Item = (usize, char)>) -> Result<u32, EvaluationError> {
+    let digits = (0..4)
+        .map(|_| chars.next().map(|(_, c)| c))
+        .collect::<Option<String>>()
+        .ok_or_else(malformed)?;
+    u32::from_str_radix(&digits, 16).map_err(|_| malformed())
+}
+
+pub fn malformed() -> EvaluationError {
+    EvaluationError::Function("malformed JSON".to_string())
+}
+
+/// Analyse descendante d'un texte JSON
+struct Reader<'a> {
+    input: &'a str,
+    position: usize,
+}
+
+impl Reader<'_> {
+    fn rest(&self) -> &str {
+        &self.input[self.position..]
+    }
+
+    fn blanks(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start_matches([' ', '\t', '\n', '\r']).len();
+    }
+
+    /// Consomme `expected` s'il suit, après les blancs
+    fn consume(&mut self, expected: &str) -> bool {
+        self.blanks();
+        let found = self.rest().starts_with(expected);
+        if found {
+            self.position += expected.len();
+        }
+        found
+    }
+
+    fn value(&mut self, depth: usize) -> Result<Json, EvaluationError> {
+        if depth > MAX_DEPTH {
+            return Err(malformed());
+        }
+        self.blanks();
+        let Some(first) = self.rest().chars().next() else {
+            return Err(malformed());
+        };
+        match first {
+            '{' => {
+                self.position += 1;
+                let mut members = vec![];
+                if self.consume("}") {
+                    return Ok(Json::Object(members));
+                }
+                loop {
+                    self.blanks();
+                    if !self.rest().starts_with('"') {
+                        return Err(malformed());
+                    }
+                    let name = self.string()?;
+                    if !self.consume(":") {
+                        return Err(malformed());
+                    }
+                    members.push((name, self.value(depth + 1)?));
+                    if self.consume("}") {
+                        return Ok(Json::Object(members));
+                    }
+                    if !self.consume(",") {
+                        return Err(malformed());
+                    }
+                }
+            }
+            '[' => {
+                self.position += 1;
+                let mut items = vec![];
+                if self.consume("]") {
+                    return Ok(Json::Array(items));
+                }
+                loop {
+                    items.push(self.value(depth + 1)?);
+                    if self.consume("]") {
+                        return Ok(Json::Array(items));
+                    }
+                    if !self.consume(",") {
+                        return Err(malformed());
+                    }
+                }
+            }
+            '"' => Ok(Json::String(self.string()?)),
+            '-' | '0'..='9' => self.number(),
+            _ => {
+                for (word, json) in [("null", Json::Null), ("true", Json::Boolean(true)), ("false", Json::Boolean(false))] {
+                    if self.rest().starts_with(word) {
+                        self.position += word.len();
+                        return Ok(json);
+                    }
+                }
+                Err(malformed())
+            }
+        }
+    }
+
+    /// Chaîne entre guillemets, le guillemet ouvrant est le prochain caractère
+    fn string(&mut self) -> Result<String, EvaluationError> {
+        let mut text = String::new();
+        let mut chars = self.rest().char_indices().skip(1);
+        while let Some((index, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.position += index + 1;
+                    return Ok(text);
+                }
+                '\\' => {
+                    let (_, escaped) = chars.next().ok_or_else(malformed)?;
+                    text.push(match escaped {
+                        '"' => '"',
+                        '\\' => '\\',
+                        '/' => '/',
+                        'b' => '\u{8}',
+                        'f' => '\u{c}',
+                        'n' => '\n',
+                        'r' => '\r',
+                        't' => '\t',
+                        'u' => {
+                            let high = unit(&mut chars)?;
+                            // un caractère hors du plan de base s'écrit en deux moitiés
+                            let code = match high {
+                                0xD800..=0xDBFF => {
+                                    if chars.next().map(|(_, c)| c) != Some('\\')
+                                        || chars.next().map(|(_, c)| c) != Some('u')
+                                    {
+                                        return Err(malformed());
+                                    }
+                                    let low = unit(&mut chars)?;
+                                    if !(0xDC00..=0xDFFF).contains(&low) {
+                                        return Err(malformed());
+                                    }
+                                    0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+                                }
+                                code => code,
+                            };
+                            char::from_u32(code).ok_or_else(malformed)?
+                        }
+                        _ => return Err(malformed()),
+                    });
+                }
+                c if (c as u32) < 0x20 => return Err(malformed()),
+                c => text.push(c),
+            }
+        }
+        Err(malformed())
+    }
+
+    fn number(&mut self) -> Result<Json, EvaluationError> {
+        let rest = self.rest();
+        let size = rest
+            .find(|c: char| !matches!(c, '0'..='9' | '-' | '+' | '.' | 'e' | 'E'))
+            .unwrap_or(rest.len());
+        let text = &rest[..size];
+        let digits = text.strip_prefix('-').unwrap_or(text);
+        // ni zéro en tête, ni point sans chiffre autour
+        let leading_zero = digits.len() > 1 && digits.starts_with('0') && digits.as_bytes()[1].is_ascii_digit();
+        if !digits.starts_with(|c: char| c.is_ascii_digit()) || leading_zero || text.contains(".e") || text.ends_with('.') {
+            return Err(malformed());
+        }
+        let json = match text.parse::<i64>() {
+            Ok(value) => Json::Integer(value),
+            Err(_) => Json::Real(text.parse().map_err(|_| malformed())?),
+        };
+        self.position += size;
+        Ok(json)
+    }
+}
+
+/// Ce que fait une écriture selon que la valeur existe déjà : `json_set`
+/// écrit toujours, `json_insert` seulement une valeur absente, et
+/// `json_replace` seulement une valeur existante
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Mode {
+    Set,
+    Insert,
+    Replace,
+}
+
+/// Étape d'un chemin JSON
+#[derive(Debug, PartialEq, Clone)]
+pub enum Step {
+    /// `.nom` ou `."nom"`
+    Key(String),
+    /// `[N]` ou `[#-N]`
+    Index(Index),
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Index {
+    /// depuis le début du tableau
+    Start(usize),
+    /// `#-N` : N éléments avant la fin, `#` désigne la place après le dernier
+    End(usize),
+}
+
+impl Index {
+    /// Position dans un tableau de cette taille, qui peut être au-delà du
+    /// dernier élément
+    fn position(self, len: usize) -> Option<usize> {
+        match self {
+            Index::Start(position) => Some(position),
+            Index::End(offset) => len.checked_sub(offset),
+        }
+    }
+
+    /// Position d'un élément existant
+    pub fn resolve(self, len: usize) -> Option<usize> {
+        self.position(len).filter(|position| *position < len)
+    }
+}
+
+/// Analyse un chemin `$.a."b c"[0][#-1]`
+pub fn parse_path(path: &str) -> Result<Vec<Step>, EvaluationError> {
+    let bad = || EvaluationError::Function(format!("bad JSON path: {path:?}"));
+    let mut rest = path.strip_prefix('$').ok_or_else(bad)?;
+    let mut steps = vec![];
+    while !rest.is_empty() {
+        if let Some(after) = rest.strip_prefix(".\"") {
+            let end = after.find('"').ok_or_else(bad)?;
+            steps.push(Step::Key(after[..end].to_string()));
+            rest = &after[end + 1..];
+        } else if let Some(after) = rest.strip_prefix('.') {
+            let end = after.find(['.', '[']).unwrap_or(after.len());
+            if end == 0 {
+                return Err(bad());
+            }
+            steps.push(Step::Key(after[..end].to_string()));
+            rest = &after[end..];
+        } else if let Some(after) = rest.strip_prefix('[') {
+            let end = after.find(']').ok_or_else(bad)?;
+            let index = match after[..end].strip_prefix('#') {
+                Some("") => Index::End(0),
+                Some(offset) => Index::End(offset.strip_prefix('-').ok_or_else(bad)?.parse().map_err(|_| bad())?),
+                None => Index::Start(after[..end].parse().map_err(|_| bad())?),
+            };
+            steps.push(Step::Index(index));
+            rest = &after[end + 1..];
+        } else {
+            return Err(bad());
+        }
+        // chaque étape est une récursion de `get`, `set` et `remove`
+        if steps.len() > MAX_DEPTH {
+            return Err(EvaluationError::Function(format!("JSON path too deep: {path:?}")));
+        }
+    }
+    Ok(steps)
+}
+
+/// Écriture d'une clé dans un chemin, entre guillemets si elle n'est pas
+/// un simple nom
+pub fn path_key(key: &str) -> String {
+    match !key.is_empty() && key.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        true => format!(".{key}"),
+        false => format!(".\"{key}\""),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let json = Json::parse(r#" { "a" : [1, -2.5e1, true, null], "b\n": "é😀" } "#).unwrap();
+        assert_eq!(
+            json,
+            Json::Object(vec![
+                (
+                    "a".to_string(),
+                    Json::Array(vec![Json::Integer(1), Json::Real(-25.0), Json::Boolean(true), Json::Null])
+                ),
+                ("b\n".to_string(), Json::String("é😀".to_string())),
+            ])
+        );
+        assert_eq!(json.to_string(), r#"{"a":[1,-25.0,true,null],"b\n":"é😀"}"#);
+        for malformed in ["", "[1,]", "{\"a\"}", "01", "[1] x", "'a'", "\"a", "1.", "tru"] {
+            assert_eq!(Json::parse(malformed), Err(super::malformed()), "{malformed}");
+        }
+        assert!(Json::parse(&"[".repeat(1000)).is_err());
+    }
+
+    #[test]
+    fn test_paths() {
+        assert_eq!(
+            parse_path("$.a.\"b.c\"[2][#-1]"),
+            Ok(vec![
+                Step::Key("a".to_string()),
+                Step::Key("b.c".to_string()),
+                Step::Index(Index::Start(2)),
+                Step::Index(Index::End(1)),
+            ])
+        );
+        assert!(parse_path("a").is_err());
+        assert!(parse_path("$[x]").is_err());
+        // un chemin trop long épuiserait la pile
+        assert!(parse_path(&format!("${}", ".a".repeat(MAX_DEPTH))).is_ok());
+        assert!(parse_path(&format!("${}", ".a".repeat(MAX_DEPTH + 1))).is_err());
+        let mut json = Json::parse(r#"{"a":[1,2,3]}"#).unwrap();
+        assert_eq!(json.get(&parse_path("$.a[#-1]").unwrap()), Some(&Json::Integer(3)));
+        assert_eq!(json.get(&parse_path("$.b").unwrap()), None);
+        json.set(&parse_path("$.a[#]").unwrap(), Json::Integer(4), Mode::Set);
+        json.set(&parse_path("$.b.c").unwrap(), Json::Null, Mode::Insert);
+        json.set(&parse_path("$.a[0]").unwrap(), Json::Integer(0), Mode::Insert);
+        json.set(&parse_path("$.d").unwrap(), Json::Integer(0), Mode::Replace);
+        assert_eq!(json.to_string(), r#"{"a":[1,2,3,4],"b":{"c":null}}"#);
+        json.remove(&parse_path("$.a[1]").unwrap());
+        json.remove(&parse_path("$.b.c").unwrap());
+        assert_eq!(json.to_string(), r#"{"a":[1,3,4],"b":{}}"#);
+    }
+}
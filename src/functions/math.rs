@@ -0,0 +1,274 @@
+//! Fonctions mathématiques de SQLite. Un argument NULL donne NULL, comme un
+//! argument hors du domaine de la fonction (`sqrt(-1)`, `ln(0)`...). Un
+//! résultat entier qui ne tient pas sur 64 bits est une erreur.
+
+use crate::data::{Affinity, Value};
+use crate::errors::EvaluationError;
+use crate::functions::Functions;
+use std::cmp::Ordering;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+
+type Scalar = fn(&[Value]) -> Result<Value, EvaluationError>;
+
+type Unary = fn(f64) -> f64;
+
+pub fn register(functions: &mut Functions) {
+    let mut scalar = |name: &str, arities: &[i32], function: Scalar| {
+        for arity in arities {
+            functions.register_scalar(name, *arity, true, function);
+        }
+    };
+    scalar("abs", &[1], abs);
+    scalar("sign", &[1], |arguments| {
+        Ok(match number(&arguments[0]) {
+            Some(Value::Integer(value)) => Value::Integer(value.signum()),
+            Some(Value::Real(value)) => Value::Integer((value > 0.0) as i64 - (value < 0.0) as i64),
+            _ => Value::Null,
+        })
+    });
+    scalar("round", &[1, 2], round);
+    scalar("ceil", &[1], |arguments| integral(&arguments[0], f64::ceil));
+    scalar("ceiling", &[1], |arguments| integral(&arguments[0], f64::ceil));
+    scalar("floor", &[1], |arguments| integral(&arguments[0], f64::floor));
+    scalar("trunc", &[1], |arguments| integral(&arguments[0], f64::trunc));
+    scalar("mod", &[2], modulo);
+    scalar("pi", &[0], |_| Ok(Value::Real(std::f64::consts::PI)));
+    scalar("pow", &[2], |arguments| binary(arguments, f64::powf));
+    scalar("power", &[2], |arguments| binary(arguments, f64::powf));
+    scalar("atan2", &[2], |arguments| binary(arguments, f64::atan2));
+    scalar("log", &[1], |arguments| real(&arguments[0], f64::log10));
+    scalar("log", &[2], |arguments| binary(arguments, |base, value| value.log(base)));
+    let unary: [(&str, Unary); 20] = [
+        ("sqrt", f64::sqrt),
+        ("exp", f64::exp),
+        ("ln", f64::ln),
+        ("log10", f64::log10),
+        ("log2", f64::log2),
+        ("sin", f64::sin),
+        ("cos", f64::cos),
+        ("tan", f64::tan),
+        ("asin", f64::asin),
+        ("acos", f64::acos),
+        ("atan", f64::atan),
+        ("sinh", f64::sinh),
+        ("cosh", f64::cosh),
+        ("tanh", f64::tanh),
+        ("asinh", f64::asinh),
+        ("acosh", f64::acosh),
+        ("atanh", f64::atanh),
+        ("degrees", f64::to_degrees),
+        ("radians", f64::to_radians),
+        ("cot", |value| 1.0 / value.tan()),
+    ];
+    for (name, function) in unary {
+        functions.register_scalar(name, 1, true, move |arguments| real(&arguments[0], function));
+    }
+    // avec un seul argument, min et max sont les fonctions d'agrégat
+    functions.register_scalar("min", -1, true, |arguments| extremum("min", arguments, Ordering::Less));
+    functions.register_scalar("max", -1, true, |arguments| extremum("max", arguments, Ordering::Greater));
+    functions.register_scalar("random", 0, false, |_| Ok(Value::Integer(random())));
+}
+
+/// Valeur numérique d'un argument, `None` pour NULL
+fn number(value: &Value) -> Option<Value> {
+    match value.clone().cast(Affinity::Numeric) {
+        Value::Null => None,
+        number => Some(number),
+    }
+}
+
+fn to_real(value: &Value) -> Option<f64> {
+    match number(value)? {
+        Value::Integer(value) => Some(value as f64),
+        Value::Real(value) => Some(value),
+        _ => None,
+    }
+}
+
+/// Résultat flottant, NULL hors du domaine de la fonction
+fn real(value: &Value, function: impl Fn(f64) -> f64) -> Result<Value, EvaluationError> {
+    Ok(to_real(value)
+        .map(function)
+        .filter(|result| !result.is_nan())
+        .map_or(Value::Null, Value::Real))
+}
+
+fn binary(arguments: &[Value], function: impl Fn(f64, f64) -> f64) -> Result<Value, EvaluationError> {
+    Ok(match (to_real(&arguments[0]), to_real(&arguments[1])) {
+        (Some(left), Some(right)) => {
+            let result = function(left, right);
+            match result.is_nan() {
+                true => Value::Null,
+                false => Value::Real(result),
+            }
+        }
+        _ => Value::Null,
+    })
+}
+
+fn abs(arguments: &[Value]) -> Result<Value, EvaluationError> {
+    Ok(match number(&arguments[0]) {
+        // |i64::MIN| ne tient pas sur 64 bits
+        Some(Value::Integer(value)) => Value::Integer(value.checked_abs().ok_or(EvaluationError::IntegerOverflow)?),
+        Some(Value::Real(value)) => Value::Real(value.abs()),
+        _ => Value::Null,
+    })
+}
+
+/// Un entier reste entier, un flottant est arrondi sans changer de type
+fn integral(value: &Value, function: fn(f64) -> f64) -> Result<Value, EvaluationError> {
+    Ok(match number(value) {
+        Some(Value::Integer(value)) => Value::Integer(value),
+        Some(Value::Real(value)) => Value::Real(function(value)),
+        _ => Value::Null,
+    })
+}
+
+/// `round(x[, chiffres])` : arrondi au plus loin de zéro, toujours flottant
+fn round(arguments: &[Value]) -> Result<Value, EvaluationError> {
+    let digits = match arguments.get(1) {
+        Some(digits) => match number(digits) {
+            Some(Value::Integer(digits)) => digits.clamp(0, 30) as i32,
+            Some(Value::Real(digits)) => (digits as i64).clamp(0, 30) as i32,
+            _ => return Ok(Value::Null),
+        },
+        None => 0,
+    };
+    Ok(match to_real(&arguments[0]) {
+        Some(value) => {
+            let scale = 10f64.powi(digits);
+            let scaled = value * scale;
+            // au-delà, le flottant n'a plus de partie décimale
+            match scaled.abs() < 1e17 {
+                true => Value::Real(scaled.round() / scale),
+                false => Value::Real(value),
+            }
+        }
+        None => Value::Null,
+    })
+}
+
+/// `mod(x, y)` : reste entier pour deux entiers, flottant sinon, NULL pour
+/// un diviseur nul
+fn modulo(arguments: &[Value]) -> Result<Value, EvaluationError> {
+    Ok(match (number(&arguments[0]), number(&arguments[1])) {
+        (Some(_), Some(Value::Integer(0))) => Value::Null,
+        // le reste de i64::MIN par -1 vaut 0, même si le quotient déborde
+        (Some(Value::Integer(left)), Some(Value::Integer(right))) => Value::Integer(left.wrapping_rem(right)),
+        (Some(left), Some(right)) => {
+            let (left, right) = (to_real(&left).unwrap_or(0.0), to_real(&right).unwrap_or(0.0));
+            match right == 0.0 {
+                true => Value::Null,
+                false => Value::Real(left % right),
+            }
+        }
+        _ => Value::Null,
+    })
+}
+
+/// `min(x, y, ...)` et `max(x, y, ...)` : NULL si un argument est NULL
+fn extremum(name: &str, arguments: &[Value], kept: Ordering) -> Result<Value, EvaluationError> {
+    let Some(first) = arguments.first() else {
+        return Err(EvaluationError::WrongArgumentCount {
+            function: name.to_string(),
+            given: 0,
+        });
+    };
+    let mut result = first;
+    for argument in arguments {
+        match argument.compare(result) {
+            None => return Ok(Value::Null),
+            Some(ordering) if ordering == kept => result = argument,
+            Some(_) => {}
+        }
+    }
+    Ok(result.clone())
+}
+
+/// Entier pseudo-aléatoire, tiré des clés aléatoires que la bibliothèque
+/// standard crée pour chaque table de hachage
+fn random() -> i64 {
+    RandomState::new().build_hasher().finish() as i64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(name: &str, arguments: &[Value]) -> Result<Value, EvaluationError> {
+        Functions::new().call(name, arguments)
+    }
+
+    #[test]
+    fn test_math() {
+        let integer = Value::Integer;
+        let real = Value::Real;
+        assert_eq!(call("abs", &[integer(-3)]), Ok(integer(3)));
+        assert_eq!(call("abs", &[real(-2.5)]), Ok(real(2.5)));
+        assert_eq!(call("abs", &[Value::Text("-4".to_string())]), Ok(integer(4)));
+        assert_eq!(call("abs", &[Value::Null]), Ok(Value::Null));
+        assert_eq!(call("sign", &[real(-0.5)]), Ok(integer(-1)));
+        assert_eq!(call("round", &[real(2.5)]), Ok(real(3.0)));
+        assert_eq!(call("round", &[real(-2.5)]), Ok(real(-3.0)));
+        assert_eq!(call("round", &[real(1.2345), integer(2)]), Ok(real(1.23)));
+        assert_eq!(call("round", &[integer(7)]), Ok(real(7.0)));
+        assert_eq!(call("ceil", &[real(1.2)]), Ok(real(2.0)));
+        assert_eq!(call("floor", &[real(-1.2)]), Ok(real(-2.0)));
+        assert_eq!(call("floor", &[integer(5)]), Ok(integer(5)));
+        assert_eq!(call("trunc", &[real(-1.7)]), Ok(real(-1.0)));
+        assert_eq!(call("mod", &[integer(7), integer(3)]), Ok(integer(1)));
+        assert_eq!(call("mod", &[real(7.5), integer(2)]), Ok(real(1.5)));
+        assert_eq!(call("mod", &[integer(7), integer(0)]), Ok(Value::Null));
+        assert_eq!(call("sqrt", &[integer(16)]), Ok(real(4.0)));
+        assert_eq!(call("sqrt", &[integer(-1)]), Ok(Value::Null));
+        assert_eq!(call("pow", &[integer(2), integer(10)]), Ok(real(1024.0)));
+        assert_eq!(call("exp", &[integer(0)]), Ok(real(1.0)));
+        assert_eq!(call("ln", &[integer(1)]), Ok(real(0.0)));
+        assert_eq!(call("log10", &[integer(1000)]), Ok(real(3.0)));
+        assert_eq!(call("log", &[integer(100)]), Ok(real(2.0)));
+        assert_eq!(call("log", &[integer(2), integer(8)]), Ok(real(3.0)));
+        assert_eq!(call("cos", &[integer(0)]), Ok(real(1.0)));
+        assert_eq!(call("degrees", &[call("pi", &[]).unwrap()]), Ok(real(180.0)));
+        assert_eq!(call("atan2", &[integer(0), integer(1)]), Ok(real(0.0)));
+        assert!(matches!(call("random", &[]), Ok(Value::Integer(_))));
+    }
+
+    #[test]
+    fn test_min_max() {
+        let integer = Value::Integer;
+        let text = Value::Text("a".to_string());
+        assert_eq!(call("min", &[integer(3), Value::Real(1.5), integer(2)]), Ok(Value::Real(1.5)));
+        // un texte est plus grand que tout nombre
+        assert_eq!(call("max", &[integer(3), text.clone()]), Ok(text));
+        assert_eq!(call("max", &[integer(3), Value::Null]), Ok(Value::Null));
+        // un seul argument : la fonction d'agrégat
+        assert_eq!(
+            call("min", &[integer(3)]),
+            Err(EvaluationError::MisusedAggregate("min".to_string()))
+        );
+        assert_eq!(
+            call("max", &[]),
+            Err(EvaluationError::WrongArgumentCount {
+                function: "max".to_string(),
+                given: 0
+            })
+        );
+    }
+
+    #[test]
+    fn test_overflow() {
+        assert_eq!(call("abs", &[Value::Integer(i64::MIN)]), Err(EvaluationError::IntegerOverflow));
+        assert_eq!(
+            call("mod", &[Value::Integer(i64::MIN), Value::Integer(-1)]),
+            Ok(Value::Integer(0))
+        );
+        // une erreur de la requête, pas une panique
+        let mut database = crate::database::Database::new();
+        let command = crate::commands::parse_sql("select abs(cast('-9223372036854775808' as integer))").unwrap();
+        assert_eq!(
+            command.run(&mut database),
+            Err(crate::errors::ExecutionError::Evaluation(EvaluationError::IntegerOverflow))
+        );
+    }
+}
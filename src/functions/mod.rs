@@ -0,0 +1,292 @@
+//! Fonctions appelables depuis les expressions SQL : celles fournies par la
+//! base et celles enregistrées par l'application.
+
+use crate::data::Value;
+use crate::errors::EvaluationError;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+mod aggregate;
+mod datetime;
+mod json;
+mod math;
+mod string;
+
+/// Fonction scalaire : une valeur calculée à partir des arguments d'un appel
+pub type ScalarFunction = Arc<dyn Fn(&[Value]) -> Result<Value, EvaluationError> + Send + Sync>;
+
+/// Fabrique d'accumulateurs d'une fonction d'agrégat
+pub type AggregateFunction = Arc<dyn Fn() -> Box<dyn Accumulator> + Send + Sync>;
+
+/// Fonction JSON de la base : elle reçoit aussi, pour chaque argument, s'il
+/// s'agit d'un texte JSON produit par une autre fonction JSON, à inclure tel
+/// quel plutôt que comme une chaîne (ce que SQLite marque d'un sous-type)
+pub type JsonFunction = fn(&[Value], &[bool]) -> Result<Value, EvaluationError>;
+
+/// Fonction table : les lignes produites à partir des arguments de l'appel
+pub type TableFunction = Arc<dyn Fn(&[Value]) -> Result<Vec<Vec<Value>>, EvaluationError> + Send + Sync>;
+
+/// État d'une fonction d'agrégat pendant le parcours des lignes
+pub trait Accumulator: Send {
+    /// Prend en compte les arguments calculés sur une ligne
+    fn step(&mut self, arguments: &[Value]) -> Result<(), EvaluationError>;
+    /// Comme `step`, en sachant quels arguments sont déjà du JSON
+    fn step_json(&mut self, arguments: &[Value], json: &[bool]) -> Result<(), EvaluationError> {
+        let _ = json;
+        self.step(arguments)
+    }
+    /// Résultat, une fois toutes les lignes vues
+    fn finalize(self: Box<Self>) -> Result<Value, EvaluationError>;
+}
+
+#[derive(Clone)]
+pub enum Function {
+    /// `deterministic` : le résultat ne dépend que des arguments, un appel
+    /// sur des constantes peut donc n'être calculé qu'une fois
+    Scalar {
+        function: ScalarFunction,
+        deterministic: bool,
+    },
+    Aggregate(AggregateFunction),
+    Json(JsonFunction),
+}
+
+/// Source de lignes appelée dans la clause FROM, comme `json_each(...)`
+#[derive(Clone)]
+pub struct TableValued {
+    pub columns: Vec<String>,
+    pub function: TableFunction,
+}
+
+/// Variante d'une fonction pour un nombre d'arguments, `None` pour un
+/// nombre quelconque
+#[derive(Clone)]
+struct Overload {
+    arity: Option<usize>,
+    function: Function,
+    /// le texte renvoyé est du JSON
+    json: bool,
+}
+
+/// Fonctions connues d'une base, par nom
+#[derive(Clone)]
+pub struct Functions {
+    functions: HashMap<String, Vec<Overload>>,
+    tables: HashMap<String, TableValued>,
+}
+
+impl Default for Functions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Functions {
+    /// Fonctions fournies par la base
+    pub fn new() -> Self {
+        let mut functions = Self {
+            functions: HashMap::new(),
+            tables: HashMap::new(),
+        };
+        aggregate::register(&mut functions);
+        string::register(&mut functions);
+        datetime::register(&mut functions);
+        math::register(&mut functions);
+        json::register(&mut functions);
+        // le MATCH d'une table fts est traité par la table elle-même
+        functions.register_scalar("match", 2, false, |_| Err(crate::fts::misused_match()));
+        functions
+    }
+
+    /// Enregistre une fonction, qui remplace celle de même nom et de même
+    /// nombre d'arguments. Un nombre négatif accepte n'importe quel nombre
+    /// d'arguments, comme dans SQLite.
+    pub fn register(&mut self, name: &str, arity: i32, function: Function) {
+        let arity = usize::try_from(arity).ok();
+        let overloads = self.functions.entry(name.to_ascii_lowercase()).or_default();
+        overloads.retain(|overload| overload.arity != arity);
+        overloads.push(Overload {
+            arity,
+            function,
+            json: false,
+        });
+    }
+
+    pub fn register_scalar(
+        &mut self,
+        name: &str,
+        arity: i32,
+        deterministic: bool,
+        function: impl Fn(&[Value]) -> Result<Value, EvaluationError> + Send + Sync + 'static,
+    ) {
+        let function = Function::Scalar {
+            function: Arc::new(function),
+            deterministic,
+        };
+        self.register(name, arity, function);
+    }
+
+    /// Enregistre une fonction d'agrégat décrite par son état initial, son
+    /// calcul sur chaque ligne et son résultat final
+    pub fn register_aggregate<S: Send + 'static>(
+        &mut self,
+        name: &str,
+        arity: i32,
+        init: impl Fn() -> S + Send + Sync + 'static,
+        step: impl Fn(&mut S, &[Value]) -> Result<(), EvaluationError> + Send + Sync + 'static,
+        finalize: impl Fn(S) -> Result<Value, EvaluationError> + Send + Sync + 'static,
+    ) {
+        let step = Arc::new(step);
+        let finalize = Arc::new(finalize);
+        let factory = move || -> Box<dyn Accumulator> {
+            Box::new(ClosureAccumulator {
+                state: init(),
+                step: step.clone(),
+                finalize: finalize.clone(),
+            })
+        };
+        self.register(name, arity, Function::Aggregate(Arc::new(factory)));
+    }
+
+    /// Enregistre une fonction table, appelable dans la clause FROM
+    pub fn register_table(
+        &mut self,
+        name: &str,
+        columns: &[&str],
+        function: impl Fn(&[Value]) -> Result<Vec<Vec<Value>>, EvaluationError> + Send + Sync + 'static,
+    ) {
+        let table = TableValued {
+            columns: columns.iter().map(|column| column.to_string()).collect(),
+            function: Arc::new(function),
+        };
+        self.tables.insert(name.to_ascii_lowercase(), table);
+    }
+
+    pub fn table(&self, name: &str) -> Result<&TableValued, EvaluationError> {
+        self.tables
+            .get(&name.to_ascii_lowercase())
+            .ok_or_else(|| EvaluationError::UnknownFunction(name.to_string()))
+    }
+
+    /// Marque comme du JSON le texte renvoyé par la variante de la fonction
+    /// enregistrée avec ce nombre d'arguments
+    fn returns_json(&mut self, name: &str, arity: i32) {
+        let arity = usize::try_from(arity).ok();
+        if let Some(overload) = self
+            .functions
+            .get_mut(&name.to_ascii_lowercase())
+            .and_then(|overloads| overloads.iter_mut().find(|overload| overload.arity == arity))
+        {
+            overload.json = true;
+        }
+    }
+
+    /// Le texte renvoyé par la fonction appelée avec ce nombre d'arguments
+    /// est du JSON
+    pub fn is_json(&self, name: &str, count: usize) -> bool {
+        self.overload(name, count).is_ok_and(|overload| overload.json)
+    }
+
+    /// Fonction appelée avec ce nombre d'arguments, la variante de même
+    /// nombre d'arguments passe avant celle qui les accepte tous
+    pub fn find(&self, name: &str, count: usize) -> Result<&Function, EvaluationError> {
+        self.overload(name, count).map(|overload| &overload.function)
+    }
+
+    fn overload(&self, name: &str, count: usize) -> Result<&Overload, EvaluationError> {
+        let overloads = self
+            .functions
+            .get(&name.to_ascii_lowercase())
+            .ok_or_else(|| EvaluationError::UnknownFunction(name.to_string()))?;
+        overloads
+            .iter()
+            .find(|overload| overload.arity == Some(count))
+            .or_else(|| overloads.iter().find(|overload| overload.arity.is_none()))
+            .ok_or_else(|| EvaluationError::WrongArgumentCount {
+                function: name.to_string(),
+                given: count,
+            })
+    }
+
+    pub fn is_aggregate(&self, name: &str, count: usize) -> Result<bool, EvaluationError> {
+        Ok(matches!(self.find(name, count)?, Function::Aggregate(_)))
+    }
+
+    /// Appelle une fonction scalaire, les textes passés à une fonction JSON
+    /// sont des chaînes
+    pub fn call(&self, name: &str, arguments: &[Value]) -> Result<Value, EvaluationError> {
+        self.call_json(name, arguments, &vec![false; arguments.len()])
+    }
+
+    /// Appelle une fonction scalaire en disant quels arguments sont du JSON
+    pub fn call_json(&self, name: &str, arguments: &[Value], json: &[bool]) -> Result<Value, EvaluationError> {
+        match self.find(name, arguments.len())? {
+            Function::Scalar { function, .. } => function(arguments),
+            Function::Json(function) => function(arguments, json),
+            Function::Aggregate(_) => Err(EvaluationError::MisusedAggregate(name.to_string())),
+        }
+    }
+}
+
+struct ClosureAccumulator<S, Step, Finalize> {
+    state: S,
+    step: Arc<Step>,
+    finalize: Arc<Finalize>,
+}
+
+impl<S, Step, Finalize> Accumulator for ClosureAccumulator<S, Step, Finalize>
+where
+    S: Send,
+    Step: Fn(&mut S, &[Value]) -> Result<(), EvaluationError> + Send + Sync,
+    Finalize: Fn(S) -> Result<Value, EvaluationError> + Send + Sync,
+{
+    fn step(&mut self, arguments: &[Value]) -> Result<(), EvaluationError> {
+        (self.step)(&mut self.state, arguments)
+    }
+
+    fn finalize(self: Box<Self>) -> Result<Value, EvaluationError> {
+        (self.finalize)(self.state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_overloads() {
+        let mut functions = Functions::new();
+        functions.register_scalar("answer", 0, true, |_| Ok(Value::Integer(42)));
+        functions.register_scalar("answer", -1, true, |arguments| {
+            Ok(Value::Integer(arguments.len() as i64))
+        });
+        assert_eq!(functions.call("ANSWER", &[]), Ok(Value::Integer(42)));
+        assert_eq!(functions.call("answer", &[Value::Null, Value::Null]), Ok(Value::Integer(2)));
+        // un nouvel enregistrement remplace l'ancien
+        functions.register_scalar("answer", 0, true, |_| Ok(Value::Integer(0)));
+        assert_eq!(functions.call("answer", &[]), Ok(Value::Integer(0)));
+        assert_eq!(
+            functions.call("question", &[]),
+            Err(EvaluationError::UnknownFunction("question".to_string()))
+        );
+        functions.register_scalar("one", 1, true, |arguments| Ok(arguments[0].clone()));
+        assert_eq!(
+            functions.call("one", &[]),
+            Err(EvaluationError::WrongArgumentCount {
+                function: "one".to_string(),
+                given: 0
+            })
+        );
+        assert_eq!(
+            functions.call("count", &[]),
+            Err(EvaluationError::MisusedAggregate("count".to_string()))
+        );
+        // remplacer une variante ne change pas ce que renvoient les autres
+        assert!(functions.is_json("json", 1));
+        functions.register_scalar("json", 2, true, |_| Ok(Value::Null));
+        assert!(functions.is_json("json", 1));
+        assert!(!functions.is_json("json", 2));
+        functions.register_scalar("json", 1, true, |_| Ok(Value::Null));
+        assert!(!functions.is_json("json", 1));
+    }
+}
@@ -0,0 +1,495 @@
+//! Fonctions de chaînes de SQLite. Les positions et les longueurs comptent
+//! des caractères pour un texte, des octets pour un blob : `length('tête')`
+//! vaut 4, alors que `"tête".len()` vaut 5. Un argument NULL donne NULL.
+
+use crate::commands::Expression;
+use crate::data::{Affinity, Value};
+use crate::errors::EvaluationError;
+use crate::functions::Functions;
+
+type Scalar = fn(&[Value]) -> Result<Value, EvaluationError>;
+
+pub fn register(functions: &mut Functions) {
+    let mut scalar = |name: &str, arities: &[i32], function: Scalar| {
+        for arity in arities {
+            functions.register_scalar(name, *arity, true, function);
+        }
+    };
+    scalar("length", &[1], length);
+    scalar("lower", &[1], |arguments| map_text(&arguments[0], |text| text.to_lowercase()));
+    scalar("upper", &[1], |arguments| map_text(&arguments[0], |text| text.to_uppercase()));
+    scalar("substr", &[2, 3], substr);
+    scalar("substring", &[2, 3], substr);
+    scalar("trim", &[1, 2], |arguments| trim(arguments, true, true));
+    scalar("ltrim", &[1, 2], |arguments| trim(arguments, true, false));
+    scalar("rtrim", &[1, 2], |arguments| trim(arguments, false, true));
+    scalar("replace", &[3], replace);
+    scalar("instr", &[2], instr);
+    scalar("printf", &[-1], printf);
+    scalar("format", &[-1], printf);
+    scalar("hex", &[1], hex);
+    scalar("quote", &[1], |arguments| {
+        Ok(Value::Text(Expression::Literal(arguments[0].clone()).to_string()))
+    });
+    scalar("char", &[-1], characters);
+    scalar("unicode", &[1], unicode);
+}
+
+/// Texte d'une valeur, `None` pour NULL
+fn text(value: &Value) -> Option<String> {
+    match value.clone().cast(Affinity::Text) {
+        Value::Text(text) => Some(text),
+        _ => None,
+    }
+}
+
+/// Valeur entière d'un argument, `None` pour NULL
+fn integer(value: &Value) -> Option<i64> {
+    match value.clone().cast(Affinity::Integer) {
+        Value::Integer(value) => Some(value),
+        _ => None,
+    }
+}
+
+fn map_text(value: &Value, map: impl Fn(&str) -> String) -> Result<Value, EvaluationError> {
+    Ok(text(value).map_or(Value::Null, |text| Value::Text(map(&text))))
+}
+
+fn length(arguments: &[Value]) -> Result<Value, EvaluationError> {
+    Ok(match &arguments[0] {
+        Value::Null => Value::Null,
+        Value::Blob(data) => Value::Integer(data.len() as i64),
+        value => Value::Integer(text(value).unwrap_or_default().chars().count() as i64),
+    })
+}
+
+/// `substr(x, début[, longueur])` : le début compte à partir de 1, ou de la
+/// fin s'il est négatif, une longueur négative prend ce qui précède
+fn substr(arguments: &[Value]) -> Result<Value, EvaluationError> {
+    let Some(start) = integer(&arguments[1]) else {
+        return Ok(Value::Null);
+    };
+    let length = match arguments.get(2) {
+        Some(length) => match integer(length) {
+            Some(length) => length,
+            None => return Ok(Value::Null),
+        },
+        None => i64::MAX,
+    };
+    match &arguments[0] {
+        Value::Null => Ok(Value::Null),
+        Value::Blob(data) => Ok(Value::Blob(data[range(data.len(), start, length)].to_vec())),
+        value => {
+            let chars = text(value).unwrap_or_default().chars().collect::<Vec<_>>();
+            Ok(Value::Text(chars[range(chars.len(), start, length)].iter().collect()))
+        }
+    }
+}
+
+/// Positions retenues par `substr`, selon les règles de SQLite
+fn range(size: usize, start: i64, length: i64) -> std::ops::Range<usize> {
+    let size = size as i64;
+    let (mut start, mut length) = (start, length);
+    let backwards = length < 0;
+    if backwards {
+        length = length.saturating_neg();
+    }
+    if start < 0 {
+        start += size;
+        if start < 0 {
+            length = (length + start).max(0);
+            start = 0;
+        }
+    } else if start > 0 {
+        start -= 1;
+    } else if length > 0 {
+        length -= 1;
+    }
+    if backwards {
+        start -= length;
+        if start < 0 {
+            length += start;
+            start = 0;
+        }
+    }
+    let start = start.min(size);
+    let end = start.saturating_add(length.max(0)).min(size);
+    start as usize..end as usize
+}
+
+/// Retire aux bords les caractères donnés, des espaces par défaut
+fn trim(arguments: &[Value], left: bool, right: bool) -> Result<Value, EvaluationError> {
+    let characters = match arguments.get(1) {
+        Some(characters) => match text(characters) {
+            Some(characters) => characters.chars().collect::<Vec<_>>(),
+            None => return Ok(Value::Null),
+        },
+        None => vec![' '],
+    };
+    map_text(&arguments[0], |text| {
+        let trimmed = match left {
+            true => text.trim_start_matches(&characters[..]),
+            false => text,
+        };
+        match right {
+            true => trimmed.trim_end_matches(&characters[..]).to_string(),
+            false => trimmed.to_string(),
+        }
+    })
+}
+
+fn replace(arguments: &[Value]) -> Result<Value, EvaluationError> {
+    let (Some(text), Some(pattern), Some(replacement)) =
+        (text(&arguments[0]), text(&arguments[1]), text(&arguments[2]))
+    else {
+        return Ok(Value::Null);
+    };
+    match pattern.is_empty() {
+        true => Ok(Value::Text(text)),
+        false => Ok(Value::Text(text.replace(&pattern, &replacement))),
+    }
+}
+
+/// Position de la première occurrence, à partir de 1, 0 si absente
+fn instr(arguments: &[Value]) -> Result<Value, EvaluationError> {
+    let position = match (&arguments[0], &arguments[1]) {
+        (Value::Null, _) | (_, Value::Null) => return Ok(Value::Null),
+        (Value::Blob(data), Value::Blob(pattern)) => match pattern.is_empty() {
+            true => Some(0),
+            false => data.windows(pattern.len()).position(|window| window == &pattern[..]),
+        },
+        (haystack, needle) => {
+            let haystack = text(haystack).unwrap_or_default();
+            haystack
+                .find(&text(needle).unwrap_or_default())
+                .map(|index| haystack[..index].chars().count())
+        }
+    };
+    Ok(Value::Integer(position.map_or(0, |position| position as i64 + 1)))
+}
+
+/// Écriture hexadécimale des octets, ceux de l'UTF-8 pour un texte
+fn hex(arguments: &[Value]) -> Result<Value, EvaluationError> {
+    let bytes = match &arguments[0] {
+        Value::Null => vec![],
+        Value::Blob(data) => data.clone(),
+        value => text(value).unwrap_or_default().into_bytes(),
+    };
+    Ok(Value::Text(bytes.iter().map(|byte| format!("{byte:02X}")).collect()))
+}
+
+/// Texte formé des caractères de ces points de code, U+FFFD pour un point
+/// de code invalide
+fn characters(arguments: &[Value]) -> Result<Value, EvaluationError> {
+    let text = arguments
+        .iter()
+        .filter_map(integer)
+        .map(|code| {
+            u32::try_from(code)
+                .ok()
+                .and_then(char::from_u32)
+                .unwrap_or(char::REPLACEMENT_CHARACTER)
+        })
+        .collect();
+    Ok(Value::Text(text))
+}
+
+/// Point de code du premier caractère
+fn unicode(arguments: &[Value]) -> Result<Value, EvaluationError> {
+    Ok(text(&arguments[0])
+        .and_then(|text| text.chars().next())
+        .map_or(Value::Null, |first| Value::Integer(first as i64)))
+}
+
+/// Taille maximale du texte produit par `printf`, comme `SQLITE_MAX_LENGTH`
+const MAX_LENGTH: usize = 1_000_000_000;
+
+/// Précision maximale d'un réel, comme `SQLITE_FP_PRECISION_LIMIT`
+const MAX_PRECISION: usize = 100_000_000;
+
+/// Options d'une conversion de `printf` : `%[drapeaux][largeur][.précision]type`
+#[derive(Default)]
+struct Specification {
+    left: bool,
+    plus: bool,
+    space: bool,
+    zero: bool,
+    alternate: bool,
+    width: usize,
+    precision: Option<usize>,
+}
+
+impl Specification {
+    /// Complète le texte jusqu'à la largeur demandée
+    fn pad(&self, text: String) -> String {
+        let size = text.chars().count();
+        if size >= self.width {
+            return text;
+        }
+        let padding = self.width - size;
+        match (self.left, self.zero) {
+            (true, _) => format!("{text}{}", " ".repeat(padding)),
+            // les zéros se placent après le signe
+            (false, true) => {
+                let sign = text.starts_with(['-', '+', ' ']) as usize;
+                format!("{}{}{}", &text[..sign], "0".repeat(padding), &text[sign..])
+            }
+            (false, false) => format!("{}{text}", " ".repeat(padding)),
+        }
+    }
+
+    fn sign(&self, negative: bool) -> &'static str {
+        match (negative, self.plus, self.space) {
+            (true, _, _) => "-",
+            (false, true, _) => "+",
+            (false, false, true) => " ",
+            _ => "",
+        }
+    }
+}
+
+/// `printf(format, ...)` : les conversions `%d %i %u %f %e %E %g %G %x %X %o
+/// %c %s %q %Q %w %%` de SQLite. Un argument manquant vaut NULL.
+fn printf(arguments: &[Value]) -> Result<Value, EvaluationError> {
+    let Some(format) = arguments.first().and_then(text) else {
+        return Ok(Value::Null);
+    };
+    let mut arguments = arguments[1..].iter();
+    let mut next = || arguments.next().cloned().unwrap_or(Value::Null);
+    let mut output = String::new();
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            output.push(c);
+            continue;
+        }
+        let mut specification = Specification::default();
+        while let Some(flag) = chars.next_if(|c| "-+ 0#".contains(*c)) {
+            match flag {
+                '-' => specification.left = true,
+                '+' => specification.plus = true,
+                ' ' => specification.space = true,
+                '0' => specification.zero = true,
+                _ => specification.alternate = true,
+            }
+        }
+        specification.width = number(&mut chars).unwrap_or(0);
+        if chars.next_if_eq(&'.').is_some() {
+            specification.precision = Some(number(&mut chars).unwrap_or(0));
+        }
+        let Some(conversion) = chars.next() else {
+            break;
+        };
+        // le texte complété jusqu'à la largeur ne tiendrait pas : il n'est
+        // pas produit
+        if output.len() + specification.width > MAX_LENGTH {
+            return Err(too_big());
+        }
+        let converted = match conversion {
+            '%' => "%".to_string(),
+            'd' | 'i' | 'u' => {
+                let value = integer(&next()).unwrap_or(0);
+                let digits = value.unsigned_abs().to_string();
+                let digits = match specification.precision {
+                    Some(precision) if output.len() + precision > MAX_LENGTH => return Err(too_big()),
+                    Some(precision) => format!("{digits:0>precision$}"),
+                    None => digits,
+                };
+                format!("{}{digits}", specification.sign(value < 0))
+            }
+            'x' | 'X' | 'o' => {
+                let value = integer(&next()).unwrap_or(0);
+                let (digits, prefix) = match conversion {
+                    'x' => (format!("{value:x}"), "0x"),
+                    'X' => (format!("{value:X}"), "0X"),
+                    _ => (format!("{value:o}"), "0"),
+                };
+                match specification.alternate && value != 0 {
+                    true => format!("{prefix}{digits}"),
+                    false => digits,
+                }
+            }
+            'f' | 'e' | 'E' | 'g' | 'G' => {
+                let value = match next().cast(Affinity::Real) {
+                    Value::Real(value) => value,
+                    _ => 0.0,
+                };
+                let precision = specification.precision.unwrap_or(6).min(MAX_PRECISION);
+                let digits = match conversion {
+                    'f' => format!("{:.precision$}", value.abs()),
+                    'e' | 'E' => exponent(value.abs(), precision),
+                    _ => general(value.abs(), precision, specification.alternate),
+                };
+                let digits = match conversion.is_ascii_uppercase() {
+                    true => digits.to_uppercase(),
+                    false => digits,
+                };
+                format!("{}{digits}", specification.sign(value.is_sign_negative() && value != 0.0))
+            }
+            'c' => text(&next()).and_then(|text| text.chars().next()).map(String::from).unwrap_or_default(),
+            's' | 'z' => {
+                let text = text(&next()).unwrap_or_default();
+                match specification.precision {
+                    Some(precision) => text.chars().take(precision).collect(),
+                    None => text,
+                }
+            }
+            // texte protégé pour être inclus dans une commande SQL
+            'q' | 'Q' | 'w' => {
+                let (value, quote) = (next(), if conversion == 'w' { '"' } else { '\'' });
+                match (conversion, text(&value)) {
+                    ('Q', None) => "NULL".to_string(),
+                    (_, None) => "(NULL)".to_string(),
+                    (_, Some(text)) => {
+                        let escaped = text.replace(quote, &format!("{quote}{quote}"));
+                        match conversion {
+                            'Q' => format!("'{escaped}'"),
+                            _ => escaped,
+                        }
+                    }
+                }
+            }
+            // conversion inconnue : recopiée telle quelle
+            other => format!("%{other}"),
+        };
+        output.push_str(&specification.pad(converted));
+        if output.len() > MAX_LENGTH {
+            return Err(too_big());
+        }
+    }
+    Ok(Value::Text(output))
+}
+
+fn too_big() -> EvaluationError {
+    EvaluationError::Function("string or blob too big".to_string())
+}
+
+/// Nombre écrit dans le format, `None` s'il n'y en a pas. Comme SQLite, il
+/// est borné à `i32::MAX`.
+fn number(chars: &mut std::iter::Peekable<std::str::Chars>) -> Option<usize> {
+    let mut number = None;
+    while let Some(digit) = chars.next_if(char::is_ascii_digit) {
+        let value = number.unwrap_or(0_usize).saturating_mul(10).saturating_add(digit.to_digit(10).unwrap_or(0) as usize);
+        number = Some(value.min(i32::MAX as usize));
+    }
+    number
+}
+
+/// Écriture scientifique à la façon du C : `1.500000e+02`
+fn exponent(value: f64, precision: usize) -> String {
+    let written = format!("{value:.precision$e}");
+    let (mantissa, exponent) = written.split_once('e').unwrap_or((&written, "0"));
+    let exponent = exponent.parse::<i32>().unwrap_or(0);
+    format!(
+        "{mantissa}e{}{:02}",
+        if exponent < 0 { '-' } else { '+' },
+        exponent.abs()
+    )
+}
+
+/// `%g` : la plus courte des écritures décimale et scientifique, sans les
+/// zéros de fin sauf avec le drapeau `#`
+fn general(value: f64, precision: usize, alternate: bool) -> String {
+    let precision = precision.max(1);
+    if value == 0.0 {
+        return "0".to_string();
+    }
+    let exponent_value = value.log10().floor() as i64;
+    let strip = |written: String| match alternate || !written.contains('.') {
+        true => written,
+        false => written.trim_end_matches('0').trim_end_matches('.').to_string(),
+    };
+    if exponent_value < -4 || exponent_value >= precision as i64 {
+        let written = exponent(value, precision - 1);
+        let (mantissa, exponent) = written.split_once('e').unwrap_or((&written, ""));
+        format!("{}e{exponent}", strip(mantissa.to_string()))
+    } else {
+        let decimals = (precision as i64 - 1 - exponent_value).max(0) as usize;
+        strip(format!("{value:.decimals$}"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn call(name: &str, arguments: &[Value]) -> Value {
+        Functions::new().call(name, arguments).expect("call failed")
+    }
+
+    fn text(text: &str) -> Value {
+        Value::Text(text.to_string())
+    }
+
+    #[test]
+    fn test_characters() {
+        // des caractères, pas des octets
+        assert_eq!(call("length", &[text("tête")]), Value::Integer(4));
+        assert_eq!(call("length", &[Value::Blob(vec![0, 1, 2])]), Value::Integer(3));
+        assert_eq!(call("length", &[Value::Real(1.5)]), Value::Integer(3));
+        assert_eq!(call("length", &[Value::Null]), Value::Null);
+        assert_eq!(call("upper", &[text("tête")]), text("TÊTE"));
+        assert_eq!(call("lower", &[text("ÉTÉ")]), text("été"));
+        assert_eq!(call("substr", &[text("héllo"), Value::Integer(2), Value::Integer(3)]), text("éll"));
+        assert_eq!(call("substr", &[text("héllo"), Value::Integer(-3)]), text("llo"));
+        assert_eq!(call("substr", &[text("héllo"), Value::Integer(0), Value::Integer(2)]), text("h"));
+        assert_eq!(call("substr", &[text("héllo"), Value::Integer(4), Value::Integer(-2)]), text("él"));
+        assert_eq!(call("substr", &[text("héllo"), Value::Integer(9)]), text(""));
+        assert_eq!(call("substr", &[Value::Blob(vec![1, 2, 3]), Value::Integer(2)]), Value::Blob(vec![2, 3]));
+        assert_eq!(call("instr", &[text("tête-à-tête"), text("à")]), Value::Integer(6));
+        assert_eq!(call("instr", &[text("abc"), text("z")]), Value::Integer(0));
+        assert_eq!(call("instr", &[Value::Null, text("z")]), Value::Null);
+        assert_eq!(call("unicode", &[text("été")]), Value::Integer(0xE9));
+        assert_eq!(call("unicode", &[text("")]), Value::Null);
+        assert_eq!(
+            call("char", &[Value::Integer(0x74), Value::Integer(0xEA), Value::Integer(-1)]),
+            text("tê\u{FFFD}")
+        );
+    }
+
+    #[test]
+    fn test_transformations() {
+        assert_eq!(call("trim", &[text("  a b  ")]), text("a b"));
+        assert_eq!(call("ltrim", &[text("xxaxx"), text("x")]), text("axx"));
+        assert_eq!(call("rtrim", &[text("aéé"), text("é")]), text("a"));
+        assert_eq!(call("replace", &[text("tête"), text("ê"), text("e")]), text("tete"));
+        assert_eq!(call("replace", &[text("abc"), text(""), text("x")]), text("abc"));
+        assert_eq!(call("hex", &[text("ê")]), text("C3AA"));
+        assert_eq!(call("hex", &[Value::Blob(vec![0, 255])]), text("00FF"));
+        assert_eq!(call("quote", &[text("it's")]), text("'it''s'"));
+        assert_eq!(call("quote", &[Value::Blob(vec![1])]), text("X'01'"));
+        assert_eq!(call("quote", &[Value::Null]), text("NULL"));
+        assert_eq!(call("quote", &[Value::Integer(3)]), text("3"));
+    }
+
+    #[test]
+    fn test_printf() {
+        let printf = |format: &str, arguments: &[Value]| {
+            let mut all = vec![text(format)];
+            all.extend_from_slice(arguments);
+            call("printf", &all)
+        };
+        assert_eq!(
+            printf("%d-%5d|%-5d|%05d|%+d", &[Value::Integer(1), Value::Integer(-42), Value::Integer(7), Value::Integer(-7), Value::Integer(3)]),
+            text("1-  -42|7    |-0007|+3")
+        );
+        assert_eq!(printf("%.2f %e", &[Value::Real(1.005), Value::Real(150.0)]), text("1.00 1.500000e+02"));
+        assert_eq!(printf("%g %g %g", &[Value::Real(0.0001), Value::Real(1e20), Value::Real(2.5)]), text("0.0001 1e+20 2.5"));
+        assert_eq!(printf("%x %X %#o", &[Value::Integer(255), Value::Integer(255), Value::Integer(8)]), text("ff FF 010"));
+        assert_eq!(printf("%s|%.2s|%6s", &[text("tête"), text("tête"), text("été")]), text("tête|tê|   été"));
+        assert_eq!(printf("%q %Q %Q", &[text("it's"), text("x"), Value::Null]), text("it''s 'x' NULL"));
+        assert_eq!(printf("%c%%", &[text("été")]), text("é%"));
+        // un argument manquant vaut NULL
+        assert_eq!(printf("%d %s!", &[]), text("0 !"));
+        assert_eq!(call("format", &[text("%s"), Value::Integer(5)]), text("5"));
+        // les largeurs démesurées sont refusées plutôt que d'épuiser la mémoire
+        let functions = Functions::new();
+        let huge = |format: &str| functions.call("printf", &[text(format), Value::Integer(1)]);
+        assert_eq!(huge("%99999999999999999999999d"), Err(too_big()));
+        assert_eq!(huge("%.2147483647d"), Err(too_big()));
+        // le texte déjà produit compte, avant que le remplissage soit écrit
+        assert_eq!(huge("0123456789%999999995d"), Err(too_big()));
+        assert_eq!(huge("0123456789%.999999995d"), Err(too_big()));
+        assert_eq!(huge("%.99999999999999999999s"), Ok(text("1")));
+    }
+}
@@ -3,14 +3,31 @@ use std::io::Write;
 use crate::commands::{parse, Execute};
 use crate::database::Database;
 
+// les chemins générés par les dérivations désignent la crate par son nom
+extern crate self as rusty_sqlite_tutorial;
+
 mod commands;
-mod data;
-mod errors;
+pub mod connection;
+pub mod data;
+pub mod errors;
+pub mod functions;
+mod fts;
+mod mvcc;
 pub mod database;
+mod pager;
+pub mod row;
 mod table;
+mod transaction;
+mod vfs;
+pub mod vtab;
+mod wal;
 
 pub fn run() -> Result<(), Box<dyn Error>> {
-    let mut database = Database::new();
+    // la base est conservée dans le fichier passé en argument, en mémoire sinon
+    let mut database = match std::env::args().nth(1) {
+        Some(path) => Database::open(path)?,
+        None => Database::new(),
+    };
     loop {
         print!("db > ");
         std::io::stdout().flush()?;
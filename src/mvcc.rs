@@ -0,0 +1,151 @@
+use crate::data::{Record, TableName};
+use crate::database::{Database, RecordCursor};
+use crate::errors::{CreationError, InsertionError, SelectError, StorageError, TransactionError};
+use crate::table::{TableState, TableVersion};
+use std::collections::HashMap;
+
+/// Vue figée de la base telle qu'elle était au dernier commit.
+///
+/// Elle se lit sans verrou : les écritures faites ensuite dans la base ne
+/// la modifient pas.
+#[derive(Clone)]
+pub struct ReadView {
+    tables: HashMap<TableName, TableVersion>,
+}
+
+impl ReadView {
+    pub fn new(tables: HashMap<TableName, TableVersion>) -> Self {
+        Self { tables }
+    }
+
+    pub fn state(&self, table_name: &TableName) -> Option<TableState> {
+        self.tables.get(table_name).map(TableVersion::state)
+    }
+
+    pub fn cursor(&self, table_name: TableName) -> Result<RecordCursor<'_>, SelectError> {
+        match self.tables.get(&table_name) {
+            Some(table) => match table_name {
+                TableName::User => Ok(RecordCursor::User(table.cursor())),
+                TableName::Car => Ok(RecordCursor::Car(table.cursor())),
+                // les lignes d'une table virtuelle ne sont pas des enregistrements
+                TableName::Virtual(_) => Err(SelectError::TableNotExist(table_name)),
+            },
+            None => Err(SelectError::TableNotExist(table_name))?,
+        }
+    }
+
+    pub fn select(&self, table_name: TableName) -> Result<Vec<Record>, SelectError> {
+        self.cursor(table_name)?.collect()
+    }
+}
+
+/// Transaction isolée par instantané (snapshot isolation).
+///
+/// Elle lit la base telle qu'elle était à son démarrage, complétée de ses
+/// propres écritures, qui restent en attente jusqu'au commit. Les lignes
+/// n'étant jamais réécrites, des ajouts concurrents dans une même table ne
+/// se gênent pas ; seule une table qu'elle crée et qu'un autre commit a créée
+/// entre-temps, ou une table où elle écrit et qui a perdu des lignes depuis,
+/// la fait échouer avec une erreur de conflit, et elle peut alors être
+/// rejouée.
+pub struct IsolatedTransaction {
+    view: ReadView,
+    created: Vec<TableName>,
+    inserted: Vec<Record>,
+}
+
+impl IsolatedTransaction {
+    pub fn new(view: ReadView) -> Self {
+        Self {
+            view,
+            created: vec![],
+            inserted: vec![],
+        }
+    }
+
+    fn table_exists(&self, table_name: &TableName) -> bool {
+        self.view.state(table_name).is_some() || self.created.contains(table_name)
+    }
+
+    pub fn create_table(&mut self, table_name: TableName) -> Result<(), CreationError> {
+        if self.table_exists(&table_name) {
+            return Err(CreationError::TableAlreadyExist(table_name));
+        }
+        self.created.push(table_name);
+        Ok(())
+    }
+
+    pub fn insert(&mut self, data: Record) -> Result<(), InsertionError> {
+        let table_name = data.table_name();
+        if !self.table_exists(&table_name) {
+            return Err(InsertionError::TableNotExist(table_name));
+        }
+        self.inserted.push(data);
+        Ok(())
+    }
+
+    pub fn select(&self, table_name: TableName) -> Result<Vec<Record>, SelectError> {
+        let mut rows = match self.view.state(&table_name) {
+            Some(_) => self.view.select(table_name.clone())?,
+            None if self.created.contains(&table_name) => vec![],
+            None => return Err(SelectError::TableNotExist(table_name)),
+        };
+        rows.extend(
+            self.inserted
+                .iter()
+                .filter(|record| record.table_name() == table_name)
+                .cloned(),
+        );
+        Ok(rows)
+    }
+
+    /// Applique les écritures en attente à la base, dans une transaction
+    /// qui leur est propre. Elle échoue si une transaction SQL y est déjà
+    /// en cours, que son rollback déferait.
+    pub fn commit(self, database: &mut Database) -> Result<(), TransactionError> {
+        if database.in_transaction() {
+            return Err(TransactionError::Storage(StorageError::Busy));
+        }
+        // le premier commit l'emporte : une table créée par un autre depuis
+        // le début de la transaction ne peut plus l'être par celle-ci
+        for table_name in self.created.iter() {
+            if database.committed_state(table_name).is_some() {
+                return Err(TransactionError::Conflict(table_name.clone()));
+            }
+        }
+        // une table écrite doit n'avoir fait que grandir depuis le début de
+        // la transaction, sans quoi les lignes lues ont changé
+        for data in self.inserted.iter() {
+            let table_name = data.table_name();
+            if let Some(state) = self.view.state(&table_name) {
+                match database.committed_state(&table_name) {
+                    Some(committed) if committed.offset >= state.offset => {}
+                    _ => return Err(TransactionError::Conflict(table_name)),
+                }
+            }
+        }
+        if self.created.is_empty() && self.inserted.is_empty() {
+            return Ok(());
+        }
+        database.begin()?;
+        match self.apply(database) {
+            Ok(()) => database.commit(),
+            Err(err) => {
+                let _ = database.rollback();
+                Err(err)
+            }
+        }
+    }
+
+    fn apply(self, database: &mut Database) -> Result<(), TransactionError> {
+        for table_name in self.created {
+            database
+                .create_table(table_name)
+                .map_err(TransactionError::Creation)?;
+        }
+        for data in self.inserted {
+            database.insert(data).map_err(TransactionError::Insertion)?;
+        }
+        Ok(())
+    }
+}
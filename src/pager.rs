@@ -0,0 +1,422 @@
+use crate::errors::{CommandError, StorageError};
+use crate::vfs::{File, Vfs};
+use crate::wal::Wal;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+
+pub const PAGE_SIZE: usize = 4096;
+/// Taille du journal WAL, en frames, au-delà de laquelle un checkpoint est
+/// déclenché automatiquement
+const WAL_AUTOCHECKPOINT: u32 = 1000;
+
+const JOURNAL_MAGIC: &[u8; 8] = b"rsqljrnl";
+/// magic, nombre de pages, taille d'origine du fichier
+const JOURNAL_HEADER_SIZE: usize = 8 + 4 + 8;
+/// numéro de page suivi de son contenu d'origine
+const JOURNAL_ENTRY_SIZE: usize = 4 + PAGE_SIZE;
+
+/// Manière dont le pager garantit l'atomicité des commits
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum JournalMode {
+    /// journal de rollback supprimé à la fin de chaque commit
+    Delete,
+    /// journal en écriture anticipée
+    Wal,
+}
+
+impl JournalMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JournalMode::Delete => "delete",
+            JournalMode::Wal => "wal",
+        }
+    }
+}
+
+impl FromStr for JournalMode {
+    type Err = CommandError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "delete" => Ok(JournalMode::Delete),
+            "wal" => Ok(JournalMode::Wal),
+            _ => Err(CommandError::InvalidPragmaValue(s.to_string())),
+        }
+    }
+}
+
+/// Lecture et écriture du fichier de base de données page par page.
+///
+/// En mode `Delete`, les écritures passent par un journal de rollback :
+/// avant de modifier le fichier, le contenu d'origine des pages est
+/// sauvegardé dans `<db>-journal`. Si un crash survient pendant l'écriture,
+/// le journal est rejoué à la prochaine ouverture et le fichier retrouve son
+/// état d'avant le commit.
+///
+/// En mode `Wal`, les pages sont ajoutées au journal `<db>-wal` et ne
+/// rejoignent le fichier principal qu'au checkpoint.
+pub struct Pager {
+    vfs: Arc<dyn Vfs>,
+    file: Box<dyn File>,
+    journal_path: PathBuf,
+    mode: JournalMode,
+    wal: Wal,
+    autocheckpoint: u32,
+}
+
+impl Pager {
+    pub fn open(vfs: Arc<dyn Vfs>, path: impl AsRef<Path>) -> Result<Self, StorageError> {
+        let path = path.as_ref();
+        let file = vfs.open(path).map_err(StorageError::io)?;
+        let mut journal_path = path.as_os_str().to_owned();
+        journal_path.push("-journal");
+        let mut wal_path = path.as_os_str().to_owned();
+        wal_path.push("-wal");
+        let mut pager = Self {
+            wal: Wal::open(vfs.clone(), PathBuf::from(wal_path))?,
+            vfs,
+            file,
+            journal_path: PathBuf::from(journal_path),
+            mode: JournalMode::Delete,
+            autocheckpoint: WAL_AUTOCHECKPOINT,
+        };
+        pager.recover()?;
+        Ok(pager)
+    }
+
+    pub fn journal_mode(&self) -> JournalMode {
+        self.mode
+    }
+
+    /// Change de mode, en quittant le mode WAL les pages du journal sont
+    /// recopiées dans le fichier principal et le journal est supprimé
+    pub fn set_journal_mode(&mut self, mode: JournalMode) -> Result<(), StorageError> {
+        if mode == JournalMode::Delete {
+            self.wal.close(self.file.as_mut())?;
+        }
+        self.mode = mode;
+        Ok(())
+    }
+
+    /// Début d'une lecture : les transactions validées depuis par d'autres
+    /// connexions dans le journal WAL deviennent visibles
+    pub fn begin_read(&mut self) -> Result<(), StorageError> {
+        self.wal.refresh()
+    }
+
+    pub fn page_count(&mut self) -> Result<u32, StorageError> {
+        let len = self.file.len().map_err(StorageError::io)?;
+        Ok((len.div_ceil(PAGE_SIZE as u64) as u32).max(self.wal.database_pages()))
+    }
+
+    pub fn read_page(&mut self, page: u32, buf: &mut [u8]) -> Result<(), StorageError> {
+        if self.wal.read_page(page, buf)? {
+            return Ok(());
+        }
+        self.file
+            .read_at(page as u64 * PAGE_SIZE as u64, &mut buf[..PAGE_SIZE])
+            .map_err(StorageError::io)
+    }
+
+    /// Recopie le journal WAL dans le fichier principal
+    pub fn checkpoint(&mut self) -> Result<(), StorageError> {
+        self.wal.checkpoint(self.file.as_mut())
+    }
+
+    /// Écrit un ensemble de pages de manière atomique : soit toutes les pages
+    /// sont écrites, soit le fichier reste dans son état précédent.
+    pub fn commit(&mut self, pages: &[(u32, &[u8])]) -> Result<(), StorageError> {
+        if self.mode == JournalMode::Wal {
+            // les frames ajoutées par une autre connexion ne doivent pas
+            // être écrasées
+            self.wal.refresh()?;
+            let database_pages = pages
+                .iter()
+                .map(|(page, _)| page + 1)
+                .fold(self.page_count()?, u32::max);
+            self.wal.append(pages, database_pages)?;
+            if self.wal.frame_count() >= self.autocheckpoint {
+                // la transaction est déjà validée dans le journal, un échec
+                // du checkpoint sera rattrapé au suivant
+                let _ = self.checkpoint();
+            }
+            return Ok(());
+        }
+        if let Err(err) = self.write_pages(pages) {
+            // le journal permet de défaire ce qui a pu être écrit,
+            // s'il n'y parvient pas il sera rejoué à la prochaine ouverture
+            let _ = self.recover();
+            return Err(err);
+        }
+        Ok(())
+    }
+
+    fn write_pages(&mut self, pages: &[(u32, &[u8])]) -> Result<(), StorageError> {
+        let original_size = self.file.len().map_err(StorageError::io)?;
+        // 1. sauvegarde des pages d'origine, le journal n'est pas encore valide
+        let mut journal = self.vfs.open(&self.journal_path).map_err(StorageError::io)?;
+        journal.set_len(0).map_err(StorageError::io)?;
+        let mut saved = 0_u32;
+        let mut original = vec![0_u8; PAGE_SIZE];
+        for (page, _) in pages {
+            let offset = *page as u64 * PAGE_SIZE as u64;
+            // les pages au-delà de la fin du fichier disparaissent avec la troncature
+            if offset >= original_size {
+                continue;
+            }
+            self.file.read_at(offset, &mut original).map_err(StorageError::io)?;
+            let entry = JOURNAL_HEADER_SIZE as u64 + saved as u64 * JOURNAL_ENTRY_SIZE as u64;
+            journal
+                .write_at(entry, &page.to_le_bytes())
+                .map_err(StorageError::io)?;
+            journal
+                .write_at(entry + 4, &original)
+                .map_err(StorageError::io)?;
+            saved += 1;
+        }
+        journal.sync().map_err(StorageError::io)?;
+        // 2. l'en-tête rend le journal valide une fois son contenu sur disque
+        let mut header = [0_u8; JOURNAL_HEADER_SIZE];
+        header[..8].copy_from_slice(JOURNAL_MAGIC);
+        header[8..12].copy_from_slice(&saved.to_le_bytes());
+        header[12..].copy_from_slice(&original_size.to_le_bytes());
+        journal.write_at(0, &header).map_err(StorageError::io)?;
+        journal.sync().map_err(StorageError::io)?;
+        // sans quoi le journal pourrait disparaître avec son répertoire
+        self.vfs.sync_directory(&self.journal_path).map_err(StorageError::io)?;
+        // 3. écriture des nouvelles pages
+        for (page, data) in pages {
+            self.file
+                .write_at(*page as u64 * PAGE_SIZE as u64, data)
+                .map_err(StorageError::io)?;
+        }
+        self.file.sync().map_err(StorageError::io)?;
+        // 4. la suppression du journal valide le commit
+        drop(journal);
+        self.vfs.remove(&self.journal_path).map_err(StorageError::io)
+    }
+
+    /// Rejoue le journal s'il est valide, puis le supprime
+    fn recover(&mut self) -> Result<(), StorageError> {
+        if !self.vfs.exists(&self.journal_path) {
+            return Ok(());
+        }
+        let mut journal = self.vfs.open(&self.journal_path).map_err(StorageError::io)?;
+        let mut header = [0_u8; JOURNAL_HEADER_SIZE];
+        journal.read_at(0, &mut header).map_err(StorageError::io)?;
+        // un journal sans en-tête n'a jamais été valide : le fichier n'a pas été touché
+        if &header[..8] == JOURNAL_MAGIC {
+            let saved = u32::from_le_bytes(header[8..12].try_into().unwrap());
+            let original_size = u64::from_le_bytes(header[12..].try_into().unwrap());
+            let mut entry = vec![0_u8; JOURNAL_ENTRY_SIZE];
+            for index in 0..saved as u64 {
+                journal
+                    .read_at(
+                        JOURNAL_HEADER_SIZE as u64 + index * JOURNAL_ENTRY_SIZE as u64,
+                        &mut entry,
+                    )
+                    .map_err(StorageError::io)?;
+                let page = u32::from_le_bytes(entry[..4].try_into().unwrap());
+                self.file
+                    .write_at(page as u64 * PAGE_SIZE as u64, &entry[4..])
+                    .map_err(StorageError::io)?;
+            }
+            self.file.set_len(original_size).map_err(StorageError::io)?;
+            self.file.sync().map_err(StorageError::io)?;
+        }
+        drop(journal);
+        self.vfs.remove(&self.journal_path).map_err(StorageError::io)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vfs::memory::MemoryVfs;
+
+    fn page(value: u8) -> Vec<u8> {
+        vec![value; PAGE_SIZE]
+    }
+
+    fn read(pager: &mut Pager, page: u32) -> Vec<u8> {
+        let mut buf = vec![0_u8; PAGE_SIZE];
+        pager.read_page(page, &mut buf).expect("read failed");
+        buf
+    }
+
+    #[test]
+    fn test_commit() {
+        let vfs = MemoryVfs::new();
+        let mut pager = Pager::open(Arc::new(vfs.clone()), "test.db").expect("open failed");
+        assert_eq!(pager.page_count(), Ok(0));
+        pager
+            .commit(&[(0, &page(1)), (1, &page(2))])
+            .expect("commit failed");
+        assert_eq!(pager.page_count(), Ok(2));
+        assert_eq!(read(&mut pager, 1), page(2));
+        // le journal est supprimé une fois le commit terminé
+        assert!(!vfs.exists(Path::new("test.db-journal")));
+    }
+
+    #[test]
+    fn test_crash_at_every_write_point() {
+        let vfs = MemoryVfs::new();
+        let mut pager = Pager::open(Arc::new(vfs.clone()), "test.db").expect("open failed");
+        pager
+            .commit(&[(0, &page(1)), (1, &page(1))])
+            .expect("commit failed");
+        drop(pager);
+
+        let mut crash_at = 0;
+        loop {
+            let mut pager = Pager::open(Arc::new(vfs.clone()), "test.db").expect("open failed");
+            vfs.crash_after(crash_at);
+            let result = pager.commit(&[(1, &page(2)), (2, &page(2))]);
+            vfs.crash();
+            drop(pager);
+
+            let mut pager = Pager::open(Arc::new(vfs.clone()), "test.db").expect("recovery failed");
+            if result.is_ok() {
+                assert_eq!(pager.page_count(), Ok(3));
+                assert_eq!(read(&mut pager, 1), page(2));
+                assert_eq!(read(&mut pager, 2), page(2));
+                break;
+            }
+            // le commit a échoué : on retrouve exactement l'état précédent
+            assert_eq!(pager.page_count(), Ok(2), "crash at write {crash_at}");
+            assert_eq!(read(&mut pager, 0), page(1), "crash at write {crash_at}");
+            assert_eq!(read(&mut pager, 1), page(1), "crash at write {crash_at}");
+            crash_at += 1;
+        }
+        assert!(crash_at > 0);
+    }
+
+    #[test]
+    fn test_wal() {
+        let vfs = MemoryVfs::new();
+        let mut pager = Pager::open(Arc::new(vfs.clone()), "test.db").expect("open failed");
+        pager.set_journal_mode(JournalMode::Wal).expect("mode failed");
+        pager
+            .commit(&[(0, &page(1)), (1, &page(1))])
+            .expect("commit failed");
+        pager.commit(&[(1, &page(2))]).expect("commit failed");
+        // les pages sont dans le journal, pas encore dans le fichier principal
+        assert!(vfs.exists(Path::new("test.db-wal")));
+        assert_eq!(pager.file.len().expect("len failed"), 0);
+        assert_eq!(pager.page_count(), Ok(2));
+        assert_eq!(read(&mut pager, 1), page(2));
+        drop(pager);
+
+        // une autre connexion relit le journal
+        let mut pager = Pager::open(Arc::new(vfs.clone()), "test.db").expect("open failed");
+        pager.set_journal_mode(JournalMode::Wal).expect("mode failed");
+        assert_eq!(read(&mut pager, 0), page(1));
+        assert_eq!(read(&mut pager, 1), page(2));
+
+        pager.checkpoint().expect("checkpoint failed");
+        assert_eq!(pager.wal.frame_count(), 0);
+        assert_eq!(pager.file.len().expect("len failed"), 2 * PAGE_SIZE as u64);
+        assert_eq!(read(&mut pager, 1), page(2));
+
+        // le journal repart de zéro après le checkpoint
+        pager.commit(&[(1, &page(3))]).expect("commit failed");
+        drop(pager);
+        let mut pager = Pager::open(Arc::new(vfs.clone()), "test.db").expect("open failed");
+        assert_eq!(read(&mut pager, 1), page(3));
+        // quitter le mode WAL vide et supprime le journal
+        pager.set_journal_mode(JournalMode::Delete).expect("mode failed");
+        assert!(!vfs.exists(Path::new("test.db-wal")));
+        assert_eq!(read(&mut pager, 1), page(3));
+    }
+
+    #[test]
+    fn test_wal_shared() {
+        let vfs = MemoryVfs::new();
+        let mut first = Pager::open(Arc::new(vfs.clone()), "test.db").expect("open failed");
+        let mut second = Pager::open(Arc::new(vfs.clone()), "test.db").expect("open failed");
+        first.set_journal_mode(JournalMode::Wal).expect("mode failed");
+        second.set_journal_mode(JournalMode::Wal).expect("mode failed");
+        first.commit(&[(0, &page(1))]).expect("commit failed");
+        // la transaction validée par l'autre connexion est visible à la
+        // lecture suivante
+        second.begin_read().expect("read failed");
+        assert_eq!(read(&mut second, 0), page(1));
+        // et n'est pas écrasée par ses propres écritures
+        second.commit(&[(1, &page(2))]).expect("commit failed");
+        first.begin_read().expect("read failed");
+        assert_eq!((read(&mut first, 0), read(&mut first, 1)), (page(1), page(2)));
+        first.commit(&[(0, &page(3))]).expect("commit failed");
+        // un checkpoint vide le journal sous les pieds de l'autre connexion
+        first.checkpoint().expect("checkpoint failed");
+        second.begin_read().expect("read failed");
+        assert_eq!(second.wal.frame_count(), 0);
+        assert_eq!((read(&mut second, 0), read(&mut second, 1)), (page(3), page(2)));
+        second.commit(&[(1, &page(4))]).expect("commit failed");
+        first.begin_read().expect("read failed");
+        assert_eq!(read(&mut first, 1), page(4));
+    }
+
+    #[test]
+    fn test_wal_autocheckpoint() {
+        let vfs = MemoryVfs::new();
+        let mut pager = Pager::open(Arc::new(vfs.clone()), "test.db").expect("open failed");
+        pager.set_journal_mode(JournalMode::Wal).expect("mode failed");
+        pager.autocheckpoint = 3;
+        pager.commit(&[(0, &page(1)), (1, &page(1))]).expect("commit failed");
+        assert_eq!(pager.wal.frame_count(), 2);
+        pager.commit(&[(1, &page(2))]).expect("commit failed");
+        assert_eq!(pager.wal.frame_count(), 0);
+        assert_eq!(pager.file.len().expect("len failed"), 2 * PAGE_SIZE as u64);
+        assert_eq!(read(&mut pager, 1), page(2));
+    }
+
+    #[test]
+    fn test_wal_crash_at_every_write_point() {
+        let vfs = MemoryVfs::new();
+        let mut pager = Pager::open(Arc::new(vfs.clone()), "test.db").expect("open failed");
+        pager.set_journal_mode(JournalMode::Wal).expect("mode failed");
+        pager
+            .commit(&[(0, &page(1)), (1, &page(1))])
+            .expect("commit failed");
+        drop(pager);
+
+        let mut crash_at = 0;
+        loop {
+            let mut pager = Pager::open(Arc::new(vfs.clone()), "test.db").expect("open failed");
+            pager.set_journal_mode(JournalMode::Wal).expect("mode failed");
+            vfs.crash_after(crash_at);
+            let result = pager
+                .commit(&[(1, &page(2)), (2, &page(2))])
+                .and_then(|_| pager.checkpoint());
+            vfs.crash();
+            drop(pager);
+
+            let mut pager = Pager::open(Arc::new(vfs.clone()), "test.db").expect("recovery failed");
+            if result.is_ok() {
+                assert_eq!(pager.page_count(), Ok(3));
+                assert_eq!(read(&mut pager, 1), page(2));
+                assert_eq!(read(&mut pager, 2), page(2));
+                break;
+            }
+            // soit la transaction est entièrement visible, soit pas du tout
+            match pager.page_count() {
+                Ok(3) => {
+                    assert_eq!(read(&mut pager, 1), page(2), "crash at write {crash_at}");
+                    assert_eq!(read(&mut pager, 2), page(2), "crash at write {crash_at}");
+                }
+                Ok(2) => assert_eq!(read(&mut pager, 1), page(1), "crash at write {crash_at}"),
+                count => panic!("unexpected page count {count:?} at write {crash_at}"),
+            }
+            assert_eq!(read(&mut pager, 0), page(1), "crash at write {crash_at}");
+            // on repart de l'état initial pour tester le point suivant
+            pager.set_journal_mode(JournalMode::Wal).expect("mode failed");
+            pager.commit(&[(1, &page(1))]).expect("reset failed");
+            pager.checkpoint().expect("reset failed");
+            pager.file.set_len(2 * PAGE_SIZE as u64).expect("reset failed");
+            pager.file.sync().expect("reset failed");
+            crash_at += 1;
+        }
+        assert!(crash_at > 0);
+    }
+}
@@ -0,0 +1,322 @@
+//! Lignes de résultat d'une requête, lues colonne par colonne.
+
+use crate::data::{Date, Time, Timestamp, Value};
+use crate::errors::RowError;
+use std::sync::Arc;
+
+pub use rusty_sqlite_derive::FromRow;
+
+/// Type Rust dans lequel une valeur de colonne peut être lue
+pub trait FromValue: Sized {
+    /// Nom du type, rapporté quand une valeur ne peut pas y être lue
+    const TYPE_NAME: &'static str;
+
+    /// `None` si la valeur n'est pas du bon type
+    fn from_value(value: &Value) -> Option<Self>;
+}
+
+impl FromValue for Value {
+    const TYPE_NAME: &'static str = "Value";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        Some(value.clone())
+    }
+}
+
+impl FromValue for i64 {
+    const TYPE_NAME: &'static str = "i64";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Integer(value) => Some(*value),
+            Value::Boolean(value) => Some(*value as i64),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for f64 {
+    const TYPE_NAME: &'static str = "f64";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Real(value) => Some(*value),
+            Value::Integer(value) => Some(*value as f64),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for bool {
+    const TYPE_NAME: &'static str = "bool";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Boolean(value) => Some(*value),
+            Value::Integer(value @ (0 | 1)) => Some(*value == 1),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for String {
+    const TYPE_NAME: &'static str = "String";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Text(value) => Some(value.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for Vec<u8> {
+    const TYPE_NAME: &'static str = "Vec<u8>";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Blob(value) => Some(value.clone()),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for Date {
+    const TYPE_NAME: &'static str = "Date";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Date(value) => Some(*value),
+            Value::Text(text) => text.parse().ok(),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for Time {
+    const TYPE_NAME: &'static str = "Time";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Time(value) => Some(*value),
+            Value::Text(text) => text.parse().ok(),
+            _ => None,
+        }
+    }
+}
+
+impl FromValue for Timestamp {
+    const TYPE_NAME: &'static str = "Timestamp";
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Timestamp(value) => Some(*value),
+            Value::Text(text) => text.parse().ok(),
+            _ => None,
+        }
+    }
+}
+
+/// NULL est lu comme `None`
+impl<T: FromValue> FromValue for Option<T> {
+    const TYPE_NAME: &'static str = T::TYPE_NAME;
+
+    fn from_value(value: &Value) -> Option<Self> {
+        match value {
+            Value::Null => Some(None),
+            value => T::from_value(value).map(Some),
+        }
+    }
+}
+
+/// Désigne une colonne par sa position, à partir de 0, ou par son nom
+pub trait ColumnIndex {
+    fn index(&self, columns: &[String]) -> Result<usize, RowError>;
+}
+
+impl ColumnIndex for usize {
+    fn index(&self, columns: &[String]) -> Result<usize, RowError> {
+        match *self < columns.len() {
+            true => Ok(*self),
+            false => Err(RowError::IndexOutOfRange(*self)),
+        }
+    }
+}
+
+/// Le nom est comparé sans tenir compte de la casse, comme en SQL
+impl ColumnIndex for &str {
+    fn index(&self, columns: &[String]) -> Result<usize, RowError> {
+        columns
+            .iter()
+            .position(|column| column.eq_ignore_ascii_case(self))
+            .ok_or_else(|| RowError::UnknownColumn(self.to_string()))
+    }
+}
+
+/// Ligne de résultat
+#[derive(Debug, PartialEq, Clone)]
+pub struct Row {
+    columns: Arc<[String]>,
+    values: Vec<Value>,
+}
+
+impl Row {
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+
+    pub fn values(&self) -> &[Value] {
+        &self.values
+    }
+
+    /// Valeur d'une colonne, lue dans le type demandé
+    pub fn get<T: FromValue>(&self, index: impl ColumnIndex) -> Result<T, RowError> {
+        let column = index.index(&self.columns)?;
+        let value = &self.values[column];
+        T::from_value(value).ok_or_else(|| RowError::InvalidType {
+            column: self.columns[column].clone(),
+            expected: T::TYPE_NAME,
+            value: value.clone(),
+        })
+    }
+}
+
+/// Type Rust construit à partir d'une ligne de résultat.
+///
+/// Dérivable sur une structure : un champ nommé est lu dans la colonne de
+/// même nom, un champ d'une structure tuple dans la colonne de même position.
+pub trait FromRow: Sized {
+    fn from_row(row: &Row) -> Result<Self, RowError>;
+}
+
+impl FromRow for Row {
+    fn from_row(row: &Row) -> Result<Self, RowError> {
+        Ok(row.clone())
+    }
+}
+
+/// Un tuple est lu colonne par colonne, dans l'ordre
+macro_rules! tuple_from_row {
+    ($($index:tt: $type:ident),+) => {
+        impl<$($type: FromValue),+> FromRow for ($($type,)+) {
+            fn from_row(row: &Row) -> Result<Self, RowError> {
+                Ok(($(row.get::<$type>($index)?,)+))
+            }
+        }
+    };
+}
+
+tuple_from_row!(0: A);
+tuple_from_row!(0: A, 1: B);
+tuple_from_row!(0: A, 1: B, 2: C);
+tuple_from_row!(0: A, 1: B, 2: C, 3: D);
+tuple_from_row!(0: A, 1: B, 2: C, 3: D, 4: E);
+tuple_from_row!(0: A, 1: B, 2: C, 3: D, 4: E, 5: F);
+
+/// Lignes renvoyées par une requête
+pub struct Rows {
+    columns: Arc<[String]>,
+    rows: std::vec::IntoIter<Vec<Value>>,
+}
+
+impl Rows {
+    pub(crate) fn new(columns: Vec<String>, rows: Vec<Vec<Value>>) -> Self {
+        Self {
+            columns: columns.into(),
+            rows: rows.into_iter(),
+        }
+    }
+
+    /// Nom des colonnes du résultat
+    pub fn columns(&self) -> &[String] {
+        &self.columns
+    }
+}
+
+impl Iterator for Rows {
+    type Item = Row;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.rows.next().map(|values| Row {
+            columns: self.columns.clone(),
+            values,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_get() {
+        let mut rows = Rows::new(
+            vec!["id".to_string(), "name".to_string(), "score".to_string()],
+            vec![vec![Value::Integer(1), Value::Text("one".to_string()), Value::Null]],
+        );
+        let row = rows.next().expect("no row");
+        assert_eq!(row.get::<i64>(0), Ok(1));
+        assert_eq!(row.get::<String>("NAME"), Ok("one".to_string()));
+        assert_eq!(row.get::<Option<f64>>("score"), Ok(None));
+        assert_eq!(row.get::<f64>("id"), Ok(1.0));
+        assert_eq!(row.get::<i64>(3), Err(RowError::IndexOutOfRange(3)));
+        assert_eq!(row.get::<i64>("age"), Err(RowError::UnknownColumn("age".to_string())));
+        assert_eq!(
+            row.get::<i64>("name"),
+            Err(RowError::InvalidType {
+                column: "name".to_string(),
+                expected: "i64",
+                value: Value::Text("one".to_string())
+            })
+        );
+        assert_eq!(rows.next(), None);
+    }
+
+    #[test]
+    fn test_from_row() {
+        #[derive(Debug, PartialEq, FromRow)]
+        struct Player {
+            name: String,
+            score: Option<f64>,
+        }
+
+        #[derive(Debug, PartialEq, FromRow)]
+        struct Pair(i64, String);
+
+        let row = Rows::new(
+            vec!["id".to_string(), "name".to_string(), "score".to_string()],
+            vec![vec![Value::Integer(1), Value::Text("one".to_string()), Value::Null]],
+        )
+        .next()
+        .expect("no row");
+        // par nom, quelle que soit la position
+        assert_eq!(
+            Player::from_row(&row),
+            Ok(Player {
+                name: "one".to_string(),
+                score: None
+            })
+        );
+        // par position
+        assert_eq!(Pair::from_row(&row), Ok(Pair(1, "one".to_string())));
+        assert_eq!(<(i64, String)>::from_row(&row), Ok((1, "one".to_string())));
+
+        #[derive(Debug, FromRow)]
+        #[allow(dead_code)]
+        struct Missing {
+            email: String,
+        }
+        assert_eq!(
+            Missing::from_row(&row).err(),
+            Some(RowError::UnknownColumn("email".to_string()))
+        );
+        assert_eq!(
+            <(String,)>::from_row(&row),
+            Err(RowError::InvalidType {
+                column: "id".to_string(),
+                expected: "String",
+                value: Value::Integer(1)
+            })
+        );
+    }
+}
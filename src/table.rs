@@ -1,40 +1,399 @@
-use std::io::Cursor;
-use crate::data::{Deserializable, Serializable};
+use std::cell::RefCell;
+use std::marker::PhantomData;
+use std::sync::Arc;
+use crate::data::{Deserializable, Reader, Serializable};
 use crate::errors::{InsertionError, SelectError};
+use crate::pager::PAGE_SIZE;
 
 const TABLE_SIZE: usize = 1024 * 1024;
+/// Nombre de pages réservées à chaque table dans le fichier
+pub const TABLE_PAGES: u32 = (TABLE_SIZE / PAGE_SIZE) as u32;
 
+type Page = [u8; PAGE_SIZE];
+
+thread_local! {
+    /// Buffer où sont sérialisées les lignes avant d'être recopiées dans les
+    /// pages, gardé d'une insertion à l'autre pour ne pas allouer l'espace
+    /// libre de la table à chaque ligne
+    static BUFFER: RefCell<Vec<u8>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Position d'écriture d'une table à un instant donné
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TableState {
+    pub(crate) offset: usize,
+    pub(crate) row_number: usize,
+    /// début des pages de débordement, qui sont allouées depuis la fin de
+    /// la table vers les lignes
+    pub(crate) overflow: usize,
+}
+
+impl TableState {
+    /// Nombre de pages de débordement allouées
+    pub fn overflow_pages(&self) -> usize {
+        (TABLE_SIZE - self.overflow) / PAGE_SIZE
+    }
+}
+
+#[derive(Clone)]
 pub struct Table {
-    inner: Vec<u8>,
+    /// pages partagées avec les versions figées de la table, chacune copiée
+    /// avant d'être modifiée si une version la lit encore
+    pages: Vec<Arc<Page>>,
     offset: usize,
     row_number: usize,
+    overflow: usize,
+    /// première page de la table dans le fichier
+    root_page: u32,
+    /// position d'écriture au dernier enregistrement sur disque
+    synced_offset: usize,
+    /// début des pages de débordement au dernier enregistrement sur disque
+    synced_overflow: usize,
+    /// les valeurs insérées doivent avoir exactement le type de leur colonne
+    strict: bool,
 }
 
 impl Table {
-    pub fn new() -> Self {
+    pub fn new(root_page: u32) -> Self {
+        // les pages vides partagent la même page de zéros
+        let empty = Arc::new([0; PAGE_SIZE]);
         Self {
-            inner: vec![0; TABLE_SIZE],
+            pages: (0..TABLE_PAGES).map(|_| empty.clone()).collect(),
             offset: 0,
             row_number: 0,
+            overflow: TABLE_SIZE,
+            root_page,
+            synced_offset: 0,
+            synced_overflow: TABLE_SIZE,
+            strict: false,
         }
     }
 
+    /// Recrée une table à partir de ses pages lues sur disque, `read_page`
+    /// n'est appelée que pour les pages utilisées
+    pub fn load<E>(
+        root_page: u32,
+        state: TableState,
+        mut read_page: impl FnMut(u32, &mut [u8]) -> Result<(), E>,
+    ) -> Result<Self, E> {
+        let mut table = Self::new(root_page);
+        let rows = 0..state.offset.div_ceil(PAGE_SIZE);
+        let overflow = state.overflow / PAGE_SIZE..TABLE_SIZE / PAGE_SIZE;
+        for page in rows.chain(overflow) {
+            read_page(root_page + page as u32, &mut Arc::make_mut(&mut table.pages[page])[..])?;
+        }
+        table.offset = state.offset;
+        table.row_number = state.row_number;
+        table.overflow = state.overflow;
+        table.synced_offset = state.offset;
+        table.synced_overflow = state.overflow;
+        Ok(table)
+    }
+
+    pub fn root_page(&self) -> u32 {
+        self.root_page
+    }
+
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// Pages modifiées depuis le dernier enregistrement sur disque
+    pub fn dirty_pages(&self) -> impl Iterator<Item = (u32, &[u8])> {
+        let rows = self.synced_offset / PAGE_SIZE..self.offset.div_ceil(PAGE_SIZE);
+        let overflow = self.overflow / PAGE_SIZE..self.synced_overflow / PAGE_SIZE;
+        rows.chain(overflow).map(move |page| {
+            (self.root_page + page as u32, &self.pages[page][..])
+        })
+    }
+
+    pub fn mark_synced(&mut self) {
+        self.synced_offset = self.offset;
+        self.synced_overflow = self.overflow;
+    }
+
+    /// Ajoute une ligne après les précédentes. Le sérialiseur reçoit l'espace
+    /// libre jusqu'aux pages de débordement ; il en alloue de nouvelles en
+    /// raccourcissant ce buffer par la fin.
     pub fn insert<S: Serializable>(&mut self, row: S) -> Result<(), InsertionError> {
-        let mut writer = Cursor::new(&mut self.inner[self.offset..]);
-        row.serialize(&mut writer)
-            .map_err(InsertionError::Serialization)?;
-        self.offset += writer.position() as usize;
-        self.row_number += 1;
-        Ok(())
+        // la ligne est écrite à part puis recopiée dans les seules pages
+        // qu'elle occupe, la table est intacte si l'écriture échoue. Le
+        // buffer garde les octets des lignes précédentes : seuls ceux que le
+        // sérialiseur vient d'écrire sont recopiés.
+        BUFFER.with_borrow_mut(|buffer| {
+            buffer.resize(self.overflow - self.offset, 0);
+            let mut writer = std::io::Cursor::new(&mut buffer[..]);
+            row.serialize(&mut writer)
+                .map_err(InsertionError::Serialization)?;
+            let written = writer.position() as usize;
+            let free = writer.get_ref().len();
+            self.write(self.offset, &buffer[..written]);
+            self.write(self.offset + free, &buffer[free..]);
+            self.overflow = self.offset + free;
+            self.offset += written;
+            self.row_number += 1;
+            Ok(())
+        })
+    }
+
+    /// Copie des octets à partir de `offset`, en copiant d'abord chaque page
+    /// touchée encore lue par une version
+    fn write(&mut self, mut offset: usize, mut data: &[u8]) {
+        while !data.is_empty() {
+            let start = offset % PAGE_SIZE;
+            let size = data.len().min(PAGE_SIZE - start);
+            Arc::make_mut(&mut self.pages[offset / PAGE_SIZE])[start..start + size].copy_from_slice(&data[..size]);
+            offset += size;
+            data = &data[size..];
+        }
+    }
+
+    /// Remet à zéro les octets de `start` à `end`
+    fn clear(&mut self, start: usize, end: usize) {
+        for page in start / PAGE_SIZE..end.div_ceil(PAGE_SIZE) {
+            let from = start.max(page * PAGE_SIZE) - page * PAGE_SIZE;
+            let to = end.min((page + 1) * PAGE_SIZE) - page * PAGE_SIZE;
+            match (from, to) {
+                // une page effacée en entier n'a pas besoin d'être copiée
+                (0, PAGE_SIZE) => self.pages[page] = Arc::new([0; PAGE_SIZE]),
+                _ => Arc::make_mut(&mut self.pages[page])[from..to].fill(0),
+            }
+        }
+    }
+
+    /// Ouvre un curseur qui désérialise les lignes une à une, à la demande
+    pub fn cursor<D: Deserializable>(&self) -> Cursor<'_, D> {
+        Cursor::new(&self.pages, self.state())
     }
 
-    pub fn select<D: Deserializable>(&self) -> Result<Vec<D>, SelectError> {
-        let mut reader = Cursor::new(&self.inner[..]);
-        let mut rows = Vec::with_capacity(self.row_number);
-        for _row_number in 0..self.row_number {
-            rows.push(D::deserialize(&mut reader).map_err(SelectError::Deserialization)?)
+    /// Relit la seule ligne qui commence à `offset`, relevé par
+    /// `Cursor::offset` ou dans `state` avant son insertion
+    pub fn row<D: Deserializable>(&self, offset: usize) -> Result<D, SelectError> {
+        let mut reader = Reader::from_pages(self.pages.iter().map(|page| &page[..]));
+        reader.set_position(offset as u64);
+        D::deserialize(&mut reader).map_err(SelectError::Deserialization)
+    }
+
+    /// Version figée de la table dans l'état donné, qui peut être lue
+    /// pendant que la table continue d'être modifiée
+    pub fn version(&self, state: TableState) -> TableVersion {
+        TableVersion {
+            pages: self.pages.clone(),
+            state,
+        }
+    }
+
+    pub fn state(&self) -> TableState {
+        TableState {
+            offset: self.offset,
+            row_number: self.row_number,
+            overflow: self.overflow,
+        }
+    }
+
+    /// Revient à un état précédent en oubliant les lignes insérées depuis
+    pub fn restore(&mut self, state: TableState) {
+        if state.offset < self.offset {
+            self.clear(state.offset, self.offset);
+        }
+        if self.overflow < state.overflow {
+            self.clear(self.overflow, state.overflow);
         }
-        Ok(rows)
+        self.offset = state.offset;
+        self.row_number = state.row_number;
+        self.overflow = state.overflow;
     }
 
-}
\ No newline at end of file
+}
+
+/// Version figée d'une table.
+///
+/// Les lignes et leurs pages de débordement n'étant jamais réécrites, les
+/// lignes présentes au moment de la version ne changent plus : elle se
+/// contente de garder les pages et la position d'écriture de l'époque.
+#[derive(Clone)]
+pub struct TableVersion {
+    pages: Vec<Arc<Page>>,
+    state: TableState,
+}
+
+impl TableVersion {
+    pub fn state(&self) -> TableState {
+        self.state
+    }
+
+    pub fn cursor<D: Deserializable>(&self) -> Cursor<'_, D> {
+        Cursor::new(&self.pages, self.state)
+    }
+}
+
+/// Parcours paresseux des lignes d'une table.
+///
+/// Aucune ligne n'est désérialisée avant l'appel à `next`, ce qui permet de
+/// parcourir une table de taille quelconque en mémoire constante et de
+/// s'arrêter à tout moment.
+pub struct Cursor<'a, D> {
+    reader: Reader<'a>,
+    /// nombre de lignes restant à lire
+    remaining: usize,
+    row: PhantomData<D>,
+}
+
+impl<'a, D> Cursor<'a, D> {
+    fn new(pages: &'a [Arc<Page>], state: TableState) -> Self {
+        // la table entière reste lisible pour suivre les pages de débordement,
+        // le nombre de lignes borne le parcours
+        Cursor {
+            reader: Reader::from_pages(pages.iter().map(|page| &page[..])),
+            remaining: state.row_number,
+            row: PhantomData,
+        }
+    }
+
+    /// Position dans la table de la prochaine ligne lue
+    pub fn offset(&self) -> usize {
+        self.reader.position() as usize
+    }
+}
+
+impl<D: Deserializable> Iterator for Cursor<'_, D> {
+    type Item = Result<D, SelectError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            return None;
+        }
+        match D::deserialize(&mut self.reader) {
+            Ok(row) => {
+                self.remaining -= 1;
+                Some(Ok(row))
+            }
+            Err(err) => {
+                // la suite du buffer n'est plus fiable, on arrête le parcours
+                self.remaining = 0;
+                Some(Err(SelectError::Deserialization(err)))
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.remaining))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::User;
+
+    #[test]
+    fn test_cursor() {
+        let mut table = Table::new(1);
+        for i in 0..10 {
+            table
+                .insert(User::new(i, format!("test_{i}"), format!("email_{i}@example.com")))
+                .expect("insert failed");
+        }
+        let mut cursor = table.cursor::<User>();
+        assert_eq!(
+            cursor.next(),
+            Some(Ok(User::new(0, "test_0".to_string(), "email_0@example.com".to_string())))
+        );
+        // le reste du curseur reprend là où on s'était arrêté
+        assert_eq!(cursor.count(), 9);
+        // un curseur vide ne renvoie rien
+        assert_eq!(Table::new(1).cursor::<User>().next(), None);
+    }
+
+    #[test]
+    fn test_restore() {
+        let mut table = Table::new(1);
+        table
+            .insert(User::new(1, "one".to_string(), "one@example.com".to_string()))
+            .expect("insert failed");
+        let state = table.state();
+        table
+            .insert(User::new(2, "two".to_string(), "two@example.com".to_string()))
+            .expect("insert failed");
+        table.restore(state);
+        assert_eq!(table.state(), state);
+        assert_eq!(table.cursor::<User>().count(), 1);
+    }
+
+    #[test]
+    fn test_version() {
+        let mut table = Table::new(1);
+        table
+            .insert(User::new(1, "one".to_string(), "one@example.com".to_string()))
+            .expect("insert failed");
+        let version = table.version(table.state());
+        // la version ne voit ni les ajouts ni les annulations suivants
+        table
+            .insert(User::new(2, "two".to_string(), "two@example.com".to_string()))
+            .expect("insert failed");
+        assert_eq!(version.cursor::<User>().count(), 1);
+        // seule la page qui a reçu la ligne a été copiée
+        let copied = table
+            .pages
+            .iter()
+            .zip(&version.pages)
+            .filter(|(page, shared)| !Arc::ptr_eq(page, shared))
+            .count();
+        assert_eq!(copied, 1);
+        table.restore(TableState {
+            offset: 0,
+            row_number: 0,
+            overflow: TABLE_SIZE,
+        });
+        assert_eq!(
+            version.cursor::<User>().collect::<Result<Vec<_>, _>>(),
+            Ok(vec![User::new(1, "one".to_string(), "one@example.com".to_string())])
+        );
+    }
+
+    #[test]
+    fn test_overflow() {
+        let mut table = Table::new(1);
+        let document = "x".repeat(100_000);
+        table
+            .insert(User::new(1, document.clone(), "one@example.com".to_string()))
+            .expect("insert failed");
+        let state = table.state();
+        assert_eq!(state.overflow_pages(), 25);
+        // les lignes suivantes restent dans la ligne, avant les pages de débordement
+        table
+            .insert(User::new(2, "two".to_string(), "x".repeat(10_000)))
+            .expect("insert failed");
+        assert_eq!(
+            table.cursor::<User>().collect::<Result<Vec<_>, _>>(),
+            Ok(vec![
+                User::new(1, document, "one@example.com".to_string()),
+                User::new(2, "two".to_string(), "x".repeat(10_000)),
+            ])
+        );
+        // l'annulation libère les pages allouées depuis
+        table.restore(state);
+        assert_eq!(table.state(), state);
+        // une valeur qui ne tient plus dans la table est refusée
+        assert!(table
+            .insert(User::new(3, "x".repeat(TABLE_SIZE), String::new()))
+            .is_err());
+        // une ligne refusée en cours d'écriture ne laisse rien dans la table
+        assert!(table
+            .insert(User::new(3, "x".repeat(400_000), "y".repeat(600_000)))
+            .is_err());
+        assert_eq!(table.state(), state);
+        let free = state.offset.div_ceil(PAGE_SIZE)..state.overflow / PAGE_SIZE;
+        assert!(table.pages[free].iter().all(|page| page.iter().all(|byte| *byte == 0)));
+        // les pages ne gardent rien des lignes écrites avant dans le buffer
+        table
+            .insert(User::new(4, "y".repeat(5_000), String::new()))
+            .expect("insert failed");
+        let pages = table.overflow / PAGE_SIZE..state.overflow / PAGE_SIZE;
+        assert!(table.pages[pages].iter().all(|page| !page.contains(&b'x')));
+    }
+}
@@ -0,0 +1,95 @@
+use crate::data::TableName;
+use crate::errors::TransactionError;
+use crate::table::{Table, TableState};
+use std::collections::{BTreeMap, HashMap};
+
+/// Photographie de l'état des tables prise au début d'une transaction.
+///
+/// Les tables ne font qu'ajouter des lignes à la suite les unes des autres,
+/// il suffit donc de retenir la position d'écriture de chacune pour pouvoir
+/// annuler tout ce qui a été fait depuis.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Snapshot {
+    tables: HashMap<TableName, TableState>,
+    /// définition des vues, qui changent rarement et se copient entières
+    views: BTreeMap<String, String>,
+}
+
+impl Snapshot {
+    pub fn take(tables: &HashMap<TableName, Table>, views: &BTreeMap<String, String>) -> Self {
+        Self {
+            tables: tables
+                .iter()
+                .map(|(name, table)| (name.clone(), table.state()))
+                .collect(),
+            views: views.clone(),
+        }
+    }
+
+    pub fn state(&self, table_name: &TableName) -> Option<TableState> {
+        self.tables.get(table_name).copied()
+    }
+
+    /// Remet les tables et les vues dans l'état de la photographie
+    pub fn restore(&self, tables: &mut HashMap<TableName, Table>, views: &mut BTreeMap<String, String>) {
+        views.clone_from(&self.views);
+        // les tables créées depuis disparaissent
+        tables.retain(|name, _| self.tables.contains_key(name));
+        for (name, table) in tables.iter_mut() {
+            table.restore(self.tables[name]);
+        }
+    }
+}
+
+/// Transaction en cours et pile de ses points de sauvegarde
+pub struct Transaction {
+    /// état des tables au début de la transaction
+    pub snapshot: Snapshot,
+    savepoints: Vec<Savepoint>,
+    /// transaction ouverte par un `savepoint`, validée lorsque le premier
+    /// point de sauvegarde est relâché
+    implicit: bool,
+}
+
+struct Savepoint {
+    name: String,
+    snapshot: Snapshot,
+}
+
+impl Transaction {
+    pub fn new(snapshot: Snapshot, implicit: bool) -> Self {
+        Self {
+            snapshot,
+            savepoints: vec![],
+            implicit,
+        }
+    }
+
+    pub fn savepoint(&mut self, name: String, snapshot: Snapshot) {
+        self.savepoints.push(Savepoint { name, snapshot });
+    }
+
+    /// Position du plus récent point de sauvegarde portant ce nom
+    fn find(&self, name: &str) -> Result<usize, TransactionError> {
+        self.savepoints
+            .iter()
+            .rposition(|savepoint| savepoint.name.eq_ignore_ascii_case(name))
+            .ok_or_else(|| TransactionError::NoSuchSavepoint(name.to_string()))
+    }
+
+    /// Oublie le point de sauvegarde et tous ceux créés après lui, en gardant
+    /// leurs modifications. Renvoie vrai si la transaction doit être validée.
+    pub fn release(&mut self, name: &str) -> Result<bool, TransactionError> {
+        let position = self.find(name)?;
+        self.savepoints.truncate(position);
+        Ok(self.implicit && self.savepoints.is_empty())
+    }
+
+    /// Renvoie l'état des tables au point de sauvegarde, qui reste sur la pile
+    /// alors que ceux créés après lui sont oubliés
+    pub fn rollback_to(&mut self, name: &str) -> Result<&Snapshot, TransactionError> {
+        let position = self.find(name)?;
+        self.savepoints.truncate(position + 1);
+        Ok(&self.savepoints[position].snapshot)
+    }
+}
@@ -0,0 +1,239 @@
+use std::fs::OpenOptions;
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+/// Accès au système de fichiers utilisé par le pager.
+///
+/// L'abstraction permet de remplacer le disque par une implémentation en
+/// mémoire capable de simuler un crash.
+pub trait Vfs: Send + Sync {
+    /// Ouvre le fichier, en le créant s'il n'existe pas
+    fn open(&self, path: &Path) -> std::io::Result<Box<dyn File>>;
+    fn exists(&self, path: &Path) -> bool;
+    fn remove(&self, path: &Path) -> std::io::Result<()>;
+    /// Garantit que la création du fichier, inscrite dans son répertoire,
+    /// a atteint le disque
+    fn sync_directory(&self, path: &Path) -> std::io::Result<()>;
+}
+
+pub trait File: Send + Sync {
+    /// Lit `buf.len()` octets à partir de `offset`, ce qui se trouve au-delà
+    /// de la fin du fichier est lu comme des zéros
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> std::io::Result<()>;
+    fn write_at(&mut self, offset: u64, buf: &[u8]) -> std::io::Result<()>;
+    fn len(&mut self) -> std::io::Result<u64>;
+    fn set_len(&mut self, len: u64) -> std::io::Result<()>;
+    /// Garantit que les écritures précédentes ont atteint le disque
+    fn sync(&mut self) -> std::io::Result<()>;
+}
+
+/// Système de fichiers du système d'exploitation
+pub struct OsVfs;
+
+impl Vfs for OsVfs {
+    fn open(&self, path: &Path) -> std::io::Result<Box<dyn File>> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+        Ok(Box::new(file))
+    }
+
+    fn exists(&self, path: &Path) -> bool {
+        path.exists()
+    }
+
+    fn remove(&self, path: &Path) -> std::io::Result<()> {
+        std::fs::remove_file(path)
+    }
+
+    fn sync_directory(&self, path: &Path) -> std::io::Result<()> {
+        // seuls les systèmes unix permettent d'ouvrir un répertoire
+        if cfg!(unix) {
+            let directory = path.parent().filter(|parent| !parent.as_os_str().is_empty());
+            std::fs::File::open(directory.unwrap_or(Path::new(".")))?.sync_all()?;
+        }
+        Ok(())
+    }
+}
+
+impl File for std::fs::File {
+    fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> std::io::Result<()> {
+        buf.fill(0);
+        self.seek(SeekFrom::Start(offset))?;
+        let mut read = 0;
+        while read < buf.len() {
+            match self.read(&mut buf[read..])? {
+                0 => break,
+                n => read += n,
+            }
+        }
+        Ok(())
+    }
+
+    fn write_at(&mut self, offset: u64, buf: &[u8]) -> std::io::Result<()> {
+        self.seek(SeekFrom::Start(offset))?;
+        self.write_all(buf)
+    }
+
+    fn len(&mut self) -> std::io::Result<u64> {
+        Ok(self.metadata()?.len())
+    }
+
+    fn set_len(&mut self, len: u64) -> std::io::Result<()> {
+        std::fs::File::set_len(self, len)
+    }
+
+    fn sync(&mut self) -> std::io::Result<()> {
+        self.sync_all()
+    }
+}
+
+#[cfg(test)]
+pub mod memory {
+    use super::{File, Vfs};
+    use std::collections::HashMap;
+    use std::path::{Path, PathBuf};
+    use std::sync::{Arc, Mutex};
+
+    /// Système de fichiers en mémoire qui simule des crashs.
+    ///
+    /// Chaque fichier conserve son contenu courant et le contenu qui a
+    /// survécu au dernier `sync`. Après `crash_after(n)`, la n+1-ième
+    /// opération d'écriture échoue ; `crash` oublie alors tout ce qui n'a pas
+    /// été synchronisé, comme après une coupure de courant.
+    #[derive(Clone, Default)]
+    pub struct MemoryVfs {
+        state: Arc<Mutex<State>>,
+    }
+
+    #[derive(Default)]
+    struct State {
+        files: HashMap<PathBuf, MemoryFile>,
+        /// nombre d'opérations d'écriture autorisées avant le crash
+        remaining_writes: Option<usize>,
+    }
+
+    #[derive(Default, Clone)]
+    struct MemoryFile {
+        content: Vec<u8>,
+        durable: Vec<u8>,
+    }
+
+    impl State {
+        fn write_point(&mut self) -> std::io::Result<()> {
+            if let Some(remaining) = self.remaining_writes.as_mut() {
+                if *remaining == 0 {
+                    return Err(std::io::Error::other("simulated crash"));
+                }
+                *remaining -= 1;
+            }
+            Ok(())
+        }
+    }
+
+    impl MemoryVfs {
+        pub fn new() -> Self {
+            Self::default()
+        }
+
+        /// Fait échouer toutes les écritures après les `writes` prochaines
+        pub fn crash_after(&self, writes: usize) {
+            self.state.lock().unwrap().remaining_writes = Some(writes);
+        }
+
+        /// Perd les écritures non synchronisées et lève la panne
+        pub fn crash(&self) {
+            let mut state = self.state.lock().unwrap();
+            for file in state.files.values_mut() {
+                file.content = file.durable.clone();
+            }
+            state.remaining_writes = None;
+        }
+    }
+
+    impl Vfs for MemoryVfs {
+        fn open(&self, path: &Path) -> std::io::Result<Box<dyn File>> {
+            self.state
+                .lock()
+                .unwrap()
+                .files
+                .entry(path.to_path_buf())
+                .or_default();
+            Ok(Box::new(MemoryHandle {
+                vfs: self.clone(),
+                path: path.to_path_buf(),
+            }))
+        }
+
+        fn exists(&self, path: &Path) -> bool {
+            self.state.lock().unwrap().files.contains_key(path)
+        }
+
+        fn remove(&self, path: &Path) -> std::io::Result<()> {
+            let mut state = self.state.lock().unwrap();
+            state.write_point()?;
+            state.files.remove(path);
+            Ok(())
+        }
+
+        /// Les fichiers créés survivent déjà à un crash
+        fn sync_directory(&self, _: &Path) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    struct MemoryHandle {
+        vfs: MemoryVfs,
+        path: PathBuf,
+    }
+
+    impl MemoryHandle {
+        fn with_file<T>(
+            &self,
+            write: bool,
+            action: impl FnOnce(&mut MemoryFile) -> T,
+        ) -> std::io::Result<T> {
+            let mut state = self.vfs.state.lock().unwrap();
+            if write {
+                state.write_point()?;
+            }
+            Ok(action(state.files.entry(self.path.clone()).or_default()))
+        }
+    }
+
+    impl File for MemoryHandle {
+        fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> std::io::Result<()> {
+            self.with_file(false, |file| {
+                buf.fill(0);
+                let start = (offset as usize).min(file.content.len());
+                let end = (offset as usize + buf.len()).min(file.content.len());
+                buf[..end - start].copy_from_slice(&file.content[start..end]);
+            })
+        }
+
+        fn write_at(&mut self, offset: u64, buf: &[u8]) -> std::io::Result<()> {
+            self.with_file(true, |file| {
+                let end = offset as usize + buf.len();
+                if file.content.len() < end {
+                    file.content.resize(end, 0);
+                }
+                file.content[offset as usize..end].copy_from_slice(buf);
+            })
+        }
+
+        fn len(&mut self) -> std::io::Result<u64> {
+            self.with_file(false, |file| file.content.len() as u64)
+        }
+
+        fn set_len(&mut self, len: u64) -> std::io::Result<()> {
+            self.with_file(true, |file| file.content.resize(len as usize, 0))
+        }
+
+        fn sync(&mut self) -> std::io::Result<()> {
+            self.with_file(true, |file| file.durable = file.content.clone())
+        }
+    }
+}
@@ -0,0 +1,216 @@
+//! Tables virtuelles fournies par l'application : leurs lignes ne viennent
+//! pas des pages de la base mais d'un fichier CSV, d'une collection en
+//! mémoire, d'un calcul... Une fois enregistrée, une table se lit en SQL par
+//! son nom, `select * from serie`, ou avec des arguments, `select * from
+//! generate_series(1, 10)`.
+
+mod series;
+
+use crate::data::Value;
+use crate::errors::EvaluationError;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Colonnes d'une table virtuelle. Les paramètres sont des colonnes cachées,
+/// placées après les autres et absentes de `*`, qui reçoivent les arguments
+/// de l'appel dans la clause FROM.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct Schema {
+    pub columns: Vec<String>,
+    pub parameters: Vec<String>,
+}
+
+/// Source de lignes enregistrée auprès de la base
+pub trait VirtualTable: Send + Sync {
+    fn schema(&self) -> Schema;
+
+    /// Nouveau parcours de la table
+    fn open(&self) -> Result<Box<dyn VirtualCursor + '_>, EvaluationError>;
+
+    /// Ajoute une ligne, une valeur par colonne hors paramètres. Une table
+    /// en lecture seule n'a pas à la fournir. La ligne est ajoutée tout de
+    /// suite : une transaction annulée ne la retire pas.
+    fn update(&self, values: &[Value]) -> Result<(), EvaluationError> {
+        let _ = values;
+        Err(EvaluationError::Function("virtual table is read-only".to_string()))
+    }
+}
+
+/// Parcours d'une table virtuelle, ligne à ligne
+pub trait VirtualCursor {
+    /// Recommence le parcours avec les arguments de l'appel, un par
+    /// paramètre au plus
+    fn filter(&mut self, arguments: &[Value]) -> Result<(), EvaluationError>;
+    /// Passe à la ligne suivante, la première après `filter`. Renvoie faux
+    /// une fois la dernière ligne dépassée.
+    fn next(&mut self) -> Result<bool, EvaluationError>;
+    /// Valeur de la ligne courante, les paramètres suivant les colonnes
+    fn column(&self, index: usize) -> Result<Value, EvaluationError>;
+}
+
+/// Tables virtuelles connues d'une base, par nom
+#[derive(Clone)]
+pub struct VirtualTables {
+    tables: HashMap<String, Arc<dyn VirtualTable>>,
+}
+
+impl Default for VirtualTables {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VirtualTables {
+    /// Tables fournies par la base
+    pub fn new() -> Self {
+        let mut tables = Self {
+            tables: HashMap::new(),
+        };
+        tables.register("generate_series", series::GenerateSeries);
+        tables
+    }
+
+    /// Enregistre une table, qui remplace celle de même nom
+    pub fn register(&mut self, name: &str, table: impl VirtualTable + 'static) {
+        self.tables.insert(name.to_ascii_lowercase(), Arc::new(table));
+    }
+
+    pub fn get(&self, name: &str) -> Option<&dyn VirtualTable> {
+        self.tables.get(&name.to_ascii_lowercase()).map(Arc::as_ref)
+    }
+}
+
+/// Parcourt les lignes de la table retenues pour ces arguments, lues une à
+/// une à la demande : une table infinie peut être lue jusqu'à un limit
+pub fn scan<'a>(name: &str, table: &'a dyn VirtualTable, arguments: &[Value]) -> Result<Scan<'a>, EvaluationError> {
+    let schema = table.schema();
+    if arguments.len() > schema.parameters.len() {
+        return Err(EvaluationError::WrongArgumentCount {
+            function: name.to_string(),
+            given: arguments.len(),
+        });
+    }
+    let mut cursor = table.open()?;
+    cursor.filter(arguments)?;
+    Ok(Scan {
+        cursor,
+        width: schema.columns.len() + schema.parameters.len(),
+        done: false,
+    })
+}
+
+/// Parcours des lignes d'une table virtuelle, paramètres compris
+pub struct Scan<'a> {
+    cursor: Box<dyn VirtualCursor + 'a>,
+    width: usize,
+    /// dernière ligne dépassée, ou erreur déjà rendue
+    done: bool,
+}
+
+impl Iterator for Scan<'_> {
+    type Item = Result<Vec<Value>, EvaluationError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        let row = match self.cursor.next() {
+            Ok(true) => (0..self.width).map(|index| self.cursor.column(index)).collect(),
+            Ok(false) => {
+                self.done = true;
+                return None;
+            }
+            Err(err) => Err(err),
+        };
+        self.done = row.is_err();
+        Some(row)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Collection de textes en mémoire, qui accepte les ajouts
+    pub(crate) struct Collection {
+        columns: Vec<String>,
+        rows: Mutex<Vec<Vec<String>>>,
+    }
+
+    impl Collection {
+        pub(crate) fn new(columns: &[&str], rows: &[&[&str]]) -> Self {
+            let text = |texts: &[&str]| texts.iter().map(|text| text.to_string()).collect();
+            Self {
+                columns: text(columns),
+                rows: Mutex::new(rows.iter().map(|row| text(row)).collect()),
+            }
+        }
+    }
+
+    struct CollectionCursor {
+        rows: Vec<Vec<String>>,
+        position: Option<usize>,
+    }
+
+    impl VirtualTable for Collection {
+        fn schema(&self) -> Schema {
+            Schema {
+                columns: self.columns.clone(),
+                parameters: vec![],
+            }
+        }
+
+        fn open(&self) -> Result<Box<dyn VirtualCursor + '_>, EvaluationError> {
+            Ok(Box::new(CollectionCursor {
+                rows: self.rows.lock().unwrap().clone(),
+                position: None,
+            }))
+        }
+
+        fn update(&self, values: &[Value]) -> Result<(), EvaluationError> {
+            self.rows.lock().unwrap().push(values.iter().map(Value::to_string).collect());
+            Ok(())
+        }
+    }
+
+    impl VirtualCursor for CollectionCursor {
+        fn filter(&mut self, _: &[Value]) -> Result<(), EvaluationError> {
+            self.position = None;
+            Ok(())
+        }
+
+        fn next(&mut self) -> Result<bool, EvaluationError> {
+            let position = self.position.map_or(0, |position| position + 1);
+            self.position = Some(position);
+            Ok(position < self.rows.len())
+        }
+
+        fn column(&self, index: usize) -> Result<Value, EvaluationError> {
+            Ok(Value::Text(self.rows[self.position.unwrap_or_default()][index].clone()))
+        }
+    }
+
+    #[test]
+    fn test_registry() {
+        let mut tables = VirtualTables::new();
+        tables.register("Names", Collection::new(&["name"], &[&["Ada"]]));
+        let names = tables.get("names").expect("missing table");
+        names.update(&[Value::Text("Grace".to_string())]).expect("update failed");
+        assert_eq!(
+            scan("names", names, &[]).and_then(Iterator::collect::<Result<Vec<_>, _>>),
+            Ok(vec![vec![Value::Text("Ada".to_string())], vec![Value::Text("Grace".to_string())]])
+        );
+        assert_eq!(
+            scan("names", names, &[Value::Integer(1)]).err(),
+            Some(EvaluationError::WrongArgumentCount {
+                function: "names".to_string(),
+                given: 1
+            })
+        );
+        assert!(tables.get("unknown").is_none());
+        // les tables de la base sont en lecture seule
+        let series = tables.get("generate_series").expect("missing table");
+        assert!(series.update(&[Value::Integer(1)]).is_err());
+    }
+}
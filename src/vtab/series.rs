@@ -0,0 +1,133 @@
+//! `generate_series(start, stop[, step])` : les entiers de `start` à `stop`
+//! inclus, de `step` en `step`. Un pas négatif descend de `start` à `stop`,
+//! un pas nul vaut 1 comme dans SQLite. Sans `stop`, la série va jusqu'à la
+//! borne des entiers : un limit l'arrête.
+
+use crate::data::{Affinity, Value};
+use crate::errors::EvaluationError;
+use crate::vtab::{Schema, VirtualCursor, VirtualTable};
+
+pub struct GenerateSeries;
+
+#[derive(Default)]
+struct SeriesCursor {
+    start: i64,
+    stop: i64,
+    step: i64,
+    /// valeur courante, `None` avant la première ligne
+    value: Option<i64>,
+    /// un argument NULL ne produit aucune ligne
+    done: bool,
+}
+
+impl VirtualTable for GenerateSeries {
+    fn schema(&self) -> Schema {
+        Schema {
+            columns: vec!["value".to_string()],
+            parameters: ["start", "stop", "step"].map(str::to_string).to_vec(),
+        }
+    }
+
+    fn open(&self) -> Result<Box<dyn VirtualCursor + '_>, EvaluationError> {
+        Ok(Box::new(SeriesCursor::default()))
+    }
+}
+
+impl VirtualCursor for SeriesCursor {
+    fn filter(&mut self, arguments: &[Value]) -> Result<(), EvaluationError> {
+        if arguments.is_empty() {
+            return Err(EvaluationError::WrongArgumentCount {
+                function: "generate_series".to_string(),
+                given: arguments.len(),
+            });
+        }
+        let arguments = arguments
+            .iter()
+            .map(|argument| match argument.clone().cast(Affinity::Integer) {
+                Value::Integer(value) => Ok(Some(value)),
+                Value::Null => Ok(None),
+                value => Err(EvaluationError::Function(format!("generate_series: invalid argument {value}"))),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        *self = SeriesCursor::default();
+        let step = arguments.get(2).copied().unwrap_or(Some(1)).map(|step| if step == 0 { 1 } else { step });
+        let bound = match step {
+            Some(step) if step < 0 => i64::MIN,
+            _ => i64::MAX,
+        };
+        match (arguments[0], arguments.get(1).copied().unwrap_or(Some(bound)), step) {
+            (Some(start), Some(stop), Some(step)) => {
+                self.start = start;
+                self.stop = stop;
+                self.step = step;
+            }
+            _ => self.done = true,
+        }
+        Ok(())
+    }
+
+    fn next(&mut self) -> Result<bool, EvaluationError> {
+        if self.done {
+            return Ok(false);
+        }
+        let value = match self.value {
+            None => Some(self.start),
+            Some(value) => value.checked_add(self.step),
+        };
+        self.value = value.filter(|value| match self.step > 0 {
+            true => *value <= self.stop,
+            false => *value >= self.stop,
+        });
+        self.done = self.value.is_none();
+        Ok(!self.done)
+    }
+
+    fn column(&self, index: usize) -> Result<Value, EvaluationError> {
+        Ok(match index {
+            0 => self.value.map_or(Value::Null, Value::Integer),
+            1 => Value::Integer(self.start),
+            2 => Value::Integer(self.stop),
+            3 => Value::Integer(self.step),
+            _ => Value::Null,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::vtab::scan;
+
+    fn series(arguments: &[Value]) -> Result<Vec<i64>, EvaluationError> {
+        scan("generate_series", &GenerateSeries, arguments)?
+            .map(|row| match row?[0] {
+                Value::Integer(value) => Ok(value),
+                _ => panic!("not an integer"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_generate_series() {
+        let integer = Value::Integer;
+        assert_eq!(series(&[integer(1), integer(5)]), Ok(vec![1, 2, 3, 4, 5]));
+        assert_eq!(series(&[integer(0), integer(10), integer(4)]), Ok(vec![0, 4, 8]));
+        assert_eq!(series(&[integer(3), integer(1), integer(-1)]), Ok(vec![3, 2, 1]));
+        assert_eq!(series(&[integer(1), integer(2), integer(0)]), Ok(vec![1, 2]));
+        assert_eq!(series(&[integer(5), integer(1)]), Ok(vec![]));
+        assert_eq!(series(&[integer(1), Value::Null]), Ok(vec![]));
+        assert_eq!(series(&[Value::Text("2".to_string()), Value::Real(3.5)]), Ok(vec![2, 3]));
+        // sans dépasser les bornes d'un entier
+        assert_eq!(series(&[integer(i64::MAX - 1), integer(i64::MAX)]), Ok(vec![i64::MAX - 1, i64::MAX]));
+        assert!(series(&[]).is_err());
+        // sans fin, la série se lit à la demande
+        let rows = scan("generate_series", &GenerateSeries, &[integer(1)]).expect("scan failed");
+        assert_eq!(rows.take(3).count(), 3);
+        let mut rows = scan("generate_series", &GenerateSeries, &[integer(i64::MAX)]).expect("scan failed");
+        assert!(rows.next().is_some() && rows.next().is_none());
+        // comme CAST, un texte qui n'est pas un nombre vaut 0
+        assert_eq!(series(&[integer(-1), Value::Text("x".to_string())]), Ok(vec![-1, 0]));
+        let row = scan("generate_series", &GenerateSeries, &[integer(7), integer(7)]).map(Iterator::collect::<Result<Vec<_>, _>>);
+        assert_eq!(row, Ok(Ok(vec![vec![integer(7), integer(7), integer(7), integer(1)]])));
+    }
+}
@@ -0,0 +1,256 @@
+use crate::errors::StorageError;
+use crate::pager::PAGE_SIZE;
+use crate::vfs::{File, Vfs};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+const WAL_MAGIC: &[u8; 8] = b"rsqlwal\0";
+/// magic, sel
+const WAL_HEADER_SIZE: usize = 8 + 4;
+/// numéro de page, taille de la base si la frame valide une transaction,
+/// sel et somme de contrôle cumulée
+const FRAME_HEADER_SIZE: usize = 4 + 4 + 4 + 4;
+const FRAME_SIZE: usize = FRAME_HEADER_SIZE + PAGE_SIZE;
+
+/// Journal en écriture anticipée (write-ahead log).
+///
+/// Les pages validées sont ajoutées sous forme de frames à la fin de
+/// `<db>-wal` au lieu d'être écrites dans le fichier principal. La dernière
+/// frame d'une transaction porte la taille de la base : tant qu'elle n'est
+/// pas écrite, les frames précédentes sont ignorées, si bien qu'un lecteur ne
+/// voit jamais qu'une partie d'une transaction. Le checkpoint recopie ensuite
+/// les pages dans le fichier principal et vide le journal.
+///
+/// Chaque frame porte le sel du journal et une somme de contrôle cumulée :
+/// une frame abîmée ou restée d'un journal précédent arrête la relecture.
+pub struct Wal {
+    vfs: Arc<dyn Vfs>,
+    path: PathBuf,
+    /// ouvert à la première écriture
+    file: Option<Box<dyn File>>,
+    salt: u32,
+    /// dernière frame validée de chaque page
+    index: HashMap<u32, u32>,
+    /// nombre de frames validées
+    frames: u32,
+    /// somme de contrôle de la dernière frame validée
+    checksum: u32,
+    /// taille de la base en pages à la dernière transaction validée
+    database_pages: u32,
+    /// en-tête et taille du fichier tels que lus par `scan` ou laissés par
+    /// la dernière écriture : s'ils ont changé, une autre connexion a écrit
+    seen: ([u8; WAL_HEADER_SIZE], u64),
+}
+
+impl Wal {
+    pub fn open(vfs: Arc<dyn Vfs>, path: impl AsRef<Path>) -> Result<Self, StorageError> {
+        let mut wal = Self {
+            vfs,
+            path: path.as_ref().to_path_buf(),
+            file: None,
+            salt: 0,
+            index: HashMap::new(),
+            frames: 0,
+            checksum: 0,
+            database_pages: 0,
+            seen: ([0; WAL_HEADER_SIZE], 0),
+        };
+        wal.refresh()?;
+        Ok(wal)
+    }
+
+    /// Relit le journal si une autre connexion l'a modifié depuis la
+    /// dernière lecture, à appeler au début de chaque transaction
+    pub fn refresh(&mut self) -> Result<(), StorageError> {
+        if !self.vfs.exists(&self.path) {
+            // journal supprimé par une autre connexion qui a quitté le mode WAL
+            if self.file.take().is_some() {
+                self.reset();
+            }
+            return Ok(());
+        }
+        let file = match self.file.as_mut() {
+            Some(file) => file,
+            None => self.file.insert(self.vfs.open(&self.path).map_err(StorageError::io)?),
+        };
+        let mut header = [0_u8; WAL_HEADER_SIZE];
+        file.read_at(0, &mut header).map_err(StorageError::io)?;
+        let len = file.len().map_err(StorageError::io)?;
+        if (header, len) != self.seen {
+            self.reset();
+            self.scan()?;
+        }
+        Ok(())
+    }
+
+    fn reset(&mut self) {
+        self.index.clear();
+        self.frames = 0;
+        self.checksum = 0;
+        self.database_pages = 0;
+        self.seen = ([0; WAL_HEADER_SIZE], 0);
+    }
+
+    /// Reconstruit l'index à partir des frames valides du journal
+    fn scan(&mut self) -> Result<(), StorageError> {
+        let Some(file) = self.file.as_mut() else {
+            return Ok(());
+        };
+        let mut header = [0_u8; WAL_HEADER_SIZE];
+        file.read_at(0, &mut header).map_err(StorageError::io)?;
+        let len = file.len().map_err(StorageError::io)?;
+        self.seen = (header, len);
+        let salt = u32::from_le_bytes(header[8..].try_into().unwrap());
+        // le prochain journal devra avoir un sel différent de celui-ci
+        self.salt = salt.wrapping_add(1);
+        if &header[..8] != WAL_MAGIC {
+            return Ok(());
+        }
+        let mut pending = HashMap::new();
+        let mut checksum = 0;
+        let mut frame = vec![0_u8; FRAME_SIZE];
+        let mut index = 0_u32;
+        while frame_offset(index) + FRAME_SIZE as u64 <= len {
+            file.read_at(frame_offset(index), &mut frame)
+                .map_err(StorageError::io)?;
+            let page = read_u32(&frame, 0);
+            let database_pages = read_u32(&frame, 4);
+            checksum = frame_checksum(checksum, &frame);
+            if read_u32(&frame, 8) != salt || read_u32(&frame, 12) != checksum {
+                break;
+            }
+            pending.insert(page, index);
+            index += 1;
+            if database_pages != 0 {
+                // transaction complète : elle devient visible
+                self.index.extend(pending.drain());
+                self.frames = index;
+                self.checksum = checksum;
+                self.database_pages = database_pages;
+            }
+        }
+        if self.frames > 0 {
+            // les prochaines frames s'ajoutent à la suite des frames valides
+            self.salt = salt;
+        }
+        Ok(())
+    }
+
+    /// Nombre de frames validées dans le journal
+    pub fn frame_count(&self) -> u32 {
+        self.frames
+    }
+
+    /// Taille de la base en pages d'après le journal, 0 s'il est vide
+    pub fn database_pages(&self) -> u32 {
+        self.database_pages
+    }
+
+    /// Lit la dernière version validée de la page si elle est dans le journal
+    pub fn read_page(&mut self, page: u32, buf: &mut [u8]) -> Result<bool, StorageError> {
+        let (Some(frame), Some(file)) = (self.index.get(&page), self.file.as_mut()) else {
+            return Ok(false);
+        };
+        file.read_at(
+            frame_offset(*frame) + FRAME_HEADER_SIZE as u64,
+            &mut buf[..PAGE_SIZE],
+        )
+        .map_err(StorageError::io)?;
+        Ok(true)
+    }
+
+    /// Ajoute une transaction au journal, elle n'est visible qu'une fois
+    /// toutes ses frames synchronisées
+    pub fn append(&mut self, pages: &[(u32, &[u8])], database_pages: u32) -> Result<(), StorageError> {
+        if pages.is_empty() {
+            return Ok(());
+        }
+        let created = self.file.is_none();
+        if created {
+            self.file = Some(self.vfs.open(&self.path).map_err(StorageError::io)?);
+        }
+        let file = self.file.as_mut().unwrap();
+        let mut header = [0_u8; WAL_HEADER_SIZE];
+        header[..8].copy_from_slice(WAL_MAGIC);
+        header[8..].copy_from_slice(&self.salt.to_le_bytes());
+        if self.frames == 0 {
+            file.write_at(0, &header).map_err(StorageError::io)?;
+        }
+        let mut checksum = self.checksum;
+        let mut frame = vec![0_u8; FRAME_SIZE];
+        for (position, (page, data)) in pages.iter().enumerate() {
+            let commit = position == pages.len() - 1;
+            frame[..4].copy_from_slice(&page.to_le_bytes());
+            frame[4..8].copy_from_slice(&(if commit { database_pages } else { 0 }).to_le_bytes());
+            frame[8..12].copy_from_slice(&self.salt.to_le_bytes());
+            frame[FRAME_HEADER_SIZE..].copy_from_slice(&data[..PAGE_SIZE]);
+            checksum = frame_checksum(checksum, &frame);
+            frame[12..16].copy_from_slice(&checksum.to_le_bytes());
+            file.write_at(frame_offset(self.frames + position as u32), &frame)
+                .map_err(StorageError::io)?;
+        }
+        file.sync().map_err(StorageError::io)?;
+        if created {
+            self.vfs.sync_directory(&self.path).map_err(StorageError::io)?;
+        }
+        for (position, (page, _)) in pages.iter().enumerate() {
+            self.index.insert(*page, self.frames + position as u32);
+        }
+        self.frames += pages.len() as u32;
+        self.checksum = checksum;
+        self.database_pages = database_pages;
+        self.seen = (header, frame_offset(self.frames));
+        Ok(())
+    }
+
+    /// Recopie les pages du journal dans le fichier principal puis vide le journal
+    pub fn checkpoint(&mut self, database: &mut dyn File) -> Result<(), StorageError> {
+        let Some(file) = self.file.as_mut() else {
+            return Ok(());
+        };
+        if self.frames > 0 {
+            let mut data = vec![0_u8; PAGE_SIZE];
+            for (page, frame) in &self.index {
+                file.read_at(frame_offset(*frame) + FRAME_HEADER_SIZE as u64, &mut data)
+                    .map_err(StorageError::io)?;
+                database
+                    .write_at(*page as u64 * PAGE_SIZE as u64, &data)
+                    .map_err(StorageError::io)?;
+            }
+            // le journal ne peut être vidé qu'une fois les pages sur disque
+            database.sync().map_err(StorageError::io)?;
+        }
+        file.set_len(0).map_err(StorageError::io)?;
+        file.sync().map_err(StorageError::io)?;
+        self.reset();
+        self.salt = self.salt.wrapping_add(1);
+        Ok(())
+    }
+
+    /// Vide le journal et supprime son fichier
+    pub fn close(&mut self, database: &mut dyn File) -> Result<(), StorageError> {
+        self.checkpoint(database)?;
+        if self.file.take().is_some() {
+            self.vfs.remove(&self.path).map_err(StorageError::io)?;
+        }
+        Ok(())
+    }
+}
+
+fn frame_offset(frame: u32) -> u64 {
+    WAL_HEADER_SIZE as u64 + frame as u64 * FRAME_SIZE as u64
+}
+
+fn read_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+/// Somme de contrôle de la frame, hors emplacement de la somme elle-même,
+/// chaînée avec celle de la frame précédente
+fn frame_checksum(previous: u32, frame: &[u8]) -> u32 {
+    frame[..12]
+        .iter()
+        .chain(&frame[FRAME_HEADER_SIZE..])
+        .fold(previous, |sum, byte| sum.wrapping_mul(31).wrapping_add(*byte as u32))
+}